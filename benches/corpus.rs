@@ -0,0 +1,199 @@
+//! Generates synthetic message corpora for the benchmarks.
+//!
+//! Everything is made up from fixed word lists and a seeded PRNG, so the corpora are the same on
+//! every run and never contain anyone's real logs.
+
+/// A tiny xorshift PRNG, so that the benchmarks don't need a `rand` dependency
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+const HOSTS: &[&str] = &[
+    "web1",
+    "web2",
+    "db-primary",
+    "cache3.example.net",
+    "batch6",
+    "192.0.2.17",
+];
+const APPS: &[&str] = &[
+    "nginx",
+    "sshd",
+    "CROND",
+    "postgres",
+    "someservice",
+    "kernel",
+    "-",
+];
+const MSGIDS: &[&str] = &["-", "-", "ID47", "AUDIT", "REQ"];
+const WORDS: &[&str] = &[
+    "request",
+    "completed",
+    "in",
+    "ms",
+    "status",
+    "upstream",
+    "timeout",
+    "connection",
+    "reset",
+    "by",
+    "peer",
+    "session",
+    "opened",
+    "closed",
+    "for",
+    "user",
+    "job",
+    "queued",
+    "retrying",
+    "cache",
+    "miss",
+    "hit",
+    "GET",
+    "POST",
+    "/v1/items",
+    "/healthz",
+    "200",
+    "404",
+    "503",
+];
+const SD_IDS: &[&str] = &["meta", "origin", "req@32473", "app@32473", "trace@32473"];
+const PARAMS: &[&str] = &[
+    "sequenceId",
+    "ip",
+    "software",
+    "x-group",
+    "traceId",
+    "status",
+    "path",
+];
+const MONTHS: &[&str] = &[
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// The kinds of message the benchmarks run over
+#[derive(Clone, Copy, Debug)]
+pub enum Shape {
+    /// `<1>1 - - - - - -`
+    Minimal,
+    /// A full header and a short MSG, but no STRUCTURED-DATA
+    NoSd,
+    /// Several SD-ELEMENTs with several params each, some needing escapes
+    HeavySd,
+    /// A header and a MSG of a couple of kilobytes
+    LongMsg,
+    /// An old BSD-style message, which only `parse_message_lossy` makes sense of
+    Rfc3164,
+}
+
+impl Shape {
+    pub const ALL: &'static [Shape] = &[
+        Shape::Minimal,
+        Shape::NoSd,
+        Shape::HeavySd,
+        Shape::LongMsg,
+        Shape::Rfc3164,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shape::Minimal => "minimal",
+            Shape::NoSd => "no_sd",
+            Shape::HeavySd => "heavy_sd",
+            Shape::LongMsg => "long_msg",
+            Shape::Rfc3164 => "rfc3164",
+        }
+    }
+}
+
+fn words(rng: &mut Rng, count: usize) -> String {
+    (0..count)
+        .map(|_| rng.pick(WORDS))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn header(rng: &mut Rng) -> String {
+    format!(
+        "<{}>1 2016-02-{:02}T{:02}:{:02}:{:02}.{:06}+00:00 {} {} {} {}",
+        rng.below(192),
+        rng.below(28) + 1,
+        rng.below(24),
+        rng.below(60),
+        rng.below(60),
+        rng.below(1_000_000),
+        rng.pick(HOSTS),
+        rng.pick(APPS),
+        rng.below(65536),
+        rng.pick(MSGIDS),
+    )
+}
+
+fn structured_data(rng: &mut Rng) -> String {
+    let mut sd = String::new();
+    for _ in 0..rng.below(3) + 3 {
+        sd.push('[');
+        sd.push_str(rng.pick(SD_IDS));
+        for _ in 0..rng.below(4) + 2 {
+            let value = match rng.below(4) {
+                0 => format!("{}", rng.next() % 100_000_000),
+                1 => format!(r#"a \"quoted\" {}"#, rng.pick(WORDS)),
+                _ => words(rng, 3),
+            };
+            sd.push_str(&format!(r#" {}="{}""#, rng.pick(PARAMS), value));
+        }
+        sd.push(']');
+    }
+    sd
+}
+
+/// Generate one message of the given shape
+pub fn message(rng: &mut Rng, shape: Shape) -> String {
+    match shape {
+        Shape::Minimal => String::from("<1>1 - - - - - -"),
+        Shape::NoSd => format!("{} - {}", header(rng), words(rng, 12)),
+        Shape::HeavySd => {
+            let sd = structured_data(rng);
+            format!("{} {} {}", header(rng), sd, words(rng, 8))
+        }
+        Shape::LongMsg => format!("{} - \u{feff}{}", header(rng), words(rng, 300)),
+        Shape::Rfc3164 => format!(
+            "<{}>{} {:2} {:02}:{:02}:{:02} {} {}[{}]: {}",
+            rng.below(192),
+            rng.pick(MONTHS),
+            rng.below(28) + 1,
+            rng.below(24),
+            rng.below(60),
+            rng.below(60),
+            rng.pick(HOSTS),
+            rng.pick(APPS),
+            rng.below(65536),
+            words(rng, 12),
+        ),
+    }
+}
+
+/// Generate `count` messages of the given shape, always the same ones for the same seed
+pub fn corpus(shape: Shape, count: usize, seed: u64) -> Vec<String> {
+    let mut rng = Rng::new(seed);
+    (0..count).map(|_| message(&mut rng, shape)).collect()
+}
@@ -0,0 +1,117 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use syslog_rfc5424::framing::{FrameDecoder, OctetCountingDecoder, OctetCountingEncoder};
+use syslog_rfc5424::parser::{
+    parse_header, parse_message_lazy, parse_message_lossy, parse_message_ref, ParserConfig,
+};
+use syslog_rfc5424::{parse_message, SyslogMessage};
+
+mod corpus;
+
+use corpus::{corpus, Shape};
+
+const CORPUS_SIZE: usize = 1000;
+const SEED: u64 = 0x5151_4f47;
+
+fn throughput(messages: &[String]) -> Throughput {
+    Throughput::Bytes(messages.iter().map(|m| m.len() as u64).sum())
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    let lenient = ParserConfig::new().with_lenient(true);
+    for &shape in Shape::ALL {
+        let messages = corpus(shape, CORPUS_SIZE, SEED);
+        group.throughput(throughput(&messages));
+        match shape {
+            Shape::Rfc3164 => {
+                group.bench_function(shape.name(), |b| {
+                    b.iter(|| {
+                        for m in &messages {
+                            parse_message_lossy(&lenient, m);
+                        }
+                    })
+                });
+            }
+            _ => {
+                group.bench_function(shape.name(), |b| {
+                    b.iter(|| {
+                        for m in &messages {
+                            parse_message(m).unwrap();
+                        }
+                    })
+                });
+                group.bench_function(format!("{}_ref", shape.name()), |b| {
+                    b.iter(|| {
+                        for m in &messages {
+                            parse_message_ref(m).unwrap();
+                        }
+                    })
+                });
+                let default = ParserConfig::default();
+                group.bench_function(format!("{}_lazy", shape.name()), |b| {
+                    b.iter(|| {
+                        for m in &messages {
+                            parse_message_lazy(&default, m).unwrap();
+                        }
+                    })
+                });
+                group.bench_function(format!("{}_header", shape.name()), |b| {
+                    b.iter(|| {
+                        for m in &messages {
+                            parse_header(m).unwrap();
+                        }
+                    })
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for &shape in &[Shape::NoSd, Shape::HeavySd, Shape::LongMsg] {
+        let messages = corpus(shape, CORPUS_SIZE, SEED);
+        group.throughput(throughput(&messages));
+        let parsed = messages
+            .iter()
+            .map(|m| parse_message(m).unwrap())
+            .collect::<Vec<SyslogMessage>>();
+        group.bench_function(shape.name(), |b| {
+            b.iter(|| {
+                for m in &parsed {
+                    m.to_string();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_framing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("framing");
+    let messages = corpus(Shape::HeavySd, CORPUS_SIZE, SEED)
+        .iter()
+        .map(|m| parse_message(m).unwrap())
+        .collect::<Vec<_>>();
+    let mut stream = Vec::new();
+    OctetCountingEncoder::new().encode_batch(&messages, &mut stream);
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+    group.bench_function("octet_counting_decode", |b| {
+        b.iter_batched(
+            OctetCountingDecoder::new,
+            |mut decoder| {
+                for chunk in stream.chunks(1500) {
+                    decoder.push(chunk);
+                    while decoder.next_frame().unwrap().is_some() {}
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse, bench_encode, bench_framing);
+criterion_main!(benches);
@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use syslog_rfc5424::framing::{
+    DetectingDecoder, FrameDecoder, NonTransparentDecoder, OctetCountingDecoder,
+};
+
+fn drain<D: FrameDecoder>(mut decoder: D, data: &[u8]) {
+    // feed the input in uneven chunks, to exercise the buffering
+    for chunk in data.chunks(7) {
+        decoder.push(chunk);
+        while let Ok(Some(_)) = decoder.next_frame() {}
+    }
+    let _ = decoder.finish();
+}
+
+fuzz_target!(|data: &[u8]| {
+    drain(OctetCountingDecoder::new(), data);
+    drain(NonTransparentDecoder::default(), data);
+    drain(DetectingDecoder::default(), data);
+});
@@ -0,0 +1,26 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use syslog_rfc5424::parser::{
+    parse_message_bytes, parse_message_bytes_with, ParserConfig, Utf8Policy,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_message_bytes(data, Utf8Policy::Preserve);
+    for config in &[
+        ParserConfig::new().with_lenient(true),
+        ParserConfig::new().with_strict(true),
+        ParserConfig::new()
+            .with_preserve_sd_order(true)
+            .with_strict_sd_escapes(true),
+    ] {
+        let _ = parse_message_bytes_with(config, data, Utf8Policy::Lossy);
+    }
+
+    // anything which parses should survive a round trip through the encoder
+    if let Ok(m) = parse_message_bytes(data, Utf8Policy::Strict) {
+        let encoded = m.to_string();
+        let reparsed = syslog_rfc5424::parse_message(&encoded).expect("encoded message should parse");
+        assert_eq!(reparsed.to_string(), encoded);
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use syslog_rfc5424::{parse_message, SyslogMessage};
+
+fuzz_target!(|m: SyslogMessage| {
+    let encoded = m.to_string();
+    let parsed = parse_message(&encoded).expect("generated messages should parse");
+    assert_eq!(parsed, m);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use syslog_rfc5424::sd::{escape_param_value, unescape_param_value};
+
+fuzz_target!(|value: &str| {
+    let escaped = escape_param_value(value);
+    assert_eq!(unescape_param_value(&escaped), value);
+    let _ = unescape_param_value(value);
+});
@@ -0,0 +1,140 @@
+#ifndef SYSLOG_RFC5424_H
+#define SYSLOG_RFC5424_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// A RFC5424-protocol syslog message
+typedef struct SyslogMessage SyslogMessage;
+
+// A borrowed, length-delimited UTF-8 string; `ptr` is NULL for a NIL field
+typedef struct SyslogStr {
+  const uint8_t *ptr;
+  size_t len;
+} SyslogStr;
+
+// Parse `len` bytes at `input` as an RFC 5424 message
+//
+// Returns NULL on failure; if `error` is not NULL, it's then set to a description of the problem,
+// which must be freed with `syslog_string_free`. A MSG which isn't valid UTF-8 is converted
+// lossily.
+//
+// # Safety
+//
+// `input` must point to at least `len` readable bytes, and `error` must be NULL or point to
+// writable memory for one pointer.
+struct SyslogMessage *syslog_message_parse(const uint8_t *input, size_t len, char **error);
+
+// Free a message returned by `syslog_message_parse`; NULL is ignored
+//
+// # Safety
+//
+// `m` must be NULL or a pointer returned by `syslog_message_parse` which hasn't been freed yet.
+void syslog_message_free(struct SyslogMessage *m);
+
+// Free a string returned by this library; NULL is ignored
+//
+// # Safety
+//
+// `s` must be NULL or a string returned by this library which hasn't been freed yet.
+void syslog_string_free(char *s);
+
+// The severity code (0-7), or -1 if `m` is NULL
+//
+// # Safety
+//
+// `m` must be NULL or a valid message.
+int32_t syslog_message_severity(const struct SyslogMessage *m);
+
+// The facility code (0-23), or -1 if `m` is NULL
+//
+// # Safety
+//
+// `m` must be NULL or a valid message.
+int32_t syslog_message_facility(const struct SyslogMessage *m);
+
+// The VERSION, or -1 if `m` is NULL
+//
+// # Safety
+//
+// `m` must be NULL or a valid message.
+int32_t syslog_message_version(const struct SyslogMessage *m);
+
+// Read the timestamp into `secs` (since the epoch, in UTC), `nanos` and `offset` (seconds east
+// of UTC), any of which may be NULL
+//
+// Returns false, leaving the outputs alone, if the timestamp is NIL.
+//
+// # Safety
+//
+// `m` must be NULL or a valid message, and each output must be NULL or writable.
+bool syslog_message_timestamp(const struct SyslogMessage *m,
+                              int64_t *secs,
+                              uint32_t *nanos,
+                              int32_t *offset);
+
+// The HOSTNAME
+//
+// # Safety
+//
+// `m` must be NULL or a valid message.
+struct SyslogStr syslog_message_hostname(const struct SyslogMessage *m);
+
+// The APP-NAME
+//
+// # Safety
+//
+// `m` must be NULL or a valid message.
+struct SyslogStr syslog_message_appname(const struct SyslogMessage *m);
+
+// The PROCID, if it's a number; returns false, leaving `pid` alone, otherwise
+//
+// # Safety
+//
+// `m` must be NULL or a valid message, and `pid` must be writable.
+bool syslog_message_pid(const struct SyslogMessage *m, int32_t *pid);
+
+// The PROCID, if it isn't a number (see `syslog_message_pid`)
+//
+// # Safety
+//
+// `m` must be NULL or a valid message.
+struct SyslogStr syslog_message_procid_name(const struct SyslogMessage *m);
+
+// The MSGID
+//
+// # Safety
+//
+// `m` must be NULL or a valid message.
+struct SyslogStr syslog_message_msgid(const struct SyslogMessage *m);
+
+// The MSG, which is empty (but not NIL) if the message had none
+//
+// # Safety
+//
+// `m` must be NULL or a valid message.
+struct SyslogStr syslog_message_msg(const struct SyslogMessage *m);
+
+// Look up the value of SD-PARAM `name` in SD-ELEMENT `sd_id`, both NUL-terminated
+//
+// # Safety
+//
+// `m` must be NULL or a valid message, and `sd_id` and `name` must be NULL or NUL-terminated.
+struct SyslogStr syslog_message_sd_param(const struct SyslogMessage *m,
+                                         const char *sd_id,
+                                         const char *name);
+
+// Render the message back into the RFC 5424 wire format; free the result with
+// `syslog_string_free`
+//
+// Returns NULL if `m` is NULL.
+//
+// # Safety
+//
+// `m` must be NULL or a valid message.
+char *syslog_message_to_string(const struct SyslogMessage *m);
+
+#endif  /* SYSLOG_RFC5424_H */
@@ -0,0 +1,161 @@
+//! `arbitrary::Arbitrary` implementations (requires the `arbitrary` feature).
+//!
+//! These only generate messages which are valid by RFC 5424 and which the encoder can represent
+//! exactly, so that `parse_message(m.to_string())` gives back `m`. That makes them suitable for
+//! round-trip property tests and structure-aware fuzzing.
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::message::{ProcId, StructuredData, SyslogMessage};
+use crate::parser::BOM;
+use crate::timestamp::Timestamp;
+use crate::validation::{
+    MAX_APPNAME_LENGTH, MAX_HOSTNAME_LENGTH, MAX_MSGID_LENGTH, MAX_PROCID_LENGTH,
+};
+use crate::{SyslogFacility, SyslogSeverity};
+
+/// 9999-12-31T23:59:59Z, the last second which fits in an RFC 3339 timestamp
+const MAX_SECS: i64 = 253_402_300_799;
+
+/// A non-empty string of PRINTUSASCII characters, which isn't the NIL value `-`
+fn header_field(u: &mut Unstructured<'_>, max: usize) -> Result<String> {
+    let len = u.int_in_range(1..=max)?;
+    let mut s = (0..len)
+        .map(|_| u.int_in_range(33u8..=126).map(char::from))
+        .collect::<Result<String>>()?;
+    if s == "-" {
+        s.push('-');
+    }
+    Ok(s)
+}
+
+/// An SD-NAME: PRINTUSASCII except `=`, SP, `]`, `"` and (since we use it for SD-IDs) `@`
+fn sd_name(u: &mut Unstructured<'_>, max: usize) -> Result<String> {
+    let len = u.int_in_range(1..=max)?;
+    (0..len)
+        .map(|_| loop {
+            let c = char::from(u.int_in_range(33u8..=126)?);
+            if !matches!(c, '=' | ']' | '"' | '@') {
+                return Ok(c);
+            }
+        })
+        .collect()
+}
+
+impl<'a> Arbitrary<'a> for Timestamp {
+    /// Somewhere in years 0-9999, to microsecond precision, with an offset in whole minutes
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let offset = i32::from(u.int_in_range(-(23 * 60 + 59)..=23 * 60 + 59i16)?) * 60;
+        // keep the local time within range, too
+        let secs = u.int_in_range(-62_167_219_200 + 86_400..=MAX_SECS - 86_400)?;
+        let nanos = u.int_in_range(0..=999_999u32)? * 1000;
+        Ok(Timestamp::new(secs, nanos, offset))
+    }
+}
+
+impl<'a> Arbitrary<'a> for ProcId {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        if u.arbitrary()? {
+            Ok(ProcId::PID(u.int_in_range(0..=i32::MAX)?))
+        } else {
+            let mut name = header_field(u, MAX_PROCID_LENGTH)?;
+            // an all-digit name would come back as a PID
+            if name.bytes().all(|b| b.is_ascii_digit()) {
+                name.replace_range(..1, "p");
+            }
+            Ok(ProcId::Name(name))
+        }
+    }
+}
+
+impl<'a> Arbitrary<'a> for StructuredData {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut sd = StructuredData::new_empty();
+        for _ in 0..u.int_in_range(0..=3)? {
+            let mut sd_id = sd_name(u, 20)?;
+            sd_id.push_str(&format!("@{}", u.int_in_range(1..=999_999_999u32)?));
+            // an SD-ELEMENT with no params is legal, and has to be created explicitly
+            sd.entry(sd_id.clone());
+            for _ in 0..u.int_in_range(0..=3)? {
+                let name = sd_name(u, 32)?;
+                let value: String = u.arbitrary()?;
+                sd.insert_tuple(sd_id.clone(), name, value);
+            }
+        }
+        Ok(sd)
+    }
+}
+
+impl<'a> Arbitrary<'a> for SyslogMessage {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let severity = SyslogSeverity::from_int(u.int_in_range(0..=7)?).expect("in range");
+        let facility = SyslogFacility::from_int(u.int_in_range(0..=23)?).expect("in range");
+        let mut msg: String = u.arbitrary()?;
+        // a leading BOM is how MSG says that it's UTF-8, so it can't be part of `msg`
+        while msg.starts_with(BOM) {
+            msg.remove(0);
+        }
+        Ok(SyslogMessage {
+            severity,
+            facility,
+            version: 1,
+            timestamp: u.arbitrary()?,
+            timestamp_precision_lost: false,
+            leap_second: false,
+            hostname: option(u, |u| header_field(u, MAX_HOSTNAME_LENGTH))?,
+            appname: option(u, |u| header_field(u, MAX_APPNAME_LENGTH))?,
+            procid: u.arbitrary()?,
+            msgid: option(u, |u| header_field(u, MAX_MSGID_LENGTH))?,
+            sd: u.arbitrary()?,
+            msg,
+            msg_is_utf8: u.arbitrary()?,
+            msg_bytes: None,
+            truncated: false,
+        })
+    }
+}
+
+fn option<T>(
+    u: &mut Unstructured<'_>,
+    f: impl FnOnce(&mut Unstructured<'_>) -> Result<T>,
+) -> Result<Option<T>> {
+    if u.arbitrary()? {
+        f(u).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::arbitrary::{Arbitrary, Unstructured};
+
+    use crate::message::SyslogMessage;
+    use crate::parser::parse_message;
+
+    #[test]
+    fn test_round_trip() {
+        // a cheap deterministic byte stream; any input at all should give a valid message
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let data = (0..64 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect::<Vec<_>>();
+        let mut u = Unstructured::new(&data);
+        for _ in 0..200 {
+            let m = SyslogMessage::arbitrary(&mut u).expect("should generate");
+            assert!(m.validate().is_ok(), "{:?}", m);
+            let encoded = m.to_string();
+            assert_eq!(
+                parse_message(&encoded).expect("should parse"),
+                m,
+                "{}",
+                encoded
+            );
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Parsing into a `bumpalo` arena (requires the `bumpalo` feature).
+//!
+//! An `ArenaMessage` keeps every one of its strings and slices in a `bumpalo::Bump`, so a batch
+//! processor can parse thousands of messages, work on them, and then free all of them at once with
+//! `Bump::reset`, instead of freeing each message's allocations one by one.
+//!
+//! # Example
+//!
+//! ```
+//! use bumpalo::Bump;
+//! use syslog_rfc5424::arena::parse_message_in;
+//!
+//! let mut bump = Bump::new();
+//! for batch in &[["<1>1 - host1 app - - - one", "<1>1 - host2 app - - - two"]] {
+//!     let messages = batch
+//!         .iter()
+//!         .map(|line| parse_message_in(&bump, line))
+//!         .collect::<Result<Vec<_>, _>>()
+//!         .unwrap();
+//!     assert_eq!(messages[1].hostname, Some("host2"));
+//!     drop(messages);
+//!     bump.reset();
+//! }
+//! ```
+
+use std::borrow::Cow;
+
+use bumpalo::Bump;
+
+use crate::borrowed::ProcIdRef;
+use crate::facility::SyslogFacility;
+use crate::message::{StructuredData, SyslogMessage};
+use crate::parser::{self, ParseErr, ParserConfig};
+use crate::severity::SyslogSeverity;
+use crate::timestamp::Timestamp;
+use crate::validation;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A single SD-ELEMENT, with its SD-PARAMs in input order
+pub struct ArenaSdElement<'a> {
+    pub id: &'a str,
+    pub params: &'a [(&'a str, &'a str)],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// A RFC5424-protocol syslog message which lives in a `Bump`
+///
+/// This has the same fields as `SyslogMessageRef`, but nothing in it needs dropping, so it's
+/// `Copy` and resetting the arena is all it takes to free it.
+pub struct ArenaMessage<'a> {
+    pub severity: SyslogSeverity,
+    pub facility: SyslogFacility,
+    pub version: i32,
+    pub timestamp: Option<Timestamp>,
+    pub timestamp_precision_lost: bool,
+    pub leap_second: bool,
+    pub hostname: Option<&'a str>,
+    pub appname: Option<&'a str>,
+    pub procid: Option<ProcIdRef<'a>>,
+    pub msgid: Option<&'a str>,
+    /// The SD-ELEMENTs in input order, including any with repeated SD-IDs
+    pub sd: &'a [ArenaSdElement<'a>],
+    pub msg: &'a str,
+    pub msg_is_utf8: bool,
+    pub truncated: bool,
+}
+
+impl<'a> ArenaMessage<'a> {
+    /// Lookup by SDID, SDParamID pair, returning the last matching value
+    pub fn find_tuple(&self, sd_id: &str, sd_param_id: &str) -> Option<&'a str> {
+        self.sd
+            .iter()
+            .rev()
+            .filter(|e| e.id == sd_id)
+            .flat_map(|e| e.params.iter().rev())
+            .find(|(k, _)| *k == sd_param_id)
+            .map(|(_, v)| *v)
+    }
+
+    /// Copy all of the fields out of the arena into a new, owned `SyslogMessage`
+    pub fn to_owned(&self) -> SyslogMessage {
+        let mut sd = StructuredData::new_empty();
+        for element in self.sd {
+            let params = sd.entry(element.id);
+            for (name, value) in element.params {
+                params.insert(name.to_string(), value.to_string());
+            }
+        }
+        SyslogMessage {
+            severity: self.severity,
+            facility: self.facility,
+            version: self.version,
+            timestamp: self.timestamp,
+            timestamp_precision_lost: self.timestamp_precision_lost,
+            leap_second: self.leap_second,
+            hostname: self.hostname.map(String::from),
+            appname: self.appname.map(String::from),
+            procid: self.procid.map(|p| p.to_owned()),
+            msgid: self.msgid.map(String::from),
+            sd,
+            msg: String::from(self.msg),
+            msg_is_utf8: self.msg_is_utf8,
+            msg_bytes: None,
+            truncated: self.truncated,
+        }
+    }
+}
+
+/// Parse a message into `bump`, which it then borrows from instead of `input`
+pub fn parse_message_in<'a>(bump: &'a Bump, input: &str) -> Result<ArenaMessage<'a>, ParseErr> {
+    parse_message_in_with(bump, &ParserConfig::default(), input)
+}
+
+/// Like `parse_message_in`, but with a `ParserConfig`
+///
+/// In strict mode, the message is copied out of the arena to be validated, so this allocates.
+pub fn parse_message_in_with<'a>(
+    bump: &'a Bump,
+    config: &ParserConfig,
+    input: &str,
+) -> Result<ArenaMessage<'a>, ParseErr> {
+    let input: &'a str = bump.alloc_str(input);
+    let m = parser::parse_message_s(input, config)?;
+    // only values which had escapes in them aren't already in the arena
+    let in_bump = |s: &Cow<'a, str>| -> &'a str {
+        match s {
+            Cow::Borrowed(s) => s,
+            Cow::Owned(s) => bump.alloc_str(s),
+        }
+    };
+    let sd = bump.alloc_slice_fill_iter(m.sd.iter().map(|(id, params)| ArenaSdElement {
+        id,
+        params:
+            bump.alloc_slice_fill_iter(params.iter().map(|(name, value)| (*name, in_bump(value)))),
+    }));
+    let m = ArenaMessage {
+        severity: m.severity,
+        facility: m.facility,
+        version: m.version,
+        timestamp: m.timestamp,
+        timestamp_precision_lost: m.timestamp_precision_lost,
+        leap_second: m.leap_second,
+        hostname: m.hostname.as_ref().map(in_bump),
+        appname: m.appname.as_ref().map(in_bump),
+        procid: m.procid,
+        msgid: m.msgid.as_ref().map(in_bump),
+        sd,
+        msg: in_bump(&m.msg),
+        msg_is_utf8: m.msg_is_utf8,
+        truncated: m.truncated,
+    };
+    if config.strict {
+        validation::validate(&m.to_owned()).map_err(ParseErr::Nonconformant)?;
+    }
+    Ok(m)
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::Bump;
+
+    use super::{parse_message_in, parse_message_in_with};
+    use crate::borrowed::ProcIdRef;
+    use crate::parser::{parse_message, ParserConfig};
+
+    #[test]
+    fn test_parse_in_arena() {
+        let input = r#"<78>1 2016-01-15T00:04:01Z host1 CROND 10391 - [meta a="1" b="x\"y"][meta a="2"][empty@1] msg"#;
+        let mut bump = Bump::new();
+        {
+            let m = parse_message_in(&bump, input).expect("should parse");
+            assert_eq!(m.hostname, Some("host1"));
+            assert_eq!(m.procid, Some(ProcIdRef::PID(10391)));
+            assert_eq!(m.sd.len(), 3);
+            assert_eq!(m.find_tuple("meta", "a"), Some("2"));
+            assert_eq!(m.find_tuple("meta", "b"), Some("x\"y"));
+            assert_eq!(m.to_owned(), parse_message(input).unwrap());
+        }
+        assert!(bump.allocated_bytes() > 0);
+        bump.reset();
+
+        assert!(parse_message_in(&bump, "<1>1 - - - - - [bad").is_err());
+        let strict = ParserConfig::new().with_strict(true);
+        let long_sd_id = r#"<1>1 - - - - - [this-sd-id-is-much-too-long-for-the-rfc a="b"]"#;
+        assert!(parse_message_in(&bump, long_sd_id).is_ok());
+        assert!(parse_message_in_with(&bump, &strict, long_sd_id).is_err());
+    }
+}
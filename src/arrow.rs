@@ -0,0 +1,304 @@
+//! Exporting messages as Apache Arrow record batches (requires the `arrow` feature).
+//!
+//! `RecordBatchBuilder` appends messages to a set of Arrow arrays, and `finish` hands them over as
+//! a `RecordBatch`, ready for DataFusion, Polars or anything else which speaks Arrow. Every batch
+//! has the schema returned by `schema`:
+//!
+//! | Column       | Type                              | Nullable | Contents                      |
+//! |--------------|-----------------------------------|----------|-------------------------------|
+//! | `timestamp`  | `Timestamp(Nanosecond, "UTC")`    | yes      | TIMESTAMP, as an instant      |
+//! | `utc_offset` | `Int32`                           | yes      | the sender's UTC offset, in s |
+//! | `hostname`   | `Utf8`                            | yes      | HOSTNAME                      |
+//! | `appname`    | `Utf8`                            | yes      | APP-NAME                      |
+//! | `procid`     | `Utf8`                            | yes      | PROCID                        |
+//! | `msgid`      | `Utf8`                            | yes      | MSGID                         |
+//! | `severity`   | `Dictionary(Int8, Utf8)`          | no       | the severity's name           |
+//! | `facility`   | `Dictionary(Int8, Utf8)`          | no       | the facility's name           |
+//! | `sd`         | `Map(Utf8, Map(Utf8, Utf8))`      | no       | SD-IDs to their SD-PARAMs     |
+//! | `msg`        | `Utf8`                            | no       | MSG                           |
+//!
+//! NIL fields are nulls. Timestamps outside of the years 1677 to 2262 don't fit in nanoseconds
+//! since the epoch, so they're null too, though `utc_offset` is still set.
+//!
+//! # Example
+//!
+//! ```
+//! use arrow_array::{Array, StringArray};
+//! use syslog_rfc5424::arrow::RecordBatchBuilder;
+//! use syslog_rfc5424::parse_message;
+//!
+//! let mut builder = RecordBatchBuilder::new();
+//! builder.append(&parse_message("<11>1 2020-01-01T00:00:00Z web1 nginx - - - timed out").unwrap());
+//! builder.append(&parse_message("<14>1 - - - - - - hi").unwrap());
+//! let batch = builder.finish();
+//!
+//! assert_eq!(batch.num_rows(), 2);
+//! let hostname = batch.column_by_name("hostname").unwrap();
+//! let hostname = hostname.as_any().downcast_ref::<StringArray>().unwrap();
+//! assert_eq!(hostname.value(0), "web1");
+//! assert!(hostname.is_null(1));
+//! ```
+
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    Int32Builder, MapBuilder, StringBuilder, StringDictionaryBuilder, TimestampNanosecondBuilder,
+};
+use arrow_array::types::Int8Type;
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+
+use crate::message::{ProcId, SyslogMessage};
+
+/// A `Map` type with `Utf8` keys and nullable values of type `value`, as `MapBuilder` builds
+fn map_type(value: DataType) -> DataType {
+    let entries = Fields::from(vec![
+        Field::new("keys", DataType::Utf8, false),
+        Field::new("values", value, true),
+    ]);
+    DataType::Map(
+        Arc::new(Field::new("entries", DataType::Struct(entries), false)),
+        false,
+    )
+}
+
+/// The schema of every `RecordBatch` that a `RecordBatchBuilder` builds
+pub fn schema() -> SchemaRef {
+    let dictionary = DataType::Dictionary(Box::new(DataType::Int8), Box::new(DataType::Utf8));
+    Arc::new(Schema::new(vec![
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+            true,
+        ),
+        Field::new("utc_offset", DataType::Int32, true),
+        Field::new("hostname", DataType::Utf8, true),
+        Field::new("appname", DataType::Utf8, true),
+        Field::new("procid", DataType::Utf8, true),
+        Field::new("msgid", DataType::Utf8, true),
+        Field::new("severity", dictionary.clone(), false),
+        Field::new("facility", dictionary, false),
+        Field::new("sd", map_type(map_type(DataType::Utf8)), false),
+        Field::new("msg", DataType::Utf8, false),
+    ]))
+}
+
+/// Builds `RecordBatch`es out of messages, one row per message
+pub struct RecordBatchBuilder {
+    schema: SchemaRef,
+    timestamp: TimestampNanosecondBuilder,
+    utc_offset: Int32Builder,
+    hostname: StringBuilder,
+    appname: StringBuilder,
+    procid: StringBuilder,
+    msgid: StringBuilder,
+    severity: StringDictionaryBuilder<Int8Type>,
+    facility: StringDictionaryBuilder<Int8Type>,
+    sd: MapBuilder<StringBuilder, MapBuilder<StringBuilder, StringBuilder>>,
+    msg: StringBuilder,
+    len: usize,
+}
+
+impl RecordBatchBuilder {
+    pub fn new() -> Self {
+        RecordBatchBuilder {
+            schema: schema(),
+            timestamp: TimestampNanosecondBuilder::new().with_timezone("UTC"),
+            utc_offset: Int32Builder::new(),
+            hostname: StringBuilder::new(),
+            appname: StringBuilder::new(),
+            procid: StringBuilder::new(),
+            msgid: StringBuilder::new(),
+            severity: StringDictionaryBuilder::new(),
+            facility: StringDictionaryBuilder::new(),
+            sd: MapBuilder::new(
+                None,
+                StringBuilder::new(),
+                MapBuilder::new(None, StringBuilder::new(), StringBuilder::new()),
+            ),
+            msg: StringBuilder::new(),
+            len: 0,
+        }
+    }
+
+    /// Append a message as a new row
+    pub fn append(&mut self, m: &SyslogMessage) {
+        match m.timestamp {
+            Some(ts) => {
+                let nanos = ts
+                    .secs
+                    .checked_mul(1_000_000_000)
+                    .and_then(|n| n.checked_add(i64::from(ts.nanos)));
+                self.timestamp.append_option(nanos);
+                self.utc_offset.append_value(ts.offset);
+            }
+            None => {
+                self.timestamp.append_null();
+                self.utc_offset.append_null();
+            }
+        }
+        self.hostname.append_option(m.hostname.as_ref());
+        self.appname.append_option(m.appname.as_ref());
+        match m.procid {
+            Some(ProcId::PID(pid)) => self.procid.append_value(pid.to_string()),
+            Some(ProcId::Name(ref name)) => self.procid.append_value(name),
+            None => self.procid.append_null(),
+        }
+        self.msgid.append_option(m.msgid.as_ref());
+        self.severity.append_value(m.severity.as_str());
+        self.facility.append_value(m.facility.as_str());
+        for (sd_id, params) in m.sd.iter() {
+            self.sd.keys().append_value(sd_id);
+            let element = self.sd.values();
+            for (name, value) in params {
+                element.keys().append_value(name);
+                element.values().append_value(value);
+            }
+            element
+                .append(true)
+                .expect("keys and values are appended together");
+        }
+        self.sd
+            .append(true)
+            .expect("keys and values are appended together");
+        self.msg.append_value(&m.msg);
+        self.len += 1;
+    }
+
+    /// The number of rows appended since the last `finish`
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Build a `RecordBatch` out of the rows appended so far, and start over with no rows
+    pub fn finish(&mut self) -> RecordBatch {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.timestamp.finish()),
+            Arc::new(self.utc_offset.finish()),
+            Arc::new(self.hostname.finish()),
+            Arc::new(self.appname.finish()),
+            Arc::new(self.procid.finish()),
+            Arc::new(self.msgid.finish()),
+            Arc::new(self.severity.finish()),
+            Arc::new(self.facility.finish()),
+            Arc::new(self.sd.finish()),
+            Arc::new(self.msg.finish()),
+        ];
+        self.len = 0;
+        RecordBatch::try_new(self.schema.clone(), columns).expect("columns match the schema")
+    }
+}
+
+impl Default for RecordBatchBuilder {
+    fn default() -> Self {
+        RecordBatchBuilder::new()
+    }
+}
+
+impl<'a> Extend<&'a SyslogMessage> for RecordBatchBuilder {
+    fn extend<I: IntoIterator<Item = &'a SyslogMessage>>(&mut self, iter: I) {
+        for m in iter {
+            self.append(m);
+        }
+    }
+}
+
+/// Build a single `RecordBatch` out of `messages`
+pub fn to_record_batch<'a, I>(messages: I) -> RecordBatch
+where
+    I: IntoIterator<Item = &'a SyslogMessage>,
+{
+    let mut builder = RecordBatchBuilder::new();
+    builder.extend(messages);
+    builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Int32Type, Int8Type, TimestampNanosecondType};
+    use arrow_array::Array;
+
+    use super::{schema, to_record_batch, RecordBatchBuilder};
+    use crate::parse_message;
+
+    #[test]
+    fn test_record_batch() {
+        let messages = vec![
+            parse_message(
+                r#"<165>1 2003-10-11T22:14:15.003-07:00 mymachine evntslog 12 ID47 [exampleSDID@32473 iut="3" eventSource="Application"][origin ip="10.0.0.1"] An application event"#,
+            )
+            .unwrap(),
+            parse_message("<14>1 - - - - - - hi").unwrap(),
+        ];
+        let batch = to_record_batch(&messages);
+        assert_eq!(batch.schema(), schema());
+        assert_eq!(batch.num_rows(), 2);
+
+        let timestamp = batch.column(0).as_primitive::<TimestampNanosecondType>();
+        assert_eq!(timestamp.value(0), 1_065_935_655_003_000_000);
+        assert!(timestamp.is_null(1));
+        let utc_offset = batch.column(1).as_primitive::<Int32Type>();
+        assert_eq!(utc_offset.value(0), -7 * 3600);
+        assert!(utc_offset.is_null(1));
+
+        for (i, value) in [(2, "mymachine"), (3, "evntslog"), (4, "12"), (5, "ID47")] {
+            let column = batch.column(i).as_string::<i32>();
+            assert_eq!(column.value(0), value);
+            assert!(column.is_null(1));
+        }
+
+        let severity = batch.column(6).as_dictionary::<Int8Type>();
+        let names = severity.values().as_string::<i32>();
+        let severities = severity
+            .keys()
+            .values()
+            .iter()
+            .map(|&k| names.value(k as usize))
+            .collect::<Vec<_>>();
+        assert_eq!(severities, vec!["notice", "info"]);
+
+        let sd = batch.column(8).as_map();
+        assert_eq!(sd.value_length(0), 2);
+        assert_eq!(sd.value_length(1), 0);
+        let sd_ids = sd.keys().as_string::<i32>();
+        assert_eq!(sd_ids.value(0), "exampleSDID@32473");
+        assert_eq!(sd_ids.value(1), "origin");
+        let elements = sd.values().as_map();
+        assert_eq!(elements.value_length(0), 2);
+        let names = elements.keys().as_string::<i32>();
+        let values = elements.values().as_string::<i32>();
+        assert_eq!(
+            (names.value(0), values.value(0)),
+            ("eventSource", "Application")
+        );
+        assert_eq!((names.value(1), values.value(1)), ("iut", "3"));
+        assert_eq!((names.value(2), values.value(2)), ("ip", "10.0.0.1"));
+
+        let msg = batch.column(9).as_string::<i32>();
+        assert_eq!(msg.value(0), "An application event");
+        assert_eq!(msg.value(1), "hi");
+    }
+
+    #[test]
+    fn test_builder_reuse() {
+        let mut builder = RecordBatchBuilder::new();
+        assert!(builder.is_empty());
+        builder.append(&parse_message("<14>1 - - - - - - one").unwrap());
+        builder.append(&parse_message("<14>1 - - - - - - two").unwrap());
+        assert_eq!(builder.len(), 2);
+        assert_eq!(builder.finish().num_rows(), 2);
+        assert!(builder.is_empty());
+
+        builder.append(&parse_message("<14>1 9999-01-01T00:00:00Z - - - - - three").unwrap());
+        let batch = builder.finish();
+        assert_eq!(batch.num_rows(), 1);
+        assert!(batch.column(0).is_null(0));
+        assert!(batch.column(1).is_valid(0));
+        assert_eq!(batch.column(9).as_string::<i32>().value(0), "three");
+    }
+}
@@ -0,0 +1,275 @@
+//! Zero-copy representation of a single Syslog message.
+//!
+//! A `SyslogMessageRef` borrows all of its string fields from the input it was parsed from, so
+//! parsing one doesn't allocate (except for structured data values containing escapes, which have
+//! to be un-escaped into a new string). Call `.to_owned()` to get a regular `SyslogMessage` once
+//! you need to hold on to it past the lifetime of the input buffer.
+//!
+//! The HOSTNAME, APP-NAME, MSGID, MSG and SD-PARAM values are `Cow`s, so they can still be
+//! rewritten (e.g. `m.hostname = Some(Cow::Owned(canonical))`) without copying everything else;
+//! `into_owned` then moves any owned values across instead of copying them again.
+
+use std::borrow::Cow;
+use std::net::IpAddr;
+
+use crate::facility;
+use crate::message::{
+    self, pid_t, HostnameKind, MultiStructuredData, ProcId, StructuredData, SyslogMessage,
+};
+use crate::severity;
+use crate::timestamp::Timestamp;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Borrowed equivalent of `ProcId`
+pub enum ProcIdRef<'a> {
+    PID(pid_t),
+    Name(&'a str),
+}
+
+impl<'a> ProcIdRef<'a> {
+    pub fn to_owned(&self) -> ProcId {
+        match *self {
+            ProcIdRef::PID(p) => ProcId::PID(p),
+            ProcIdRef::Name(n) => ProcId::Name(n.to_string()),
+        }
+    }
+}
+
+/// A single SD-ELEMENT: an SD-ID and its (SD-PARAM name, SD-PARAM value) pairs
+pub type StructuredDataElementRef<'a> = (&'a str, Vec<(&'a str, Cow<'a, str>)>);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+/// Borrowed equivalent of `StructuredData`
+///
+/// Elements and params are kept in the order in which they appeared in the message, including
+/// any duplicates. Lookups return the *last* matching value, which is the same value that ends up
+/// in the owned `StructuredData`.
+pub struct StructuredDataRef<'a> {
+    elements: Vec<StructuredDataElementRef<'a>>,
+}
+
+impl<'a> StructuredDataRef<'a> {
+    pub fn new_empty() -> Self {
+        StructuredDataRef {
+            elements: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, sd_id: &'a str, params: Vec<(&'a str, Cow<'a, str>)>) {
+        self.elements.push((sd_id, params));
+    }
+
+    /// Lookup by SDID, SDParamID pair
+    pub fn find_tuple(&self, sd_id: &str, sd_param_id: &str) -> Option<&str> {
+        self.elements
+            .iter()
+            .rev()
+            .filter(|(id, _)| *id == sd_id)
+            .flat_map(|(_, params)| params.iter().rev())
+            .find(|(k, _)| *k == sd_param_id)
+            .map(|(_, v)| v.as_ref())
+    }
+
+    /// Iterate over all of the elements, in input order
+    pub fn iter(&self) -> std::slice::Iter<'_, StructuredDataElementRef<'a>> {
+        self.elements.iter()
+    }
+
+    /// The number of SD-ELEMENTs (not necessarily distinct SD-IDs)
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Whether or not this is empty
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Lookup by SDID, SDParamID pair, for rewriting the value in place
+    pub fn find_tuple_mut(&mut self, sd_id: &str, sd_param_id: &str) -> Option<&mut Cow<'a, str>> {
+        self.elements
+            .iter_mut()
+            .rev()
+            .filter(|(id, _)| *id == sd_id)
+            .flat_map(|(_, params)| params.iter_mut().rev())
+            .find(|(k, _)| *k == sd_param_id)
+            .map(|(_, v)| v)
+    }
+
+    pub fn to_owned(&self) -> StructuredData {
+        let mut sd = StructuredData::new_empty();
+        for (sd_id, params) in self.elements.iter() {
+            let sub_map = sd.entry(*sd_id);
+            for (sd_param_id, sd_param_value) in params {
+                sub_map.insert(sd_param_id.to_string(), sd_param_value.to_string());
+            }
+        }
+        sd
+    }
+
+    /// Like `to_owned`, but moving any owned values instead of copying them
+    pub fn into_owned(self) -> StructuredData {
+        let mut sd = StructuredData::new_empty();
+        for (sd_id, params) in self.elements {
+            let sub_map = sd.entry(sd_id);
+            for (sd_param_id, sd_param_value) in params {
+                sub_map.insert(sd_param_id.to_string(), sd_param_value.into_owned());
+            }
+        }
+        sd
+    }
+
+    /// Like `to_owned`, but the result remembers the order of the elements and params
+    pub fn to_owned_ordered(&self) -> StructuredData {
+        let mut sd = StructuredData::new_ordered();
+        for (sd_id, params) in self.elements.iter() {
+            // so that elements without params are kept
+            sd.entry(*sd_id);
+            for (sd_param_id, sd_param_value) in params {
+                sd.insert_tuple(*sd_id, *sd_param_id, sd_param_value.as_ref());
+            }
+        }
+        sd
+    }
+
+    /// Copy into a `MultiStructuredData`, keeping every value of repeated params
+    pub fn to_multi(&self) -> MultiStructuredData {
+        let mut sd = MultiStructuredData::new_empty();
+        for (sd_id, params) in self.elements.iter() {
+            for (sd_param_id, sd_param_value) in params {
+                sd.insert_tuple(*sd_id, *sd_param_id, sd_param_value.as_ref());
+            }
+        }
+        sd
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/// A RFC5424-protocol syslog message which borrows from its input
+pub struct SyslogMessageRef<'a> {
+    pub severity: severity::SyslogSeverity,
+    pub facility: facility::SyslogFacility,
+    pub version: i32,
+    pub timestamp: Option<Timestamp>,
+    pub timestamp_precision_lost: bool,
+    pub leap_second: bool,
+    pub hostname: Option<Cow<'a, str>>,
+    pub appname: Option<Cow<'a, str>>,
+    pub procid: Option<ProcIdRef<'a>>,
+    pub msgid: Option<Cow<'a, str>>,
+    pub sd: StructuredDataRef<'a>,
+    pub msg: Cow<'a, str>,
+    pub msg_is_utf8: bool,
+    pub truncated: bool,
+}
+
+impl<'a> SyslogMessageRef<'a> {
+    /// Whether HOSTNAME is NIL, an IP address or a name; see `SyslogMessage::hostname_kind`
+    pub fn hostname_kind(&self) -> HostnameKind {
+        HostnameKind::of(self.hostname.as_deref())
+    }
+
+    /// HOSTNAME as an IP address, if it is one; see `SyslogMessage::hostname_ip`
+    pub fn hostname_ip(&self) -> Option<IpAddr> {
+        message::hostname_ip(self.hostname.as_deref()?)
+    }
+
+    /// Copy all of the borrowed fields into a new, owned `SyslogMessage`
+    pub fn to_owned(&self) -> SyslogMessage {
+        SyslogMessage {
+            severity: self.severity,
+            facility: self.facility,
+            version: self.version,
+            timestamp: self.timestamp,
+            timestamp_precision_lost: self.timestamp_precision_lost,
+            leap_second: self.leap_second,
+            hostname: self.hostname.as_deref().map(String::from),
+            appname: self.appname.as_deref().map(String::from),
+            procid: self.procid.map(|p| p.to_owned()),
+            msgid: self.msgid.as_deref().map(String::from),
+            sd: self.sd.to_owned(),
+            msg: String::from(&*self.msg),
+            msg_is_utf8: self.msg_is_utf8,
+            msg_bytes: None,
+            truncated: self.truncated,
+        }
+    }
+
+    /// Like `to_owned`, but reusing the fields which are already owned
+    pub fn into_owned(self) -> SyslogMessage {
+        SyslogMessage {
+            severity: self.severity,
+            facility: self.facility,
+            version: self.version,
+            timestamp: self.timestamp,
+            timestamp_precision_lost: self.timestamp_precision_lost,
+            leap_second: self.leap_second,
+            hostname: self.hostname.map(Into::into),
+            appname: self.appname.map(Into::into),
+            procid: self.procid.map(|p| p.to_owned()),
+            msgid: self.msgid.map(Into::into),
+            sd: self.sd.into_owned(),
+            msg: self.msg.into_owned(),
+            msg_is_utf8: self.msg_is_utf8,
+            msg_bytes: None,
+            truncated: self.truncated,
+        }
+    }
+}
+
+impl<'a> From<SyslogMessageRef<'a>> for SyslogMessage {
+    fn from(m: SyslogMessageRef<'a>) -> Self {
+        m.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::parser::parse_message_ref;
+
+    #[test]
+    fn test_borrows_from_input() {
+        let input = String::from(
+            r#"<78>1 2016-01-15T00:04:01Z host1 CROND 10391 - [meta a="1" b="x\"y"][meta a="2"] msg"#,
+        );
+        let m = parse_message_ref(&input).expect("should parse");
+        assert_eq!(m.hostname.as_deref(), Some("host1"));
+        assert_eq!(m.msg, "msg");
+        let range = input.as_bytes().as_ptr_range();
+        assert!(matches!(m.hostname, Some(Cow::Borrowed(_))));
+        assert!(range.contains(&m.hostname.as_ref().unwrap().as_ptr()));
+        assert!(range.contains(&m.msg.as_ptr()));
+        assert_eq!(m.sd.len(), 2);
+        assert_eq!(m.sd.find_tuple("meta", "a"), Some("2"));
+        assert_eq!(m.sd.find_tuple("meta", "b"), Some(r#"x"y"#));
+        let escaped = &m.sd.iter().next().unwrap().1[1].1;
+        assert!(matches!(escaped, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_to_owned_matches_owned_parse() {
+        let input =
+            r#"<78>1 2016-01-15T00:04:01Z host1 CROND 10391 - [meta a="1"][meta a="2" b="3"] msg"#;
+        let borrowed = parse_message_ref(input).expect("should parse");
+        let owned = crate::parse_message(input).expect("should parse");
+        assert_eq!(borrowed.to_owned(), owned);
+        assert_eq!(borrowed.into_owned(), owned);
+        assert_eq!(owned.sd.find_tuple("meta", "a"), Some(&"2".to_string()));
+    }
+
+    #[test]
+    fn test_rewrite_fields() {
+        let input = r#"<78>1 2016-01-15T00:04:01Z host1 CROND 10391 - [meta a="1"] msg"#;
+        let mut m = parse_message_ref(input).expect("should parse");
+        m.hostname = Some(Cow::Owned("host1.example.com".to_string()));
+        m.msg.to_mut().push_str(" (relayed)");
+        *m.sd.find_tuple_mut("meta", "a").unwrap() = Cow::Borrowed("2");
+        let owned = m.into_owned();
+        assert_eq!(owned.hostname.as_deref(), Some("host1.example.com"));
+        assert_eq!(owned.appname.as_deref(), Some("CROND"));
+        assert_eq!(owned.msg, "msg (relayed)");
+        assert_eq!(owned.sd.find_tuple("meta", "a"), Some(&"2".to_string()));
+    }
+}
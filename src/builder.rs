@@ -0,0 +1,279 @@
+//! Constructing messages from scratch.
+//!
+//! `SyslogMessage` has a lot of fields, most of which an emitter doesn't care about;
+//! `SyslogMessageBuilder` fills them in with sensible defaults and checks the result against
+//! RFC 5424 (see the `validation` module) when you're done.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::{SyslogFacility, SyslogMessageBuilder, SyslogSeverity};
+//!
+//! let m = SyslogMessageBuilder::new()
+//!     .severity(SyslogSeverity::SEV_WARNING)
+//!     .facility(SyslogFacility::LOG_LOCAL0)
+//!     .hostname("myhost")
+//!     .appname("myapp")
+//!     .sd_element("meta", vec![("sequenceId", "1")])
+//!     .msg("disk almost full")
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(m.sd.find_tuple("meta", "sequenceId"), Some(&"1".to_string()));
+//! ```
+
+use crate::facility::SyslogFacility;
+use crate::message::{pid_t, ProcId, StructuredData, SyslogMessage};
+use crate::severity::SyslogSeverity;
+use crate::validation::{self, Field, Violation};
+
+#[derive(Clone, Debug)]
+/// A builder for `SyslogMessage`
+///
+/// Defaults to version 1, `LOG_USER`/`SEV_NOTICE` (like `logger(1)`), the current time in UTC,
+/// NIL header fields, no structured data and an empty MSG.
+pub struct SyslogMessageBuilder {
+    message: SyslogMessage,
+}
+
+impl SyslogMessageBuilder {
+    pub fn new() -> Self {
+        let mut message = SyslogMessage {
+            severity: SyslogSeverity::SEV_NOTICE,
+            facility: SyslogFacility::LOG_USER,
+            version: 1,
+            timestamp: None,
+            timestamp_precision_lost: false,
+            leap_second: false,
+            hostname: None,
+            appname: None,
+            procid: None,
+            msgid: None,
+            sd: StructuredData::new_empty(),
+            msg: String::new(),
+            msg_is_utf8: false,
+            msg_bytes: None,
+            truncated: false,
+        };
+        message.set_timestamp_offset_datetime(time::OffsetDateTime::now_utc());
+        SyslogMessageBuilder { message }
+    }
+
+    pub fn severity(mut self, severity: SyslogSeverity) -> Self {
+        self.message.severity = severity;
+        self
+    }
+
+    pub fn facility(mut self, facility: SyslogFacility) -> Self {
+        self.message.facility = facility;
+        self
+    }
+
+    pub fn version(mut self, version: i32) -> Self {
+        self.message.version = version;
+        self
+    }
+
+    /// Set the timestamp, keeping its UTC offset
+    pub fn timestamp(mut self, timestamp: time::OffsetDateTime) -> Self {
+        self.message.set_timestamp_offset_datetime(timestamp);
+        self
+    }
+
+    /// Set the timestamp from a `chrono::DateTime`, keeping its UTC offset
+    #[cfg(feature = "chrono")]
+    pub fn timestamp_datetime<Tz: chrono::TimeZone>(mut self, dt: &chrono::DateTime<Tz>) -> Self {
+        self.message.set_timestamp_datetime(dt);
+        self
+    }
+
+    /// Set the timestamp from a `SystemTime`, in UTC
+    pub fn timestamp_systemtime(mut self, t: std::time::SystemTime) -> Self {
+        self.message.set_timestamp_systemtime(t);
+        self
+    }
+
+    /// Send a NIL timestamp instead of the current time
+    pub fn no_timestamp(mut self) -> Self {
+        self.message.timestamp = None;
+        self
+    }
+
+    pub fn hostname<S: Into<String>>(mut self, hostname: S) -> Self {
+        self.message.hostname = Some(hostname.into());
+        self
+    }
+
+    pub fn appname<S: Into<String>>(mut self, appname: S) -> Self {
+        self.message.appname = Some(appname.into());
+        self
+    }
+
+    pub fn procid(mut self, procid: ProcId) -> Self {
+        self.message.procid = Some(procid);
+        self
+    }
+
+    /// Shorthand for `procid(ProcId::PID(pid))`
+    pub fn pid(self, pid: pid_t) -> Self {
+        self.procid(ProcId::PID(pid))
+    }
+
+    pub fn msgid<S: Into<String>>(mut self, msgid: S) -> Self {
+        self.message.msgid = Some(msgid.into());
+        self
+    }
+
+    /// Add an SD-ELEMENT; calling this again with the same SD-ID adds to the existing element
+    pub fn sd_element<I, K, V>(mut self, sd_id: &str, params: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let element = self.message.sd.entry(sd_id);
+        for (k, v) in params {
+            element.insert(k.into(), v.into());
+        }
+        self
+    }
+
+    /// Like `sd_element`, but checks the SD-ID and PARAM-NAMEs straight away
+    ///
+    /// `build` checks them too; this is for reporting the problem closer to its source. Nothing
+    /// is added if any of the names is invalid.
+    pub fn try_sd_element<I, K, V>(mut self, sd_id: &str, params: I) -> Result<Self, Vec<Violation>>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let mut element = StructuredData::new_empty();
+        element.entry(sd_id);
+        for (k, v) in params {
+            element.try_insert_tuple(sd_id, k, v)?;
+        }
+        validation::validate_sd_name(Field::SdId(sd_id.to_string()), sd_id)?;
+        self.message.sd.merge(element);
+        Ok(self)
+    }
+
+    pub fn msg<S: Into<String>>(mut self, msg: S) -> Self {
+        self.message.msg = msg.into();
+        self
+    }
+
+    /// Set MSG from raw bytes, which needn't be valid UTF-8; see `SyslogMessage::set_msg_bytes`
+    pub fn msg_bytes<B: Into<Vec<u8>>>(mut self, bytes: B) -> Self {
+        self.message.set_msg_bytes(bytes.into());
+        self
+    }
+
+    /// Mark MSG as UTF-8, so that it's serialized with a leading BOM
+    pub fn msg_is_utf8(mut self, msg_is_utf8: bool) -> Self {
+        self.message.msg_is_utf8 = msg_is_utf8;
+        self
+    }
+
+    /// Check the message against RFC 5424 and return it
+    ///
+    /// Returns every violation found if it doesn't conform.
+    pub fn build(self) -> Result<SyslogMessage, Vec<Violation>> {
+        validation::validate(&self.message)?;
+        Ok(self.message)
+    }
+
+    /// Return the message without checking it
+    pub fn build_unchecked(self) -> SyslogMessage {
+        self.message
+    }
+}
+
+impl Default for SyslogMessageBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyslogMessage {
+    /// Start building a new message; see `SyslogMessageBuilder`
+    pub fn builder() -> SyslogMessageBuilder {
+        SyslogMessageBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SyslogMessageBuilder;
+    use crate::message::{ProcId, SyslogMessage};
+    use crate::validation::{Field, Violation};
+    use crate::{SyslogFacility, SyslogSeverity};
+
+    #[test]
+    fn test_defaults() {
+        let m = SyslogMessage::builder().build().expect("should be valid");
+        assert_eq!(m.version, 1);
+        assert_eq!(m.severity, SyslogSeverity::SEV_NOTICE);
+        assert_eq!(m.facility, SyslogFacility::LOG_USER);
+        assert!(m.timestamp.is_some());
+        assert_eq!(m.timestamp.map(|t| t.offset), Some(0));
+        assert!(m.hostname.is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let timestamp = time::OffsetDateTime::from_unix_timestamp(1065890655)
+            .unwrap()
+            .replace_nanosecond(3_000_000)
+            .unwrap()
+            .to_offset(time::UtcOffset::from_hms(5, 30, 0).unwrap());
+        let m = SyslogMessageBuilder::new()
+            .facility(SyslogFacility::LOG_LOCAL0)
+            .severity(SyslogSeverity::SEV_INFO)
+            .timestamp(timestamp)
+            .hostname("mymachine")
+            .appname("su")
+            .pid(42)
+            .msgid("ID47")
+            .sd_element("meta", vec![("a", "1")])
+            .sd_element("meta", vec![("b", "2")])
+            .msg("hi")
+            .build()
+            .expect("should be valid");
+        assert_eq!(
+            m.to_string(),
+            r#"<134>1 2003-10-11T22:14:15.003+05:30 mymachine su 42 ID47 [meta a="1" b="2"] hi"#
+        );
+        assert_eq!(crate::parse_message(m.to_string()).unwrap(), m);
+        assert_eq!(m.procid, Some(ProcId::PID(42)));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let errors = SyslogMessageBuilder::new()
+            .no_timestamp()
+            .appname("my app")
+            .build()
+            .expect_err("should be invalid");
+        assert_eq!(
+            errors,
+            vec![Violation::NotPrintUsAscii {
+                field: Field::AppName,
+                found: ' '
+            }]
+        );
+        let errors = SyslogMessageBuilder::new()
+            .try_sd_element("ok@1", vec![("a=b", "v")])
+            .expect_err("should be invalid");
+        assert_eq!(
+            errors,
+            vec![Violation::InvalidSdNameChar {
+                field: Field::SdParamName(String::from("ok@1"), String::from("a=b")),
+                found: '='
+            }]
+        );
+        let m = SyslogMessageBuilder::new()
+            .appname("my app")
+            .build_unchecked();
+        assert_eq!(m.appname.as_deref(), Some("my app"));
+    }
+}
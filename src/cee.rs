@@ -0,0 +1,177 @@
+//! JSON payloads in MSG, as written for rsyslog's `mmjsonparse` (requires the `cee` feature).
+//!
+//! Many applications log a JSON object as MSG, either prefixed with the `@cee:` cookie from the
+//! CEE (Common Event Expression) logging format or on its own, and mix these messages with plain
+//! text ones. `SyslogMessage::cee_fields` parses the JSON out of such a message, and
+//! `SyslogMessage::merge_cee_into_sd` turns it into STRUCTURED-DATA, so that the fields can be
+//! filtered on and passed on like any others. Messages with no JSON in MSG are left alone.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::cee::DEFAULT_SD_ID;
+//! use syslog_rfc5424::parse_message;
+//!
+//! let mut m = parse_message(
+//!     r#"<14>1 - host app - - - @cee: {"user":"bob","req":{"status":404}}"#,
+//! ).unwrap();
+//! assert!(m.merge_cee_into_sd(DEFAULT_SD_ID).unwrap());
+//! assert_eq!(m.sd.find_tuple(DEFAULT_SD_ID, "req.status"), Some(&"404".to_string()));
+//! assert_eq!(m.msg, "");
+//!
+//! let mut m = parse_message("<14>1 - host app - - - just text").unwrap();
+//! assert!(!m.merge_cee_into_sd(DEFAULT_SD_ID).unwrap());
+//! ```
+
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::parser::BOM;
+use crate::validation;
+
+/// The cookie which marks MSG as a CEE payload
+pub const CEE_COOKIE: &str = "@cee:";
+
+/// The SD-ID fields are merged into by default
+///
+/// 32473 is the enterprise number reserved for documentation (RFC 5612); use your own if you have
+/// one.
+pub const DEFAULT_SD_ID: &str = "cee@32473";
+
+/// The fields of a JSON payload
+pub type Fields = serde_json::Map<String, Value>;
+
+#[derive(Debug, Error)]
+pub enum CeeError {
+    #[error("invalid JSON in MSG: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("JSON in MSG is not an object")]
+    NotAnObject,
+}
+
+/// Find the JSON in MSG: whatever follows an `@cee:` cookie, or all of it if it starts with `{`
+///
+/// Leading whitespace and a BOM are skipped. Returns `None` if MSG doesn't look like JSON.
+pub fn find_json(msg: &str) -> Option<&str> {
+    let msg = msg.trim_start_matches(|c: char| c == BOM || c.is_whitespace());
+    if let Some(json) = msg.strip_prefix(CEE_COOKIE) {
+        Some(json.trim_start())
+    } else if msg.starts_with('{') {
+        Some(msg)
+    } else {
+        None
+    }
+}
+
+/// Parse the JSON object in MSG, if there is one (see `find_json`)
+///
+/// A MSG which looks like JSON but isn't a valid JSON object is an error, rather than `None`.
+pub fn parse_fields(msg: &str) -> Result<Option<Fields>, CeeError> {
+    let json = match find_json(msg) {
+        Some(json) => json,
+        None => return Ok(None),
+    };
+    match serde_json::from_str(json)? {
+        Value::Object(fields) => Ok(Some(fields)),
+        _ => Err(CeeError::NotAnObject),
+    }
+}
+
+/// Flatten fields into SD-PARAM (name, value) pairs
+///
+/// Nested objects and arrays are joined with `.` (so `{"a":{"b":[1,2]}}` gives `a.b.0` and
+/// `a.b.1`), and names are made into valid SD-NAMEs by replacing anything which isn't allowed with
+/// `_` and cutting them down to 32 characters. Strings are used as-is, other values as JSON, and
+/// `null`s are left out.
+pub fn flatten(fields: &Fields) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    for (name, value) in fields {
+        flatten_value(name.clone(), value, &mut params);
+    }
+    params
+}
+
+fn flatten_value(name: String, value: &Value, params: &mut Vec<(String, String)>) {
+    match value {
+        Value::Null => {}
+        Value::String(s) => params.push((validation::sanitize_sd_name(&name), s.clone())),
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                flatten_value(format!("{}.{}", name, key), value, params);
+            }
+        }
+        Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                flatten_value(format!("{}.{}", name, i), value, params);
+            }
+        }
+        Value::Bool(_) | Value::Number(_) => {
+            params.push((validation::sanitize_sd_name(&name), value.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_json, flatten, parse_fields, CeeError};
+    use crate::parse_message;
+
+    #[test]
+    fn test_find_json() {
+        assert_eq!(find_json(r#"@cee: {"a":1}"#), Some(r#"{"a":1}"#));
+        assert_eq!(find_json(r#"@cee:{"a":1}"#), Some(r#"{"a":1}"#));
+        assert_eq!(find_json(" \u{feff}{\"a\":1}"), Some(r#"{"a":1}"#));
+        assert_eq!(find_json("plain text {\"a\":1}"), None);
+        assert_eq!(find_json(""), None);
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let fields = parse_fields(r#"@cee: {"a":"x","b":[1,{"c":true}],"d":null} "#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            flatten(&fields),
+            vec![
+                ("a".to_string(), "x".to_string()),
+                ("b.0".to_string(), "1".to_string()),
+                ("b.1.c".to_string(), "true".to_string()),
+            ]
+        );
+        assert!(parse_fields("no json here").unwrap().is_none());
+        assert!(matches!(
+            parse_fields("@cee: [1]"),
+            Err(CeeError::NotAnObject)
+        ));
+        assert!(matches!(parse_fields("{broken"), Err(CeeError::Json(_))));
+        assert!(matches!(
+            parse_fields(r#"{"a":1} and then text"#),
+            Err(CeeError::Json(_))
+        ));
+
+        let fields = parse_fields(r#"{"a b=\"c\"]":"v"}"#).unwrap().unwrap();
+        assert_eq!(flatten(&fields)[0].0, "a_b__c__");
+    }
+
+    #[test]
+    fn test_merge_into_sd() {
+        let mut m = parse_message(
+            r#"<14>1 - host app - - [cee@32473 old="1" user="alice"] @cee: {"user":"bob"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            m.cee_fields().unwrap().unwrap()["user"],
+            serde_json::json!("bob")
+        );
+        assert!(m.merge_cee_into_sd("cee@32473").unwrap());
+        assert_eq!(
+            m.to_string(),
+            r#"<14>1 - host app - - [cee@32473 old="1" user="bob"]"#
+        );
+
+        let mut m = parse_message("<14>1 - host app - - - {oops").unwrap();
+        assert!(m.merge_cee_into_sd("cee@32473").is_err());
+        assert_eq!(m.msg, "{oops");
+        assert!(m.sd.is_empty());
+    }
+}
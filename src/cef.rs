@@ -0,0 +1,358 @@
+//! ArcSight Common Event Format payloads in MSG.
+//!
+//! Firewalls and other security appliances often send a CEF string as MSG:
+//!
+//! ```text
+//! CEF:0|Security|threatmanager|1.0|100|worm successfully stopped|10|src=10.0.0.1 dst=2.1.2.2 spt=1232
+//! ```
+//!
+//! `SyslogMessage::cef_event` parses it into a `CefEvent`, and `SyslogMessage::merge_cef_into_sd`
+//! turns it into STRUCTURED-DATA. Messages without a CEF string are left alone.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::parse_message;
+//!
+//! let mut m = parse_message(
+//!     "<134>1 - fw1 - - - - CEF:0|Acme|Firewall|2.1|4000|Port scan|7|src=10.0.0.1 msg=scan of 22\\=ssh",
+//! ).unwrap();
+//! let event = m.cef_event().unwrap().unwrap();
+//! assert_eq!(event.name, "Port scan");
+//! assert_eq!(event.extension("msg"), Some("scan of 22=ssh"));
+//!
+//! assert!(m.merge_cef_into_sd("cef@32473").unwrap());
+//! assert_eq!(m.sd.find_tuple("cef@32473", "src"), Some(&"10.0.0.1".to_string()));
+//! assert_eq!(m.sd.find_tuple("cef@32473", "deviceVendor"), Some(&"Acme".to_string()));
+//! ```
+
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::message::{SDIDType, SyslogMessage};
+
+/// The marker CEF strings start with
+pub const CEF_PREFIX: &str = "CEF:";
+
+/// The SD-ID events are merged into by default
+///
+/// 32473 is the enterprise number reserved for documentation (RFC 5612); use your own if you have
+/// one.
+pub const DEFAULT_SD_ID: &str = "cef@32473";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CefError {
+    #[error("not a CEF string")]
+    MissingPrefix,
+    #[error("invalid CEF version {0:?}")]
+    InvalidVersion(String),
+    #[error("CEF header is missing the {0} field")]
+    MissingField(&'static str),
+    #[error("invalid CEF extension at byte {0}")]
+    InvalidExtension(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A parsed CEF event
+pub struct CefEvent {
+    pub version: u32,
+    pub device_vendor: String,
+    pub device_product: String,
+    pub device_version: String,
+    /// The Device Event Class ID (`signatureId` in the CEF dictionary)
+    pub signature_id: String,
+    pub name: String,
+    /// `0` to `10`, or (since CEF 1) `Unknown`, `Low`, `Medium`, `High` or `Very-High`
+    pub severity: String,
+    /// The extension's (key, value) pairs, in order, unescaped
+    pub extensions: Vec<(String, String)>,
+}
+
+impl CefEvent {
+    /// The value of an extension, if it's present
+    ///
+    /// If a key appears more than once, the last value wins.
+    pub fn extension(&self, key: &str) -> Option<&str> {
+        self.extensions
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The header fields as (CEF dictionary key, value) pairs, followed by the extensions
+    pub fn to_params(&self) -> Vec<(String, String)> {
+        let version = self.version.to_string();
+        let header = [
+            ("cefVersion", &version),
+            ("deviceVendor", &self.device_vendor),
+            ("deviceProduct", &self.device_product),
+            ("deviceVersion", &self.device_version),
+            ("signatureId", &self.signature_id),
+            ("name", &self.name),
+            ("severity", &self.severity),
+        ];
+        header
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .chain(self.extensions.iter().cloned())
+            .collect()
+    }
+}
+
+impl FromStr for CefEvent {
+    type Err = CefError;
+
+    /// Parse a CEF string, which must start with `CEF:`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix(CEF_PREFIX).ok_or(CefError::MissingPrefix)?;
+        let mut fields = Vec::with_capacity(7);
+        let mut start = 0;
+        let mut escaped = false;
+        for (i, c) in s.char_indices() {
+            if fields.len() == 7 {
+                break;
+            }
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '|' => {
+                    fields.push(unescape_header(&s[start..i]));
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        const NAMES: [&str; 7] = [
+            "Version",
+            "Device Vendor",
+            "Device Product",
+            "Device Version",
+            "Device Event Class ID",
+            "Name",
+            "Severity",
+        ];
+        if fields.len() < 7 {
+            return Err(CefError::MissingField(NAMES[fields.len()]));
+        }
+        let extension_offset = CEF_PREFIX.len() + start;
+        let mut fields = fields.into_iter();
+        let mut next = || fields.next().unwrap_or_default();
+        let version = next();
+        Ok(CefEvent {
+            version: version
+                .trim()
+                .parse()
+                .map_err(|_| CefError::InvalidVersion(version.clone()))?,
+            device_vendor: next(),
+            device_product: next(),
+            device_version: next(),
+            signature_id: next(),
+            name: next(),
+            severity: next(),
+            extensions: parse_extensions(&s[start..], extension_offset)?,
+        })
+    }
+}
+
+/// Find and parse the CEF string in MSG, if there is one
+///
+/// The string may come after other text, like the RFC 3164 style header some devices put before
+/// it. Returns the text before it, and the event.
+pub fn find_cef(msg: &str) -> Result<Option<(&str, CefEvent)>, CefError> {
+    match msg.find(CEF_PREFIX) {
+        Some(i) => Ok(Some((&msg[..i], msg[i..].trim_end().parse()?))),
+        None => Ok(None),
+    }
+}
+
+fn unescape_header(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c @ ('|' | '\\')) => out.push(c),
+                Some(c) => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                None => out.push('\\'),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape_value(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some(c @ ('=' | '\\')) => out.push(c),
+                Some(c) => {
+                    out.push('\\');
+                    out.push(c);
+                }
+                None => out.push('\\'),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn is_key_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'-' | b'[' | b']')
+}
+
+/// The length of the key at the start of `s`, if `s` starts with `key=`
+fn key_len(s: &[u8]) -> Option<usize> {
+    let len = s.iter().take_while(|&&b| is_key_char(b)).count();
+    if len > 0 && s.get(len) == Some(&b'=') {
+        Some(len)
+    } else {
+        None
+    }
+}
+
+/// Parse `key=value key=value ...`, where values run until the next ` key=`
+fn parse_extensions(s: &str, offset: usize) -> Result<Vec<(String, String)>, CefError> {
+    let bytes = s.as_bytes();
+    let mut extensions = Vec::new();
+    let mut pos = bytes.iter().take_while(|&&b| b == b' ').count();
+    while pos < bytes.len() {
+        let len = key_len(&bytes[pos..]).ok_or(CefError::InvalidExtension(offset + pos))?;
+        let key = &s[pos..pos + len];
+        let value_start = pos + len + 1;
+        let mut end = value_start;
+        let mut next = bytes.len();
+        while end < bytes.len() {
+            match bytes[end] {
+                b'\\' => end += 2,
+                b' ' => {
+                    let key_start = end + bytes[end..].iter().take_while(|&&b| b == b' ').count();
+                    if key_start == bytes.len() || key_len(&bytes[key_start..]).is_some() {
+                        next = key_start;
+                        break;
+                    }
+                    end += 1;
+                }
+                _ => end += 1,
+            }
+        }
+        let end = end.min(bytes.len());
+        extensions.push((key.to_string(), unescape_value(&s[value_start..end])));
+        pos = next;
+    }
+    Ok(extensions)
+}
+
+impl SyslogMessage {
+    /// The CEF event in MSG, if it has one; see the `cef` module
+    pub fn cef_event(&self) -> Result<Option<CefEvent>, CefError> {
+        Ok(find_cef(&self.msg)?.map(|(_, event)| event))
+    }
+
+    /// Move the CEF event in MSG into the SD-ELEMENT `sd_id`
+    ///
+    /// The header fields and extensions become SD-PARAMs (see `CefEvent::to_params`), replacing
+    /// any of the same name, and MSG is cut down to whatever came before the CEF string. Returns
+    /// whether MSG had a CEF string; if it's invalid, the message is left as it was.
+    pub fn merge_cef_into_sd<SI: Into<SDIDType>>(&mut self, sd_id: SI) -> Result<bool, CefError> {
+        match find_cef(&self.msg)? {
+            Some((before, event)) => {
+                let before = before.trim_end().len();
+                self.merge_params_into_sd(sd_id.into(), before, event.to_params());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_cef, CefError, CefEvent};
+    use crate::parse_message;
+
+    #[test]
+    fn test_parse() {
+        let event: CefEvent =
+            r"CEF:0|Security|threat\|manager|1.0|100|worm successfully stopped|10|src=10.0.0.1 dst=2.1.2.2 msg=a b\=c\\d\nnext  cs1Label=Path cs1=C:\temp|x"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            event,
+            CefEvent {
+                version: 0,
+                device_vendor: "Security".into(),
+                device_product: "threat|manager".into(),
+                device_version: "1.0".into(),
+                signature_id: "100".into(),
+                name: "worm successfully stopped".into(),
+                severity: "10".into(),
+                extensions: vec![
+                    ("src".into(), "10.0.0.1".into()),
+                    ("dst".into(), "2.1.2.2".into()),
+                    ("msg".into(), "a b=c\\d\nnext".into()),
+                    ("cs1Label".into(), "Path".into()),
+                    ("cs1".into(), r"C:\temp|x".into()),
+                ],
+            }
+        );
+        assert_eq!(event.extension("cs1"), Some(r"C:\temp|x"));
+        assert_eq!(event.extension("nope"), None);
+
+        let event: CefEvent = "CEF:1|V|P|1|sig|name|High|".parse().unwrap();
+        assert_eq!(event.severity, "High");
+        assert!(event.extensions.is_empty());
+
+        assert_eq!(
+            "LEEF:1.0|x".parse::<CefEvent>(),
+            Err(CefError::MissingPrefix)
+        );
+        assert_eq!(
+            "CEF:0|V|P|1|sig|".parse::<CefEvent>(),
+            Err(CefError::MissingField("Name"))
+        );
+        assert_eq!(
+            "CEF:x|V|P|1|sig|name|1|".parse::<CefEvent>(),
+            Err(CefError::InvalidVersion("x".into()))
+        );
+        assert_eq!(
+            "CEF:0|V|P|1|sig|name|1|=oops".parse::<CefEvent>(),
+            Err(CefError::InvalidExtension(23))
+        );
+    }
+
+    #[test]
+    fn test_find_cef() {
+        let (before, event) = find_cef("Sep 19 08:26:10 host CEF:0|V|P|1|sig|name|1|a=b \n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(before, "Sep 19 08:26:10 host ");
+        assert_eq!(event.extensions, vec![("a".into(), "b".into())]);
+        assert_eq!(find_cef("just text"), Ok(None));
+    }
+
+    #[test]
+    fn test_merge_into_sd() {
+        let mut m =
+            parse_message("<134>1 - fw1 - - - - CEF:0|V|P|1|sig|name|5|src=10.0.0.1").unwrap();
+        assert!(m.merge_cef_into_sd("cef@32473").unwrap());
+        assert_eq!(
+            m.to_string(),
+            r#"<134>1 - fw1 - - - [cef@32473 cefVersion="0" deviceProduct="P" deviceVendor="V" deviceVersion="1" name="name" severity="5" signatureId="sig" src="10.0.0.1"]"#
+        );
+
+        let mut m = parse_message("<134>1 - fw1 - - - - plain text").unwrap();
+        assert!(!m.merge_cef_into_sd("cef@32473").unwrap());
+        assert_eq!(m.msg, "plain text");
+    }
+}
@@ -0,0 +1,279 @@
+//! A `tokio_util::codec` implementation for syslog streams (requires the `tokio` feature).
+//!
+//! `SyslogCodec` does both halves of the job: it splits incoming bytes into frames and parses
+//! them, and it serializes outgoing messages and frames them, so it works with `FramedRead`,
+//! `FramedWrite` and `Framed` over any TCP (or other stream) connection.
+//!
+//! # Example
+//!
+//! ```
+//! use bytes::BytesMut;
+//! use syslog_rfc5424::codec::SyslogCodec;
+//! use tokio_util::codec::{Decoder, Encoder};
+//!
+//! let mut codec = SyslogCodec::octet_counting();
+//! let message = syslog_rfc5424::parse_message("<1>1 - host - - - - hi").unwrap();
+//! let mut buf = BytesMut::new();
+//! codec.encode(&message, &mut buf).unwrap();
+//! assert_eq!(&buf[..], b"22 <1>1 - host - - - - hi");
+//! assert_eq!(codec.decode(&mut buf).unwrap(), Some(message));
+//! ```
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::encoder;
+use crate::framing::{FrameBuf, NonTransparentDecoder, OctetCountingDecoder, Trailer};
+use crate::message::SyslogMessage;
+use crate::parser::{parse_message_bytes, Utf8Policy};
+use crate::stream::StreamError;
+
+#[derive(Clone, Debug)]
+enum Framing {
+    OctetCounting(OctetCountingDecoder),
+    NonTransparent(NonTransparentDecoder, Trailer),
+}
+
+#[derive(Clone, Debug)]
+/// Decodes and encodes framed syslog messages
+///
+/// Note that `FramedRead` stops at the first error, so a single malformed message will end the
+/// stream. Non-transparent framing can't represent a MSG containing the trailer; such messages
+/// are sent as-is and will be split in two by the receiver.
+pub struct SyslogCodec {
+    framing: Framing,
+    utf8_policy: Utf8Policy,
+}
+
+impl SyslogCodec {
+    /// Use octet-counting framing (RFC 6587 §3.4.1); this is what RFC 5425 requires
+    pub fn octet_counting() -> Self {
+        SyslogCodec {
+            framing: Framing::OctetCounting(OctetCountingDecoder::new()),
+            utf8_policy: Utf8Policy::Strict,
+        }
+    }
+
+    /// Use non-transparent framing (RFC 6587 §3.4.2), terminating each message with `trailer`
+    pub fn non_transparent(trailer: Trailer) -> Self {
+        SyslogCodec {
+            framing: Framing::NonTransparent(NonTransparentDecoder::new(trailer), trailer),
+            utf8_policy: Utf8Policy::Strict,
+        }
+    }
+
+    /// Use newline-terminated framing; shorthand for `non_transparent(Trailer::LF)`
+    pub fn newline() -> Self {
+        Self::non_transparent(Trailer::LF)
+    }
+
+    /// Set the largest frame which will be accepted when decoding
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.framing = match self.framing {
+            Framing::OctetCounting(d) => {
+                Framing::OctetCounting(d.with_max_frame_length(max_frame_length))
+            }
+            Framing::NonTransparent(d, trailer) => {
+                Framing::NonTransparent(d.with_max_frame_length(max_frame_length), trailer)
+            }
+        };
+        self
+    }
+
+    /// Set how frames which aren't valid UTF-8 are handled when decoding
+    pub fn with_utf8_policy(mut self, utf8_policy: Utf8Policy) -> Self {
+        self.utf8_policy = utf8_policy;
+        self
+    }
+
+    fn parse(&self, frame: Option<BytesMut>) -> Result<Option<SyslogMessage>, StreamError> {
+        match frame {
+            Some(frame) => Ok(Some(parse_message_bytes(&frame, self.utf8_policy)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+// Frames are split straight off the front of the read buffer, rather than going through the
+// decoders' own buffers.
+impl FrameBuf for BytesMut {
+    type Frame = BytesMut;
+
+    fn split_frame(&mut self, n: usize) -> BytesMut {
+        self.split_to(n)
+    }
+
+    fn skip(&mut self, n: usize) {
+        self.advance(n)
+    }
+
+    fn clear(&mut self) {
+        BytesMut::clear(self)
+    }
+}
+
+impl Decoder for SyslogCodec {
+    type Item = SyslogMessage;
+    type Error = StreamError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<SyslogMessage>, StreamError> {
+        let frame = match self.framing {
+            Framing::OctetCounting(ref mut d) => d.next_frame_in(src)?,
+            Framing::NonTransparent(ref mut d, _) => d.next_frame_in(src)?,
+        };
+        self.parse(frame)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<SyslogMessage>, StreamError> {
+        if let Some(m) = self.decode(src)? {
+            return Ok(Some(m));
+        }
+        let frame = match self.framing {
+            Framing::OctetCounting(ref mut d) => d.finish_in(src)?,
+            Framing::NonTransparent(ref mut d, _) => d.finish_in(src)?,
+        };
+        self.parse(frame)
+    }
+}
+
+fn encode_body(m: &SyslogMessage, dst: &mut BytesMut) {
+    // writing into a BytesMut can't fail
+    match m.msg_bytes {
+        Some(ref bytes) => {
+            let _ = encoder::write_header(dst, m);
+            dst.put_slice(encoder::msg_prefix(m).as_bytes());
+            dst.put_slice(bytes);
+        }
+        None => {
+            let _ = encoder::write_message(dst, m);
+        }
+    }
+}
+
+impl<'a> Encoder<&'a SyslogMessage> for SyslogCodec {
+    type Error = StreamError;
+
+    fn encode(&mut self, m: &'a SyslogMessage, dst: &mut BytesMut) -> Result<(), StreamError> {
+        match self.framing {
+            Framing::OctetCounting(_) => {
+                // MSG-LEN comes first but isn't known until the message is written, so write the
+                // message and then rotate its length in front of it
+                let start = dst.len();
+                encode_body(m, dst);
+                let header = format!("{} ", dst.len() - start);
+                dst.put_slice(header.as_bytes());
+                dst[start..].rotate_right(header.len());
+            }
+            Framing::NonTransparent(_, trailer) => {
+                encode_body(m, dst);
+                dst.put_slice(match trailer {
+                    Trailer::LF => b"\n",
+                    Trailer::CRLF => b"\r\n",
+                    Trailer::NUL => b"\0",
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Encoder<SyslogMessage> for SyslogCodec {
+    type Error = StreamError;
+
+    fn encode(&mut self, m: SyslogMessage, dst: &mut BytesMut) -> Result<(), StreamError> {
+        self.encode(&m, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use super::SyslogCodec;
+    use crate::framing::Trailer;
+    use crate::parse_message;
+    use crate::stream::StreamError;
+
+    #[test]
+    fn test_round_trip() {
+        let messages = vec![
+            parse_message("<1>1 - host1 - - - - caf\u{e9}").unwrap(),
+            parse_message("<78>1 2016-01-15T00:04:01Z host2 CROND 10391 - [a b=\"c\"] two")
+                .unwrap(),
+        ];
+        for mut codec in [
+            SyslogCodec::octet_counting(),
+            SyslogCodec::newline(),
+            SyslogCodec::non_transparent(Trailer::NUL),
+        ] {
+            let mut buf = BytesMut::new();
+            for m in messages.iter() {
+                codec.encode(m, &mut buf).expect("should encode");
+            }
+            // feed the decoder a byte at a time to exercise partial reads
+            let wire = buf.split();
+            let mut decoded = Vec::new();
+            for b in wire.iter() {
+                buf.extend_from_slice(&[*b]);
+                while let Some(m) = codec.decode(&mut buf).expect("should decode") {
+                    decoded.push(m);
+                }
+            }
+            assert!(codec.decode_eof(&mut buf).expect("should decode").is_none());
+            assert_eq!(decoded, messages);
+        }
+    }
+
+    #[test]
+    fn test_octet_count_is_bytes() {
+        let mut codec = SyslogCodec::octet_counting();
+        let mut buf = BytesMut::new();
+        codec
+            .encode(parse_message("<1>1 - - - - - - \u{e9}").unwrap(), &mut buf)
+            .expect("should encode");
+        assert_eq!(&buf[..], "19 <1>1 - - - - - - \u{e9}".as_bytes());
+    }
+
+    #[test]
+    fn test_decode_in_place() {
+        let mut codec = SyslogCodec::octet_counting();
+        let mut buf = BytesMut::from(&b"prior "[..]);
+        codec
+            .encode(parse_message("<1>1 - - - - - - a").unwrap(), &mut buf)
+            .expect("should encode");
+        assert_eq!(&buf[..], b"prior 18 <1>1 - - - - - - a");
+
+        // the partial frame is left in the read buffer, not copied out of it
+        let mut buf = BytesMut::from(&b"18 <1>1 - - - - - - a18 <1>1"[..]);
+        let m = codec.decode(&mut buf).expect("should decode");
+        assert_eq!(m.map(|m| m.msg), Some(String::from("a")));
+        assert_eq!(&buf[..], b"18 <1>1");
+        assert!(codec.decode(&mut buf).expect("should decode").is_none());
+        assert_eq!(&buf[..], b"18 <1>1");
+
+        let mut codec = SyslogCodec::newline().with_max_frame_length(8);
+        let mut buf = BytesMut::from(&b"<1>1 - - - - - - too long\n<1>1 - -"[..]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(StreamError::Framing(_))
+        ));
+        assert_eq!(&buf[..], b"<1>1 - -");
+    }
+
+    #[test]
+    fn test_decode_eof() {
+        let mut codec = SyslogCodec::newline();
+        let mut buf = BytesMut::from(&b"<1>1 - - - - - - last"[..]);
+        assert!(codec.decode(&mut buf).expect("should decode").is_none());
+        let m = codec.decode_eof(&mut buf).expect("should decode");
+        assert_eq!(m.map(|m| m.msg), Some(String::from("last")));
+
+        let mut codec = SyslogCodec::octet_counting();
+        let mut buf = BytesMut::from(&b"30 <1>1"[..]);
+        assert!(matches!(
+            codec.decode_eof(&mut buf),
+            Err(StreamError::Framing(_))
+        ));
+    }
+}
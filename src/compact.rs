@@ -0,0 +1,181 @@
+//! Messages which keep their short header fields inline (requires the `compact-str` feature).
+//!
+//! HOSTNAME, APP-NAME and MSGID are nearly always short. A `CompactMessage` stores them as
+//! `compact_str::CompactString`s, which keep up to 24 bytes inline instead of allocating, so
+//! parsing into one saves up to three allocations compared to a `SyslogMessage`. Everything else
+//! is the same, and the two convert into each other with `From`.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::compact::parse_message_compact;
+//! use syslog_rfc5424::SyslogMessage;
+//!
+//! let m = parse_message_compact("<1>1 - web1 nginx - ID47 - hi").unwrap();
+//! assert_eq!(m.hostname.as_deref(), Some("web1"));
+//! assert!(!m.hostname.as_ref().unwrap().is_heap_allocated());
+//! let m = SyslogMessage::from(m);
+//! assert_eq!(m.msgid, Some(String::from("ID47")));
+//! ```
+
+use compact_str::CompactString;
+
+use crate::facility::SyslogFacility;
+use crate::message::{ProcId, StructuredData, SyslogMessage};
+use crate::parser::{self, ParseErr, ParserConfig};
+use crate::severity::SyslogSeverity;
+use crate::timestamp::Timestamp;
+use crate::validation;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A RFC5424-protocol syslog message with `CompactString` header fields
+///
+/// This has the same fields as `SyslogMessage`; see there for what they mean.
+pub struct CompactMessage {
+    pub severity: SyslogSeverity,
+    pub facility: SyslogFacility,
+    pub version: i32,
+    pub timestamp: Option<Timestamp>,
+    pub timestamp_precision_lost: bool,
+    pub leap_second: bool,
+    pub hostname: Option<CompactString>,
+    pub appname: Option<CompactString>,
+    pub procid: Option<ProcId>,
+    pub msgid: Option<CompactString>,
+    pub sd: StructuredData,
+    pub msg: String,
+    pub msg_is_utf8: bool,
+    pub msg_bytes: Option<Vec<u8>>,
+    pub truncated: bool,
+}
+
+impl From<SyslogMessage> for CompactMessage {
+    fn from(m: SyslogMessage) -> Self {
+        CompactMessage {
+            severity: m.severity,
+            facility: m.facility,
+            version: m.version,
+            timestamp: m.timestamp,
+            timestamp_precision_lost: m.timestamp_precision_lost,
+            leap_second: m.leap_second,
+            hostname: m.hostname.map(Into::into),
+            appname: m.appname.map(Into::into),
+            procid: m.procid,
+            msgid: m.msgid.map(Into::into),
+            sd: m.sd,
+            msg: m.msg,
+            msg_is_utf8: m.msg_is_utf8,
+            msg_bytes: m.msg_bytes,
+            truncated: m.truncated,
+        }
+    }
+}
+
+impl From<CompactMessage> for SyslogMessage {
+    fn from(m: CompactMessage) -> Self {
+        SyslogMessage {
+            severity: m.severity,
+            facility: m.facility,
+            version: m.version,
+            timestamp: m.timestamp,
+            timestamp_precision_lost: m.timestamp_precision_lost,
+            leap_second: m.leap_second,
+            hostname: m.hostname.map(Into::into),
+            appname: m.appname.map(Into::into),
+            procid: m.procid,
+            msgid: m.msgid.map(Into::into),
+            sd: m.sd,
+            msg: m.msg,
+            msg_is_utf8: m.msg_is_utf8,
+            msg_bytes: m.msg_bytes,
+            truncated: m.truncated,
+        }
+    }
+}
+
+/// Parse a string into a `CompactMessage`
+pub fn parse_message_compact(input: &str) -> Result<CompactMessage, ParseErr> {
+    parse_message_compact_with(&ParserConfig::default(), input)
+}
+
+/// Like `parse_message_compact`, but with a `ParserConfig`
+///
+/// In strict mode, the message is copied into a `SyslogMessage` to be validated, so this
+/// allocates.
+pub fn parse_message_compact_with(
+    config: &ParserConfig,
+    input: &str,
+) -> Result<CompactMessage, ParseErr> {
+    config.observe(input.len(), parse_compact(config, input))
+}
+
+fn parse_compact(config: &ParserConfig, input: &str) -> Result<CompactMessage, ParseErr> {
+    let m = parser::parse_message_s(input, config)?;
+    let sd = if config.preserve_sd_order {
+        m.sd.to_owned_ordered()
+    } else {
+        m.sd.to_owned()
+    };
+    let m = CompactMessage {
+        severity: m.severity,
+        facility: m.facility,
+        version: m.version,
+        timestamp: m.timestamp,
+        timestamp_precision_lost: m.timestamp_precision_lost,
+        leap_second: m.leap_second,
+        hostname: m.hostname.as_deref().map(CompactString::from),
+        appname: m.appname.as_deref().map(CompactString::from),
+        procid: m.procid.map(|p| p.to_owned()),
+        msgid: m.msgid.as_deref().map(CompactString::from),
+        sd,
+        msg: m.msg.into_owned(),
+        msg_is_utf8: m.msg_is_utf8,
+        msg_bytes: None,
+        truncated: m.truncated,
+    };
+    if config.strict {
+        validation::validate(&SyslogMessage::from(m.clone())).map_err(ParseErr::Nonconformant)?;
+    }
+    Ok(m)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{parse_message_compact, parse_message_compact_with, CompactMessage};
+    use crate::message::SyslogMessage;
+    use crate::metrics::{AtomicParserMetrics, ErrorClass};
+    use crate::parser::{parse_message, ParserConfig};
+
+    #[test]
+    fn test_parse_compact() {
+        let input = r#"<78>1 2016-01-15T00:04:01Z host1 CROND 10391 ID47 [meta a="x\"y"] msg"#;
+        let m = parse_message_compact(input).expect("should parse");
+        assert_eq!(m.appname.as_deref(), Some("CROND"));
+        assert!(!m.appname.as_ref().unwrap().is_heap_allocated());
+        assert_eq!(m.sd.find_tuple("meta", "a"), Some(&String::from("x\"y")));
+        let owned = parse_message(input).unwrap();
+        assert_eq!(SyslogMessage::from(m.clone()), owned);
+        assert_eq!(CompactMessage::from(owned), m);
+
+        let strict = ParserConfig::new().with_strict(true);
+        let long_sd_id = r#"<1>1 - - - - - [this-sd-id-is-much-too-long-for-the-rfc a="b"]"#;
+        assert!(parse_message_compact(long_sd_id).is_ok());
+        assert!(parse_message_compact_with(&strict, long_sd_id).is_err());
+    }
+
+    #[test]
+    fn test_compact_metrics() {
+        let metrics = Arc::new(AtomicParserMetrics::new());
+        let config = ParserConfig::new().with_metrics(metrics.clone());
+        assert!(parse_message_compact_with(&config, "<1>1 - - - - - - hi").is_ok());
+        assert!(parse_message_compact_with(&config, "<200>1 - - - - - - hi").is_err());
+
+        let stats = metrics.snapshot();
+        assert_eq!(stats.messages, 2);
+        assert_eq!(stats.failures, 1);
+        assert_eq!(stats.bytes, 19 + 21);
+        assert_eq!(stats.errors(ErrorClass::Pri), 1);
+    }
+}
@@ -0,0 +1,255 @@
+//! Rendering parse errors for people.
+//!
+//! `ParseErr::diagnostic` pairs an error with the input it came from. Displaying the result shows
+//! the line of input with the field that failed underlined, and a hint about what the parser
+//! wanted, which is handy when working out what a new device is sending:
+//!
+//! ```text
+//! error: invalid HOSTNAME at byte 32 ("web1west\u{1}"): expected ' '
+//!   |
+//! 1 | <39>1 2018-05-15T20:56:58+00:00 web1west  - - - - msg
+//!   |                                 ^^^^^^^^^
+//!   = hint: expected SP after HOSTNAME
+//! ```
+//!
+//! Lines longer than 100 characters are cut down to the part around the error, and control
+//! characters are shown as spaces so that the carets line up.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::parse_message;
+//!
+//! let input = "<39>1 2018-05-15T25:56:58+00:00 web1west - - - - msg";
+//! let err = parse_message(input).unwrap_err();
+//! let rendered = err.diagnostic(input).to_string();
+//! assert!(rendered.contains("      ^^^^^^^^^^^^^^^^^^^^^^^^^"));
+//! assert!(rendered.contains("= hint: TIMESTAMP should look like 2003-10-11T22:14:15.003Z"));
+//! ```
+
+use std::fmt;
+
+use crate::parser::{ErrorKind, MessageField, ParseErr};
+
+/// Lines longer than this many characters are shown cut down
+const MAX_LINE_WIDTH: usize = 100;
+
+/// How many characters to show before the error on a line which has been cut down
+const LEADING_CONTEXT: usize = 40;
+
+const TIMESTAMP_EXAMPLE: &str = "TIMESTAMP should look like 2003-10-11T22:14:15.003Z";
+
+impl ParseErr {
+    /// Render this error against `input`, which should be what was parsed
+    pub fn diagnostic<'a>(&'a self, input: &'a str) -> Diagnostic<'a> {
+        Diagnostic { err: self, input }
+    }
+
+    /// A suggestion of what the input should have looked like, if there is one
+    pub fn hint(&self) -> Option<String> {
+        match (self.field(), self.kind()) {
+            (Some(field), Some(kind)) => field_hint(field, kind),
+            _ => other_hint(self),
+        }
+    }
+}
+
+/// A hint for an error which isn't about one field
+fn other_hint(err: &ParseErr) -> Option<String> {
+    match *err {
+        ParseErr::TooLong { .. } => Some(String::from(
+            "the limits can be raised or removed with ParserConfig",
+        )),
+        ParseErr::Nonconformant(ref violations) => Some(
+            violations
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join("; "),
+        ),
+        _ => None,
+    }
+}
+
+fn field_hint(field: MessageField, kind: &ErrorKind) -> Option<String> {
+    let name = field.name();
+    let hint = match (field, kind) {
+        (_, ErrorKind::ExpectedToken(' ')) => format!("expected SP after {}", name),
+        (MessageField::Pri, ErrorKind::ExpectedToken('<')) => {
+            String::from("messages start with a PRI, like <34>")
+        }
+        (MessageField::StructuredData, ErrorKind::ExpectedToken('[')) => String::from(
+            "STRUCTURED-DATA is either - or [SD-ID NAME=\"value\" ...] elements; \
+             ParserConfig::with_quirks accepts messages without it",
+        ),
+        (_, ErrorKind::ExpectedToken(c)) => format!("expected {:?} in {}", c, name),
+        (_, ErrorKind::UnexpectedEndOfInput) => {
+            format!("the input ends part of the way through {}", name)
+        }
+        (MessageField::Timestamp, ErrorKind::UnexpectedChar(c)) => {
+            format!("unexpected {:?} after TIMESTAMP", c)
+        }
+        (_, ErrorKind::UnexpectedChar(c)) => format!(
+            "{} has to be printable ASCII with no spaces, but found {:?}",
+            name, c
+        ),
+        (MessageField::Pri, ErrorKind::BadFacility) => String::from(
+            "PRI values only go up to 191; ParserConfig::with_unknown_facility accepts larger ones",
+        ),
+        (MessageField::Pri, _) => String::from("PRI is 1 to 3 digits between < and >, like <34>"),
+        (_, ErrorKind::UnsupportedVersion(_)) => {
+            String::from("ParserConfig::with_max_version rejects versions this high")
+        }
+        (MessageField::Version, _) => String::from(
+            "VERSION is 1 or 2 digits after the PRI, like <34>1; \
+             ParserConfig::with_lenient accepts messages without it",
+        ),
+        (_, ErrorKind::InvalidOffset) => {
+            String::from("UTC offsets should look like Z, +01:00 or -07:00")
+        }
+        (_, ErrorKind::Nil) => String::from("a timestamp is required here, not -"),
+        (MessageField::Timestamp, _) => String::from(TIMESTAMP_EXAMPLE),
+        (_, ErrorKind::InvalidEscape(_)) => {
+            String::from("only \", \\ and ] can be escaped in SD-PARAM values")
+        }
+        (_, ErrorKind::InvalidUtf8(_)) => String::from(
+            "the input isn't valid UTF-8; see parser::Utf8Policy for other ways to handle it",
+        ),
+        _ => return None,
+    };
+    Some(hint)
+}
+
+#[derive(Clone, Copy, Debug)]
+/// A `ParseErr` along with its input, which is displayed as a multi-line report; see
+/// `ParseErr::diagnostic`
+pub struct Diagnostic<'a> {
+    err: &'a ParseErr,
+    input: &'a str,
+}
+
+impl<'a> Diagnostic<'a> {
+    pub fn error(&self) -> &'a ParseErr {
+        self.err
+    }
+
+    pub fn input(&self) -> &'a str {
+        self.input
+    }
+}
+
+/// The largest char boundary in `s` which is at most `i`
+fn floor_boundary(s: &str, i: usize) -> usize {
+    let mut i = i.min(s.len());
+    while !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+impl<'a> fmt::Display for Diagnostic<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "error: {}", self.err)?;
+        if let Some(span) = self.err.span() {
+            let input = self.input;
+            let start = floor_boundary(input, span.start);
+            let end = floor_boundary(input, span.end).max(start);
+            let line_start = input[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = input[start..].find('\n').map_or(input.len(), |i| start + i);
+            let line_number = input[..start].matches('\n').count() + 1;
+            let line = &input[line_start..line_end];
+            let before = input[line_start..start].chars().count();
+            let width = input[start..end.min(line_end)].chars().count().max(1);
+
+            // cut long lines down to a window around the error
+            let total = line.chars().count();
+            let skip = if total > MAX_LINE_WIDTH {
+                before.saturating_sub(LEADING_CONTEXT)
+            } else {
+                0
+            };
+            let mut shown: String = line
+                .chars()
+                .skip(skip)
+                .take(MAX_LINE_WIDTH)
+                .map(|c| if c.is_control() { ' ' } else { c })
+                .collect();
+            let mut caret_col = before - skip;
+            if skip > 0 {
+                shown.insert_str(0, "...");
+                caret_col += 3;
+            }
+            if skip + MAX_LINE_WIDTH < total {
+                shown.push_str("...");
+            }
+
+            let gutter = line_number.to_string();
+            let pad = " ".repeat(gutter.len());
+            writeln!(f, "{} |", pad)?;
+            writeln!(f, "{} | {}", gutter, shown)?;
+            writeln!(
+                f,
+                "{} | {}{}",
+                pad,
+                " ".repeat(caret_col),
+                "^".repeat(width.min(MAX_LINE_WIDTH))
+            )?;
+        }
+        if let Some(hint) = self.err.hint() {
+            writeln!(f, "  = hint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_message;
+    use crate::parser::{parse_message_with, ParserConfig};
+
+    #[test]
+    fn test_render() {
+        let input = "<39>1 2018-05-15T20:56:58+00:00 web1west\u{1} - - - - msg";
+        let err = parse_message(input).unwrap_err();
+        assert_eq!(
+            err.diagnostic(input).to_string(),
+            "error: invalid HOSTNAME at byte 32 (\"web1west\\u{1}\"): expected ' '\n  \
+             |\n\
+             1 | <39>1 2018-05-15T20:56:58+00:00 web1west  - - - - msg\n  \
+             |                                 ^^^^^^^^^\n  \
+             = hint: expected SP after HOSTNAME\n"
+        );
+    }
+
+    #[test]
+    fn test_hints() {
+        let hint = |input: &str| parse_message(input).unwrap_err().hint().unwrap();
+        assert_eq!(hint("hello"), "messages start with a PRI, like <34>");
+        assert_eq!(
+            hint("<1>1 - host"),
+            "the input ends part of the way through HOSTNAME"
+        );
+        assert!(hint("<1>1 2020-13-01T00:00:00Z - - - - -").starts_with("TIMESTAMP should"));
+        assert!(hint("<1>1 - - - - - x").contains("with_quirks"));
+        let config = ParserConfig::new().with_max_message_length(Some(5));
+        let err = parse_message_with(&config, "<1>1 - - - - - -").unwrap_err();
+        assert!(err.hint().unwrap().contains("ParserConfig"));
+    }
+
+    #[test]
+    fn test_long_and_multiline_input() {
+        let input = format!("<1>1 - {} - - {} - msg", "h".repeat(100), "m".repeat(100));
+        let err = parse_message(&input).unwrap_err();
+        let rendered = err.diagnostic(&input).to_string();
+        let lines: Vec<_> = rendered.lines().collect();
+        assert!(lines[2].starts_with("1 | ...hhh"));
+        assert!(lines[2].ends_with("mmm..."));
+        assert_eq!(lines[3].find('^'), lines[2].find('m'));
+        assert_eq!(lines[3].matches('^').count(), 32);
+
+        let input = "<1>1 - host\nname - - - - hi";
+        let err = parse_message(input).unwrap_err();
+        let rendered = err.diagnostic(input).to_string();
+        assert!(rendered.contains("\n1 | <1>1 - host\n"), "{}", rendered);
+    }
+}
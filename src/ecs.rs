@@ -0,0 +1,166 @@
+//! Conversion to Elastic Common Schema documents (requires the `serde-serialize` feature).
+//!
+//! `SyslogMessage::to_ecs_json` maps a message onto the ECS fields Elasticsearch and Kibana know
+//! about:
+//!
+//! | Message field       | ECS field                                              |
+//! |---------------------|--------------------------------------------------------|
+//! | TIMESTAMP           | `@timestamp`                                           |
+//! | MSG                 | `message`                                              |
+//! | severity            | `log.level`, `log.syslog.severity.{code,name}`         |
+//! | facility            | `log.syslog.facility.{code,name}`                      |
+//! | PRI                 | `log.syslog.priority`                                  |
+//! | VERSION             | `log.syslog.version`                                   |
+//! | HOSTNAME            | `host.name`, `log.syslog.hostname`                     |
+//! | APP-NAME            | `process.name`, `log.syslog.appname`                   |
+//! | PROCID              | `process.pid` (if it's numeric), `log.syslog.procid`   |
+//! | MSGID               | `log.syslog.msgid`                                     |
+//! | STRUCTURED-DATA     | `log.syslog.structured_data`, `labels`                 |
+//!
+//! `log.syslog.structured_data` keeps the SD-ELEMENTs as objects of their SD-PARAMs. `labels`
+//! has each SD-PARAM as `<SD-ID>_<name>`, with anything but letters, digits and `_` replaced by
+//! `_`, since ECS recommends against dots in label keys. NIL fields are left out.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::parse_message;
+//!
+//! let m = parse_message(
+//!     r#"<11>1 2020-01-01T00:00:00Z web1 nginx 42 - [req@32473 status="500"] upstream timed out"#,
+//! ).unwrap();
+//! let doc = m.to_ecs_json();
+//! assert_eq!(doc["log"]["level"], "err");
+//! assert_eq!(doc["host"]["name"], "web1");
+//! assert_eq!(doc["process"]["pid"], 42);
+//! assert_eq!(doc["labels"]["req_32473_status"], "500");
+//! ```
+
+use serde_json::{json, Map, Value};
+
+use crate::message::{ProcId, SyslogMessage};
+
+/// The version of ECS documents are written for
+pub const ECS_VERSION: &str = "8.11.0";
+
+/// Turn an SD-ID and SD-PARAM name into a `labels` key
+fn label_key(sd_id: &str, name: &str) -> String {
+    format!("{}_{}", sd_id, name)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+pub(crate) fn to_ecs_json(m: &SyslogMessage) -> Value {
+    let mut syslog = json!({
+        "severity": {"code": m.severity as u8, "name": m.severity.as_str()},
+        "facility": {"code": m.facility as u8, "name": m.facility.as_str()},
+        "priority": m.priority(),
+        "version": m.version,
+    });
+    let mut doc = json!({
+        "ecs": {"version": ECS_VERSION},
+        "message": m.msg,
+        "log": {"level": m.severity.as_str()},
+    });
+    if let Some(ts) = m.timestamp_rfc3339() {
+        doc["@timestamp"] = ts.into();
+    }
+    if let Some(ref hostname) = m.hostname {
+        doc["host"] = json!({ "name": hostname.as_str() });
+        syslog["hostname"] = hostname.as_str().into();
+    }
+    let mut process = Map::new();
+    if let Some(ref appname) = m.appname {
+        process.insert("name".into(), appname.as_str().into());
+        syslog["appname"] = appname.as_str().into();
+    }
+    match m.procid {
+        Some(ProcId::PID(pid)) => {
+            process.insert("pid".into(), pid.into());
+            syslog["procid"] = pid.to_string().into();
+        }
+        Some(ProcId::Name(ref name)) => syslog["procid"] = name.as_str().into(),
+        None => {}
+    }
+    if !process.is_empty() {
+        doc["process"] = process.into();
+    }
+    if let Some(ref msgid) = m.msgid {
+        syslog["msgid"] = msgid.as_str().into();
+    }
+    if !m.sd.is_empty() {
+        let mut labels = Map::new();
+        for (sd_id, name, value) in m.sd.iter_tuples() {
+            labels.insert(label_key(sd_id, name), value.into());
+        }
+        syslog["structured_data"] = json!(*m.sd);
+        doc["labels"] = labels.into();
+    }
+    doc["log"]["syslog"] = syslog;
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::parse_message;
+
+    #[test]
+    fn test_to_ecs_json() {
+        let m = parse_message(
+            r#"<165>1 2003-10-11T22:14:15.003-07:00 mymachine evntslog app1 ID47 [exampleSDID@32473 iut="3" eventSource="Application"][b@1] An application event"#,
+        )
+        .unwrap();
+        assert_eq!(
+            m.to_ecs_json(),
+            json!({
+                "@timestamp": "2003-10-11T22:14:15.003-07:00",
+                "ecs": {"version": "8.11.0"},
+                "message": "An application event",
+                "host": {"name": "mymachine"},
+                "process": {"name": "evntslog"},
+                "labels": {
+                    "exampleSDID_32473_eventSource": "Application",
+                    "exampleSDID_32473_iut": "3",
+                },
+                "log": {
+                    "level": "notice",
+                    "syslog": {
+                        "severity": {"code": 5, "name": "notice"},
+                        "facility": {"code": 20, "name": "local4"},
+                        "priority": 165,
+                        "version": 1,
+                        "hostname": "mymachine",
+                        "appname": "evntslog",
+                        "procid": "app1",
+                        "msgid": "ID47",
+                        "structured_data": {
+                            "b@1": {},
+                            "exampleSDID@32473": {"eventSource": "Application", "iut": "3"},
+                        },
+                    },
+                },
+            })
+        );
+
+        let m = parse_message("<14>1 - - - - - -").unwrap();
+        assert_eq!(
+            m.to_ecs_json(),
+            json!({
+                "ecs": {"version": "8.11.0"},
+                "message": "",
+                "log": {
+                    "level": "info",
+                    "syslog": {
+                        "severity": {"code": 6, "name": "info"},
+                        "facility": {"code": 1, "name": "user"},
+                        "priority": 14,
+                        "version": 1,
+                    },
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Serializing a `SyslogMessage` back into the RFC 5424 wire format.
+
+use std::fmt::{self, Write};
+use std::io;
+
+use crate::message::{ProcId, SyslogMessage};
+use crate::parser::BOM;
+use crate::sd::escape_param_value;
+use crate::timestamp::Timestamp;
+
+fn write_nilable<W: Write + ?Sized>(w: &mut W, value: Option<&str>) -> fmt::Result {
+    match value {
+        Some(v) if !v.is_empty() => w.write_str(v),
+        _ => w.write_char('-'),
+    }
+}
+
+fn write_timestamp<W: Write + ?Sized>(w: &mut W, m: &SyslogMessage) -> fmt::Result {
+    // RFC 5424 only allows six digits of TIME-SECFRAC
+    match m.timestamp {
+        Some(ts) if write_rfc3339(w, ts, m.leap_second, 6)? => Ok(()),
+        _ => w.write_char('-'),
+    }
+}
+
+/// Write `ts` as an RFC 3339 timestamp, with at most `frac_digits` (up to 9) digits of
+/// TIME-SECFRAC and without trailing zeros
+///
+/// Writes nothing and returns false if `ts` isn't within the years 0 to 9999.
+pub(crate) fn write_rfc3339<W: Write + ?Sized>(
+    w: &mut W,
+    ts: Timestamp,
+    leap_second: bool,
+    frac_digits: usize,
+) -> Result<bool, fmt::Error> {
+    let mut dt = match ts.to_offset_datetime() {
+        Some(dt) if (0..=9999).contains(&dt.year()) => dt,
+        _ => return Ok(false),
+    };
+    let mut second = dt.second();
+    let mut nanos = dt.nanosecond();
+    if leap_second {
+        // undo whichever `LeapSecondPolicy` the parser applied
+        if second == 59 && nanos == 999_999_999 {
+            second = 60;
+            nanos = 0;
+        } else if second == 0 {
+            dt -= time::Duration::SECOND;
+            second = 60;
+        }
+    }
+    write!(
+        w,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        dt.year(),
+        u8::from(dt.month()),
+        dt.day(),
+        dt.hour(),
+        dt.minute(),
+        second
+    )?;
+    let mut width = frac_digits.min(9);
+    let mut frac = nanos / 10u32.pow(9 - width as u32);
+    while width > 0 && frac % 10 == 0 {
+        frac /= 10;
+        width -= 1;
+    }
+    if width > 0 {
+        write!(w, ".{:0width$}", frac, width = width)?;
+    }
+    match dt.offset().whole_seconds() {
+        0 => w.write_char('Z')?,
+        offset => {
+            let sign = if offset < 0 { '-' } else { '+' };
+            let offset = offset.abs();
+            write!(w, "{}{:02}:{:02}", sign, offset / 3600, offset % 3600 / 60)?
+        }
+    }
+    Ok(true)
+}
+
+fn write_sd_element<'a, W: Write + ?Sized>(
+    w: &mut W,
+    sd_id: &str,
+    params: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> fmt::Result {
+    write!(w, "[{}", sd_id)?;
+    for (name, value) in params {
+        write!(w, " {}=\"{}\"", name, escape_param_value(value))?;
+    }
+    w.write_char(']')
+}
+
+fn write_sd<W: Write + ?Sized>(w: &mut W, m: &SyslogMessage) -> fmt::Result {
+    if m.sd.is_empty() {
+        return w.write_char('-');
+    }
+    // the `BTreeMap` is already in the right order, unless the original order is being kept
+    if !m.sd.preserves_order() {
+        for (sd_id, params) in m.sd.iter() {
+            write_sd_element(w, sd_id, params)?;
+        }
+        return Ok(());
+    }
+    for (sd_id, params) in m.sd.iter_ordered() {
+        write_sd_element(w, sd_id, params)?;
+    }
+    Ok(())
+}
+
+/// Write everything up to and including STRUCTURED-DATA
+pub(crate) fn write_header<W: Write + ?Sized>(w: &mut W, m: &SyslogMessage) -> fmt::Result {
+    write!(w, "<{}>{} ", m.pri(), m.version)?;
+    write_timestamp(w, m)?;
+    w.write_char(' ')?;
+    write_nilable(w, m.hostname.as_deref())?;
+    w.write_char(' ')?;
+    write_nilable(w, m.appname.as_deref())?;
+    w.write_char(' ')?;
+    match m.procid {
+        Some(ProcId::PID(pid)) => write!(w, "{}", pid)?,
+        Some(ProcId::Name(ref name)) => write_nilable(w, Some(name))?,
+        None => w.write_char('-')?,
+    }
+    w.write_char(' ')?;
+    write_nilable(w, m.msgid.as_deref())?;
+    w.write_char(' ')?;
+    write_sd(w, m)
+}
+
+/// The prefix (SP and possibly a BOM) which goes between STRUCTURED-DATA and MSG
+pub(crate) fn msg_prefix(m: &SyslogMessage) -> &'static str {
+    let body_empty = m.msg_bytes.as_ref().map_or(m.msg.is_empty(), Vec::is_empty);
+    if m.msg_is_utf8 && !m.msg.starts_with(BOM) {
+        " \u{feff}"
+    } else if body_empty {
+        ""
+    } else {
+        " "
+    }
+}
+
+/// Write a complete message, using `msg` (not `msg_bytes`) as the body
+pub(crate) fn write_message<W: Write + ?Sized>(w: &mut W, m: &SyslogMessage) -> fmt::Result {
+    write_header(w, m)?;
+    w.write_str(msg_prefix(m))?;
+    w.write_str(&m.msg)
+}
+
+/// Adapts an `io::Write` for the `fmt::Write`-based functions above, keeping the real error
+struct IoAdapter<'a, W: ?Sized> {
+    inner: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<'a, W: io::Write + ?Sized> Write for IoAdapter<'a, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+/// Write a complete message to an `io::Write`, using `msg_bytes` for the body if it's set
+pub(crate) fn write_bytes<W: io::Write + ?Sized>(w: &mut W, m: &SyslogMessage) -> io::Result<()> {
+    let mut adapter = IoAdapter {
+        inner: w,
+        error: None,
+    };
+    if write_header(&mut adapter, m)
+        .and_then(|_| adapter.write_str(msg_prefix(m)))
+        .is_err()
+    {
+        return Err(adapter
+            .error
+            .unwrap_or_else(|| io::Error::new(io::ErrorKind::Other, "formatter error")));
+    }
+    match m.msg_bytes {
+        Some(ref bytes) => w.write_all(bytes),
+        None => w.write_all(m.msg.as_bytes()),
+    }
+}
+
+/// Serialize a complete message, using `msg_bytes` for the body if it's set
+pub(crate) fn to_bytes(m: &SyslogMessage) -> Vec<u8> {
+    let mut out = Vec::new();
+    // writing into a Vec can't fail
+    let _ = write_bytes(&mut out, m);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_message;
+    use crate::message::SyslogMessage;
+    use crate::parser::{parse_message, parse_message_with, LeapSecondPolicy, ParserConfig};
+
+    fn encode(m: &SyslogMessage) -> String {
+        let mut s = String::new();
+        write_message(&mut s, m).expect("should encode");
+        s
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for input in &[
+            "<1>1 - - - - - -",
+            "<78>1 2016-01-15T00:04:01Z host1 CROND 10391 - [meta sequenceId=\"29\"] some_message",
+            "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 eventID=\"1011\" eventSource=\"Application\" iut=\"3\"] An application event log entry...",
+            "<165>1 2003-08-24T05:14:15.000003-07:00 192.0.2.1 myproc 8710 - - %% It's time to make the do-nuts.",
+            "<34>1 2003-10-11T22:14:15.52+05:30 mymachine su - ID47 [a b=\"q\\\"u\\\\o\\]te\"] \u{feff}'su root' failed",
+        ] {
+            let m = parse_message(input).expect("should parse");
+            assert_eq!(&encode(&m), input);
+            assert_eq!(parse_message(encode(&m)).expect("should reparse"), m);
+        }
+    }
+
+    #[test]
+    fn test_leap_second() {
+        let input = "<1>1 2016-12-31T23:59:60.5Z - - - - -";
+        for policy in &[LeapSecondPolicy::Clamp, LeapSecondPolicy::Rollover] {
+            let config = ParserConfig::new().with_leap_second_policy(*policy);
+            let m = parse_message_with(&config, input).expect("should parse");
+            let encoded = encode(&m);
+            assert!(encoded.starts_with("<1>1 2016-12-31T23:59:60"));
+        }
+    }
+
+    #[test]
+    fn test_nil_and_empty() {
+        let mut m = parse_message("<1>1 - - - - - - hello").expect("should parse");
+        m.hostname = Some(String::new());
+        m.msg = String::new();
+        assert_eq!(encode(&m), "<1>1 - - - - - -");
+        m.msg_is_utf8 = true;
+        assert_eq!(encode(&m), "<1>1 - - - - - - \u{feff}");
+    }
+}
@@ -2,12 +2,14 @@
 use serde::{de::Visitor, Deserialize, Serialize, Serializer};
 
 use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
 
 use thiserror::Error;
 
-use crate::parser::ParseErr;
+use crate::parser::{ErrorKind, ParseErr};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
 #[allow(non_camel_case_types)]
 /// Syslog facilities. Taken From RFC 5424, but I've heard that some platforms mix these around.
 /// Names are from Linux.
@@ -116,13 +118,18 @@ impl SyslogFacility {
     }
 
     /// Convert a str to a syslog facility
+    ///
+    /// Matching is case-insensitive, and also accepts a `log_` prefix (as in `LOG_LOCAL0`), the
+    /// deprecated syslog.conf keyword `security` (for `auth`), and `kernel`.
     pub fn from_str(facility: &str) -> Result<SyslogFacility, ParseErr> {
+        let facility = facility.to_ascii_lowercase();
+        let facility = facility.strip_prefix("log_").unwrap_or(&facility);
         match facility {
-            "kern" => Ok(SyslogFacility::LOG_KERN),
+            "kern" | "kernel" => Ok(SyslogFacility::LOG_KERN),
             "user" => Ok(SyslogFacility::LOG_USER),
             "mail" => Ok(SyslogFacility::LOG_MAIL),
             "daemon" => Ok(SyslogFacility::LOG_DAEMON),
-            "auth" => Ok(SyslogFacility::LOG_AUTH),
+            "auth" | "security" => Ok(SyslogFacility::LOG_AUTH),
             "syslog" => Ok(SyslogFacility::LOG_SYSLOG),
             "lpr" => Ok(SyslogFacility::LOG_LPR),
             "news" => Ok(SyslogFacility::LOG_NEWS),
@@ -142,11 +149,30 @@ impl SyslogFacility {
             "local5" => Ok(SyslogFacility::LOG_LOCAL5),
             "local6" => Ok(SyslogFacility::LOG_LOCAL6),
             "local7" => Ok(SyslogFacility::LOG_LOCAL7),
-            &_ => Err(ParseErr::BadFacilityInPri),
+            &_ => Err(ParseErr::Pri {
+                offset: 0,
+                found: facility.to_string(),
+                kind: ErrorKind::BadFacility,
+            }),
         }
     }
 }
 
+impl fmt::Display for SyslogFacility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SyslogFacility {
+    type Err = ParseErr;
+
+    /// Same as the inherent `from_str`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SyslogFacility::from_str(s)
+    }
+}
+
 #[cfg(feature = "serde-serialize")]
 impl Serialize for SyslogFacility {
     fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
@@ -162,15 +188,35 @@ impl<'de> Visitor<'de> for SyslogFacilityVisitor {
     type Value = SyslogFacility;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("a string")
+        formatter.write_str("a facility name or integer code")
     }
 
-    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
         SyslogFacility::from_str(v).map_err(|err| E::custom(err.to_string()))
     }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        i32::try_from(v)
+            .ok()
+            .and_then(|v| SyslogFacility::try_from(v).ok())
+            .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Signed(v), &self))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        i32::try_from(v)
+            .ok()
+            .and_then(|v| SyslogFacility::try_from(v).ok())
+            .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+    }
 }
 
 #[cfg(feature = "serde-serialize")]
@@ -179,7 +225,12 @@ impl<'de> Deserialize<'de> for SyslogFacility {
     where
         D: serde::Deserializer<'de>,
     {
-        des.deserialize_str(SyslogFacilityVisitor)
+        // accept either form from self-describing formats; other formats get what we'd write
+        if des.is_human_readable() {
+            des.deserialize_any(SyslogFacilityVisitor)
+        } else {
+            des.deserialize_str(SyslogFacilityVisitor)
+        }
     }
 }
 
@@ -191,4 +242,32 @@ mod tests {
     fn test_deref() {
         assert_eq!(SyslogFacility::LOG_KERN.as_str(), "kern");
     }
+
+    #[test]
+    fn test_display_fromstr() {
+        assert_eq!(SyslogFacility::LOG_LOCAL3.to_string(), "local3");
+        assert_eq!(
+            "cron".parse::<SyslogFacility>().unwrap(),
+            SyslogFacility::LOG_CRON
+        );
+        assert!("bogus".parse::<SyslogFacility>().is_err());
+    }
+
+    #[test]
+    fn test_from_str_aliases() {
+        for (name, facility) in [
+            ("AUTH", SyslogFacility::LOG_AUTH),
+            ("security", SyslogFacility::LOG_AUTH),
+            ("LOG_LOCAL0", SyslogFacility::LOG_LOCAL0),
+            ("Local7", SyslogFacility::LOG_LOCAL7),
+            ("kernel", SyslogFacility::LOG_KERN),
+        ] {
+            assert_eq!(
+                SyslogFacility::from_str(name).unwrap(),
+                facility,
+                "{}",
+                name
+            );
+        }
+    }
 }
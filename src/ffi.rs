@@ -0,0 +1,360 @@
+//! A C interface to the parser (requires the `ffi` feature).
+//!
+//! The matching header is `include/syslog_rfc5424.h`, generated with
+//! `cbindgen --config cbindgen.toml --output include/syslog_rfc5424.h`. Build a shared or static
+//! library with `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`).
+//!
+//! # Ownership
+//!
+//! * A `SyslogMessage *` returned by `syslog_message_parse` is owned by the caller and must be
+//!   released with `syslog_message_free`.
+//! * A `char *` returned by this library (error messages and `syslog_message_to_string`) is owned
+//!   by the caller and must be released with `syslog_string_free`, never with `free`.
+//! * A `SyslogStr` points into the message it came from and is only valid until that message is
+//!   freed. It is *not* NUL-terminated; use its `len`.
+//!
+//! No function here panics across the FFI boundary on bad input; a NULL message pointer is treated
+//! as a message with every field NIL.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use crate::message::{ProcId, SyslogMessage};
+use crate::parser::{parse_message_bytes, Utf8Policy};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+/// A borrowed, length-delimited UTF-8 string; `ptr` is NULL for a NIL field
+pub struct SyslogStr {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+impl SyslogStr {
+    fn nil() -> Self {
+        SyslogStr {
+            ptr: ptr::null(),
+            len: 0,
+        }
+    }
+
+    fn from_opt(s: Option<&str>) -> Self {
+        s.map_or_else(SyslogStr::nil, |s| SyslogStr {
+            ptr: s.as_ptr(),
+            len: s.len(),
+        })
+    }
+}
+
+/// Turn a Rust string into a caller-owned C string, dropping any interior NULs
+fn to_c_string(s: String) -> *mut c_char {
+    let bytes = s
+        .into_bytes()
+        .into_iter()
+        .filter(|b| *b != 0)
+        .collect::<Vec<_>>();
+    CString::new(bytes).expect("NULs were removed").into_raw()
+}
+
+/// Parse `len` bytes at `input` as an RFC 5424 message
+///
+/// Returns NULL on failure; if `error` is not NULL, it's then set to a description of the problem,
+/// which must be freed with `syslog_string_free`. A MSG which isn't valid UTF-8 is converted
+/// lossily.
+///
+/// # Safety
+///
+/// `input` must point to at least `len` readable bytes, and `error` must be NULL or point to
+/// writable memory for one pointer.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_parse(
+    input: *const u8,
+    len: usize,
+    error: *mut *mut c_char,
+) -> *mut SyslogMessage {
+    if !error.is_null() {
+        *error = ptr::null_mut();
+    }
+    let bytes = if input.is_null() {
+        &[][..]
+    } else {
+        slice::from_raw_parts(input, len)
+    };
+    match parse_message_bytes(bytes, Utf8Policy::Lossy) {
+        Ok(m) => Box::into_raw(Box::new(m)),
+        Err(e) => {
+            if !error.is_null() {
+                *error = to_c_string(e.to_string());
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a message returned by `syslog_message_parse`; NULL is ignored
+///
+/// # Safety
+///
+/// `m` must be NULL or a pointer returned by `syslog_message_parse` which hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_free(m: *mut SyslogMessage) {
+    if !m.is_null() {
+        drop(Box::from_raw(m));
+    }
+}
+
+/// Free a string returned by this library; NULL is ignored
+///
+/// # Safety
+///
+/// `s` must be NULL or a string returned by this library which hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// The severity code (0-7), or -1 if `m` is NULL
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_severity(m: *const SyslogMessage) -> i32 {
+    m.as_ref().map_or(-1, |m| m.severity as i32)
+}
+
+/// The facility code (0-23), or -1 if `m` is NULL
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_facility(m: *const SyslogMessage) -> i32 {
+    m.as_ref().map_or(-1, |m| m.facility as i32)
+}
+
+/// The VERSION, or -1 if `m` is NULL
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_version(m: *const SyslogMessage) -> i32 {
+    m.as_ref().map_or(-1, |m| m.version)
+}
+
+/// Read the timestamp into `secs` (since the epoch, in UTC), `nanos` and `offset` (seconds east
+/// of UTC), any of which may be NULL
+///
+/// Returns false, leaving the outputs alone, if the timestamp is NIL.
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message, and each output must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_timestamp(
+    m: *const SyslogMessage,
+    secs: *mut i64,
+    nanos: *mut u32,
+    offset: *mut i32,
+) -> bool {
+    let ts = match m.as_ref().and_then(|m| m.timestamp) {
+        Some(ts) => ts,
+        None => return false,
+    };
+    if !secs.is_null() {
+        *secs = ts.secs;
+    }
+    if !nanos.is_null() {
+        *nanos = ts.nanos;
+    }
+    if !offset.is_null() {
+        *offset = ts.offset;
+    }
+    true
+}
+
+/// The HOSTNAME
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_hostname(m: *const SyslogMessage) -> SyslogStr {
+    SyslogStr::from_opt(m.as_ref().and_then(|m| m.hostname.as_deref()))
+}
+
+/// The APP-NAME
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_appname(m: *const SyslogMessage) -> SyslogStr {
+    SyslogStr::from_opt(m.as_ref().and_then(|m| m.appname.as_deref()))
+}
+
+/// The PROCID, if it's a number; returns false, leaving `pid` alone, otherwise
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message, and `pid` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_pid(m: *const SyslogMessage, pid: *mut i32) -> bool {
+    match m.as_ref().and_then(|m| m.procid.as_ref()) {
+        Some(ProcId::PID(p)) if !pid.is_null() => {
+            *pid = *p;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The PROCID, if it isn't a number (see `syslog_message_pid`)
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_procid_name(m: *const SyslogMessage) -> SyslogStr {
+    match m.as_ref().and_then(|m| m.procid.as_ref()) {
+        Some(ProcId::Name(name)) => SyslogStr::from_opt(Some(name)),
+        _ => SyslogStr::nil(),
+    }
+}
+
+/// The MSGID
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_msgid(m: *const SyslogMessage) -> SyslogStr {
+    SyslogStr::from_opt(m.as_ref().and_then(|m| m.msgid.as_deref()))
+}
+
+/// The MSG, which is empty (but not NIL) if the message had none
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_msg(m: *const SyslogMessage) -> SyslogStr {
+    SyslogStr::from_opt(m.as_ref().map(|m| m.msg.as_str()))
+}
+
+/// Look up the value of SD-PARAM `name` in SD-ELEMENT `sd_id`, both NUL-terminated
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message, and `sd_id` and `name` must be NULL or NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_sd_param(
+    m: *const SyslogMessage,
+    sd_id: *const c_char,
+    name: *const c_char,
+) -> SyslogStr {
+    if sd_id.is_null() || name.is_null() {
+        return SyslogStr::nil();
+    }
+    let (sd_id, name) = match (
+        CStr::from_ptr(sd_id).to_str(),
+        CStr::from_ptr(name).to_str(),
+    ) {
+        (Ok(sd_id), Ok(name)) => (sd_id, name),
+        _ => return SyslogStr::nil(),
+    };
+    SyslogStr::from_opt(
+        m.as_ref()
+            .and_then(|m| m.sd.find_tuple(sd_id, name))
+            .map(String::as_str),
+    )
+}
+
+/// Render the message back into the RFC 5424 wire format; free the result with
+/// `syslog_string_free`
+///
+/// Returns NULL if `m` is NULL.
+///
+/// # Safety
+///
+/// `m` must be NULL or a valid message.
+#[no_mangle]
+pub unsafe extern "C" fn syslog_message_to_string(m: *const SyslogMessage) -> *mut c_char {
+    m.as_ref()
+        .map_or(ptr::null_mut(), |m| to_c_string(m.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+
+    use super::*;
+
+    unsafe fn as_str<'a>(s: SyslogStr) -> Option<&'a str> {
+        if s.ptr.is_null() {
+            None
+        } else {
+            Some(std::str::from_utf8(slice::from_raw_parts(s.ptr, s.len)).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_parse_and_query() {
+        let input =
+            b"<165>1 2003-10-11T22:14:15.003Z mymachine evntslog 42 ID47 [ex@32473 iut=\"3\"] hi";
+        unsafe {
+            let m = syslog_message_parse(input.as_ptr(), input.len(), ptr::null_mut());
+            assert!(!m.is_null());
+            assert_eq!(syslog_message_severity(m), 5);
+            assert_eq!(syslog_message_facility(m), 20);
+            assert_eq!(as_str(syslog_message_hostname(m)), Some("mymachine"));
+            assert_eq!(as_str(syslog_message_procid_name(m)), None);
+            let mut pid = 0;
+            assert!(syslog_message_pid(m, &mut pid));
+            assert_eq!(pid, 42);
+            let (mut secs, mut nanos) = (0, 0);
+            assert!(syslog_message_timestamp(
+                m,
+                &mut secs,
+                &mut nanos,
+                ptr::null_mut()
+            ));
+            assert_eq!((secs, nanos), (1065910455, 3_000_000));
+            let (sd_id, name) = (
+                CString::new("ex@32473").unwrap(),
+                CString::new("iut").unwrap(),
+            );
+            let value = syslog_message_sd_param(m, sd_id.as_ptr(), name.as_ptr());
+            assert_eq!(as_str(value), Some("3"));
+            assert_eq!(as_str(syslog_message_msg(m)), Some("hi"));
+
+            let s = syslog_message_to_string(m);
+            assert_eq!(CStr::from_ptr(s).to_bytes(), &input[..]);
+            syslog_string_free(s);
+            syslog_message_free(m);
+        }
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let input = b"<200>1 - - - - - -";
+        let mut error = ptr::null_mut();
+        unsafe {
+            let m = syslog_message_parse(input.as_ptr(), input.len(), &mut error);
+            assert!(m.is_null());
+            assert!(!error.is_null());
+            assert!(CStr::from_ptr(error).to_str().unwrap().contains("facility"));
+            syslog_string_free(error);
+
+            assert_eq!(syslog_message_severity(ptr::null()), -1);
+            assert_eq!(as_str(syslog_message_hostname(ptr::null())), None);
+            syslog_message_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,564 @@
+//! Deciding which messages a rule applies to.
+//!
+//! A `Matcher` is a predicate on `SyslogMessage`s, built up either in code:
+//!
+//! ```
+//! use syslog_rfc5424::filter::Matcher;
+//! use syslog_rfc5424::{parse_message, SyslogSeverity};
+//!
+//! let matcher = Matcher::severity_at_least(SyslogSeverity::SEV_WARNING)
+//!     .and(Matcher::appname("nginx"))
+//!     .and(Matcher::sd_equals("origin", "ip", "10.0.0.1"));
+//!
+//! let m = parse_message(r#"<28>1 - web1 nginx - - [origin ip="10.0.0.1"] upstream timed out"#).unwrap();
+//! assert!(matcher.matches(&m));
+//! ```
+//!
+//! or from a string, so that routing rules can live in a configuration file:
+//!
+//! ```
+//! use syslog_rfc5424::filter::Matcher;
+//!
+//! let matcher: Matcher =
+//!     r#"severity >= warning and appname == nginx and sd[origin][ip] == "10.0.0.1""#.parse().unwrap();
+//! assert_eq!(
+//!     matcher,
+//!     Matcher::severity_at_least(syslog_rfc5424::SyslogSeverity::SEV_WARNING)
+//!         .and(Matcher::appname("nginx"))
+//!         .and(Matcher::sd_equals("origin", "ip", "10.0.0.1"))
+//! );
+//! ```
+//!
+//! # Expressions
+//!
+//! An expression is made of comparisons joined with `and`, `or` and `not` (in increasing order
+//! of precedence) and grouped with parentheses. The comparisons are:
+//!
+//! * `severity OP NAME`, where `OP` is one of `==`, `!=`, `>=`, `>`, `<=` and `<`. Severities
+//!   compare by importance, so `severity >= warning` means "warning or worse".
+//! * `facility == NAME` and `facility != NAME`
+//! * `hostname`, `appname`, `procid`, `msgid` or `msg`, followed by `==`, `!=` or `contains` and a
+//!   value. A NIL field doesn't equal or contain anything.
+//! * `sd[SD-ID]` and `sd[SD-ID][NAME]` on their own, to test whether an SD-ELEMENT or SD-PARAM is
+//!   present, or followed by `==`, `!=` or `contains` and a value to test an SD-PARAM's value.
+//! * `true` and `false`
+//!
+//! Values are either bare words or double-quoted strings, in which `\"` and `\\` are escapes.
+//! Keywords are case-insensitive.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::message::{ProcId, SyslogMessage};
+use crate::{SyslogFacility, SyslogSeverity};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// A header field which a `Matcher` can look at
+pub enum Field {
+    Hostname,
+    AppName,
+    ProcId,
+    MsgId,
+    Msg,
+}
+
+impl Field {
+    /// Call `f` with the field's value, or return false if it's NIL
+    fn with<F: FnOnce(&str) -> bool>(self, m: &SyslogMessage, f: F) -> bool {
+        match self {
+            Field::Hostname => m.hostname.as_deref().is_some_and(f),
+            Field::AppName => m.appname.as_deref().is_some_and(f),
+            Field::ProcId => match &m.procid {
+                Some(ProcId::PID(pid)) => f(&pid.to_string()),
+                Some(ProcId::Name(name)) => f(name),
+                None => false,
+            },
+            Field::MsgId => m.msgid.as_deref().is_some_and(f),
+            Field::Msg => f(&m.msg),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A predicate on messages; see the module docs
+pub enum Matcher {
+    /// Always matches
+    True,
+    /// Never matches
+    False,
+    /// The severity is this one or a more important one
+    SeverityAtLeast(SyslogSeverity),
+    /// The severity is this one or a less important one
+    SeverityAtMost(SyslogSeverity),
+    Facility(SyslogFacility),
+    /// The field is present and equal to the value
+    Equals(Field, String),
+    /// The field is present and contains the value
+    Contains(Field, String),
+    /// There's an SD-ELEMENT with this SD-ID
+    SdExists(String),
+    /// There's an SD-PARAM with this SD-ID and name
+    SdParamExists(String, String),
+    /// The SD-PARAM with this SD-ID and name has the value
+    SdEquals(String, String, String),
+    /// The SD-PARAM with this SD-ID and name contains the value
+    SdContains(String, String, String),
+    And(Box<Matcher>, Box<Matcher>),
+    Or(Box<Matcher>, Box<Matcher>),
+    Not(Box<Matcher>),
+}
+
+impl Matcher {
+    pub fn severity_at_least(severity: SyslogSeverity) -> Self {
+        Matcher::SeverityAtLeast(severity)
+    }
+
+    pub fn severity_at_most(severity: SyslogSeverity) -> Self {
+        Matcher::SeverityAtMost(severity)
+    }
+
+    pub fn facility(facility: SyslogFacility) -> Self {
+        Matcher::Facility(facility)
+    }
+
+    pub fn hostname<S: Into<String>>(hostname: S) -> Self {
+        Matcher::Equals(Field::Hostname, hostname.into())
+    }
+
+    pub fn appname<S: Into<String>>(appname: S) -> Self {
+        Matcher::Equals(Field::AppName, appname.into())
+    }
+
+    pub fn msgid<S: Into<String>>(msgid: S) -> Self {
+        Matcher::Equals(Field::MsgId, msgid.into())
+    }
+
+    pub fn msg_contains<S: Into<String>>(needle: S) -> Self {
+        Matcher::Contains(Field::Msg, needle.into())
+    }
+
+    pub fn sd_exists<S: Into<String>>(sd_id: S) -> Self {
+        Matcher::SdExists(sd_id.into())
+    }
+
+    pub fn sd_equals<SI, SN, SV>(sd_id: SI, name: SN, value: SV) -> Self
+    where
+        SI: Into<String>,
+        SN: Into<String>,
+        SV: Into<String>,
+    {
+        Matcher::SdEquals(sd_id.into(), name.into(), value.into())
+    }
+
+    /// Match only messages which both `self` and `other` match
+    pub fn and(self, other: Matcher) -> Self {
+        Matcher::And(Box::new(self), Box::new(other))
+    }
+
+    /// Match messages which either `self` or `other` matches
+    pub fn or(self, other: Matcher) -> Self {
+        Matcher::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Match messages which `self` doesn't match
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Matcher::Not(Box::new(self))
+    }
+
+    /// Parse a string expression, as described in the module docs
+    pub fn parse(expr: &str) -> Result<Self, FilterError> {
+        let mut parser = ExprParser {
+            tokens: tokenize(expr)?,
+            pos: 0,
+            end: expr.len(),
+        };
+        let matcher = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(matcher),
+            Some((offset, token)) => Err(FilterError::Unexpected(token.to_string(), *offset)),
+        }
+    }
+
+    pub fn matches(&self, m: &SyslogMessage) -> bool {
+        match self {
+            Matcher::True => true,
+            Matcher::False => false,
+            // lower numbers are more important
+            Matcher::SeverityAtLeast(s) => m.severity <= *s,
+            Matcher::SeverityAtMost(s) => m.severity >= *s,
+            Matcher::Facility(f) => m.facility == *f,
+            Matcher::Equals(field, value) => field.with(m, |v| v == value),
+            Matcher::Contains(field, value) => field.with(m, |v| v.contains(value.as_str())),
+            Matcher::SdExists(sd_id) => m.sd.find_sdid(sd_id).is_some(),
+            Matcher::SdParamExists(sd_id, name) => m.sd.find_tuple(sd_id, name).is_some(),
+            Matcher::SdEquals(sd_id, name, value) => m.sd.find_tuple(sd_id, name) == Some(value),
+            Matcher::SdContains(sd_id, name, value) => {
+                m.sd.find_tuple(sd_id, name)
+                    .is_some_and(|v| v.contains(value.as_str()))
+            }
+            Matcher::And(a, b) => a.matches(m) && b.matches(m),
+            Matcher::Or(a, b) => a.matches(m) || b.matches(m),
+            Matcher::Not(a) => !a.matches(m),
+        }
+    }
+}
+
+impl FromStr for Matcher {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Matcher::parse(s)
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FilterError {
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("unexpected {0} at byte {1}")]
+    Unexpected(String, usize),
+    #[error("unterminated string starting at byte {0}")]
+    UnterminatedString(usize),
+    #[error("unknown field {0:?}")]
+    UnknownField(String),
+    #[error("{1} can't be compared with {0}")]
+    BadOperator(&'static str, String),
+    #[error("unknown severity {0:?}")]
+    UnknownSeverity(String),
+    #[error("unknown facility {0:?}")]
+    UnknownFacility(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    LParen,
+    RParen,
+    /// The contents of `[...]`
+    Bracket(String),
+    Op(&'static str),
+    Word(String),
+    Str(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::LParen => f.write_str("`(`"),
+            Token::RParen => f.write_str("`)`"),
+            Token::Bracket(s) => write!(f, "`[{}]`", s),
+            Token::Op(op) => write!(f, "`{}`", op),
+            Token::Word(w) => write!(f, "`{}`", w),
+            Token::Str(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+const OPS: &[&str] = &["==", "!=", ">=", "<=", ">", "<"];
+
+fn tokenize(expr: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut rest = expr;
+    loop {
+        rest = rest.trim_start();
+        let offset = expr.len() - rest.len();
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => return Ok(tokens),
+        };
+        let (token, len) = if c == '(' {
+            (Token::LParen, 1)
+        } else if c == ')' {
+            (Token::RParen, 1)
+        } else if c == '[' {
+            let end = rest
+                .find(']')
+                .ok_or(FilterError::UnterminatedString(offset))?;
+            (Token::Bracket(rest[1..end].to_string()), end + 1)
+        } else if c == '"' {
+            let mut value = String::new();
+            let mut chars = rest.char_indices().skip(1);
+            let end = loop {
+                match chars.next() {
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => value.push(c),
+                        None => return Err(FilterError::UnterminatedString(offset)),
+                    },
+                    Some((idx, '"')) => break idx,
+                    Some((_, c)) => value.push(c),
+                    None => return Err(FilterError::UnterminatedString(offset)),
+                }
+            };
+            (Token::Str(value), end + 1)
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(*op)) {
+            (Token::Op(op), op.len())
+        } else {
+            let len = rest
+                .find(|c: char| c.is_whitespace() || "()[]\"=!<>".contains(c))
+                .unwrap_or(rest.len());
+            if len == 0 {
+                return Err(FilterError::Unexpected(format!("`{}`", c), offset));
+            }
+            (Token::Word(rest[..len].to_string()), len)
+        };
+        tokens.push((offset, token));
+        rest = &rest[len..];
+    }
+}
+
+struct ExprParser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// The length of the expression, for errors at the end
+    end: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), FilterError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or(FilterError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Consume the next token if it's the (case-insensitive) keyword `kw`
+    fn keyword(&mut self, kw: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case(kw) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Matcher, FilterError> {
+        let mut matcher = self.and()?;
+        while self.keyword("or") {
+            matcher = matcher.or(self.and()?);
+        }
+        Ok(matcher)
+    }
+
+    fn and(&mut self) -> Result<Matcher, FilterError> {
+        let mut matcher = self.unary()?;
+        while self.keyword("and") {
+            matcher = matcher.and(self.unary()?);
+        }
+        Ok(matcher)
+    }
+
+    fn unary(&mut self) -> Result<Matcher, FilterError> {
+        if self.keyword("not") {
+            return Ok(self.unary()?.not());
+        }
+        let (offset, token) = self.next()?;
+        match token {
+            Token::LParen => {
+                let matcher = self.or()?;
+                match self.next() {
+                    Ok((_, Token::RParen)) => Ok(matcher),
+                    Ok((offset, token)) => Err(FilterError::Unexpected(token.to_string(), offset)),
+                    Err(_) => Err(FilterError::UnexpectedEnd),
+                }
+            }
+            Token::Word(word) => self.comparison(&word.to_ascii_lowercase(), offset),
+            token => Err(FilterError::Unexpected(token.to_string(), offset)),
+        }
+    }
+
+    /// An operator: a symbol, or `contains`
+    fn operator(&mut self) -> Result<String, FilterError> {
+        match self.next()? {
+            (_, Token::Op(op)) => Ok(op.to_string()),
+            (_, Token::Word(w)) if w.eq_ignore_ascii_case("contains") => Ok("contains".into()),
+            (offset, token) => Err(FilterError::Unexpected(token.to_string(), offset)),
+        }
+    }
+
+    fn value(&mut self) -> Result<String, FilterError> {
+        match self.next() {
+            Ok((_, Token::Word(v))) | Ok((_, Token::Str(v))) => Ok(v),
+            Ok((offset, token)) => Err(FilterError::Unexpected(token.to_string(), offset)),
+            Err(_) => Err(FilterError::Unexpected("end".into(), self.end)),
+        }
+    }
+
+    fn comparison(&mut self, field: &str, offset: usize) -> Result<Matcher, FilterError> {
+        let string_field = match field {
+            "true" => return Ok(Matcher::True),
+            "false" => return Ok(Matcher::False),
+            "severity" => {
+                let op = self.operator()?;
+                let value = self.value()?;
+                let sev = SyslogSeverity::from_str(&value)
+                    .map_err(|_| FilterError::UnknownSeverity(value))?;
+                return Ok(match op.as_str() {
+                    "==" => Matcher::SeverityAtLeast(sev).and(Matcher::SeverityAtMost(sev)),
+                    "!=" => Matcher::SeverityAtLeast(sev)
+                        .and(Matcher::SeverityAtMost(sev))
+                        .not(),
+                    ">=" => Matcher::SeverityAtLeast(sev),
+                    "<=" => Matcher::SeverityAtMost(sev),
+                    ">" => Matcher::SeverityAtMost(sev).not(),
+                    "<" => Matcher::SeverityAtLeast(sev).not(),
+                    _ => return Err(FilterError::BadOperator("severity", op)),
+                });
+            }
+            "facility" => {
+                let op = self.operator()?;
+                let value = self.value()?;
+                let fac = SyslogFacility::from_str(&value)
+                    .map_err(|_| FilterError::UnknownFacility(value))?;
+                return match op.as_str() {
+                    "==" => Ok(Matcher::Facility(fac)),
+                    "!=" => Ok(Matcher::Facility(fac).not()),
+                    _ => Err(FilterError::BadOperator("facility", op)),
+                };
+            }
+            "sd" => return self.sd_comparison(offset),
+            "hostname" => Field::Hostname,
+            "appname" => Field::AppName,
+            "procid" => Field::ProcId,
+            "msgid" => Field::MsgId,
+            "msg" => Field::Msg,
+            _ => return Err(FilterError::UnknownField(field.to_string())),
+        };
+        let op = self.operator()?;
+        let value = self.value()?;
+        match op.as_str() {
+            "==" => Ok(Matcher::Equals(string_field, value)),
+            "!=" => Ok(Matcher::Equals(string_field, value).not()),
+            "contains" => Ok(Matcher::Contains(string_field, value)),
+            _ => Err(FilterError::BadOperator("a string", op)),
+        }
+    }
+
+    fn sd_comparison(&mut self, offset: usize) -> Result<Matcher, FilterError> {
+        let sd_id = match self.next() {
+            Ok((_, Token::Bracket(sd_id))) => sd_id,
+            Ok((offset, token)) => return Err(FilterError::Unexpected(token.to_string(), offset)),
+            Err(_) => return Err(FilterError::Unexpected("`sd`".into(), offset)),
+        };
+        let name = match self.peek() {
+            Some(Token::Bracket(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                name
+            }
+            _ => return Ok(Matcher::SdExists(sd_id)),
+        };
+        let has_operator = match self.peek() {
+            Some(Token::Op(_)) => true,
+            Some(Token::Word(w)) => w.eq_ignore_ascii_case("contains"),
+            _ => false,
+        };
+        if !has_operator {
+            return Ok(Matcher::SdParamExists(sd_id, name));
+        }
+        let op = self.operator()?;
+        let value = self.value()?;
+        match op.as_str() {
+            "==" => Ok(Matcher::SdEquals(sd_id, name, value)),
+            "!=" => Ok(Matcher::SdEquals(sd_id, name, value).not()),
+            "contains" => Ok(Matcher::SdContains(sd_id, name, value)),
+            _ => Err(FilterError::BadOperator("an SD-PARAM", op)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Field, FilterError, Matcher};
+    use crate::parse_message;
+    use crate::{SyslogFacility, SyslogSeverity};
+
+    #[test]
+    fn test_matchers() {
+        let m = parse_message(
+            r#"<28>1 - web1 nginx 42 - [origin ip="10.0.0.1"][meta sequenceId="7"] upstream timed out"#,
+        )
+        .unwrap();
+        assert_eq!(m.severity, SyslogSeverity::SEV_WARNING);
+        assert!(Matcher::severity_at_least(SyslogSeverity::SEV_WARNING).matches(&m));
+        assert!(Matcher::severity_at_least(SyslogSeverity::SEV_INFO).matches(&m));
+        assert!(!Matcher::severity_at_least(SyslogSeverity::SEV_ERR).matches(&m));
+        assert!(Matcher::facility(SyslogFacility::LOG_DAEMON).matches(&m));
+        assert!(Matcher::Equals(Field::ProcId, "42".into()).matches(&m));
+        assert!(!Matcher::msgid("ID1").matches(&m));
+        assert!(Matcher::msg_contains("timed out")
+            .and(Matcher::sd_exists("meta"))
+            .matches(&m));
+        assert!(Matcher::hostname("web2")
+            .or(Matcher::sd_equals("origin", "ip", "10.0.0.1"))
+            .matches(&m));
+        assert!(Matcher::appname("nginx").not().not().matches(&m));
+    }
+
+    #[test]
+    fn test_parse_expressions() {
+        let m =
+            parse_message(r#"<28>1 - web1 nginx 42 - [origin ip="10.0.0.1"] upstream "timed" out"#)
+                .unwrap();
+        let matches = |expr: &str| {
+            Matcher::parse(expr)
+                .unwrap_or_else(|e| panic!("{}: {}", expr, e))
+                .matches(&m)
+        };
+        for expr in &[
+            "severity == warning",
+            "severity >= WARN and severity <= warning",
+            "severity > notice and severity < err",
+            "facility == daemon and not facility != daemon",
+            r#"msg contains "\"timed\"""#,
+            "sd[origin] and sd[origin][ip] and sd[origin][ip] contains 10.0.",
+            r#"(hostname == web2 or appname == "nginx") AND procid == 42"#,
+            "not msgid == x",
+            "true",
+        ] {
+            assert!(matches(expr), "{}", expr);
+        }
+        for expr in &[
+            "severity != warning",
+            "severity > warning",
+            "sd[meta]",
+            "sd[origin][ip] != 10.0.0.1",
+            "msgid == -",
+            "false or hostname contains 2",
+        ] {
+            assert!(!matches(expr), "{}", expr);
+        }
+
+        assert_eq!(Matcher::parse(""), Err(FilterError::UnexpectedEnd));
+        assert_eq!(
+            Matcher::parse("hostname == a b"),
+            Err(FilterError::Unexpected("`b`".into(), 14))
+        );
+        assert_eq!(
+            Matcher::parse("uptime > 3"),
+            Err(FilterError::UnknownField("uptime".into()))
+        );
+        assert_eq!(
+            Matcher::parse("facility > kern"),
+            Err(FilterError::BadOperator("facility", ">".into()))
+        );
+        assert_eq!(
+            Matcher::parse("severity == loud"),
+            Err(FilterError::UnknownSeverity("loud".into()))
+        );
+        assert_eq!(
+            Matcher::parse(r#"msg == "open"#),
+            Err(FilterError::UnterminatedString(7))
+        );
+        assert!(Matcher::parse("(true").is_err());
+    }
+}
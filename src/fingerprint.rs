@@ -0,0 +1,226 @@
+//! Stable fingerprints of messages, for deduplication.
+//!
+//! `SyslogMessage::fingerprint` hashes a canonical form of a message, so two copies of it get the
+//! same fingerprint however they were received. Unlike `Hash`, the result doesn't depend on the
+//! process, platform or version of this crate, so it can be stored or compared between machines.
+//!
+//! The canonical form ignores:
+//!
+//!  * the UTC offset of the timestamp (only the instant counts), and how it was parsed
+//!    (`timestamp_precision_lost` and `leap_second`)
+//!  * the case of the HOSTNAME
+//!  * the difference between an empty HOSTNAME, APP-NAME, PROCID or MSGID and a NIL one
+//!  * the order of SD-ELEMENTs and SD-PARAMs
+//!  * a BOM at the start of MSG, and `msg_is_utf8`
+//!  * whether the message was `truncated`
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::fingerprint::FingerprintOptions;
+//! use syslog_rfc5424::parse_message;
+//!
+//! let a = parse_message("<14>1 2020-01-01T00:00:00Z relay1.example.com app - - - hi").unwrap();
+//! let b = parse_message("<14>1 2020-01-01T00:00:05Z relay2.example.com app - - - hi").unwrap();
+//! assert_ne!(a.fingerprint(), b.fingerprint());
+//!
+//! let options = FingerprintOptions::new().with_timestamp(false).with_hostname(false);
+//! assert_eq!(a.fingerprint_with(&options), b.fingerprint_with(&options));
+//! ```
+
+use crate::message::{ProcId, SyslogMessage};
+use crate::parser::BOM;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Which fields go into a fingerprint; see the module docs
+pub struct FingerprintOptions {
+    timestamp: bool,
+    hostname: bool,
+}
+
+impl Default for FingerprintOptions {
+    fn default() -> Self {
+        FingerprintOptions {
+            timestamp: true,
+            hostname: true,
+        }
+    }
+}
+
+impl FingerprintOptions {
+    /// Include every field
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to include the TIMESTAMP (default: true)
+    pub fn with_timestamp(mut self, timestamp: bool) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Whether to include the HOSTNAME (default: true)
+    ///
+    /// Leaving it out treats copies of a message which relays have stamped with their own
+    /// hostname as the same message.
+    pub fn with_hostname(mut self, hostname: bool) -> Self {
+        self.hostname = hostname;
+        self
+    }
+}
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+const FNV_PRIME: u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+
+/// 128-bit FNV-1a, which is simple enough to be sure it'll never change
+struct Fnv(u128);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u128::from(b);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Write a field, prefixed with its length so that neighbouring fields can't run together
+    fn field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+
+    /// Write a field which may be NIL, treating empty as NIL
+    fn nilable(&mut self, value: Option<&str>) {
+        match value.filter(|v| !v.is_empty()) {
+            Some(v) => {
+                self.write(&[1]);
+                self.field(v.as_bytes());
+            }
+            None => self.write(&[0]),
+        }
+    }
+}
+
+pub(crate) fn fingerprint(m: &SyslogMessage, options: &FingerprintOptions) -> u128 {
+    let mut h = Fnv(FNV_OFFSET_BASIS);
+    h.write(&[m.facility as u8, m.severity as u8]);
+    h.write(&m.version.to_le_bytes());
+    match m.timestamp.filter(|_| options.timestamp) {
+        Some(ts) => {
+            h.write(&[1]);
+            h.write(&ts.secs.to_le_bytes());
+            h.write(&ts.nanos.to_le_bytes());
+        }
+        None => h.write(&[0]),
+    }
+    let hostname = m
+        .hostname
+        .as_deref()
+        .filter(|_| options.hostname)
+        .map(str::to_ascii_lowercase);
+    h.nilable(hostname.as_deref());
+    h.nilable(m.appname.as_deref());
+    let pid;
+    h.nilable(match m.procid {
+        Some(ProcId::PID(p)) => {
+            pid = p.to_string();
+            Some(pid.as_str())
+        }
+        Some(ProcId::Name(ref name)) => Some(name.as_str()),
+        None => None,
+    });
+    h.nilable(m.msgid.as_deref());
+    h.write(&(m.sd.len() as u64).to_le_bytes());
+    for (sd_id, params) in m.sd.iter() {
+        h.field(sd_id.as_bytes());
+        h.write(&(params.len() as u64).to_le_bytes());
+        for (name, value) in params {
+            h.field(name.as_bytes());
+            h.field(value.as_bytes());
+        }
+    }
+    let msg = m.msg_as_bytes();
+    let mut bom = [0; 3];
+    BOM.encode_utf8(&mut bom);
+    h.field(msg.strip_prefix(&bom[..]).unwrap_or(msg));
+    h.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::FingerprintOptions;
+    use crate::message::SyslogMessage;
+    use crate::parse_message;
+    use crate::parser::{parse_message_with, ParserConfig};
+
+    #[test]
+    fn test_fingerprint() {
+        let m = |s: &str| parse_message(s).unwrap();
+        let base = m(r#"<14>1 2020-01-01T00:00:00Z Host app 12 ID [a@1 x="1" y="2"][b@1] hi"#);
+        // the same message, written differently
+        for same in [
+            r#"<14>1 2020-01-01T01:00:00+01:00 host app 12 ID [b@1][a@1 y="2" x="1"] hi"#,
+            "<14>1 2020-01-01T00:00:00.000Z HOST app 12 ID [a@1 x=\"1\" y=\"2\"][b@1] \u{feff}hi",
+        ] {
+            assert_eq!(m(same).fingerprint(), base.fingerprint(), "{}", same);
+        }
+        let bom = "<14>1 2020-01-01T00:00:00Z host app 12 ID [a@1 x=\"1\" y=\"2\"][b@1] \u{feff}hi";
+        let kept = parse_message_with(&ParserConfig::new().with_strip_bom(false), bom).unwrap();
+        assert_eq!(kept.fingerprint(), base.fingerprint());
+        let mut empty = m("<14>1 - - - - - - hi");
+        empty.hostname = Some("".into());
+        assert_eq!(empty.fingerprint(), m("<14>1 - - - - - - hi").fingerprint());
+
+        let different = [
+            r#"<13>1 2020-01-01T00:00:00Z host app 12 ID [a@1 x="1" y="2"][b@1] hi"#,
+            r#"<14>1 2020-01-01T00:00:01Z host app 12 ID [a@1 x="1" y="2"][b@1] hi"#,
+            r#"<14>1 2020-01-01T00:00:00Z host2 app 12 ID [a@1 x="1" y="2"][b@1] hi"#,
+            r#"<14>1 2020-01-01T00:00:00Z host app 13 ID [a@1 x="1" y="2"][b@1] hi"#,
+            r#"<14>1 2020-01-01T00:00:00Z host app 12 - [a@1 x="1" y="2"][b@1] hi"#,
+            r#"<14>1 2020-01-01T00:00:00Z host app 12 ID [a@1 x="1" y="3"][b@1] hi"#,
+            r#"<14>1 2020-01-01T00:00:00Z host app 12 ID [a@1 x="1" y="2"] hi"#,
+            r#"<14>1 2020-01-01T00:00:00Z host app 12 ID [a@1 x="1" y="2"][b@1] ho"#,
+            // fields mustn't run into each other
+            r#"<14>1 2020-01-01T00:00:00Z host ap p12 ID [a@1 x="1" y="2"][b@1] hi"#,
+        ];
+        let mut seen = HashSet::new();
+        seen.insert(base.fingerprint());
+        for s in different {
+            assert!(seen.insert(m(s).fingerprint()), "{}", s);
+        }
+
+        let options = FingerprintOptions::new()
+            .with_timestamp(false)
+            .with_hostname(false);
+        assert_eq!(
+            m(different[1]).fingerprint_with(&options),
+            base.fingerprint_with(&options)
+        );
+        assert_eq!(
+            m(different[2]).fingerprint_with(&options),
+            base.fingerprint_with(&options)
+        );
+        // pinned, so that any change to the canonical form is noticed
+        assert_eq!(
+            m("<1>1 - - - - - -").fingerprint(),
+            267201878775716792624615663209882742569
+        );
+    }
+
+    #[test]
+    fn test_hash() {
+        let a: SyslogMessage = "<14>1 - host app - - [a@1 x=\"1\"][b@1] hi"
+            .parse()
+            .unwrap();
+        let b = parse_message_with(
+            &ParserConfig::new().with_preserve_sd_order(true),
+            "<14>1 - host app - - [b@1][a@1 x=\"1\"] hi",
+        )
+        .unwrap();
+        assert_eq!(a, b);
+        let set: HashSet<SyslogMessage> = vec![a, b].into_iter().collect();
+        assert_eq!(set.len(), 1);
+    }
+}
@@ -0,0 +1,765 @@
+//! Decoders for splitting a byte stream into individual syslog frames, and an encoder for
+//! producing them.
+//!
+//! When syslog is carried over a stream transport (TCP, TLS, Unix stream sockets), the messages
+//! have to be delimited somehow. [RFC 6587](https://tools.ietf.org/html/rfc6587) describes the
+//! two methods seen in the wild:
+//!
+//!  * Octet counting (§3.4.1), where every message is prefixed with its length in bytes
+//!  * Non-transparent framing (§3.4.2), where every message is followed by a trailer character
+//!
+//! Both are implemented here behind the common [`FrameDecoder`] trait. Decoders own an internal
+//! buffer, so you can push data into them exactly as it comes off the wire and pull out complete
+//! frames as they become available; partial frames are kept around until the rest arrives.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::framing::{FrameDecoder, NonTransparentDecoder};
+//!
+//! let mut decoder = NonTransparentDecoder::default();
+//! decoder.push(b"<1>1 - - - - - first\n<1>1 - - - - - sec");
+//! assert_eq!(decoder.next_frame().unwrap(), Some(b"<1>1 - - - - - first".to_vec()));
+//! assert_eq!(decoder.next_frame().unwrap(), None);
+//! decoder.push(b"ond\n");
+//! assert_eq!(decoder.next_frame().unwrap(), Some(b"<1>1 - - - - - second".to_vec()));
+//! ```
+//!
+//! Going the other way, `OctetCountingEncoder` serializes messages and prefixes them with their
+//! length.
+
+use std::io::{self, Write};
+
+use thiserror::Error;
+
+use crate::encoder;
+use crate::message::SyslogMessage;
+
+/// The default upper bound on the size of a single frame, in bytes
+pub const DEFAULT_MAX_FRAME_LENGTH: usize = 64 * 1024;
+
+/// The maximum number of digits allowed in an octet-counting `MSG-LEN`
+const MAX_LENGTH_DIGITS: usize = 10;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+/// Why a decoder couldn't produce a frame
+///
+/// Decoders drop the bytes behind an error, so the same error isn't returned again.
+pub enum FramingError {
+    /// The frame was longer than the maximum; it's skipped, and decoding carries on with the
+    /// next one
+    #[error("frame of {0} bytes exceeds the maximum frame length")]
+    FrameTooLong(usize),
+    /// An octet-counting header was malformed; since there's no telling where the next frame
+    /// starts, everything buffered is dropped
+    #[error("invalid octet count in frame header")]
+    InvalidLength,
+    #[error("stream ended in the middle of a frame")]
+    TruncatedFrame,
+}
+
+/// A decoder which splits a stream of bytes into syslog frames
+pub trait FrameDecoder {
+    /// Append newly-received bytes to the decoder's internal buffer
+    fn push(&mut self, data: &[u8]);
+
+    /// Pull the next complete frame out of the buffer, if there is one
+    ///
+    /// Returns `Ok(None)` if more data is needed to complete the next frame.
+    fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FramingError>;
+
+    /// Signal that the underlying stream has been closed and return whatever is left
+    ///
+    /// Any complete frames should be drained with `next_frame` first; this only deals with the
+    /// trailing partial frame, if any.
+    fn finish(&mut self) -> Result<Option<Vec<u8>>, FramingError>;
+
+    /// The number of bytes which have been pushed but not yet returned as part of a frame
+    fn buffered_len(&self) -> usize;
+}
+
+impl<D: FrameDecoder + ?Sized> FrameDecoder for Box<D> {
+    fn push(&mut self, data: &[u8]) {
+        (**self).push(data)
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FramingError> {
+        (**self).next_frame()
+    }
+
+    fn finish(&mut self) -> Result<Option<Vec<u8>>, FramingError> {
+        (**self).finish()
+    }
+
+    fn buffered_len(&self) -> usize {
+        (**self).buffered_len()
+    }
+}
+
+/// A buffer the decoders can split frames off the front of
+///
+/// The decoders keep their own `Vec`, but `SyslogCodec` runs them straight over tokio's
+/// `BytesMut` so that incoming data isn't copied into a second buffer.
+pub(crate) trait FrameBuf: AsRef<[u8]> {
+    type Frame: AsRef<[u8]>;
+
+    /// Remove the first `n` bytes and return them
+    fn split_frame(&mut self, n: usize) -> Self::Frame;
+
+    /// Remove the first `n` bytes
+    fn skip(&mut self, n: usize);
+
+    /// Remove everything
+    fn clear(&mut self);
+}
+
+impl FrameBuf for Vec<u8> {
+    type Frame = Vec<u8>;
+
+    fn split_frame(&mut self, n: usize) -> Vec<u8> {
+        self.drain(..n).collect()
+    }
+
+    fn skip(&mut self, n: usize) {
+        self.drain(..n);
+    }
+
+    fn clear(&mut self) {
+        Vec::clear(self)
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Decoder for octet-counted frames (RFC 6587 §3.4.1), e.g. `27 <1>1 - - - - - a message`
+pub struct OctetCountingDecoder {
+    buf: Vec<u8>,
+    max_frame_length: usize,
+    /// How much more of a too-long frame is still to be dropped
+    skip: usize,
+}
+
+impl OctetCountingDecoder {
+    pub fn new() -> Self {
+        OctetCountingDecoder {
+            buf: Vec::new(),
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+            skip: 0,
+        }
+    }
+
+    /// Set the largest `MSG-LEN` which will be accepted
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    /// Drop as much of a too-long frame as has arrived
+    fn skip_buffered<B: FrameBuf>(&mut self, buf: &mut B) {
+        let n = self.skip.min(buf.as_ref().len());
+        buf.skip(n);
+        self.skip -= n;
+    }
+
+    /// `next_frame`, over `buf` rather than the internal buffer
+    pub(crate) fn next_frame_in<B: FrameBuf>(
+        &mut self,
+        buf: &mut B,
+    ) -> Result<Option<B::Frame>, FramingError> {
+        self.skip_buffered(buf);
+        if self.skip > 0 {
+            return Ok(None);
+        }
+        let bytes = buf.as_ref();
+        let digits = bytes
+            .iter()
+            .take(MAX_LENGTH_DIGITS + 1)
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits == bytes.len() && digits <= MAX_LENGTH_DIGITS {
+            // still waiting on the rest of the header
+            return Ok(None);
+        }
+        let len = if digits == 0
+            || digits > MAX_LENGTH_DIGITS
+            || bytes[0] == b'0'
+            || bytes[digits] != b' '
+        {
+            None
+        } else {
+            // safe to unwrap: we just checked that these are all ASCII digits
+            std::str::from_utf8(&bytes[..digits])
+                .unwrap()
+                .parse::<usize>()
+                .ok()
+        };
+        let len = match len {
+            Some(len) => len,
+            None => {
+                buf.clear();
+                return Err(FramingError::InvalidLength);
+            }
+        };
+        let start = digits + 1;
+        if len > self.max_frame_length {
+            self.skip = start + len;
+            self.skip_buffered(buf);
+            return Err(FramingError::FrameTooLong(len));
+        }
+        if bytes.len() < start + len {
+            return Ok(None);
+        }
+        buf.skip(start);
+        Ok(Some(buf.split_frame(len)))
+    }
+
+    /// `finish`, over `buf` rather than the internal buffer
+    pub(crate) fn finish_in<B: FrameBuf>(
+        &mut self,
+        buf: &mut B,
+    ) -> Result<Option<B::Frame>, FramingError> {
+        self.skip = 0;
+        if buf.as_ref().is_empty() {
+            Ok(None)
+        } else {
+            buf.clear();
+            Err(FramingError::TruncatedFrame)
+        }
+    }
+}
+
+impl Default for OctetCountingDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameDecoder for OctetCountingDecoder {
+    fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FramingError> {
+        let mut buf = std::mem::take(&mut self.buf);
+        let frame = self.next_frame_in(&mut buf);
+        self.buf = buf;
+        frame
+    }
+
+    fn finish(&mut self) -> Result<Option<Vec<u8>>, FramingError> {
+        let mut buf = std::mem::take(&mut self.buf);
+        let frame = self.finish_in(&mut buf);
+        self.buf = buf;
+        frame
+    }
+
+    fn buffered_len(&self) -> usize {
+        self.buf.len()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+/// Encoder for octet-counted frames (RFC 6587 §3.4.1)
+///
+/// `MSG-LEN` is the length of the serialized message in bytes, including any multi-byte UTF-8
+/// characters and the BOM, which is what receivers expect.
+///
+/// # Example
+///
+/// ```
+/// use syslog_rfc5424::framing::OctetCountingEncoder;
+///
+/// let m = syslog_rfc5424::parse_message("<1>1 - - - - - - caf\u{e9}").unwrap();
+/// let mut framed = Vec::new();
+/// OctetCountingEncoder::new().encode(&m, &mut framed);
+/// assert_eq!(framed, "22 <1>1 - - - - - - caf\u{e9}".as_bytes());
+/// ```
+pub struct OctetCountingEncoder {
+    _private: (),
+}
+
+impl OctetCountingEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a single framed message to `dst`
+    pub fn encode(&self, m: &SyslogMessage, dst: &mut Vec<u8>) {
+        self.encode_frame(&encoder::to_bytes(m), dst)
+    }
+
+    /// Append an already-serialized message to `dst`, prefixed with its length
+    pub fn encode_frame(&self, body: &[u8], dst: &mut Vec<u8>) {
+        // writing into a Vec can't fail
+        let _ = write!(dst, "{} ", body.len());
+        dst.extend_from_slice(body);
+    }
+
+    /// Append several framed messages to `dst`
+    pub fn encode_batch<'a, I>(&self, messages: I, dst: &mut Vec<u8>)
+    where
+        I: IntoIterator<Item = &'a SyslogMessage>,
+    {
+        for m in messages {
+            self.encode(m, dst);
+        }
+    }
+
+    /// Write a single framed message
+    pub fn write<W: Write + ?Sized>(&self, w: &mut W, m: &SyslogMessage) -> io::Result<()> {
+        let mut framed = Vec::new();
+        self.encode(m, &mut framed);
+        w.write_all(&framed)
+    }
+
+    /// Write several framed messages with a single `write_all`
+    pub fn write_batch<'a, W, I>(&self, w: &mut W, messages: I) -> io::Result<()>
+    where
+        W: Write + ?Sized,
+        I: IntoIterator<Item = &'a SyslogMessage>,
+    {
+        let mut framed = Vec::new();
+        self.encode_batch(messages, &mut framed);
+        w.write_all(&framed)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// The trailer which terminates a non-transparently framed message
+pub enum Trailer {
+    /// A single line feed (`\n`); the only trailer RFC 6587 actually recommends
+    LF,
+    /// A carriage return followed by a line feed (`\r\n`)
+    CRLF,
+    /// A single NUL byte, as emitted by some older relays
+    NUL,
+}
+
+impl Trailer {
+    fn terminator(self) -> u8 {
+        match self {
+            Trailer::LF | Trailer::CRLF => b'\n',
+            Trailer::NUL => b'\0',
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Decoder for non-transparently framed messages (RFC 6587 §3.4.2)
+///
+/// By default, frames are split on `LF`. Empty frames (e.g. from blank lines) are skipped.
+pub struct NonTransparentDecoder {
+    buf: Vec<u8>,
+    trailers: Vec<Trailer>,
+    max_frame_length: usize,
+    /// How far into `buf` we've already searched for a trailer
+    scanned: usize,
+    /// Whether we're dropping the rest of a too-long frame, up to its trailer
+    discarding: bool,
+}
+
+impl NonTransparentDecoder {
+    /// Construct a decoder which splits on the given trailer
+    pub fn new(trailer: Trailer) -> Self {
+        NonTransparentDecoder {
+            buf: Vec::new(),
+            trailers: vec![trailer],
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+            scanned: 0,
+            discarding: false,
+        }
+    }
+
+    /// Additionally accept another trailer; useful for listeners shared by differently-behaved
+    /// senders
+    pub fn with_trailer(mut self, trailer: Trailer) -> Self {
+        if !self.trailers.contains(&trailer) {
+            self.trailers.push(trailer);
+        }
+        self
+    }
+
+    /// Set the largest frame which will be buffered while waiting for a trailer
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    fn is_terminator(&self, b: u8) -> bool {
+        self.trailers.iter().any(|t| t.terminator() == b)
+    }
+
+    /// The length of `frame` without the `CR` of a `CRLF` trailer
+    fn frame_len(&self, frame: &[u8]) -> usize {
+        if self.trailers.contains(&Trailer::CRLF) && frame.last() == Some(&b'\r') {
+            frame.len() - 1
+        } else {
+            frame.len()
+        }
+    }
+
+    /// `next_frame`, over `buf` rather than the internal buffer
+    ///
+    /// `buf` must be the same buffer each time, since how far it's been searched is remembered.
+    pub(crate) fn next_frame_in<B: FrameBuf>(
+        &mut self,
+        buf: &mut B,
+    ) -> Result<Option<B::Frame>, FramingError> {
+        loop {
+            let bytes = buf.as_ref();
+            let found = bytes[self.scanned..]
+                .iter()
+                .position(|b| self.is_terminator(*b))
+                .map(|idx| idx + self.scanned);
+            match found {
+                Some(idx) if self.discarding => {
+                    buf.skip(idx + 1);
+                    self.scanned = 0;
+                    self.discarding = false;
+                }
+                Some(idx) => {
+                    self.scanned = 0;
+                    let len = self.frame_len(&bytes[..idx]);
+                    if len > self.max_frame_length {
+                        buf.skip(idx + 1);
+                        return Err(FramingError::FrameTooLong(len));
+                    }
+                    if len == 0 {
+                        buf.skip(idx + 1);
+                        continue;
+                    }
+                    let frame = buf.split_frame(len);
+                    buf.skip(idx + 1 - len);
+                    return Ok(Some(frame));
+                }
+                None if self.discarding => {
+                    buf.clear();
+                    self.scanned = 0;
+                    return Ok(None);
+                }
+                None => {
+                    let len = bytes.len();
+                    self.scanned = len;
+                    if len > self.max_frame_length {
+                        buf.clear();
+                        self.scanned = 0;
+                        self.discarding = true;
+                        return Err(FramingError::FrameTooLong(len));
+                    }
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// `finish`, over `buf` rather than the internal buffer
+    pub(crate) fn finish_in<B: FrameBuf>(
+        &mut self,
+        buf: &mut B,
+    ) -> Result<Option<B::Frame>, FramingError> {
+        // A missing trailer on the very last message is common enough (e.g. from `nc`) that we
+        // just hand back whatever's left.
+        self.scanned = 0;
+        if std::mem::take(&mut self.discarding) {
+            buf.clear();
+            return Ok(None);
+        }
+        let len = self.frame_len(buf.as_ref());
+        let frame = buf.split_frame(len);
+        buf.clear();
+        if len == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(frame))
+        }
+    }
+}
+
+impl Default for NonTransparentDecoder {
+    fn default() -> Self {
+        Self::new(Trailer::LF)
+    }
+}
+
+impl FrameDecoder for NonTransparentDecoder {
+    fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FramingError> {
+        let mut buf = std::mem::take(&mut self.buf);
+        let frame = self.next_frame_in(&mut buf);
+        self.buf = buf;
+        frame
+    }
+
+    fn finish(&mut self) -> Result<Option<Vec<u8>>, FramingError> {
+        let mut buf = std::mem::take(&mut self.buf);
+        let frame = self.finish_in(&mut buf);
+        self.buf = buf;
+        frame
+    }
+
+    fn buffered_len(&self) -> usize {
+        self.buf.len()
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Detected {
+    OctetCounting(OctetCountingDecoder),
+    NonTransparent(NonTransparentDecoder),
+}
+
+#[derive(Clone, Debug)]
+/// Decoder which works out the framing from the first byte of the stream
+///
+/// RFC 6587 §3.4 suggests this: octet-counted frames start with a digit, while non-transparently
+/// framed messages start with the `<` of the PRI. Non-transparent frames may end in any trailer.
+pub struct DetectingDecoder {
+    detected: Option<Detected>,
+    max_frame_length: usize,
+}
+
+impl DetectingDecoder {
+    pub fn new() -> Self {
+        DetectingDecoder {
+            detected: None,
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+        }
+    }
+
+    /// Set the largest frame which will be accepted, whichever framing turns out to be in use
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    fn inner(&mut self) -> Option<&mut dyn FrameDecoder> {
+        match self.detected {
+            Some(Detected::OctetCounting(ref mut d)) => Some(d),
+            Some(Detected::NonTransparent(ref mut d)) => Some(d),
+            None => None,
+        }
+    }
+}
+
+impl Default for DetectingDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameDecoder for DetectingDecoder {
+    fn push(&mut self, data: &[u8]) {
+        if self.detected.is_none() {
+            self.detected = match data.first() {
+                None => return,
+                Some(b) if b.is_ascii_digit() => Some(Detected::OctetCounting(
+                    OctetCountingDecoder::new().with_max_frame_length(self.max_frame_length),
+                )),
+                Some(_) => Some(Detected::NonTransparent(
+                    NonTransparentDecoder::new(Trailer::LF)
+                        .with_trailer(Trailer::CRLF)
+                        .with_trailer(Trailer::NUL)
+                        .with_max_frame_length(self.max_frame_length),
+                )),
+            };
+        }
+        if let Some(d) = self.inner() {
+            d.push(data);
+        }
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Vec<u8>>, FramingError> {
+        self.inner().map_or(Ok(None), |d| d.next_frame())
+    }
+
+    fn finish(&mut self) -> Result<Option<Vec<u8>>, FramingError> {
+        self.inner().map_or(Ok(None), |d| d.finish())
+    }
+
+    fn buffered_len(&self) -> usize {
+        match self.detected {
+            Some(Detected::OctetCounting(ref d)) => d.buffered_len(),
+            Some(Detected::NonTransparent(ref d)) => d.buffered_len(),
+            None => 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+/// Which framing method a stream uses, for picking a decoder at runtime
+pub enum Framing {
+    /// See `OctetCountingDecoder`
+    OctetCounting,
+    /// See `NonTransparentDecoder`
+    NonTransparent(Trailer),
+    /// See `DetectingDecoder`
+    #[default]
+    Detect,
+}
+
+impl Framing {
+    /// Construct a new decoder for this framing method
+    pub fn decoder(self, max_frame_length: usize) -> Box<dyn FrameDecoder + Send> {
+        match self {
+            Framing::OctetCounting => {
+                Box::new(OctetCountingDecoder::new().with_max_frame_length(max_frame_length))
+            }
+            Framing::NonTransparent(trailer) => Box::new(
+                NonTransparentDecoder::new(trailer).with_max_frame_length(max_frame_length),
+            ),
+            Framing::Detect => {
+                Box::new(DetectingDecoder::new().with_max_frame_length(max_frame_length))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DetectingDecoder, FrameDecoder, Framing, FramingError, NonTransparentDecoder,
+        OctetCountingDecoder, OctetCountingEncoder, Trailer,
+    };
+
+    fn drain<D: FrameDecoder>(decoder: &mut D) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.next_frame().expect("should decode") {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn test_octet_counting() {
+        let mut d = OctetCountingDecoder::new();
+        d.push(b"16 <1>1 - - - - - a16 <1>1 - - - - - b");
+        assert_eq!(
+            drain(&mut d),
+            vec![b"<1>1 - - - - - a".to_vec(), b"<1>1 - - - - - b".to_vec()]
+        );
+        assert_eq!(d.buffered_len(), 0);
+        assert_eq!(d.finish(), Ok(None));
+    }
+
+    #[test]
+    fn test_octet_counting_encoder() {
+        let messages = [
+            crate::parse_message("<1>1 - - - - - - \u{1f980}").unwrap(),
+            crate::parse_message("<1>1 - host - - - -").unwrap(),
+        ];
+        let mut out = Vec::new();
+        OctetCountingEncoder::new()
+            .write_batch(&mut out, messages.iter())
+            .expect("should write");
+        assert_eq!(
+            out,
+            "21 <1>1 - - - - - - \u{1f980}19 <1>1 - host - - - -".as_bytes()
+        );
+
+        let mut decoder = OctetCountingDecoder::new();
+        decoder.push(&out);
+        let frames = drain(&mut decoder);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(
+            crate::parse_message(std::str::from_utf8(&frames[0]).unwrap()).unwrap(),
+            messages[0]
+        );
+    }
+
+    #[test]
+    fn test_octet_counting_partial() {
+        let mut d = OctetCountingDecoder::new();
+        d.push(b"1");
+        assert_eq!(d.next_frame(), Ok(None));
+        d.push(b"6 <1>1 - - ");
+        assert_eq!(d.next_frame(), Ok(None));
+        d.push(b"- - - a");
+        assert_eq!(d.next_frame(), Ok(Some(b"<1>1 - - - - - a".to_vec())));
+        d.push(b"16 <1>1");
+        assert_eq!(d.finish(), Err(FramingError::TruncatedFrame));
+    }
+
+    #[test]
+    fn test_octet_counting_bad_header() {
+        let mut d = OctetCountingDecoder::new();
+        d.push(b"<1>1 - - - - - a");
+        assert_eq!(d.next_frame(), Err(FramingError::InvalidLength));
+        assert_eq!(d.buffered_len(), 0);
+        assert_eq!(d.next_frame(), Ok(None));
+
+        let mut d = OctetCountingDecoder::new();
+        d.push(b"012 hello");
+        assert_eq!(d.next_frame(), Err(FramingError::InvalidLength));
+
+        let mut d = OctetCountingDecoder::new().with_max_frame_length(10);
+        d.push(b"16 <1>1 - - ");
+        assert_eq!(d.next_frame(), Err(FramingError::FrameTooLong(16)));
+        // the rest of the too-long frame is dropped, and the next one comes through
+        d.push(b"- - - a5 short");
+        assert_eq!(d.next_frame(), Ok(Some(b"short".to_vec())));
+        assert_eq!(d.finish(), Ok(None));
+    }
+
+    #[test]
+    fn test_non_transparent_lf() {
+        let mut d = NonTransparentDecoder::default();
+        d.push(b"one\n\ntwo\nthr");
+        assert_eq!(drain(&mut d), vec![b"one".to_vec(), b"two".to_vec()]);
+        d.push(b"ee\n");
+        assert_eq!(drain(&mut d), vec![b"three".to_vec()]);
+        assert_eq!(d.finish(), Ok(None));
+    }
+
+    #[test]
+    fn test_non_transparent_crlf_and_nul() {
+        let mut d = NonTransparentDecoder::new(Trailer::CRLF).with_trailer(Trailer::NUL);
+        d.push(b"one\r\ntwo\0three\r");
+        assert_eq!(drain(&mut d), vec![b"one".to_vec(), b"two".to_vec()]);
+        d.push(b"\n");
+        assert_eq!(drain(&mut d), vec![b"three".to_vec()]);
+    }
+
+    #[test]
+    fn test_non_transparent_finish() {
+        let mut d = NonTransparentDecoder::new(Trailer::NUL);
+        d.push(b"one\0two");
+        assert_eq!(drain(&mut d), vec![b"one".to_vec()]);
+        assert_eq!(d.finish(), Ok(Some(b"two".to_vec())));
+        assert_eq!(d.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_non_transparent_too_long() {
+        let mut d = NonTransparentDecoder::default().with_max_frame_length(4);
+        d.push(b"abcdefgh");
+        assert_eq!(d.next_frame(), Err(FramingError::FrameTooLong(8)));
+        // the rest of the too-long line is dropped, up to its trailer
+        d.push(b"ijk");
+        assert_eq!(d.next_frame(), Ok(None));
+        d.push(b"lm\nok\n");
+        assert_eq!(drain(&mut d), vec![b"ok".to_vec()]);
+
+        d.push(b"abcdefgh");
+        assert_eq!(d.next_frame(), Err(FramingError::FrameTooLong(8)));
+        d.push(b"ijk");
+        assert_eq!(d.finish(), Ok(None));
+        d.push(b"ok\n");
+        assert_eq!(drain(&mut d), vec![b"ok".to_vec()]);
+    }
+
+    #[test]
+    fn test_detecting() {
+        let mut d = DetectingDecoder::new();
+        assert_eq!(d.next_frame(), Ok(None));
+        d.push(b"");
+        d.push(b"3 one3 two");
+        assert_eq!(drain(&mut d), vec![b"one".to_vec(), b"two".to_vec()]);
+
+        let mut d = Framing::Detect.decoder(1024);
+        d.push(b"<1>one\r\n<1>two\0<1>3");
+        assert_eq!(drain(&mut d), vec![b"<1>one".to_vec(), b"<1>two".to_vec()]);
+        assert_eq!(d.finish(), Ok(Some(b"<1>3".to_vec())));
+    }
+}
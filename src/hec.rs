@@ -0,0 +1,188 @@
+//! Splunk HTTP Event Collector events (requires the `serde-serialize` feature).
+//!
+//! `HecFormatter` turns messages into the JSON envelope the HEC `/services/collector/event`
+//! endpoint takes:
+//!
+//!  * `time`: the TIMESTAMP, in seconds since the epoch (left out if it's NIL, so HEC uses the
+//!    time it received the event)
+//!  * `host`: the HOSTNAME
+//!  * `source`: as configured, or else the APP-NAME
+//!  * `sourcetype`: as configured, `syslog` by default
+//!  * `index`: as configured, if at all
+//!  * `event`: MSG
+//!  * `fields`: each SD-PARAM as `<SD-ID>.<name>`, plus `severity` and `facility`
+//!
+//! `write_batch` writes several events in one body, which is how HEC takes batches.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::hec::HecFormatter;
+//! use syslog_rfc5424::parse_message;
+//!
+//! let m = parse_message(
+//!     r#"<11>1 2020-01-01T00:00:00.5Z web1 nginx - - [req@32473 status="500"] timed out"#,
+//! ).unwrap();
+//! let event = HecFormatter::new().with_index("web").to_event(&m);
+//! assert_eq!(event["time"], 1577836800.5);
+//! assert_eq!(event["host"], "web1");
+//! assert_eq!(event["source"], "nginx");
+//! assert_eq!(event["fields"]["req@32473.status"], "500");
+//! ```
+
+use std::io::{self, Write};
+
+use serde_json::{json, Map, Value};
+
+use crate::message::SyslogMessage;
+
+/// The `sourcetype` events get, unless told otherwise
+pub const DEFAULT_SOURCETYPE: &str = "syslog";
+
+#[derive(Clone, Debug)]
+/// Formats messages as HEC events
+pub struct HecFormatter {
+    source: Option<String>,
+    sourcetype: String,
+    index: Option<String>,
+}
+
+impl Default for HecFormatter {
+    fn default() -> Self {
+        HecFormatter {
+            source: None,
+            sourcetype: DEFAULT_SOURCETYPE.to_string(),
+            index: None,
+        }
+    }
+}
+
+impl HecFormatter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the `source` of every event, instead of using the APP-NAME
+    pub fn with_source<S: Into<String>>(mut self, source: S) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Set the `sourcetype` of every event (default: `syslog`)
+    pub fn with_sourcetype<S: Into<String>>(mut self, sourcetype: S) -> Self {
+        self.sourcetype = sourcetype.into();
+        self
+    }
+
+    /// Send events to this index, instead of the token's default one
+    pub fn with_index<S: Into<String>>(mut self, index: S) -> Self {
+        self.index = Some(index.into());
+        self
+    }
+
+    /// The HEC event for a message
+    pub fn to_event(&self, m: &SyslogMessage) -> Value {
+        let mut event = Map::new();
+        if let Some(ts) = m.timestamp {
+            let time = ts.secs as f64 + f64::from(ts.nanos) / 1e9;
+            event.insert("time".into(), json!(time));
+        }
+        if let Some(ref hostname) = m.hostname {
+            event.insert("host".into(), hostname.as_str().into());
+        }
+        let source = self.source.as_deref().or(m.appname.as_deref());
+        if let Some(source) = source {
+            event.insert("source".into(), source.into());
+        }
+        event.insert("sourcetype".into(), self.sourcetype.as_str().into());
+        if let Some(ref index) = self.index {
+            event.insert("index".into(), index.as_str().into());
+        }
+        event.insert("event".into(), m.msg.as_str().into());
+        let mut fields = Map::new();
+        fields.insert("severity".into(), m.severity.as_str().into());
+        fields.insert("facility".into(), m.facility.as_str().into());
+        for (sd_id, name, value) in m.sd.iter_tuples() {
+            fields.insert(format!("{}.{}", sd_id, name), value.into());
+        }
+        event.insert("fields".into(), fields.into());
+        event.into()
+    }
+
+    /// Write the events for some messages as one HEC request body, one per line
+    pub fn write_batch<'a, W, I>(&self, w: &mut W, messages: I) -> io::Result<()>
+    where
+        W: Write + ?Sized,
+        I: IntoIterator<Item = &'a SyslogMessage>,
+    {
+        for m in messages {
+            serde_json::to_writer(&mut *w, &self.to_event(m))?;
+            w.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::HecFormatter;
+    use crate::parse_message;
+
+    #[test]
+    fn test_to_event() {
+        let m = parse_message(
+            r#"<165>1 2003-10-11T22:14:15.003-07:00 mymachine evntslog - ID47 [exampleSDID@32473 iut="3"] An application event"#,
+        )
+        .unwrap();
+        assert_eq!(
+            HecFormatter::new().to_event(&m),
+            json!({
+                "time": 1065935655.003,
+                "host": "mymachine",
+                "source": "evntslog",
+                "sourcetype": "syslog",
+                "event": "An application event",
+                "fields": {
+                    "severity": "notice",
+                    "facility": "local4",
+                    "exampleSDID@32473.iut": "3",
+                },
+            })
+        );
+
+        let m = parse_message("<14>1 - - - - - - hi").unwrap();
+        let formatter = HecFormatter::new()
+            .with_source("relay")
+            .with_sourcetype("rfc5424")
+            .with_index("main");
+        assert_eq!(
+            formatter.to_event(&m),
+            json!({
+                "source": "relay",
+                "sourcetype": "rfc5424",
+                "index": "main",
+                "event": "hi",
+                "fields": {"severity": "info", "facility": "user"},
+            })
+        );
+    }
+
+    #[test]
+    fn test_write_batch() {
+        let a = parse_message("<14>1 - - app - - - one").unwrap();
+        let b = parse_message("<14>1 - - app - - - two").unwrap();
+        let mut body = Vec::new();
+        HecFormatter::new()
+            .write_batch(&mut body, [&a, &b])
+            .unwrap();
+        let events: Vec<serde_json::Value> = serde_json::Deserializer::from_slice(&body)
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1]["event"], "two");
+        assert_eq!(body.iter().filter(|&&b| b == b'\n').count(), 2);
+    }
+}
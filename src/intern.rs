@@ -0,0 +1,153 @@
+//! Sharing repeated header values between messages.
+//!
+//! A collector usually hears from the same few thousand hosts and applications over and over, so
+//! `parser::Parser::parse_interned` can hand out shared `Arc<str>`s for HOSTNAME, APP-NAME and
+//! MSGID instead of allocating a fresh `String` for each message.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::message::SyslogMessage;
+
+/// Somewhere to look up previously-seen strings
+///
+/// Implement this to plug in your own cache (e.g. one shared between threads, or with a smarter
+/// eviction policy); `Interner` is a simple default.
+pub trait Cache {
+    /// Return a shared copy of `s`, reusing an earlier one if possible
+    fn intern(&mut self, s: &str) -> Arc<str>;
+}
+
+/// The default number of distinct strings an `Interner` holds before it starts over
+pub const DEFAULT_MAX_ENTRIES: usize = 64 * 1024;
+
+#[derive(Clone, Debug)]
+/// A `Cache` which keeps every string it's seen, up to a limit
+///
+/// Once the limit is reached, the whole cache is cleared, so that a sender making up a new
+/// hostname for every message can't use up all of the memory. Strings which are still in use
+/// elsewhere aren't affected; they just stop being shared with newer messages.
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+    max_entries: usize,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner {
+            strings: HashSet::new(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+
+    /// Set how many distinct strings to hold before clearing the cache
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// How many distinct strings are currently cached
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.strings.clear()
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Interner::new()
+    }
+}
+
+impl Cache for Interner {
+    fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(existing) = self.strings.get(s) {
+            return Arc::clone(existing);
+        }
+        if self.strings.len() >= self.max_entries {
+            self.strings.clear();
+        }
+        let new: Arc<str> = Arc::from(s);
+        self.strings.insert(Arc::clone(&new));
+        new
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A parsed message whose HOSTNAME, APP-NAME and MSGID are shared with other messages
+///
+/// Those three fields live here rather than in `message`, where they're always `None`.
+pub struct InternedMessage {
+    pub hostname: Option<Arc<str>>,
+    pub appname: Option<Arc<str>>,
+    pub msgid: Option<Arc<str>>,
+    /// Every other field
+    pub message: SyslogMessage,
+}
+
+impl InternedMessage {
+    /// Move the interned fields out of an owned message
+    pub fn from_message<C: Cache + ?Sized>(mut message: SyslogMessage, cache: &mut C) -> Self {
+        InternedMessage {
+            hostname: message.hostname.take().map(|s| cache.intern(&s)),
+            appname: message.appname.take().map(|s| cache.intern(&s)),
+            msgid: message.msgid.take().map(|s| cache.intern(&s)),
+            message,
+        }
+    }
+
+    /// Copy the interned fields back into a plain `SyslogMessage`
+    pub fn into_message(self) -> SyslogMessage {
+        let mut message = self.message;
+        message.hostname = self.hostname.map(|s| String::from(&*s));
+        message.appname = self.appname.map(|s| String::from(&*s));
+        message.msgid = self.msgid.map(|s| String::from(&*s));
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{Cache, Interner};
+    use crate::parser::{parse_message, Parser, ParserConfig};
+
+    #[test]
+    fn test_interner() {
+        let mut interner = Interner::new().with_max_entries(2);
+        let a = interner.intern("host1");
+        assert!(Arc::ptr_eq(&a, &interner.intern("host1")));
+        interner.intern("host2");
+        assert_eq!(interner.len(), 2);
+        // full, so this starts over
+        interner.intern("host3");
+        assert_eq!(interner.len(), 1);
+        assert!(!Arc::ptr_eq(&a, &interner.intern("host1")));
+    }
+
+    #[test]
+    fn test_parse_interned() {
+        let input = "<1>1 - host1 app 42 ID7 [a@1 b=\"c\"] hi";
+        for strict in &[false, true] {
+            let mut parser = Parser::new().with_config(ParserConfig::new().with_strict(*strict));
+            let m = parser.parse_interned(input).expect("should parse");
+            assert_eq!(m.appname.as_deref(), Some("app"));
+            assert!(m.message.hostname.is_none());
+            assert_eq!(m.into_message(), parse_message(input).unwrap());
+            assert_eq!(parser.cache().len(), 3);
+        }
+
+        let mut parser = Parser::new().with_config(ParserConfig::new().with_strict(true));
+        let long_sd_id =
+            r#"<1>1 - host1 app 42 - [this-sd-id-is-much-too-long-for-the-rfc a="b"] hi"#;
+        assert!(parser.parse_interned(long_sd_id).is_err());
+    }
+}
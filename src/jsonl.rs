@@ -0,0 +1,309 @@
+//! Writing messages as JSON Lines (requires the `serde-serialize` feature).
+//!
+//! `JsonLinesWriter` is a `Sink` which writes each message as one JSON object on its own line,
+//! as log shippers and `jq` expect:
+//!
+//! ```text
+//! {"timestamp":"2020-01-01T00:00:00Z","hostname":"web1","appname":"nginx","severity":"err","facility":"local0","sd":{"req@32473":{"status":"500"}},"msg":"upstream timed out"}
+//! ```
+//!
+//! The keys are `timestamp`, `hostname`, `appname`, `procid`, `msgid`, `severity`, `facility`,
+//! `sd` and `msg`, as in the `serde` representation of `SyslogMessage`; NIL fields are left out.
+//! `SeverityFormat` picks between severity and facility names and their numeric codes, and
+//! `TimeFormat` between RFC 3339 strings and times since the epoch.
+//!
+//! Each line is serialized into a buffer which is kept between messages, and then written with a
+//! single `write_all`. By default the writer is flushed after every message; with
+//! `FlushPolicy::Manual`, it's only flushed when `flush` is called, which is much faster when
+//! writing to a `BufWriter` or a file.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::jsonl::{JsonLinesWriter, SeverityFormat, TimeFormat};
+//! use syslog_rfc5424::parse_message;
+//! use syslog_rfc5424::sink::Sink;
+//!
+//! let m = parse_message("<11>1 2020-01-01T00:00:00.5Z web1 nginx - - - timed out").unwrap();
+//! let mut writer = JsonLinesWriter::new(Vec::new())
+//!     .with_severity_format(SeverityFormat::Numeric)
+//!     .with_time_format(TimeFormat::EpochMillis);
+//! writer.send(&m).unwrap();
+//! assert_eq!(
+//!     String::from_utf8(writer.into_inner()).unwrap(),
+//!     "{\"timestamp\":1577836800500,\"hostname\":\"web1\",\"appname\":\"nginx\",\"severity\":3,\"facility\":1,\"sd\":{},\"msg\":\"timed out\"}\n"
+//! );
+//! ```
+
+use std::io::{self, Write};
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::message::{ProcId, SyslogMessage};
+use crate::sink::Sink;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// How `severity` and `facility` are written
+pub enum SeverityFormat {
+    /// As names, like `"err"` and `"local0"`
+    Name,
+    /// As their numeric codes, like `3` and `16`
+    Numeric,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// How `timestamp` is written
+pub enum TimeFormat {
+    /// As an RFC 3339 string, in the sender's UTC offset; see `SyslogMessage::timestamp_rfc3339`
+    Rfc3339,
+    /// As fractional seconds since the epoch
+    EpochSeconds,
+    /// As whole milliseconds since the epoch
+    EpochMillis,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// When a `JsonLinesWriter` flushes the underlying writer
+pub enum FlushPolicy {
+    /// After every message
+    EveryMessage,
+    /// Only when `JsonLinesWriter::flush` is called
+    Manual,
+}
+
+/// A message, as serialized by a `JsonLinesWriter`
+struct Line<'a> {
+    m: &'a SyslogMessage,
+    severity_format: SeverityFormat,
+    time_format: TimeFormat,
+}
+
+impl<'a> Serialize for Line<'a> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let m = self.m;
+        let mut map = ser.serialize_map(None)?;
+        if let Some(ts) = m.timestamp {
+            match self.time_format {
+                TimeFormat::Rfc3339 => {
+                    if let Some(s) = m.timestamp_rfc3339() {
+                        map.serialize_entry("timestamp", &s)?;
+                    }
+                }
+                TimeFormat::EpochSeconds => {
+                    let secs = ts.secs as f64 + f64::from(ts.nanos) / 1e9;
+                    map.serialize_entry("timestamp", &secs)?;
+                }
+                TimeFormat::EpochMillis => {
+                    let millis = ts.secs * 1000 + i64::from(ts.nanos / 1_000_000);
+                    map.serialize_entry("timestamp", &millis)?;
+                }
+            }
+        }
+        if let Some(ref hostname) = m.hostname {
+            map.serialize_entry("hostname", hostname.as_str())?;
+        }
+        if let Some(ref appname) = m.appname {
+            map.serialize_entry("appname", appname.as_str())?;
+        }
+        match m.procid {
+            Some(ProcId::PID(pid)) => map.serialize_entry("procid", &pid)?,
+            Some(ProcId::Name(ref name)) => map.serialize_entry("procid", name)?,
+            None => {}
+        }
+        if let Some(ref msgid) = m.msgid {
+            map.serialize_entry("msgid", msgid.as_str())?;
+        }
+        match self.severity_format {
+            SeverityFormat::Name => {
+                map.serialize_entry("severity", m.severity.as_str())?;
+                map.serialize_entry("facility", m.facility.as_str())?;
+            }
+            SeverityFormat::Numeric => {
+                map.serialize_entry("severity", &(m.severity as u8))?;
+                map.serialize_entry("facility", &(m.facility as u8))?;
+            }
+        }
+        map.serialize_entry("sd", &m.sd)?;
+        map.serialize_entry("msg", &m.msg)?;
+        map.end()
+    }
+}
+
+#[derive(Debug)]
+/// A `Sink` which writes each message to an `io::Write` as a line of JSON
+pub struct JsonLinesWriter<W> {
+    writer: W,
+    line: Vec<u8>,
+    severity_format: SeverityFormat,
+    time_format: TimeFormat,
+    flush_policy: FlushPolicy,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLinesWriter {
+            writer,
+            line: Vec::new(),
+            severity_format: SeverityFormat::Name,
+            time_format: TimeFormat::Rfc3339,
+            flush_policy: FlushPolicy::EveryMessage,
+        }
+    }
+
+    /// Set how `severity` and `facility` are written (default: `SeverityFormat::Name`)
+    pub fn with_severity_format(mut self, format: SeverityFormat) -> Self {
+        self.severity_format = format;
+        self
+    }
+
+    /// Set how `timestamp` is written (default: `TimeFormat::Rfc3339`)
+    pub fn with_time_format(mut self, format: TimeFormat) -> Self {
+        self.time_format = format;
+        self
+    }
+
+    /// Set when the underlying writer is flushed (default: `FlushPolicy::EveryMessage`)
+    pub fn with_flush_policy(mut self, policy: FlushPolicy) -> Self {
+        self.flush_policy = policy;
+        self
+    }
+
+    /// Flush the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Sink for JsonLinesWriter<W> {
+    fn send(&mut self, m: &SyslogMessage) -> io::Result<()> {
+        self.line.clear();
+        let line = Line {
+            m,
+            severity_format: self.severity_format,
+            time_format: self.time_format,
+        };
+        serde_json::to_writer(&mut self.line, &line)?;
+        self.line.push(b'\n');
+        self.writer.write_all(&self.line)?;
+        if self.flush_policy == FlushPolicy::EveryMessage {
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+
+    use serde_json::json;
+
+    use super::{FlushPolicy, JsonLinesWriter, SeverityFormat, TimeFormat};
+    use crate::parse_message;
+    use crate::sink::Sink;
+
+    fn lines(bytes: &[u8]) -> Vec<serde_json::Value> {
+        bytes
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_json_lines_writer() {
+        let m = parse_message(
+            r#"<165>1 2003-10-11T22:14:15.003-07:00 mymachine evntslog 12 ID47 [exampleSDID@32473 iut="3"] An application event"#,
+        )
+        .unwrap();
+        let mut writer = JsonLinesWriter::new(Vec::new());
+        writer.send(&m).unwrap();
+        writer
+            .send(&parse_message("<14>1 - - - app - - hi").unwrap())
+            .unwrap();
+        assert_eq!(
+            lines(&writer.into_inner()),
+            vec![
+                json!({
+                    "timestamp": "2003-10-11T22:14:15.003-07:00",
+                    "hostname": "mymachine",
+                    "appname": "evntslog",
+                    "procid": 12,
+                    "msgid": "ID47",
+                    "severity": "notice",
+                    "facility": "local4",
+                    "sd": {"exampleSDID@32473": {"iut": "3"}},
+                    "msg": "An application event",
+                }),
+                json!({
+                    "procid": "app",
+                    "severity": "info",
+                    "facility": "user",
+                    "sd": {},
+                    "msg": "hi",
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_formats() {
+        let m = parse_message("<165>1 2003-10-11T22:14:15.003-07:00 - - - - - x").unwrap();
+        let mut writer = JsonLinesWriter::new(Vec::new())
+            .with_severity_format(SeverityFormat::Numeric)
+            .with_time_format(TimeFormat::EpochSeconds);
+        writer.send(&m).unwrap();
+        let line = &lines(writer.get_ref())[0];
+        assert_eq!(line["timestamp"], 1065935655.003);
+        assert_eq!(line["severity"], 5);
+        assert_eq!(line["facility"], 20);
+
+        let mut writer = JsonLinesWriter::new(Vec::new()).with_time_format(TimeFormat::EpochMillis);
+        writer.send(&m).unwrap();
+        assert_eq!(lines(writer.get_ref())[0]["timestamp"], 1065935655003i64);
+    }
+
+    #[derive(Default)]
+    struct CountingWriter {
+        data: Vec<u8>,
+        flushes: usize,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.data.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_flush_policy() {
+        let m = parse_message("<14>1 - - - - - - hi").unwrap();
+        let mut writer = JsonLinesWriter::new(CountingWriter::default());
+        writer.send(&m).unwrap();
+        writer.send(&m).unwrap();
+        assert_eq!(writer.get_ref().flushes, 2);
+
+        let mut writer =
+            JsonLinesWriter::new(CountingWriter::default()).with_flush_policy(FlushPolicy::Manual);
+        writer.send(&m).unwrap();
+        writer.send(&m).unwrap();
+        assert_eq!(writer.get_ref().flushes, 0);
+        writer.flush().unwrap();
+        let inner = writer.into_inner();
+        assert_eq!(inner.flushes, 1);
+        assert_eq!(lines(&inner.data).len(), 2);
+    }
+}
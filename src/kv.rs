@@ -0,0 +1,151 @@
+//! A flat, key-value view of a message.
+//!
+//! `SyslogMessage::iter_kv` yields every field of a message as a `(key, FieldValue)` pair, for
+//! exporters which deal in flat keys (metrics labels, key-value stores, ...) and would rather not
+//! know how `SyslogMessage` is laid out. The keys are `severity`, `facility`, `version`,
+//! `timestamp`, `hostname`, `appname`, `procid`, `msgid`, each SD-PARAM as `sd.<SD-ID>.<name>`,
+//! and last `msg`; NIL fields are left out.
+//!
+//! The header keys are borrowed; the SD keys have to be put together, so each one is allocated.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::kv::FieldValue;
+//! use syslog_rfc5424::parse_message;
+//!
+//! let m = parse_message(r#"<14>1 - web1 nginx 42 - [origin ip="10.0.0.1"] hi"#).unwrap();
+//! let kv: Vec<_> = m.iter_kv().collect();
+//! assert_eq!(kv[3], ("hostname".into(), FieldValue::Str("web1")));
+//! assert_eq!(kv[5], ("procid".into(), FieldValue::Int(42)));
+//! assert_eq!(kv[6], ("sd.origin.ip".into(), FieldValue::Str("10.0.0.1")));
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::encoder;
+use crate::message::{ProcId, SyslogMessage};
+use crate::timestamp::Timestamp;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The value of one field of a message
+pub enum FieldValue<'a> {
+    Str(&'a str),
+    Int(i64),
+    Timestamp(Timestamp),
+}
+
+impl<'a> FieldValue<'a> {
+    /// The value, if it's a string
+    pub fn as_str(&self) -> Option<&'a str> {
+        match *self {
+            FieldValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> fmt::Display for FieldValue<'a> {
+    /// Strings as they are, integers in decimal, and timestamps as RFC 3339 (or seconds since the
+    /// epoch, if they can't be written that way)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FieldValue::Str(s) => f.write_str(s),
+            FieldValue::Int(i) => write!(f, "{}", i),
+            FieldValue::Timestamp(ts) => {
+                if !encoder::write_rfc3339(f, ts, false, 9)? {
+                    write!(f, "{}", ts.secs)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Every field of `m`, in order; see the module documentation
+pub(crate) fn iter_kv(
+    m: &SyslogMessage,
+) -> impl Iterator<Item = (Cow<'_, str>, FieldValue<'_>)> + '_ {
+    let mut header = vec![
+        ("severity", FieldValue::Str(m.severity.as_str())),
+        ("facility", FieldValue::Str(m.facility.as_str())),
+        ("version", FieldValue::Int(i64::from(m.version))),
+    ];
+    if let Some(ts) = m.timestamp {
+        header.push(("timestamp", FieldValue::Timestamp(ts)));
+    }
+    if let Some(ref hostname) = m.hostname {
+        header.push(("hostname", FieldValue::Str(hostname)));
+    }
+    if let Some(ref appname) = m.appname {
+        header.push(("appname", FieldValue::Str(appname)));
+    }
+    match m.procid {
+        Some(ProcId::PID(pid)) => header.push(("procid", FieldValue::Int(i64::from(pid)))),
+        Some(ProcId::Name(ref name)) => header.push(("procid", FieldValue::Str(name))),
+        None => {}
+    }
+    if let Some(ref msgid) = m.msgid {
+        header.push(("msgid", FieldValue::Str(msgid)));
+    }
+    let sd = m.sd.iter_tuples().map(|(sd_id, name, value)| {
+        let key = format!("sd.{}.{}", sd_id, name);
+        (Cow::Owned(key), FieldValue::Str(value))
+    });
+    header
+        .into_iter()
+        .map(|(key, value)| (Cow::Borrowed(key), value))
+        .chain(sd)
+        .chain(std::iter::once((
+            Cow::Borrowed("msg"),
+            FieldValue::Str(m.msg.as_str()),
+        )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FieldValue;
+    use crate::parse_message;
+    use crate::timestamp::Timestamp;
+
+    #[test]
+    fn test_iter_kv() {
+        let m = parse_message(
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine evntslog - ID47 [a@1 x="1" y="2"][b z="3"] hi"#,
+        )
+        .unwrap();
+        let kv: Vec<_> = m
+            .iter_kv()
+            .map(|(key, value)| (key.into_owned(), value))
+            .collect();
+        assert_eq!(
+            kv.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>(),
+            vec![
+                "severity",
+                "facility",
+                "version",
+                "timestamp",
+                "hostname",
+                "appname",
+                "msgid",
+                "sd.a@1.x",
+                "sd.a@1.y",
+                "sd.b.z",
+                "msg"
+            ]
+        );
+        assert_eq!(kv[0].1, FieldValue::Str("notice"));
+        assert_eq!(kv[2].1, FieldValue::Int(1));
+        assert_eq!(
+            kv[3].1,
+            FieldValue::Timestamp(Timestamp::new(1065910455, 3_000_000, 0))
+        );
+        assert_eq!(kv[3].1.to_string(), "2003-10-11T22:14:15.003Z");
+        assert_eq!(kv[8].1.as_str(), Some("2"));
+        assert_eq!(kv[10].1.to_string(), "hi");
+
+        let m = parse_message("<1>1 - - - - - -").unwrap();
+        assert_eq!(m.iter_kv().count(), 4);
+    }
+}
@@ -0,0 +1,426 @@
+//! IBM QRadar Log Event Extended Format payloads in MSG.
+//!
+//! A LEEF string has a `|`-separated header and then attributes separated by a delimiter, which
+//! is a tab in LEEF 1.0 and can be chosen in the header in LEEF 2.0:
+//!
+//! ```text
+//! LEEF:1.0|Microsoft|MSExchange|4.0 SP1|15345|src=10.50.1.1<TAB>dst=2.10.20.20<TAB>spt=1200
+//! LEEF:2.0|Lancope|StealthWatch|1.0|41|^|src=10.0.1.8^dst=10.0.0.5^sev=5
+//! ```
+//!
+//! `SyslogMessage::leef_event` parses one out of MSG, `merge_leef_into_sd` turns it into
+//! STRUCTURED-DATA, and `set_leef_event` writes one into MSG.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::leef::LeefEvent;
+//! use syslog_rfc5424::parse_message;
+//!
+//! let m = parse_message(
+//!     "<134>1 - fw1 - - - - LEEF:2.0|Acme|Firewall|2.1|deny|^|src=10.0.0.1^dst=10.0.0.2",
+//! ).unwrap();
+//! let event = m.leef_event().unwrap().unwrap();
+//! assert_eq!(event.event_id, "deny");
+//! assert_eq!(event.attribute("dst"), Some("10.0.0.2"));
+//!
+//! let event = LeefEvent::new("Acme", "Firewall", "2.1", "allow").with_attribute("src", "10.0.0.3");
+//! assert_eq!(event.to_string(), "LEEF:1.0|Acme|Firewall|2.1|allow|src=10.0.0.3");
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::message::{SDIDType, SyslogMessage};
+
+/// The marker LEEF strings start with
+pub const LEEF_PREFIX: &str = "LEEF:";
+
+/// The SD-ID events are merged into by default
+///
+/// 32473 is the enterprise number reserved for documentation (RFC 5612); use your own if you have
+/// one.
+pub const DEFAULT_SD_ID: &str = "leef@32473";
+
+/// The attribute delimiter of LEEF 1.0, and of LEEF 2.0 if the header doesn't give one
+pub const DEFAULT_DELIMITER: char = '\t';
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LeefError {
+    #[error("not a LEEF string")]
+    MissingPrefix,
+    #[error("unsupported LEEF version {0:?}")]
+    UnsupportedVersion(String),
+    #[error("LEEF header is missing the {0} field")]
+    MissingField(&'static str),
+    #[error("invalid LEEF delimiter {0:?}")]
+    InvalidDelimiter(String),
+    #[error("invalid LEEF attribute {0:?}")]
+    InvalidAttribute(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LeefVersion {
+    V1,
+    V2,
+}
+
+impl LeefVersion {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LeefVersion::V1 => "1.0",
+            LeefVersion::V2 => "2.0",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A parsed LEEF event
+pub struct LeefEvent {
+    pub version: LeefVersion,
+    pub vendor: String,
+    pub product: String,
+    pub product_version: String,
+    pub event_id: String,
+    /// What separates the attributes; only LEEF 2.0 can have anything but a tab
+    pub delimiter: char,
+    /// The (key, value) attribute pairs, in order
+    pub attributes: Vec<(String, String)>,
+}
+
+impl LeefEvent {
+    /// A LEEF 1.0 event with no attributes
+    pub fn new<V, P, PV, E>(vendor: V, product: P, product_version: PV, event_id: E) -> Self
+    where
+        V: Into<String>,
+        P: Into<String>,
+        PV: Into<String>,
+        E: Into<String>,
+    {
+        LeefEvent {
+            version: LeefVersion::V1,
+            vendor: vendor.into(),
+            product: product.into(),
+            product_version: product_version.into(),
+            event_id: event_id.into(),
+            delimiter: DEFAULT_DELIMITER,
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Separate attributes with `delimiter`, which makes this a LEEF 2.0 event
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.version = LeefVersion::V2;
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Add an attribute
+    pub fn with_attribute<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.attributes.push((key.into(), value.into()));
+        self
+    }
+
+    /// The value of an attribute, if it's present
+    ///
+    /// If a key appears more than once, the last value wins.
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The header fields as (key, value) pairs, followed by the attributes
+    pub fn to_params(&self) -> Vec<(String, String)> {
+        let header = [
+            ("leefVersion", self.version.as_str()),
+            ("vendor", &self.vendor),
+            ("product", &self.product),
+            ("productVersion", &self.product_version),
+            ("eventId", &self.event_id),
+        ];
+        header
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .chain(self.attributes.iter().cloned())
+            .collect()
+    }
+}
+
+/// Parse a LEEF 2.0 delimiter: a single character, or its code in hex as `x5E` or `0x5E`
+fn parse_delimiter(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => Some(DEFAULT_DELIMITER),
+        (Some(c), None) => Some(c),
+        _ => {
+            let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix('x'))?;
+            u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+        }
+    }
+}
+
+impl FromStr for LeefEvent {
+    type Err = LeefError;
+
+    /// Parse a LEEF string, which must start with `LEEF:`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const NAMES: [&str; 5] = ["Version", "Vendor", "Product", "Product Version", "EventID"];
+        let s = s
+            .strip_prefix(LEEF_PREFIX)
+            .ok_or(LeefError::MissingPrefix)?;
+        let mut parts = s.splitn(6, '|');
+        let mut header = Vec::with_capacity(5);
+        for name in NAMES.iter() {
+            match parts.next() {
+                Some(field) if header.len() < 4 || parts.clone().next().is_some() => {
+                    header.push(field)
+                }
+                _ => return Err(LeefError::MissingField(name)),
+            }
+        }
+        let mut rest = parts.next().unwrap_or("");
+        let version = match header[0].trim() {
+            "1.0" | "1" => LeefVersion::V1,
+            "2.0" | "2" => LeefVersion::V2,
+            other => return Err(LeefError::UnsupportedVersion(other.to_string())),
+        };
+        let mut delimiter = DEFAULT_DELIMITER;
+        if version == LeefVersion::V2 {
+            // the delimiter field is optional, so only take it if it looks like one
+            if let Some((field, attributes)) = rest.split_once('|') {
+                if field.len() <= 4 && !field.contains('=') {
+                    delimiter = parse_delimiter(field)
+                        .ok_or_else(|| LeefError::InvalidDelimiter(field.to_string()))?;
+                    rest = attributes;
+                }
+            }
+        }
+        let mut attributes = Vec::new();
+        for attribute in rest.split(delimiter) {
+            if attribute.trim().is_empty() {
+                continue;
+            }
+            match attribute.split_once('=') {
+                Some((key, value)) if !key.trim().is_empty() => {
+                    attributes.push((key.trim().to_string(), value.to_string()))
+                }
+                _ => return Err(LeefError::InvalidAttribute(attribute.to_string())),
+            }
+        }
+        Ok(LeefEvent {
+            version,
+            vendor: header[1].to_string(),
+            product: header[2].to_string(),
+            product_version: header[3].to_string(),
+            event_id: header[4].to_string(),
+            delimiter,
+            attributes,
+        })
+    }
+}
+
+impl fmt::Display for LeefEvent {
+    /// Write the event as a LEEF string
+    ///
+    /// LEEF has no escaping, so any `|` in the header, and any delimiter in the attributes (or `=`
+    /// in their keys), is written as a space.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = |s: &str| s.replace('|', " ");
+        write!(
+            f,
+            "{}{}|{}|{}|{}|{}|",
+            LEEF_PREFIX,
+            self.version.as_str(),
+            header(&self.vendor),
+            header(&self.product),
+            header(&self.product_version),
+            header(&self.event_id)
+        )?;
+        if self.version == LeefVersion::V2 {
+            let d = self.delimiter;
+            if d.is_ascii_graphic() && d != '|' {
+                write!(f, "{}|", d)?;
+            } else {
+                write!(f, "x{:02X}|", u32::from(d))?;
+            }
+        }
+        let clean = |s: &str, extra: char| {
+            s.chars()
+                .map(|c| {
+                    if c == self.delimiter || c == extra {
+                        ' '
+                    } else {
+                        c
+                    }
+                })
+                .collect::<String>()
+        };
+        for (i, (key, value)) in self.attributes.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", self.delimiter)?;
+            }
+            write!(f, "{}={}", clean(key, '='), clean(value, self.delimiter))?;
+        }
+        Ok(())
+    }
+}
+
+/// Find and parse the LEEF string in MSG, if there is one
+///
+/// The string may come after other text, like the RFC 3164 style header some devices put before
+/// it. Returns the text before it, and the event.
+pub fn find_leef(msg: &str) -> Result<Option<(&str, LeefEvent)>, LeefError> {
+    match msg.find(LEEF_PREFIX) {
+        Some(i) => Ok(Some((
+            &msg[..i],
+            msg[i..].trim_end_matches(['\r', '\n']).parse()?,
+        ))),
+        None => Ok(None),
+    }
+}
+
+impl SyslogMessage {
+    /// The LEEF event in MSG, if it has one; see the `leef` module
+    pub fn leef_event(&self) -> Result<Option<LeefEvent>, LeefError> {
+        Ok(find_leef(&self.msg)?.map(|(_, event)| event))
+    }
+
+    /// Move the LEEF event in MSG into the SD-ELEMENT `sd_id`
+    ///
+    /// This works like `merge_cef_into_sd`, using `LeefEvent::to_params`.
+    pub fn merge_leef_into_sd<SI: Into<SDIDType>>(&mut self, sd_id: SI) -> Result<bool, LeefError> {
+        match find_leef(&self.msg)? {
+            Some((before, event)) => {
+                let before = before.trim_end().len();
+                self.merge_params_into_sd(sd_id.into(), before, event.to_params());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Replace MSG with a LEEF event
+    pub fn set_leef_event(&mut self, event: &LeefEvent) {
+        self.msg = event.to_string();
+        self.msg_bytes = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_leef, parse_delimiter, LeefError, LeefEvent, LeefVersion};
+    use crate::parse_message;
+
+    #[test]
+    fn test_parse_v1() {
+        let event: LeefEvent =
+            "LEEF:1.0|Microsoft|MSExchange|4.0 SP1|15345|src=10.50.1.1\tdst=2.10.20.20\tmsg=a=b c|d"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            event,
+            LeefEvent::new("Microsoft", "MSExchange", "4.0 SP1", "15345")
+                .with_attribute("src", "10.50.1.1")
+                .with_attribute("dst", "2.10.20.20")
+                .with_attribute("msg", "a=b c|d")
+        );
+        assert_eq!(event.attribute("msg"), Some("a=b c|d"));
+
+        let event: LeefEvent = "LEEF:1.0|V|P|1|ID|".parse().unwrap();
+        assert!(event.attributes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        for s in [
+            "LEEF:2.0|V|P|1|ID|^|a=1^b=2",
+            "LEEF:2.0|V|P|1|ID|x5E|a=1^b=2",
+            "LEEF:2.0|V|P|1|ID|0x5e|a=1^b=2",
+        ] {
+            let event: LeefEvent = s.parse().unwrap();
+            assert_eq!(event.version, LeefVersion::V2);
+            assert_eq!(event.delimiter, '^');
+            assert_eq!(
+                event.attributes,
+                vec![("a".into(), "1".into()), ("b".into(), "2".into())],
+                "{}",
+                s
+            );
+        }
+        // without the delimiter field
+        let event: LeefEvent = "LEEF:2.0|V|P|1|ID|a=1\tb=2".parse().unwrap();
+        assert_eq!(event.delimiter, '\t');
+        assert_eq!(event.attributes.len(), 2);
+        let event: LeefEvent = "LEEF:2.0|V|P|1|ID||a=1\tb=2".parse().unwrap();
+        assert_eq!(event.delimiter, '\t');
+
+        assert_eq!(parse_delimiter("x09"), Some('\t'));
+        assert_eq!(parse_delimiter("xZZ"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "CEF:0|x".parse::<LeefEvent>(),
+            Err(LeefError::MissingPrefix)
+        );
+        assert_eq!(
+            "LEEF:3.0|V|P|1|ID|".parse::<LeefEvent>(),
+            Err(LeefError::UnsupportedVersion("3.0".into()))
+        );
+        assert_eq!(
+            "LEEF:1.0|V|P|1".parse::<LeefEvent>(),
+            Err(LeefError::MissingField("EventID"))
+        );
+        assert_eq!(
+            "LEEF:2.0|V|P|1|ID|xZZ|a=1".parse::<LeefEvent>(),
+            Err(LeefError::InvalidDelimiter("xZZ".into()))
+        );
+        assert_eq!(
+            "LEEF:1.0|V|P|1|ID|a=1\toops".parse::<LeefEvent>(),
+            Err(LeefError::InvalidAttribute("oops".into()))
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        for s in [
+            "LEEF:1.0|Microsoft|MSExchange|4.0 SP1|15345|src=10.50.1.1\tdst=2.10.20.20",
+            "LEEF:2.0|V|P|1|ID|^|a=1^b=2",
+            "LEEF:2.0|V|P|1|ID|x09|a=1\tb=2",
+        ] {
+            assert_eq!(s.parse::<LeefEvent>().unwrap().to_string(), s);
+        }
+        let event = LeefEvent::new("V|x", "P", "1", "ID")
+            .with_delimiter('^')
+            .with_attribute("k=", "a^b");
+        assert_eq!(event.to_string(), "LEEF:2.0|V x|P|1|ID|^|k =a b");
+    }
+
+    #[test]
+    fn test_message() {
+        let (before, event) = find_leef("Sep 19 08:26:10 host LEEF:1.0|V|P|1|ID|a=b\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(before, "Sep 19 08:26:10 host ");
+        assert_eq!(event.attributes, vec![("a".into(), "b".into())]);
+
+        let mut m = parse_message("<134>1 - fw1 - - - - LEEF:1.0|V|P|1|ID|src=10.0.0.1").unwrap();
+        assert!(m.merge_leef_into_sd("leef@32473").unwrap());
+        assert_eq!(
+            m.to_string(),
+            r#"<134>1 - fw1 - - - [leef@32473 eventId="ID" leefVersion="1.0" product="P" productVersion="1" src="10.0.0.1" vendor="V"]"#
+        );
+
+        let mut m = parse_message("<134>1 - fw1 - - - - plain text").unwrap();
+        assert_eq!(m.leef_event(), Ok(None));
+        assert!(!m.merge_leef_into_sd("leef@32473").unwrap());
+        m.set_leef_event(&LeefEvent::new("V", "P", "1", "ID").with_attribute("a", "b"));
+        assert_eq!(m.msg, "LEEF:1.0|V|P|1|ID|a=b");
+    }
+}
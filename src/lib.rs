@@ -24,19 +24,98 @@
 //! }
 //! ```
 //!
-//! # Unimplemented Features
+//! # Non-UTF-8 messages
 //!
-//!  * Theoretically, you can send arbitrary (non-unicode) bytes for the message part of a syslog
-//!    message. Rust doesn't have a convenient way to only treat *some* of a buffer as utf-8,
-//!    so I'm just not supporting that. Most "real" syslog servers barf on it anway.
+//! Theoretically, you can send arbitrary (non-unicode) bytes for the message part of a syslog
+//! message. If you need to deal with that, use `parser::parse_message_bytes` and pick a
+//! `parser::Utf8Policy`.
 //!
+//! # Serializing
+//!
+//! `SyslogMessage` implements `Display`, which renders it back into the RFC 5424 wire format, so
+//! `message.to_string()` is all a relay needs to pass on a (possibly rewritten) message.
+//!
+#[macro_use]
+mod macros;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "bumpalo")]
+pub mod arena;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod borrowed;
+pub mod builder;
+#[cfg(feature = "cee")]
+pub mod cee;
+pub mod cef;
+#[cfg(feature = "tokio")]
+pub mod codec;
+#[cfg(feature = "compact-str")]
+pub mod compact;
+pub mod csv;
+pub mod diagnostic;
+#[cfg(feature = "serde-serialize")]
+pub mod ecs;
+mod encoder;
 mod facility;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+pub mod fingerprint;
+pub mod framing;
+#[cfg(feature = "serde-serialize")]
+pub mod hec;
+pub mod intern;
+#[cfg(feature = "serde-serialize")]
+pub mod jsonl;
+pub mod kv;
+pub mod leef;
+pub mod logfmt;
+#[cfg(feature = "log")]
+pub mod logger;
 pub mod message;
+pub mod metrics;
+pub mod order;
+#[cfg(feature = "otel")]
+pub mod otel;
+#[cfg(feature = "parquet")]
+pub mod parquet;
 pub mod parser;
+mod pri;
+pub mod reader;
+#[cfg(feature = "redact")]
+pub mod redact;
+mod scan;
+pub mod sd;
+pub mod sd_map;
+#[cfg(feature = "serde-serialize")]
+pub mod serde_numeric;
+#[cfg(feature = "serde-serialize")]
+pub mod serialize;
 mod severity;
+pub mod sink;
+pub mod ssign;
+pub mod stream;
+mod timestamp;
+#[cfg(feature = "tracing")]
+pub mod tracing_layer;
+#[cfg(feature = "transport")]
+pub mod transport;
+pub mod validation;
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
 
 pub use facility::SyslogFacility;
+pub use pri::Pri;
 pub use severity::SyslogSeverity;
+pub use timestamp::Timestamp;
 
+pub use borrowed::SyslogMessageRef;
+pub use builder::SyslogMessageBuilder;
 pub use message::SyslogMessage;
-pub use parser::parse_message;
+#[cfg(feature = "rayon")]
+pub use parser::parse_messages_par;
+pub use parser::{
+    parse_message, parse_message_ref, parse_message_with, parse_messages, ParserConfig,
+};
@@ -0,0 +1,172 @@
+//! Rendering messages as logfmt.
+//!
+//! `SyslogMessage::to_logfmt` renders a message as one logfmt line, which tools like Grafana Loki
+//! can pick apart without any further configuration:
+//!
+//! ```text
+//! ts=2020-01-01T00:00:00Z host=web1 app=nginx procid=42 severity=err facility=local0 sd_req_status=500 msg="upstream timed out"
+//! ```
+//!
+//! The keys are `ts`, `host`, `app`, `procid`, `msgid`, `severity`, `facility`, then each SD-PARAM
+//! as `sd_<SD-ID>_<name>`, and last `msg`; NIL fields are left out. Anything but letters, digits
+//! and `_` in SD-IDs and SD-PARAM names becomes `_`. Values are quoted if they need to be, with
+//! `\`, `"` and control characters escaped.
+//!
+//! `LogfmtWriter` is a `Sink` which writes each message as a line of logfmt.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::parse_message;
+//!
+//! let m = parse_message(r#"<14>1 - web1 nginx - - [origin ip="10.0.0.1"] said "hi""#).unwrap();
+//! assert_eq!(
+//!     m.to_logfmt(),
+//!     r#"host=web1 app=nginx severity=info facility=user sd_origin_ip=10.0.0.1 msg="said \"hi\"""#
+//! );
+//! ```
+
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::message::{ProcId, SyslogMessage};
+use crate::sink::Sink;
+
+/// Write `value`, quoting and escaping it if it needs it
+fn write_value<W: fmt::Write + ?Sized>(w: &mut W, value: &str) -> fmt::Result {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c == ' ' || c == '=' || c == '"' || c == '\\' || c.is_control());
+    if !needs_quotes {
+        return w.write_str(value);
+    }
+    w.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            '\n' => w.write_str("\\n")?,
+            '\r' => w.write_str("\\r")?,
+            '\t' => w.write_str("\\t")?,
+            c if c.is_control() => write!(w, "\\u{:04x}", u32::from(c))?,
+            c => w.write_char(c)?,
+        }
+    }
+    w.write_char('"')
+}
+
+/// Write `name`, with anything but letters, digits and `_` replaced
+fn write_key_part<W: fmt::Write + ?Sized>(w: &mut W, name: &str) -> fmt::Result {
+    for c in name.chars() {
+        w.write_char(if c.is_ascii_alphanumeric() { c } else { '_' })?;
+    }
+    Ok(())
+}
+
+pub(crate) fn write_logfmt<W: fmt::Write + ?Sized>(w: &mut W, m: &SyslogMessage) -> fmt::Result {
+    let mut sep = "";
+    let mut pair = |w: &mut W, key: &str, value: &str| {
+        write!(w, "{}{}=", sep, key)?;
+        sep = " ";
+        write_value(w, value)
+    };
+    if let Some(ts) = m.timestamp_rfc3339() {
+        pair(w, "ts", &ts)?;
+    }
+    if let Some(ref hostname) = m.hostname {
+        pair(w, "host", hostname)?;
+    }
+    if let Some(ref appname) = m.appname {
+        pair(w, "app", appname)?;
+    }
+    match m.procid {
+        Some(ProcId::PID(pid)) => pair(w, "procid", &pid.to_string())?,
+        Some(ProcId::Name(ref name)) => pair(w, "procid", name)?,
+        None => {}
+    }
+    if let Some(ref msgid) = m.msgid {
+        pair(w, "msgid", msgid)?;
+    }
+    pair(w, "severity", m.severity.as_str())?;
+    pair(w, "facility", m.facility.as_str())?;
+    for (sd_id, name, value) in m.sd.iter_tuples() {
+        let mut key = String::from("sd_");
+        write_key_part(&mut key, sd_id)?;
+        key.push('_');
+        write_key_part(&mut key, name)?;
+        pair(w, &key, value)?;
+    }
+    pair(w, "msg", &m.msg)
+}
+
+#[derive(Debug)]
+/// A `Sink` which writes each message to an `io::Write` as a line of logfmt
+pub struct LogfmtWriter<W> {
+    writer: W,
+    line: String,
+}
+
+impl<W: Write> LogfmtWriter<W> {
+    pub fn new(writer: W) -> Self {
+        LogfmtWriter {
+            writer,
+            line: String::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Sink for LogfmtWriter<W> {
+    fn send(&mut self, m: &SyslogMessage) -> io::Result<()> {
+        self.line.clear();
+        write_logfmt(&mut self.line, m).expect("writing to a String can't fail");
+        self.line.push('\n');
+        self.writer.write_all(self.line.as_bytes())?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LogfmtWriter;
+    use crate::parse_message;
+    use crate::sink::Sink;
+
+    #[test]
+    fn test_to_logfmt() {
+        let m = parse_message(
+            r#"<165>1 2003-10-11T22:14:15.003-07:00 mymachine evntslog 12 ID47 [exampleSDID@32473 iut="3" eventSource="App lication"] An "application" event"#,
+        )
+        .unwrap();
+        assert_eq!(
+            m.to_logfmt(),
+            r#"ts=2003-10-11T22:14:15.003-07:00 host=mymachine app=evntslog procid=12 msgid=ID47 severity=notice facility=local4 sd_exampleSDID_32473_eventSource="App lication" sd_exampleSDID_32473_iut=3 msg="An \"application\" event""#
+        );
+
+        let mut m = parse_message("<14>1 - - - - - -").unwrap();
+        assert_eq!(m.to_logfmt(), r#"severity=info facility=user msg="""#);
+        m.msg = "a=b\\c\nd\te\u{1}".to_string();
+        assert_eq!(
+            m.to_logfmt(),
+            r#"severity=info facility=user msg="a=b\\c\nd\te\u0001""#
+        );
+        m.msg = "plain".to_string();
+        assert_eq!(m.to_logfmt(), "severity=info facility=user msg=plain");
+    }
+
+    #[test]
+    fn test_logfmt_writer() {
+        let mut writer = LogfmtWriter::new(Vec::new());
+        let m = parse_message("<14>1 - host app - - - hi").unwrap();
+        writer.send(&m).unwrap();
+        writer.send(&m).unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "host=host app=app severity=info facility=user msg=hi\n".repeat(2)
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! A backend for the `log` crate (requires the `log` feature).
+//!
+//! `SyslogLogger` turns each `log::Record` into a `SyslogMessage`: the level becomes the severity,
+//! the module path becomes the APP-NAME, the formatted message becomes MSG, and the record's
+//! key-values become SD-PARAMs in a single SD-ELEMENT. The messages are written to a `Sink`, such
+//! as a `transport::sender::Sender`. It's the `log` counterpart of `tracing_layer::SyslogLayer`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use syslog_rfc5424::logger::SyslogLogger;
+//! use syslog_rfc5424::sink::WriteSink;
+//!
+//! SyslogLogger::new(WriteSink::new(std::io::stderr()))
+//!     .with_hostname("myhost")
+//!     .with_level(log::LevelFilter::Info)
+//!     .init()
+//!     .unwrap();
+//!
+//! log::warn!(path = "/var"; "disk almost full");
+//! ```
+
+use std::fmt;
+use std::process;
+use std::sync::{Mutex, PoisonError};
+
+use log::kv::{self, Key, Value, VisitSource};
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::builder::SyslogMessageBuilder;
+use crate::facility::SyslogFacility;
+use crate::message::{pid_t, SyslogMessage};
+use crate::severity::SyslogSeverity;
+use crate::sink::Sink;
+use crate::validation::{self, MAX_APPNAME_LENGTH};
+
+/// The SD-ID key-values are put under, unless told otherwise
+///
+/// 32473 is the enterprise number reserved for documentation (RFC 5612); use your own with
+/// `SyslogLogger::with_sd_id` if you have one.
+pub const DEFAULT_SD_ID: &str = "log@32473";
+
+/// The severity a `log` level maps to
+///
+/// `Trace` and `Debug` both become `SEV_DEBUG`.
+pub fn severity_for(level: Level) -> SyslogSeverity {
+    match level {
+        Level::Error => SyslogSeverity::SEV_ERR,
+        Level::Warn => SyslogSeverity::SEV_WARNING,
+        Level::Info => SyslogSeverity::SEV_INFO,
+        Level::Debug | Level::Trace => SyslogSeverity::SEV_DEBUG,
+    }
+}
+
+#[derive(Default)]
+struct KeyValues(Vec<(String, String)>);
+
+impl<'kvs> VisitSource<'kvs> for KeyValues {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push((
+            validation::sanitize_sd_name(key.as_str()),
+            value.to_string(),
+        ));
+        Ok(())
+    }
+}
+
+/// A `log::Log` which writes every record to a `Sink` as a syslog message; see the module docs
+///
+/// Errors from the sink are ignored, since there's nowhere sensible to report them.
+pub struct SyslogLogger<S> {
+    sink: Mutex<S>,
+    level: LevelFilter,
+    facility: SyslogFacility,
+    hostname: Option<String>,
+    appname: Option<String>,
+    pid: pid_t,
+    sd_id: String,
+}
+
+impl<S> fmt::Debug for SyslogLogger<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SyslogLogger")
+            .field("level", &self.level)
+            .field("facility", &self.facility)
+            .field("hostname", &self.hostname)
+            .field("appname", &self.appname)
+            .field("pid", &self.pid)
+            .field("sd_id", &self.sd_id)
+            .finish()
+    }
+}
+
+impl<S: Sink> SyslogLogger<S> {
+    /// Write records of every level to `sink` as `LOG_USER` messages, with NIL HOSTNAME and this
+    /// process's ID
+    pub fn new(sink: S) -> Self {
+        SyslogLogger {
+            sink: Mutex::new(sink),
+            level: LevelFilter::Trace,
+            facility: SyslogFacility::LOG_USER,
+            hostname: None,
+            appname: None,
+            pid: process::id() as pid_t,
+            sd_id: DEFAULT_SD_ID.to_string(),
+        }
+    }
+
+    /// Only write records at `level` or above
+    pub fn with_level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn with_facility(mut self, facility: SyslogFacility) -> Self {
+        self.facility = facility;
+        self
+    }
+
+    pub fn with_hostname<H: Into<String>>(mut self, hostname: H) -> Self {
+        self.hostname = Some(hostname.into());
+        self
+    }
+
+    /// Use `appname` as the APP-NAME of every message, instead of the record's module path
+    pub fn with_appname<A: Into<String>>(mut self, appname: A) -> Self {
+        self.appname = Some(appname.into());
+        self
+    }
+
+    /// Put key-values under `sd_id` instead of `DEFAULT_SD_ID`
+    pub fn with_sd_id<I: Into<String>>(mut self, sd_id: I) -> Self {
+        self.sd_id = sd_id.into();
+        self
+    }
+
+    /// Build the message `record` would be written as
+    ///
+    /// Keys are made into valid PARAM-NAMEs, and the module path (or the target, if there isn't
+    /// one) into a valid APP-NAME, by replacing anything which isn't allowed with `_` and
+    /// truncating them.
+    pub fn message_for(&self, record: &Record<'_>) -> SyslogMessage {
+        let mut key_values = KeyValues::default();
+        let _ = record.key_values().visit(&mut key_values);
+        let msg = record.args().to_string();
+        let mut builder = SyslogMessageBuilder::new()
+            .severity(severity_for(record.level()))
+            .facility(self.facility)
+            .pid(self.pid)
+            .msg_is_utf8(!msg.is_ascii())
+            .msg(msg);
+        if let Some(ref hostname) = self.hostname {
+            builder = builder.hostname(hostname.clone());
+        }
+        match self.appname {
+            Some(ref appname) => builder = builder.appname(appname.clone()),
+            None => {
+                let path = record.module_path().unwrap_or_else(|| record.target());
+                if let Some(appname) = validation::sanitize_header_field(path, MAX_APPNAME_LENGTH) {
+                    builder = builder.appname(appname);
+                }
+            }
+        }
+        if !key_values.0.is_empty() {
+            builder = builder.sd_element(&self.sd_id, key_values.0);
+        }
+        builder.build_unchecked()
+    }
+}
+
+impl<S: Sink + Send + 'static> SyslogLogger<S> {
+    /// Install this as the global logger, and set `log`'s maximum level to match
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let level = self.level;
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl<S: Sink + Send> Log for SyslogLogger<S> {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let m = self.message_for(record);
+        let mut sink = self.sink.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = sink.send(&m);
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use log::{Level, LevelFilter, Log, Record};
+
+    use super::SyslogLogger;
+    use crate::message::{ProcId, SyslogMessage};
+    use crate::severity::SyslogSeverity;
+
+    #[test]
+    fn test_logger() {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let out = Arc::clone(&messages);
+        let logger = SyslogLogger::new(move |m: &SyslogMessage| {
+            out.lock().unwrap().push(m.clone());
+            Ok(())
+        })
+        .with_hostname("host1")
+        .with_level(LevelFilter::Info);
+
+        let key_values = [("user", "bob"), ("bad key", "x")];
+        logger.log(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("app")
+                .module_path(Some("myapp::db"))
+                .args(format_args!("disk {} full", "/var"))
+                .key_values(&key_values)
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(Level::Debug)
+                .args(format_args!("too quiet"))
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(Level::Error)
+                .target("my target")
+                .args(format_args!("caf\u{e9}"))
+                .build(),
+        );
+
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 2);
+        let m = &messages[0];
+        assert_eq!(m.severity, SyslogSeverity::SEV_WARNING);
+        assert_eq!(m.hostname.as_deref(), Some("host1"));
+        assert_eq!(m.appname.as_deref(), Some("myapp::db"));
+        assert_eq!(m.procid, Some(ProcId::PID(std::process::id() as i32)));
+        assert_eq!(m.msg, "disk /var full");
+        assert_eq!(
+            m.sd.find_tuple("log@32473", "user"),
+            Some(&"bob".to_string())
+        );
+        assert_eq!(
+            m.sd.find_tuple("log@32473", "bad_key"),
+            Some(&"x".to_string())
+        );
+        assert_eq!(m.validate(), Ok(()));
+
+        let m = &messages[1];
+        assert_eq!(m.severity, SyslogSeverity::SEV_ERR);
+        assert_eq!(m.appname.as_deref(), Some("my_target"));
+        assert!(m.sd.is_empty());
+        assert!(m.msg_is_utf8);
+    }
+}
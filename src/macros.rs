@@ -0,0 +1,221 @@
+//! The `syslog_msg!` macro.
+
+/// Build a message inline, using `SyslogMessageBuilder`
+///
+/// Takes any number of `field: value,` pairs followed by an optional MSG, and evaluates to the
+/// result of `SyslogMessageBuilder::build()`. `severity` and `facility` can be given by their
+/// short names (`INFO`, `LOCAL0`) or as any expression; `sd` takes a map of SD-IDs to maps of
+/// params, whose values can be anything implementing `Display`. Any other field calls the builder
+/// method of the same name.
+///
+/// # Example
+///
+/// ```
+/// use syslog_rfc5424::syslog_msg;
+///
+/// let m = syslog_msg!(
+///     severity: INFO,
+///     facility: LOCAL0,
+///     appname: "myapp",
+///     sd: { "meta" => { "seq" => 1 } },
+///     "hello"
+/// )
+/// .unwrap();
+/// assert_eq!(m.msg, "hello");
+/// assert_eq!(m.sd.find_tuple("meta", "seq"), Some(&"1".to_string()));
+/// ```
+#[macro_export]
+macro_rules! syslog_msg {
+    ($($rest:tt)*) => {
+        $crate::__syslog_msg_fields!(($crate::SyslogMessageBuilder::new()) $($rest)*)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __syslog_msg_fields {
+    (($b:expr)) => {
+        $b.build()
+    };
+    (($b:expr) severity: $sev:ident $(, $($rest:tt)*)?) => {
+        $crate::__syslog_msg_fields!(
+            ($b.severity($crate::__syslog_severity!($sev))) $($($rest)*)?
+        )
+    };
+    (($b:expr) facility: $fac:ident $(, $($rest:tt)*)?) => {
+        $crate::__syslog_msg_fields!(
+            ($b.facility($crate::__syslog_facility!($fac))) $($($rest)*)?
+        )
+    };
+    (($b:expr) sd: {
+        $($id:expr => { $($k:expr => $v:expr),* $(,)? }),* $(,)?
+    } $(, $($rest:tt)*)?) => {
+        $crate::__syslog_msg_fields!(
+            ($b $(.sd_element($id, {
+                let params: ::std::vec::Vec<(::std::string::String, ::std::string::String)> =
+                    ::std::vec![$((
+                        ::std::string::ToString::to_string(&$k),
+                        ::std::string::ToString::to_string(&$v),
+                    )),*];
+                params
+            }))*) $($($rest)*)?
+        )
+    };
+    (($b:expr) $field:ident: $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__syslog_msg_fields!(($b.$field($value)) $($($rest)*)?)
+    };
+    (($b:expr) $msg:expr $(,)?) => {
+        $b.msg($msg).build()
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __syslog_severity {
+    (EMERG) => {
+        $crate::SyslogSeverity::SEV_EMERG
+    };
+    (ALERT) => {
+        $crate::SyslogSeverity::SEV_ALERT
+    };
+    (CRIT) => {
+        $crate::SyslogSeverity::SEV_CRIT
+    };
+    (ERR) => {
+        $crate::SyslogSeverity::SEV_ERR
+    };
+    (WARNING) => {
+        $crate::SyslogSeverity::SEV_WARNING
+    };
+    (NOTICE) => {
+        $crate::SyslogSeverity::SEV_NOTICE
+    };
+    (INFO) => {
+        $crate::SyslogSeverity::SEV_INFO
+    };
+    (DEBUG) => {
+        $crate::SyslogSeverity::SEV_DEBUG
+    };
+    ($other:ident) => {
+        $other
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __syslog_facility {
+    (KERN) => {
+        $crate::SyslogFacility::LOG_KERN
+    };
+    (USER) => {
+        $crate::SyslogFacility::LOG_USER
+    };
+    (MAIL) => {
+        $crate::SyslogFacility::LOG_MAIL
+    };
+    (DAEMON) => {
+        $crate::SyslogFacility::LOG_DAEMON
+    };
+    (AUTH) => {
+        $crate::SyslogFacility::LOG_AUTH
+    };
+    (SYSLOG) => {
+        $crate::SyslogFacility::LOG_SYSLOG
+    };
+    (LPR) => {
+        $crate::SyslogFacility::LOG_LPR
+    };
+    (NEWS) => {
+        $crate::SyslogFacility::LOG_NEWS
+    };
+    (UUCP) => {
+        $crate::SyslogFacility::LOG_UUCP
+    };
+    (CRON) => {
+        $crate::SyslogFacility::LOG_CRON
+    };
+    (AUTHPRIV) => {
+        $crate::SyslogFacility::LOG_AUTHPRIV
+    };
+    (FTP) => {
+        $crate::SyslogFacility::LOG_FTP
+    };
+    (NTP) => {
+        $crate::SyslogFacility::LOG_NTP
+    };
+    (AUDIT) => {
+        $crate::SyslogFacility::LOG_AUDIT
+    };
+    (ALERT) => {
+        $crate::SyslogFacility::LOG_ALERT
+    };
+    (CLOCKD) => {
+        $crate::SyslogFacility::LOG_CLOCKD
+    };
+    (LOCAL0) => {
+        $crate::SyslogFacility::LOG_LOCAL0
+    };
+    (LOCAL1) => {
+        $crate::SyslogFacility::LOG_LOCAL1
+    };
+    (LOCAL2) => {
+        $crate::SyslogFacility::LOG_LOCAL2
+    };
+    (LOCAL3) => {
+        $crate::SyslogFacility::LOG_LOCAL3
+    };
+    (LOCAL4) => {
+        $crate::SyslogFacility::LOG_LOCAL4
+    };
+    (LOCAL5) => {
+        $crate::SyslogFacility::LOG_LOCAL5
+    };
+    (LOCAL6) => {
+        $crate::SyslogFacility::LOG_LOCAL6
+    };
+    (LOCAL7) => {
+        $crate::SyslogFacility::LOG_LOCAL7
+    };
+    ($other:ident) => {
+        $other
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SyslogFacility, SyslogSeverity};
+
+    #[test]
+    fn test_syslog_msg() {
+        let body = String::from("hello");
+        let m = syslog_msg!(
+            severity: WARNING,
+            facility: LOCAL3,
+            hostname: "host",
+            pid: 42,
+            sd: {
+                "meta" => { "seq" => 1, "lang" => "en" },
+                "origin" => {},
+            },
+            body
+        )
+        .expect("should be valid");
+        assert_eq!(m.severity, SyslogSeverity::SEV_WARNING);
+        assert_eq!(m.facility, SyslogFacility::LOG_LOCAL3);
+        assert_eq!(m.hostname.as_deref(), Some("host"));
+        assert_eq!(m.sd.find_tuple("meta", "seq"), Some(&"1".to_string()));
+        assert!(m.sd.find_sdid("origin").is_some());
+        assert_eq!(m.msg, "hello");
+    }
+
+    #[test]
+    fn test_syslog_msg_expressions() {
+        let severity = SyslogSeverity::SEV_ERR;
+        let m = syslog_msg!(severity: severity, facility: SyslogFacility::LOG_CRON)
+            .expect("should be valid");
+        assert_eq!(m.severity, SyslogSeverity::SEV_ERR);
+        assert_eq!(m.facility, SyslogFacility::LOG_CRON);
+        assert_eq!(m.msg, "");
+        assert!(syslog_msg!(appname: "bad name", "x").is_err());
+    }
+}
@@ -1,14 +1,26 @@
 //! In-memory representation of a single Syslog message.
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::BTreeMap;
-use std::convert::{Into, TryFrom};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Into;
+#[cfg(feature = "serde-serialize")]
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::net::IpAddr;
 use std::ops;
 use std::str::FromStr;
 use std::string::String;
+use std::time::SystemTime;
 
 #[cfg(feature = "serde-serialize")]
-use serde::{de::Visitor, Deserialize, Serialize, Serializer};
+use serde::de::{SeqAccess, Visitor};
+#[cfg(feature = "serde-serialize")]
+use serde::ser::SerializeTuple;
+#[cfg(feature = "serde-serialize")]
+use serde::{Deserialize, Serialize, Serializer};
 
 #[allow(non_camel_case_types)]
 pub type time_t = i64;
@@ -17,33 +29,72 @@ pub type pid_t = i32;
 #[allow(non_camel_case_types)]
 pub type msgid_t = String;
 
+use crate::borrowed::StructuredDataRef;
+#[cfg(feature = "cee")]
+use crate::cee;
+#[cfg(feature = "serde-serialize")]
+use crate::ecs;
+use crate::encoder;
 use crate::facility;
+use crate::fingerprint::{self, FingerprintOptions};
+use crate::kv;
+use crate::logfmt;
+#[cfg(feature = "otel")]
+use crate::otel;
 use crate::parser;
+use crate::sd_map::{sorted_entries, SdMap, SdParamMap};
+#[cfg(feature = "serde-serialize")]
+use crate::serialize;
 use crate::severity;
+use crate::timestamp::Timestamp;
+use crate::validation;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 /// `ProcID`s are usually numeric PIDs; however, on some systems, they may be something else
+///
+/// `ProcId`s are ordered numerically by PID, then by name, with every PID sorting before every
+/// name.
 pub enum ProcId {
     PID(pid_t),
     Name(String),
 }
 
+impl Ord for ProcId {
+    fn cmp(&self, other: &ProcId) -> Ordering {
+        match (self, other) {
+            (&ProcId::PID(ref s_p), &ProcId::PID(ref o_p)) => s_p.cmp(o_p),
+            (&ProcId::Name(ref s_n), &ProcId::Name(ref o_n)) => s_n.cmp(o_n),
+            (&ProcId::PID(_), &ProcId::Name(_)) => Ordering::Less,
+            (&ProcId::Name(_), &ProcId::PID(_)) => Ordering::Greater,
+        }
+    }
+}
+
 impl PartialOrd for ProcId {
     fn partial_cmp(&self, other: &ProcId) -> Option<Ordering> {
-        match (self, other) {
-            (&ProcId::PID(ref s_p), &ProcId::PID(ref o_p)) => Some(s_p.cmp(o_p)),
-            (&ProcId::Name(ref s_n), &ProcId::Name(ref o_n)) => Some(s_n.cmp(o_n)),
-            _ => None,
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for ProcId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProcId::PID(pid) => write!(f, "{}", pid),
+            ProcId::Name(ref name) => f.write_str(name),
         }
     }
 }
 
 #[cfg(feature = "serde-serialize")]
 impl Serialize for ProcId {
+    /// Serialize as a bare integer or string for human-readable formats, or as an enum for
+    /// compact ones, which can't tell the two apart otherwise
     fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
-        match *self {
-            ProcId::PID(ref p) => ser.serialize_i32(*p),
-            ProcId::Name(ref n) => ser.serialize_str(n),
+        match (self, ser.is_human_readable()) {
+            (ProcId::PID(p), true) => ser.serialize_i32(*p),
+            (ProcId::Name(n), true) => ser.serialize_str(n),
+            (ProcId::PID(p), false) => ser.serialize_newtype_variant("ProcId", 0, "PID", p),
+            (ProcId::Name(n), false) => ser.serialize_newtype_variant("ProcId", 1, "Name", n),
         }
     }
 }
@@ -59,6 +110,22 @@ impl<'de> Visitor<'de> for ProcIDVisitor {
         formatter.write_str("an i32 or a String")
     }
 
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::EnumAccess<'de>,
+    {
+        use serde::de::VariantAccess;
+
+        match data.variant()? {
+            (0u32, variant) => variant.newtype_variant().map(ProcId::PID),
+            (1, variant) => variant.newtype_variant().map(ProcId::Name),
+            (other, _) => Err(serde::de::Error::invalid_value(
+                serde::de::Unexpected::Unsigned(u64::from(other)),
+                &"variant index 0 or 1",
+            )),
+        }
+    }
+
     fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
@@ -175,7 +242,11 @@ impl<'de> Deserialize<'de> for ProcId {
     where
         D: serde::Deserializer<'de>,
     {
-        des.deserialize_any(ProcIDVisitor)
+        if des.is_human_readable() {
+            des.deserialize_any(ProcIDVisitor)
+        } else {
+            des.deserialize_enum("ProcId", &["PID", "Name"], ProcIDVisitor)
+        }
     }
 }
 
@@ -183,9 +254,48 @@ pub type SDIDType = String;
 pub type SDParamIDType = String;
 pub type SDParamValueType = String;
 
+/// The params of one SD-ELEMENT, in the default map
 pub type StructuredDataElement = BTreeMap<SDParamIDType, SDParamValueType>;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+/// Where each SD-ID and SD-PARAM of an order-preserving `StructuredData` was first inserted
+#[derive(Clone, Debug, Default)]
+struct InsertionOrder {
+    /// The next position to hand out; SD-IDs and params share a counter, since only the relative
+    /// positions matter
+    next: usize,
+    ids: BTreeMap<SDIDType, (usize, BTreeMap<SDParamIDType, usize>)>,
+}
+
+impl InsertionOrder {
+    fn track(&mut self, sd_id: &str, name: Option<&str>) {
+        if !self.ids.contains_key(sd_id) {
+            self.ids
+                .insert(sd_id.to_owned(), (self.next, BTreeMap::new()));
+            self.next += 1;
+        }
+        if let Some(name) = name {
+            // safe to unwrap: the SD-ID was inserted above if it wasn't there yet
+            let params = &mut self.ids.get_mut(sd_id).unwrap().1;
+            if !params.contains_key(name) {
+                params.insert(name.to_owned(), self.next);
+                self.next += 1;
+            }
+        }
+    }
+
+    fn forget(&mut self, sd_id: &str, name: &str) {
+        if let Some((_, params)) = self.ids.get_mut(sd_id) {
+            params.remove(name);
+        }
+    }
+
+    /// The position of `sd_id`, and of its params
+    fn get(&self, sd_id: &str) -> Option<(usize, &BTreeMap<SDParamIDType, usize>)> {
+        self.ids.get(sd_id).map(|(pos, params)| (*pos, params))
+    }
+}
+
+#[derive(Clone, Debug)]
 /// Container for the `StructuredData` component of a syslog message.
 ///
 /// This is a map from `SD_ID` to pairs of `SD_ParamID`, `SD_ParamValue`
@@ -195,31 +305,86 @@ pub type StructuredDataElement = BTreeMap<SDParamIDType, SDParamValueType>;
 ///
 /// [foo bar="baz" bar="bing"]
 ///
-/// There's no way to retrieve the original "baz" mapping.
-pub struct StructuredData {
-    elements: BTreeMap<SDIDType, StructuredDataElement>,
+/// only the last value ("bing") is kept here. If you need all of them, parse with
+/// `parse_message_ref` and call `.sd.to_multi()` to get a `MultiStructuredData`.
+///
+/// Lookups and iteration through `Deref` are in alphabetical order, unless a different map has
+/// been picked with the type parameter (see `sd_map`). If the original order matters
+/// (e.g. to re-serialize a message byte-for-byte), parse with
+/// `ParserConfig::with_preserve_sd_order` or start from `new_ordered`, and use `iter_ordered`.
+/// The order isn't considered when comparing two `StructuredData`s.
+pub struct StructuredData<P: SdParamMap = StructuredDataElement> {
+    elements: P::SdIdMap,
+    /// The order in which SD-IDs and their params were first inserted, if it's being tracked
+    order: Option<InsertionOrder>,
+}
+
+impl<P: SdParamMap> PartialEq for StructuredData<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.elements == other.elements
+    }
+}
+
+impl<P: SdParamMap> Eq for StructuredData<P> {}
+
+impl<P: SdParamMap> Hash for StructuredData<P> {
+    /// Hashes the same whatever order things were inserted in, and whatever the map
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.elements.len());
+        for (sd_id, params) in sorted_entries(&self.elements) {
+            sd_id.hash(state);
+            state.write_usize(params.len());
+            for (name, value) in sorted_entries(params) {
+                name.hash(state);
+                value.hash(state);
+            }
+        }
+    }
+}
+
+impl<P: SdParamMap> Default for StructuredData<P> {
+    fn default() -> Self {
+        StructuredData {
+            elements: P::SdIdMap::default(),
+            order: None,
+        }
+    }
 }
 
-impl ops::Deref for StructuredData {
-    type Target = BTreeMap<SDIDType, StructuredDataElement>;
+impl<P: SdParamMap> ops::Deref for StructuredData<P> {
+    type Target = P::SdIdMap;
     fn deref(&self) -> &Self::Target {
         &self.elements
     }
 }
 
 #[cfg(feature = "serde-serialize")]
-impl Serialize for StructuredData {
+/// One SD-ELEMENT's params, serialized as a map in alphabetical order
+struct SortedParams<'a, P>(&'a P);
+
+#[cfg(feature = "serde-serialize")]
+impl<P: SdParamMap> Serialize for SortedParams<'_, P> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.collect_map(sorted_entries(self.0))
+    }
+}
+
+#[cfg(feature = "serde-serialize")]
+impl<P: SdParamMap> Serialize for StructuredData<P> {
+    /// Serialize as a map of maps, in alphabetical order
     fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
-        self.elements.serialize(ser)
+        ser.collect_map(
+            sorted_entries(&self.elements).map(|(sd_id, params)| (sd_id, SortedParams(params))),
+        )
     }
 }
 
 #[cfg(feature = "serde-serialize")]
-struct BtreeMapVisitor;
+struct BtreeMapVisitor<P>(std::marker::PhantomData<P>);
 
 #[cfg(feature = "serde-serialize")]
-impl<'de> Visitor<'de> for BtreeMapVisitor {
-    type Value = BTreeMap<SDIDType, StructuredDataElement>;
+impl<'de, P: SdParamMap + Deserialize<'de>> Visitor<'de> for BtreeMapVisitor<P> {
+    type Value = P::SdIdMap;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
         formatter.write_str("a map")
@@ -229,55 +394,219 @@ impl<'de> Visitor<'de> for BtreeMapVisitor {
     where
         A: serde::de::MapAccess<'de>,
     {
-        let mut btree = BTreeMap::new();
+        let mut elements = P::SdIdMap::default();
 
         // While there are entries remaining in the input, add them
         // into our map.
         while let Some((key, value)) = map.next_entry()? {
-            btree.insert(key, value);
+            elements.insert(key, value);
         }
 
-        Ok(btree)
+        Ok(elements)
     }
 }
 
 #[cfg(feature = "serde-serialize")]
-impl<'de> Deserialize<'de> for StructuredData {
+impl<'de, P: SdParamMap + Deserialize<'de>> Deserialize<'de> for StructuredData<P> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let elements = deserializer.deserialize_map(BtreeMapVisitor).unwrap();
-        Ok(Self { elements })
+        let elements =
+            deserializer.deserialize_map(BtreeMapVisitor::<P>(std::marker::PhantomData))?;
+        Ok(Self {
+            elements,
+            order: None,
+        })
     }
 }
 
 impl StructuredData {
     pub fn new_empty() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty `StructuredData` which remembers the order things are inserted in
+    ///
+    /// `empty_ordered` does the same for other maps.
+    pub fn new_ordered() -> Self {
+        Self::empty_ordered()
+    }
+
+    /// Create an empty `StructuredData` with room for `capacity` SD-IDs
+    ///
+    /// `BTreeMap`s don't have a capacity, so with the default map this is the same as
+    /// `new_empty`; the same goes for `reserve`, `reserve_params` and `shrink_to_fit`.
+    /// `empty_with_capacity` does the same for other maps.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::empty_with_capacity(capacity)
+    }
+
+    /// Parse a standalone STRUCTURED-DATA string, such as `[id a="b"][id2 c="d"]`
+    ///
+    /// See `parser::parse_structured_data`, and `parser::parse_structured_data_with` for more
+    /// control.
+    pub fn parse(s: &str) -> Result<Self, parser::ParseErr> {
+        parser::parse_structured_data(s).map(StructuredDataRef::into_owned)
+    }
+}
+
+impl<P: SdParamMap> StructuredData<P> {
+    /// Like `new_ordered`, for any map
+    pub fn empty_ordered() -> Self {
+        StructuredData {
+            elements: P::SdIdMap::default(),
+            order: Some(InsertionOrder::default()),
+        }
+    }
+
+    /// Like `with_capacity`, for any map
+    pub fn empty_with_capacity(capacity: usize) -> Self {
+        StructuredData {
+            elements: P::SdIdMap::with_capacity(capacity),
+            order: None,
+        }
+    }
+
+    /// Move everything into a different map, keeping the insertion order if it's being tracked
+    pub fn convert<Q: SdParamMap>(self) -> StructuredData<Q> {
+        let mut elements = Q::SdIdMap::with_capacity(self.elements.len());
+        for (sd_id, params) in self.elements.into_entries() {
+            let mut converted = Q::with_capacity(params.len());
+            for (name, value) in params.into_entries() {
+                converted.insert(name, value);
+            }
+            elements.insert(sd_id, converted);
+        }
         StructuredData {
-            elements: BTreeMap::new(),
+            elements,
+            order: self.order,
+        }
+    }
+
+    /// Make room for `additional` more SD-IDs
+    pub fn reserve(&mut self, additional: usize) {
+        self.elements.reserve(additional);
+    }
+
+    /// Make room for `additional` more params in `sd_id`, adding it if it isn't there yet
+    pub fn reserve_params<SI>(&mut self, sd_id: SI, additional: usize)
+    where
+        SI: Into<SDIDType>,
+    {
+        self.entry(sd_id).reserve(additional);
+    }
+
+    /// Free any spare capacity, including in each element
+    pub fn shrink_to_fit(&mut self) {
+        for (_, params) in self.elements.iter_mut() {
+            params.shrink_to_fit();
         }
+        self.elements.shrink_to_fit();
+    }
+
+    /// Whether insertion order is being tracked
+    pub fn preserves_order(&self) -> bool {
+        self.order.is_some()
     }
 
     /// Fetch or insert a new sd_id entry into the StructuredData
-    pub fn entry<SI>(&mut self, sd_id: SI) -> &mut BTreeMap<String, String>
+    ///
+    /// Params inserted directly into the returned map aren't tracked by `iter_ordered`; they come
+    /// after the tracked ones, in alphabetical order.
+    pub fn entry<SI>(&mut self, sd_id: SI) -> &mut P
     where
         SI: Into<SDIDType>,
     {
-        self.elements
-            .entry(sd_id.into())
-            .or_insert_with(BTreeMap::new)
+        let sd_id = sd_id.into();
+        if let Some(ref mut order) = self.order {
+            order.track(&sd_id, None);
+        }
+        self.elements.get_or_default(sd_id)
     }
 
     /// Insert a new (sd_id, sd_param_id) -> sd_value mapping into the StructuredData
+    ///
+    /// The names aren't checked, so this can produce structured data which can't be serialized
+    /// and parsed back; see `try_insert_tuple` for a checked version.
     pub fn insert_tuple<SI, SPI, SPV>(&mut self, sd_id: SI, sd_param_id: SPI, sd_param_value: SPV)
     where
         SI: Into<SDIDType>,
         SPI: Into<SDParamIDType>,
         SPV: Into<SDParamValueType>,
     {
-        self.entry(sd_id)
-            .insert(sd_param_id.into(), sd_param_value.into());
+        let sd_id = sd_id.into();
+        let sd_param_id = sd_param_id.into();
+        if let Some(ref mut order) = self.order {
+            order.track(&sd_id, Some(&sd_param_id));
+        }
+        self.entry(sd_id).insert(sd_param_id, sd_param_value.into());
+    }
+
+    /// Like `insert_tuple`, but first checks that the SD-ID and PARAM-NAME are valid SD-NAMEs
+    ///
+    /// That is, 1 to 32 printable ASCII characters other than `=`, `]`, `"` and space.
+    pub fn try_insert_tuple<SI, SPI, SPV>(
+        &mut self,
+        sd_id: SI,
+        sd_param_id: SPI,
+        sd_param_value: SPV,
+    ) -> Result<(), Vec<validation::Violation>>
+    where
+        SI: Into<SDIDType>,
+        SPI: Into<SDParamIDType>,
+        SPV: Into<SDParamValueType>,
+    {
+        let sd_id = sd_id.into();
+        let sd_param_id = sd_param_id.into();
+        let mut violations = Vec::new();
+        if let Err(v) = validation::validate_sd_name(validation::Field::SdId(sd_id.clone()), &sd_id)
+        {
+            violations.extend(v);
+        }
+        let field = validation::Field::SdParamName(sd_id.clone(), sd_param_id.clone());
+        if let Err(v) = validation::validate_sd_name(field, &sd_param_id) {
+            violations.extend(v);
+        }
+        if !violations.is_empty() {
+            return Err(violations);
+        }
+        self.insert_tuple(sd_id, sd_param_id, sd_param_value);
+        Ok(())
+    }
+
+    /// Iterate over the elements and their params in insertion order, if it's being tracked
+    ///
+    /// Anything inserted without being tracked comes after everything that was, in alphabetical
+    /// order; if order isn't being tracked at all, everything is in alphabetical order.
+    pub fn iter_ordered(
+        &self,
+    ) -> impl Iterator<Item = (&SDIDType, Vec<(&SDParamIDType, &SDParamValueType)>)> + '_ {
+        let order = self.order.as_ref();
+        let mut ids: Vec<_> = self
+            .elements
+            .iter()
+            .map(|(sd_id, element)| (sd_id, element, order.and_then(|o| o.get(sd_id))))
+            .collect();
+        match order {
+            // untracked entries go last, so sort by (position or MAX, name)
+            Some(_) => ids.sort_unstable_by_key(|(sd_id, _, tracked)| {
+                (tracked.map_or(usize::MAX, |(pos, _)| pos), *sd_id)
+            }),
+            None if !P::SORTED => ids.sort_unstable_by_key(|(sd_id, _, _)| *sd_id),
+            None => {}
+        }
+        ids.into_iter().map(|(sd_id, element, tracked)| {
+            let mut params: Vec<_> = element.iter().collect();
+            match tracked {
+                Some((_, positions)) => params.sort_unstable_by_key(|(name, _)| {
+                    (positions.get(*name).copied().unwrap_or(usize::MAX), *name)
+                }),
+                None if !P::SORTED => params.sort_unstable_by_key(|(name, _)| *name),
+                None => {}
+            }
+            (sd_id, params)
+        })
     }
 
     /// Lookup by SDID, SDParamID pair
@@ -299,15 +628,98 @@ impl StructuredData {
     }
 
     /// Find all param/value mappings for a given SDID
-    pub fn find_sdid<'b>(&'b self, sd_id: &str) -> Option<&'b StructuredDataElement> {
+    pub fn find_sdid<'b>(&'b self, sd_id: &str) -> Option<&'b P> {
         self.elements.get(sd_id)
     }
 
+    /// Mutable version of `find_tuple`
+    pub fn find_tuple_mut<'b>(
+        &'b mut self,
+        sd_id: &str,
+        sd_param_id: &str,
+    ) -> Option<&'b mut SDParamValueType> {
+        self.elements.get_mut(sd_id)?.get_mut(sd_param_id)
+    }
+
+    /// Mutable version of `find_sdid`
+    pub fn find_sdid_mut<'b>(&'b mut self, sd_id: &str) -> Option<&'b mut P> {
+        self.elements.get_mut(sd_id)
+    }
+
+    /// Remove a single param, returning its value
+    ///
+    /// The SD-ELEMENT is kept even if this was its last param; use `remove_sdid` to remove it.
+    pub fn remove_tuple(&mut self, sd_id: &str, sd_param_id: &str) -> Option<SDParamValueType> {
+        if let Some(ref mut order) = self.order {
+            order.forget(sd_id, sd_param_id);
+        }
+        self.elements.get_mut(sd_id)?.remove(sd_param_id)
+    }
+
+    /// Remove a whole SD-ELEMENT, returning its params
+    pub fn remove_sdid(&mut self, sd_id: &str) -> Option<P> {
+        if let Some(ref mut order) = self.order {
+            order.ids.remove(sd_id);
+        }
+        self.elements.remove(sd_id)
+    }
+
+    /// Insert everything from `other`, overwriting any params which are already present
+    pub fn merge(&mut self, other: Self) {
+        for (sd_id, params) in other.iter_ordered() {
+            self.entry(sd_id.as_str());
+            for (name, value) in params {
+                self.insert_tuple(sd_id.as_str(), name.as_str(), value.as_str());
+            }
+        }
+    }
+
+    /// Iterate over every (SD-ID, SD-PARAM name, SD-PARAM value) triple, in alphabetical order
+    pub fn iter_tuples(&self) -> impl Iterator<Item = (&str, &str, &str)> {
+        sorted_entries(&self.elements).flat_map(|(sd_id, params)| {
+            sorted_entries(params)
+                .map(move |(name, value)| (sd_id.as_str(), name.as_str(), value.as_str()))
+        })
+    }
+
+    /// Like `iter_tuples`, but the values can be changed in place
+    pub fn iter_tuples_mut(&mut self) -> impl Iterator<Item = (&str, &str, &mut String)> {
+        self.elements.iter_mut().flat_map(|(sd_id, params)| {
+            params
+                .iter_mut()
+                .map(move |(name, value)| (sd_id.as_str(), name.as_str(), value))
+        })
+    }
+
+    /// Keep only the params for which `f(sd_id, name, value)` returns true
+    ///
+    /// As with `remove_tuple`, SD-ELEMENTs left without any params are kept.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str, &str, &str) -> bool,
+    {
+        let order = &mut self.order;
+        for (sd_id, params) in self.elements.iter_mut() {
+            params.retain(|name, value| {
+                let keep = f(sd_id, name, value);
+                if let (false, Some(order)) = (keep, order.as_mut()) {
+                    order.forget(sd_id, name);
+                }
+                keep
+            });
+        }
+    }
+
     /// The number of distinct SD_IDs
     pub fn len(&self) -> usize {
         self.elements.len()
     }
 
+    /// The number of SD-PARAMs, across every SD-ID
+    pub fn param_count(&self) -> usize {
+        self.elements.iter().map(|(_, params)| params.len()).sum()
+    }
+
     /// Whether or not this is empty
     pub fn is_empty(&self) -> bool {
         self.elements.is_empty()
@@ -315,82 +727,977 @@ impl StructuredData {
 }
 
 #[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-serialize", serde(transparent))]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+/// Like `StructuredData`, but keeps every value of a repeated SD-PARAM
+///
+/// Values are kept in the order in which they appeared. Repeated SD-IDs are merged, as they are
+/// in `StructuredData`.
+pub struct MultiStructuredData {
+    elements: BTreeMap<SDIDType, BTreeMap<SDParamIDType, Vec<SDParamValueType>>>,
+}
+
+impl ops::Deref for MultiStructuredData {
+    type Target = BTreeMap<SDIDType, BTreeMap<SDParamIDType, Vec<SDParamValueType>>>;
+    fn deref(&self) -> &Self::Target {
+        &self.elements
+    }
+}
+
+impl MultiStructuredData {
+    pub fn new_empty() -> Self {
+        Self::default()
+    }
+
+    /// Add a value for (sd_id, sd_param_id), after any existing ones
+    pub fn insert_tuple<SI, SPI, SPV>(&mut self, sd_id: SI, sd_param_id: SPI, sd_param_value: SPV)
+    where
+        SI: Into<SDIDType>,
+        SPI: Into<SDParamIDType>,
+        SPV: Into<SDParamValueType>,
+    {
+        self.elements
+            .entry(sd_id.into())
+            .or_default()
+            .entry(sd_param_id.into())
+            .or_default()
+            .push(sd_param_value.into());
+    }
+
+    /// All of the values for an SDID, SDParamID pair, in input order
+    pub fn find_tuples<'b>(&'b self, sd_id: &str, sd_param_id: &str) -> &'b [SDParamValueType] {
+        self.elements
+            .get(sd_id)
+            .and_then(|params| params.get(sd_param_id))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The number of distinct SD_IDs
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Whether or not this is empty
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Collapse into a `StructuredData`, keeping only the last value of each param
+    pub fn to_structured_data(&self) -> StructuredData {
+        let mut sd = StructuredData::new_empty();
+        for (sd_id, params) in self.elements.iter() {
+            let element = sd.entry(sd_id.as_str());
+            for (name, values) in params {
+                if let Some(value) = values.last() {
+                    element.insert(name.clone(), value.clone());
+                }
+            }
+        }
+        sd
+    }
+}
+
+impl<'a> From<&'a StructuredData> for MultiStructuredData {
+    fn from(sd: &'a StructuredData) -> Self {
+        let mut multi = MultiStructuredData::new_empty();
+        for (sd_id, params) in sd.iter() {
+            let element = multi.elements.entry(sd_id.clone()).or_default();
+            for (name, value) in params {
+                element.insert(name.clone(), vec![value.clone()]);
+            }
+        }
+        multi
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// What sort of thing a HOSTNAME holds; see `SyslogMessage::hostname_kind`
+pub enum HostnameKind {
+    /// NIL, or empty
+    Nil,
+    /// An IPv4 address, like `192.0.2.1`
+    Ipv4,
+    /// An IPv6 address, like `2001:db8::1` (optionally in brackets)
+    Ipv6,
+    /// A name with at least one dot in it, like `mymachine.example.com`
+    Fqdn,
+    /// A name without any dots, like `mymachine`
+    Bare,
+}
+
+impl HostnameKind {
+    /// Classify a HOSTNAME
+    pub fn of(hostname: Option<&str>) -> Self {
+        match hostname {
+            None | Some("") => HostnameKind::Nil,
+            Some(name) => match hostname_ip(name) {
+                Some(IpAddr::V4(_)) => HostnameKind::Ipv4,
+                Some(IpAddr::V6(_)) => HostnameKind::Ipv6,
+                None if name.contains('.') => HostnameKind::Fqdn,
+                None => HostnameKind::Bare,
+            },
+        }
+    }
+}
+
+/// The address in a HOSTNAME, if it is one
+pub(crate) fn hostname_ip(hostname: &str) -> Option<IpAddr> {
+    let unbracketed = hostname.strip_prefix('[').and_then(|h| h.strip_suffix(']'));
+    match unbracketed {
+        Some(h) => h.parse::<std::net::Ipv6Addr>().ok().map(IpAddr::V6),
+        None => hostname.parse().ok(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 /// A RFC5424-protocol syslog message
 pub struct SyslogMessage {
     pub severity: severity::SyslogSeverity,
     pub facility: facility::SyslogFacility,
     pub version: i32,
-    pub timestamp: Option<time_t>,
-    pub timestamp_nanos: Option<u32>,
+    /// When the message was sent, and in which UTC offset, or `None` for a NIL timestamp
+    ///
+    /// In human-readable formats, this is serialized as separate `timestamp`, `timestamp_nanos` and
+    /// `utc_offset` fields.
+    pub timestamp: Option<Timestamp>,
+    /// Set if the sender's timestamp had sub-second digits beyond nanosecond precision, which
+    /// had to be dropped
+    pub timestamp_precision_lost: bool,
+    /// Set if the sender's timestamp fell on a leap second (`:60`); see
+    /// `parser::LeapSecondPolicy` for how it's represented in `timestamp`
+    pub leap_second: bool,
     pub hostname: Option<String>,
     pub appname: Option<String>,
     pub procid: Option<ProcId>,
     pub msgid: Option<msgid_t>,
     pub sd: StructuredData,
     pub msg: String,
+    /// Whether MSG started with a BOM, which RFC 5424 uses to say that it's UTF-8. The BOM itself
+    /// is stripped from `msg` unless the parser was configured otherwise.
+    pub msg_is_utf8: bool,
+    /// The original bytes of MSG, if it was not valid UTF-8 and was parsed with
+    /// `Utf8Policy::Preserve`. In that case, `msg` holds a lossily-converted copy.
+    pub msg_bytes: Option<Vec<u8>>,
+    /// Set if the message was cut off part of the way through, e.g. by a sender's maximum message
+    /// size or a UDP MTU. Only lenient and lossy parsing accept such messages.
+    pub truncated: bool,
 }
 
-impl FromStr for SyslogMessage {
-    type Err = parser::ParseErr;
-
-    /// Parse a string into a `SyslogMessage`
-    ///
-    /// Just calls `parser::parse_message`
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        parser::parse_message(s)
-    }
+/// The representation of `SyslogMessage` in human-readable formats like JSON
+#[cfg(feature = "serde-serialize")]
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SyslogMessage")]
+struct Readable {
+    severity: severity::SyslogSeverity,
+    facility: facility::SyslogFacility,
+    version: i32,
+    #[serde(flatten, with = "crate::timestamp::serde_compat")]
+    timestamp: Option<Timestamp>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    timestamp_precision_lost: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    leap_second: bool,
+    hostname: Option<String>,
+    appname: Option<String>,
+    procid: Option<ProcId>,
+    msgid: Option<msgid_t>,
+    sd: StructuredData,
+    msg: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    msg_is_utf8: bool,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "msg_bytes_base64"
+    )]
+    msg_bytes: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::StructuredData;
-    use super::SyslogMessage;
-    #[cfg(feature = "serde-serialize")]
-    use crate::facility::SyslogFacility::*;
-    #[cfg(feature = "serde-serialize")]
-    use crate::severity::SyslogSeverity::*;
-    #[cfg(feature = "serde-serialize")]
-    use serde_json;
+/// `msg_bytes` as a base64 string, for human-readable formats
+///
+/// Arrays of byte values, as earlier versions wrote, are accepted too.
+#[cfg(feature = "serde-serialize")]
+pub(crate) mod msg_bytes_base64 {
+    use std::fmt;
 
-    #[test]
-    fn test_structured_data_basic() {
-        let mut s = StructuredData::new_empty();
-        s.insert_tuple("foo", "bar", "baz");
-        let v = s.find_tuple("foo", "bar").expect("should find foo/bar");
-        assert_eq!(v, "baz");
-        assert!(s.find_tuple("foo", "baz").is_none());
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, ser: S) -> Result<S::Ok, S::Error> {
+        match *bytes {
+            Some(ref bytes) => ser.serialize_some(&STANDARD.encode(bytes)),
+            None => ser.serialize_none(),
+        }
     }
 
-    #[cfg(feature = "serde-serialize")]
-    #[test]
-    fn test_structured_data_serialization_serde() {
-        let mut s = StructuredData::new_empty();
-        s.insert_tuple("foo", "bar", "baz");
-        s.insert_tuple("foo", "baz", "bar");
-        s.insert_tuple("faa", "bar", "baz");
-        let encoded = serde_json::to_string(&s).expect("Should encode to JSON");
-        assert_eq!(
-            encoded,
-            r#"{"faa":{"bar":"baz"},"foo":{"bar":"baz","baz":"bar"}}"#
-        );
+    pub fn deserialize<'de, D: Deserializer<'de>>(des: D) -> Result<Option<Vec<u8>>, D::Error> {
+        des.deserialize_option(OptionVisitor)
     }
 
-    #[cfg(feature = "serde-serialize")]
-    #[test]
-    fn test_serialization_serde() {
-        let m = SyslogMessage {
-            severity: SEV_INFO,
-            facility: LOG_KERN,
-            version: 1,
-            timestamp: None,
-            timestamp_nanos: None,
-            hostname: None,
-            appname: None,
-            procid: None,
-            msgid: None,
-            sd: StructuredData::new_empty(),
-            msg: String::from(""),
+    struct OptionVisitor;
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<Vec<u8>>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a base64 string or null")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, des: D) -> Result<Self::Value, D::Error> {
+            des.deserialize_any(BytesVisitor).map(Some)
+        }
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a base64 string or an array of bytes")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            STANDARD
+                .decode(v)
+                .map_err(|e| E::custom(format!("invalid base64 in msg_bytes: {}", e)))
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(b) = seq.next_element()? {
+                bytes.push(b);
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+/// How many fields `SyslogMessage` has in compact formats
+#[cfg(feature = "serde-serialize")]
+const COMPACT_FIELDS: usize = 15;
+
+#[cfg(feature = "serde-serialize")]
+impl Serialize for SyslogMessage {
+    /// Serialize as a map for human-readable formats, leaving out fields which are unset, or as a
+    /// tuple of every field for compact formats like bincode and postcard, which can't skip fields
+    /// or flatten them
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        if ser.is_human_readable() {
+            return Readable::serialize(self, ser);
+        }
+        let mut t = ser.serialize_tuple(COMPACT_FIELDS)?;
+        t.serialize_element(&self.severity)?;
+        t.serialize_element(&self.facility)?;
+        t.serialize_element(&self.version)?;
+        t.serialize_element(&self.timestamp.map(|t| (t.secs, t.nanos, t.offset)))?;
+        t.serialize_element(&self.timestamp_precision_lost)?;
+        t.serialize_element(&self.leap_second)?;
+        t.serialize_element(&self.hostname)?;
+        t.serialize_element(&self.appname)?;
+        t.serialize_element(&self.procid)?;
+        t.serialize_element(&self.msgid)?;
+        t.serialize_element(&self.sd)?;
+        t.serialize_element(&self.msg)?;
+        t.serialize_element(&self.msg_is_utf8)?;
+        t.serialize_element(&self.msg_bytes)?;
+        t.serialize_element(&self.truncated)?;
+        t.end()
+    }
+}
+
+#[cfg(feature = "serde-serialize")]
+struct CompactVisitor;
+
+#[cfg(feature = "serde-serialize")]
+fn element<'de, T, A>(seq: &mut A, index: usize) -> Result<T, A::Error>
+where
+    T: Deserialize<'de>,
+    A: SeqAccess<'de>,
+{
+    seq.next_element()?
+        .ok_or_else(|| serde::de::Error::invalid_length(index, &CompactVisitor))
+}
+
+#[cfg(feature = "serde-serialize")]
+impl<'de> Visitor<'de> for CompactVisitor {
+    type Value = SyslogMessage;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "a tuple of {} message fields", COMPACT_FIELDS)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        Ok(SyslogMessage {
+            severity: element(&mut seq, 0)?,
+            facility: element(&mut seq, 1)?,
+            version: element(&mut seq, 2)?,
+            timestamp: element::<Option<(time_t, u32, i32)>, _>(&mut seq, 3)?
+                .map(|(secs, nanos, offset)| Timestamp::new(secs, nanos, offset)),
+            timestamp_precision_lost: element(&mut seq, 4)?,
+            leap_second: element(&mut seq, 5)?,
+            hostname: element(&mut seq, 6)?,
+            appname: element(&mut seq, 7)?,
+            procid: element(&mut seq, 8)?,
+            msgid: element(&mut seq, 9)?,
+            sd: element(&mut seq, 10)?,
+            msg: element(&mut seq, 11)?,
+            msg_is_utf8: element(&mut seq, 12)?,
+            msg_bytes: element(&mut seq, 13)?,
+            truncated: element(&mut seq, 14)?,
+        })
+    }
+}
+
+#[cfg(feature = "serde-serialize")]
+impl<'de> Deserialize<'de> for SyslogMessage {
+    fn deserialize<D>(des: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        if des.is_human_readable() {
+            Readable::deserialize(des)
+        } else {
+            des.deserialize_tuple(COMPACT_FIELDS, CompactVisitor)
+        }
+    }
+}
+
+#[cfg(feature = "serde-serialize")]
+impl SyslogMessage {
+    /// Pair this message with some `SerializeOptions`, for serializing with different keys; see
+    /// the `serialize` module
+    pub fn serialize_with<'a>(
+        &'a self,
+        options: &'a serialize::SerializeOptions,
+    ) -> serialize::WithOptions<'a> {
+        options.wrap(self)
+    }
+
+    /// The plain `serde` representation of this message, built straight into a
+    /// `serde_json::Value` rather than going through a string
+    pub fn to_json_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("messages always serialize to JSON")
+    }
+}
+
+impl SyslogMessage {
+    /// Check this message against the field rules in RFC 5424
+    ///
+    /// Returns every violation found, rather than stopping at the first one.
+    pub fn validate(&self) -> Result<(), Vec<validation::Violation>> {
+        validation::validate(self)
+    }
+
+    /// Report everything questionable about this message; see `validation::lint`
+    pub fn lint(&self) -> Vec<validation::LintWarning> {
+        validation::lint(self)
+    }
+}
+
+impl SyslogMessage {
+    /// The timestamp as a `time::OffsetDateTime`, in the sender's original UTC offset
+    ///
+    /// `time` is already used by the parser, so this doesn't need a feature flag.
+    pub fn timestamp_offset_datetime(&self) -> Option<time::OffsetDateTime> {
+        self.timestamp?.to_offset_datetime()
+    }
+
+    /// Set the timestamp from a `time::OffsetDateTime`, keeping its UTC offset
+    pub fn set_timestamp_offset_datetime(&mut self, dt: time::OffsetDateTime) {
+        self.timestamp = Some(Timestamp::from(dt));
+    }
+
+    /// The timestamp as an RFC 3339 string, in the sender's original UTC offset
+    ///
+    /// Unlike the wire format, this keeps all nine digits of sub-second precision (less any
+    /// trailing zeros). A leap second is written as `:60`, as the sender wrote it. Returns `None`
+    /// for a NIL timestamp, or one outside of the years 0 to 9999.
+    pub fn timestamp_rfc3339(&self) -> Option<String> {
+        let mut s = String::new();
+        match encoder::write_rfc3339(&mut s, self.timestamp?, self.leap_second, 9) {
+            Ok(true) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl SyslogMessage {
+    /// Whether HOSTNAME is NIL, an IP address or a name; see `HostnameKind`
+    pub fn hostname_kind(&self) -> HostnameKind {
+        HostnameKind::of(self.hostname.as_deref())
+    }
+
+    /// HOSTNAME as an IP address, if it is one
+    ///
+    /// IPv6 addresses may be wrapped in brackets, as some senders do.
+    pub fn hostname_ip(&self) -> Option<IpAddr> {
+        hostname_ip(self.hostname.as_deref()?)
+    }
+}
+
+impl SyslogMessage {
+    /// A stable 128-bit hash of this message, for deduplication
+    ///
+    /// Copies of a message that differ only in how they were written (UTC offset, HOSTNAME case,
+    /// SD order, a BOM, ...) get the same fingerprint; see the `fingerprint` module for the
+    /// details. The value doesn't change between runs or machines, unlike `Hash`.
+    pub fn fingerprint(&self) -> u128 {
+        fingerprint::fingerprint(self, &FingerprintOptions::default())
+    }
+
+    /// Like `fingerprint`, leaving out the fields `options` excludes
+    pub fn fingerprint_with(&self, options: &FingerprintOptions) -> u128 {
+        fingerprint::fingerprint(self, options)
+    }
+
+    /// Rewrite this message into a canonical form
+    ///
+    /// Two copies of a message which differ only in how they were written serialize identically
+    /// once normalized. This:
+    ///
+    ///  * moves the timestamp to UTC, keeping the instant
+    ///  * lowercases the HOSTNAME
+    ///  * replaces an empty HOSTNAME, APP-NAME, PROCID or MSGID with NIL
+    ///  * stops tracking the order of the STRUCTURED-DATA, so it's written in sorted order
+    ///  * strips a BOM from the start of MSG, and clears `msg_is_utf8` so none is written
+    pub fn normalize(&mut self) {
+        if let Some(ref mut ts) = self.timestamp {
+            ts.offset = 0;
+        }
+        if let Some(ref mut hostname) = self.hostname {
+            hostname.make_ascii_lowercase();
+        }
+        for field in [&mut self.hostname, &mut self.appname, &mut self.msgid] {
+            if field.as_deref() == Some("") {
+                *field = None;
+            }
+        }
+        if matches!(self.procid, Some(ProcId::Name(ref name)) if name.is_empty()) {
+            self.procid = None;
+        }
+        self.sd.order = None;
+        if self.msg.starts_with(parser::BOM) {
+            self.msg.drain(..parser::BOM.len_utf8());
+        }
+        if let Some(ref mut bytes) = self.msg_bytes {
+            if bytes.starts_with("\u{feff}".as_bytes()) {
+                bytes.drain(..parser::BOM.len_utf8());
+            }
+        }
+        self.msg_is_utf8 = false;
+    }
+}
+
+impl SyslogMessage {
+    /// The timestamp as a `std::time::SystemTime`
+    ///
+    /// `SystemTime` has no notion of a UTC offset, so the offset is ignored.
+    pub fn timestamp_systemtime(&self) -> Option<SystemTime> {
+        self.timestamp?.to_system_time()
+    }
+
+    /// Set the timestamp (in UTC) from a `SystemTime`
+    pub fn set_timestamp_systemtime(&mut self, t: SystemTime) {
+        self.timestamp = Some(Timestamp::from(t));
+    }
+}
+
+#[cfg(feature = "serde-serialize")]
+impl SyslogMessage {
+    /// Convert into an Elastic Common Schema document; see the `ecs` module for the mapping
+    pub fn to_ecs_json(&self) -> serde_json::Value {
+        ecs::to_ecs_json(self)
+    }
+}
+
+#[cfg(feature = "otel")]
+impl SyslogMessage {
+    /// Convert into an OpenTelemetry log record from `logger`; see the `otel` module
+    ///
+    /// The HOSTNAME, APP-NAME and numeric PROCIDs belong in the logger's `Resource`, so they're
+    /// left out; get them with `otel::resource_attributes`.
+    pub fn to_log_record<L: opentelemetry::logs::Logger>(&self, logger: &L) -> L::LogRecord {
+        otel::to_log_record(self, logger)
+    }
+
+    /// Convert an OpenTelemetry log record, and the `Resource` it belongs to, into a message
+    pub fn from_log_record(
+        record: &opentelemetry_sdk::logs::SdkLogRecord,
+        resource: &opentelemetry_sdk::Resource,
+    ) -> Self {
+        otel::from_log_record(record, resource)
+    }
+}
+
+impl SyslogMessage {
+    /// Cut MSG down to its first `before` bytes and put `params` into the SD-ELEMENT `sd_id`
+    ///
+    /// This is the shared tail of `merge_cef_into_sd` and `merge_leef_into_sd`. The names are
+    /// sanitized, and replace any SD-PARAMs already there.
+    pub(crate) fn merge_params_into_sd(
+        &mut self,
+        sd_id: SDIDType,
+        before: usize,
+        params: Vec<(String, String)>,
+    ) {
+        self.sd.entry(sd_id.clone());
+        for (name, value) in params {
+            self.sd
+                .insert_tuple(sd_id.clone(), validation::sanitize_sd_name(&name), value);
+        }
+        self.msg.truncate(before);
+        self.msg_bytes = None;
+    }
+}
+
+#[cfg(feature = "cee")]
+impl SyslogMessage {
+    /// The fields of the JSON object in MSG, if it has one; see the `cee` module
+    pub fn cee_fields(&self) -> Result<Option<cee::Fields>, cee::CeeError> {
+        cee::parse_fields(&self.msg)
+    }
+
+    /// Move the fields of the JSON object in MSG into the SD-ELEMENT `sd_id`
+    ///
+    /// The fields are flattened with `cee::flatten`, replacing any SD-PARAMs of the same name, and
+    /// MSG is cleared. Returns whether MSG had any JSON; if it's invalid, the message is left as it
+    /// was.
+    pub fn merge_cee_into_sd<SI: Into<SDIDType>>(
+        &mut self,
+        sd_id: SI,
+    ) -> Result<bool, cee::CeeError> {
+        let fields = match self.cee_fields()? {
+            Some(fields) => fields,
+            None => return Ok(false),
+        };
+        let sd_id = sd_id.into();
+        self.sd.entry(sd_id.clone());
+        for (name, value) in cee::flatten(&fields) {
+            self.sd.insert_tuple(sd_id.clone(), name, value);
+        }
+        self.msg.clear();
+        self.msg_bytes = None;
+        self.msg_is_utf8 = false;
+        Ok(true)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl SyslogMessage {
+    /// The timestamp as a `chrono::DateTime`, in the sender's original UTC offset
+    pub fn timestamp_datetime(&self) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        self.timestamp?.to_datetime()
+    }
+
+    /// Set the timestamp from a `chrono::DateTime`, keeping its UTC offset
+    pub fn set_timestamp_datetime<Tz: chrono::TimeZone>(&mut self, dt: &chrono::DateTime<Tz>) {
+        self.timestamp = Some(Timestamp::from_datetime(dt));
+    }
+}
+
+impl SyslogMessage {
+    /// The body of the message as bytes: `msg_bytes` if it's set, or else `msg`
+    pub fn msg_as_bytes(&self) -> &[u8] {
+        match self.msg_bytes {
+            Some(ref bytes) => bytes,
+            None => self.msg.as_bytes(),
+        }
+    }
+
+    /// Set the body of the message from raw bytes
+    ///
+    /// If they're valid UTF-8, they go straight into `msg`. Otherwise they're kept losslessly in
+    /// `msg_bytes`, and `msg` gets a copy with the invalid sequences replaced, as if the message
+    /// had been parsed with `Utf8Policy::Preserve`.
+    pub fn set_msg_bytes(&mut self, bytes: Vec<u8>) {
+        match String::from_utf8(bytes) {
+            Ok(msg) => {
+                self.msg = msg;
+                self.msg_bytes = None;
+            }
+            Err(e) => {
+                let bytes = e.into_bytes();
+                self.msg = String::from_utf8_lossy(&bytes).into_owned();
+                self.msg_bytes = Some(bytes);
+            }
+        }
+    }
+}
+
+impl SyslogMessage {
+    /// Set the timestamp to the current time, in UTC
+    pub fn set_timestamp_now(&mut self) {
+        self.set_timestamp_systemtime(SystemTime::now());
+        self.timestamp_precision_lost = false;
+        self.leap_second = false;
+    }
+
+    /// Add `prefix` to the start of the body, after the BOM if there is one
+    ///
+    /// If the body is being kept in `msg_bytes`, the prefix is added there too.
+    pub fn prepend_msg(&mut self, prefix: &str) {
+        let bom = if self.msg.starts_with(parser::BOM) {
+            parser::BOM.len_utf8()
+        } else {
+            0
+        };
+        self.msg.insert_str(bom, prefix);
+        if let Some(ref mut bytes) = self.msg_bytes {
+            let bom = if bytes.starts_with("\u{feff}".as_bytes()) {
+                parser::BOM.len_utf8()
+            } else {
+                0
+            };
+            bytes.splice(bom..bom, prefix.bytes());
+        }
+    }
+
+    /// Set an SD-PARAM to `value`, formatted with `Display`, adding the SD-ELEMENT if needed
+    ///
+    /// This is `sd.insert_tuple`, for values which aren't strings already, like a hop count:
+    /// `m.tag_sd("relay@32473", "hop", 2)`.
+    pub fn tag_sd<SI, SPI, SPV>(&mut self, sd_id: SI, sd_param_id: SPI, value: SPV)
+    where
+        SI: Into<SDIDType>,
+        SPI: Into<SDParamIDType>,
+        SPV: fmt::Display,
+    {
+        self.sd.insert_tuple(sd_id, sd_param_id, value.to_string());
+    }
+}
+
+#[cfg(feature = "hostname")]
+impl SyslogMessage {
+    /// Set HOSTNAME to this machine's hostname
+    ///
+    /// Fails if the hostname can't be looked up, or isn't valid UTF-8.
+    pub fn set_hostname_local(&mut self) -> io::Result<()> {
+        let hostname = hostname::get()?.into_string().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "hostname isn't valid UTF-8")
+        })?;
+        self.hostname = Some(hostname);
+        Ok(())
+    }
+}
+
+impl SyslogMessage {
+    /// Serialize into the RFC 5424 wire format, straight into an `io::Write`
+    ///
+    /// Unlike `Display`, this writes `msg_bytes` as the body if it's set, so messages parsed with
+    /// `Utf8Policy::Preserve` are passed on byte-for-byte. Nothing is buffered, so wrap unbuffered
+    /// writers (like a `TcpStream`) in a `BufWriter`.
+    pub fn write_to<W: io::Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        encoder::write_bytes(w, self)
+    }
+
+    /// Serialize into the RFC 5424 wire format, straight into a `fmt::Write`
+    ///
+    /// This is the same as `Display`, without going through a `Formatter`.
+    pub fn write_to_fmt<W: fmt::Write + ?Sized>(&self, w: &mut W) -> fmt::Result {
+        encoder::write_message(w, self)
+    }
+
+    /// Every field as a `(key, value)` pair, with SD-PARAMs under `sd.<SD-ID>.<name>` keys; see
+    /// the `kv` module
+    pub fn iter_kv(&self) -> impl Iterator<Item = (Cow<'_, str>, kv::FieldValue<'_>)> + '_ {
+        kv::iter_kv(self)
+    }
+
+    /// Every field as a string, under the same keys as `iter_kv`
+    ///
+    /// The strings in the message are moved into the map rather than copied. The timestamp is
+    /// written as RFC 3339, and the PID as a decimal number.
+    pub fn into_map(self) -> HashMap<String, String> {
+        let mut map = HashMap::with_capacity(8 + self.sd.param_count());
+        map.insert(String::from("severity"), self.severity.as_str().to_string());
+        map.insert(String::from("facility"), self.facility.as_str().to_string());
+        map.insert(String::from("version"), self.version.to_string());
+        if let Some(ts) = self.timestamp {
+            map.insert(
+                String::from("timestamp"),
+                kv::FieldValue::Timestamp(ts).to_string(),
+            );
+        }
+        let header = [
+            ("hostname", self.hostname),
+            ("appname", self.appname),
+            ("msgid", self.msgid),
+        ];
+        for (key, value) in header {
+            if let Some(value) = value {
+                map.insert(String::from(key), value);
+            }
+        }
+        match self.procid {
+            Some(ProcId::PID(pid)) => map.insert(String::from("procid"), pid.to_string()),
+            Some(ProcId::Name(name)) => map.insert(String::from("procid"), name),
+            None => None,
+        };
+        for (sd_id, element) in self.sd.elements {
+            for (name, value) in element {
+                map.insert(format!("sd.{}.{}", sd_id, name), value);
+            }
+        }
+        map.insert(String::from("msg"), self.msg);
+        map
+    }
+
+    /// Render as a line of logfmt (without a trailing newline); see the `logfmt` module
+    pub fn to_logfmt(&self) -> String {
+        let mut s = String::new();
+        logfmt::write_logfmt(&mut s, self).expect("writing to a String can't fail");
+        s
+    }
+}
+
+impl fmt::Display for SyslogMessage {
+    /// Serialize into the RFC 5424 wire format
+    ///
+    /// The PRI is computed from `facility` and `severity`, the timestamp is written in its
+    /// original UTC offset with at most microsecond precision, empty or missing fields are
+    /// written as NIL (`-`), and SD-PARAM values are escaped. A BOM is added before MSG if
+    /// `msg_is_utf8` is set. `msg` is always used, even if `msg_bytes` is set.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        encoder::write_message(f, self)
+    }
+}
+
+impl FromStr for StructuredData {
+    type Err = parser::ParseErr;
+
+    /// Just calls `StructuredData::parse`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StructuredData::parse(s)
+    }
+}
+
+impl FromStr for SyslogMessage {
+    type Err = parser::ParseErr;
+
+    /// Parse a string into a `SyslogMessage`
+    ///
+    /// Just calls `parser::parse_message`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parser::parse_message(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SyslogMessage;
+    use super::{HostnameKind, MultiStructuredData, ProcId, StructuredData};
+    #[cfg(feature = "serde-serialize")]
+    use crate::facility::SyslogFacility::*;
+    #[cfg(feature = "serde-serialize")]
+    use crate::severity::SyslogSeverity::*;
+    use crate::timestamp::Timestamp;
+    #[cfg(feature = "serde-serialize")]
+    use serde_json;
+
+    #[test]
+    fn test_hostname_kind() {
+        let kind = |hostname: &str| {
+            let input = format!("<1>1 - {} - - - -", hostname);
+            let msg: SyslogMessage = input.parse().unwrap();
+            (msg.hostname_kind(), msg.hostname_ip())
+        };
+        assert_eq!(kind("-"), (HostnameKind::Nil, None));
+        assert_eq!(kind("mymachine"), (HostnameKind::Bare, None));
+        assert_eq!(kind("mymachine.example.com"), (HostnameKind::Fqdn, None));
+        assert_eq!(kind("999.1.1.1"), (HostnameKind::Fqdn, None));
+        assert_eq!(
+            kind("192.0.2.1"),
+            (HostnameKind::Ipv4, Some("192.0.2.1".parse().unwrap()))
+        );
+        assert_eq!(
+            kind("2001:db8::1"),
+            (HostnameKind::Ipv6, Some("2001:db8::1".parse().unwrap()))
+        );
+        assert_eq!(
+            kind("[2001:db8::1]"),
+            (HostnameKind::Ipv6, Some("2001:db8::1".parse().unwrap()))
+        );
+        assert_eq!(kind("[192.0.2.1]"), (HostnameKind::Fqdn, None));
+        assert_eq!(HostnameKind::of(Some("")), HostnameKind::Nil);
+    }
+
+    #[test]
+    fn test_structured_data_basic() {
+        let mut s = StructuredData::new_empty();
+        s.insert_tuple("foo", "bar", "baz");
+        let v = s.find_tuple("foo", "bar").expect("should find foo/bar");
+        assert_eq!(v, "baz");
+        assert!(s.find_tuple("foo", "baz").is_none());
+    }
+
+    #[test]
+    fn test_sd_order_independent_of_map() {
+        let mut a = StructuredData::new_empty();
+        a.insert_tuple("z@1", "b", "1");
+        a.insert_tuple("z@1", "a", "2");
+        a.insert_tuple("y@1", "c", "3");
+        let mut b = StructuredData::new_empty();
+        b.insert_tuple("y@1", "c", "3");
+        b.insert_tuple("z@1", "a", "2");
+        b.insert_tuple("z@1", "b", "1");
+        assert_eq!(a, b);
+        let hash = |sd: &StructuredData| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            std::hash::Hash::hash(sd, &mut hasher);
+            std::hash::Hasher::finish(&hasher)
+        };
+        assert_eq!(hash(&a), hash(&b));
+        assert_eq!(
+            a.iter_tuples().collect::<Vec<_>>(),
+            vec![("y@1", "c", "3"), ("z@1", "a", "2"), ("z@1", "b", "1")]
+        );
+        let ordered: Vec<_> = a.iter_ordered().map(|(sd_id, _)| sd_id.as_str()).collect();
+        assert_eq!(ordered, vec!["y@1", "z@1"]);
+        assert!(a.remove_tuple("z@1", "a").is_some());
+        assert!(a.remove_sdid("y@1").is_some());
+        assert_eq!(a.iter_tuples().collect::<Vec<_>>(), vec![("z@1", "b", "1")]);
+    }
+
+    #[test]
+    fn test_sd_capacity() {
+        let mut sd = StructuredData::with_capacity(4);
+        sd.reserve_params("audit@32473", 50);
+        for i in 0..50 {
+            sd.insert_tuple("audit@32473", format!("p{}", i), "v");
+        }
+        sd.insert_tuple("meta", "sequenceId", "1");
+        assert_eq!(sd.len(), 2);
+        assert_eq!(sd.param_count(), 51);
+        sd.reserve(10);
+        sd.shrink_to_fit();
+        assert_eq!(sd.param_count(), 51);
+
+        let mut ordered = StructuredData::new_ordered();
+        ordered.reserve_params("b", 2);
+        ordered.insert_tuple("a", "x", "1");
+        ordered.insert_tuple("b", "y", "2");
+        ordered.shrink_to_fit();
+        let ids: Vec<_> = ordered.iter_ordered().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a"]);
+        assert_eq!(StructuredData::new_empty().param_count(), 0);
+
+        #[cfg(feature = "sd-indexmap")]
+        {
+            let sd = StructuredData::<crate::sd_map::IndexParamMap>::empty_with_capacity(4);
+            assert!(sd.capacity() >= 4);
+        }
+    }
+
+    #[test]
+    fn test_into_map() {
+        let m: SyslogMessage =
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine evntslog 10 - [a@1 x="1"] hi"#
+                .parse()
+                .unwrap();
+        let map = m.clone().into_map();
+        assert_eq!(map.len(), m.iter_kv().count());
+        for (key, value) in m.iter_kv() {
+            assert_eq!(map[key.as_ref()], value.to_string());
+        }
+        assert_eq!(map["timestamp"], "2003-10-11T22:14:15.003Z");
+        assert_eq!(map["procid"], "10");
+        assert_eq!(map["sd.a@1.x"], "1");
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    #[test]
+    fn test_to_json_value() {
+        let m: SyslogMessage = r#"<165>1 - host app - - [a@1 x="1"] hi"#.parse().unwrap();
+        let value = m.to_json_value();
+        assert_eq!(
+            value,
+            serde_json::from_str::<serde_json::Value>(&serde_json::to_string(&m).unwrap()).unwrap()
+        );
+        assert_eq!(value["sd"]["a@1"]["x"], "1");
+        assert_eq!(serde_json::from_value::<SyslogMessage>(value).unwrap(), m);
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    #[test]
+    fn test_structured_data_serialization_serde() {
+        let mut s = StructuredData::new_empty();
+        s.insert_tuple("foo", "bar", "baz");
+        s.insert_tuple("foo", "baz", "bar");
+        s.insert_tuple("faa", "bar", "baz");
+        let encoded = serde_json::to_string(&s).expect("Should encode to JSON");
+        assert_eq!(
+            encoded,
+            r#"{"faa":{"bar":"baz"},"foo":{"bar":"baz","baz":"bar"}}"#
+        );
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    #[test]
+    fn test_structured_data_deserialize_errors() {
+        let good = r#"{"foo":{"bar":"baz"}}"#;
+        let s: StructuredData = serde_json::from_str(good).expect("should deserialize");
+        assert_eq!(s.find_tuple("foo", "bar"), Some(&"baz".to_string()));
+
+        for bad in &[
+            r#"["foo"]"#,
+            r#"{"foo":"bar"}"#,
+            r#"{"foo":{"bar":1}}"#,
+            r#"{"foo":{"bar":"baz""#,
+            r#"{"foo":{"bar":"baz"}"#,
+            "",
+        ] {
+            assert!(
+                serde_json::from_str::<StructuredData>(bad).is_err(),
+                "{}",
+                bad
+            );
+        }
+
+        let m = r#"{"severity":"info","facility":"kern","version":1,"timestamp":null,"hostname":null,"appname":null,"procid":null,"msgid":null,"sd":{"a":[]},"msg":""}"#;
+        assert!(serde_json::from_str::<SyslogMessage>(m).is_err());
+        // ...and it's only the SD that's wrong
+        assert!(serde_json::from_str::<SyslogMessage>(&m.replace("[]", "{}")).is_ok());
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    #[test]
+    fn test_serialization_serde() {
+        let m = SyslogMessage {
+            severity: SEV_INFO,
+            facility: LOG_KERN,
+            version: 1,
+            timestamp: None,
+            timestamp_precision_lost: false,
+            leap_second: false,
+            hostname: None,
+            appname: None,
+            procid: None,
+            msgid: None,
+            sd: StructuredData::new_empty(),
+            msg: String::from(""),
+            msg_is_utf8: false,
+            msg_bytes: None,
+            truncated: false,
         };
 
         let encoded = serde_json::to_string(&m).expect("Should encode to JSON");
@@ -398,6 +1705,22 @@ mod tests {
         // version changes. *shrug*
         assert_eq!(encoded,
                    "{\"severity\":\"info\",\"facility\":\"kern\",\"version\":1,\"timestamp\":null,\"timestamp_nanos\":null,\"hostname\":null,\"appname\":null,\"procid\":null,\"msgid\":null,\"sd\":{},\"msg\":\"\"}");
+        assert_eq!(serde_json::from_str::<SyslogMessage>(&encoded).unwrap(), m);
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    #[test]
+    fn test_deserialize_numeric_codes() {
+        let m: SyslogMessage = serde_json::from_str(
+            r#"{"severity":3,"facility":"local0","version":1,"timestamp":null,"timestamp_nanos":null,"hostname":null,"appname":null,"procid":null,"msgid":null,"sd":{},"msg":""}"#,
+        )
+        .expect("should deserialize");
+        assert_eq!(m.severity, SEV_ERR);
+        assert_eq!(m.facility, LOG_LOCAL0);
+        assert!(serde_json::from_str::<SyslogMessage>(
+            r#"{"severity":8,"facility":0,"version":1,"timestamp":null,"timestamp_nanos":null,"hostname":null,"appname":null,"procid":null,"msgid":null,"sd":{},"msg":""}"#
+        )
+        .is_err());
     }
 
     #[test]
@@ -411,11 +1734,430 @@ mod tests {
         assert_eq!("baz", s.get("faa").and_then(|foo| foo.get("bar")).unwrap());
     }
 
+    #[test]
+    fn test_time_timestamp() {
+        let mut msg = "<1>1 1985-04-12T19:20:50.52-04:00 host - - - -"
+            .parse::<SyslogMessage>()
+            .expect("Should parse empty message");
+        let dt = msg
+            .timestamp_offset_datetime()
+            .expect("should have a timestamp");
+        assert_eq!(dt.offset().whole_hours(), -4);
+        assert_eq!((dt.hour(), dt.minute(), dt.second()), (19, 20, 50));
+        assert_eq!(dt.millisecond(), 520);
+
+        let dt = time::OffsetDateTime::from_unix_timestamp(1061676855)
+            .unwrap()
+            .to_offset(time::UtcOffset::from_hms(7, 0, 0).unwrap());
+        msg.set_timestamp_offset_datetime(dt);
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1061676855));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(0));
+        assert_eq!(msg.timestamp.map(|t| t.offset), Some(7 * 3600));
+        assert_eq!(msg.timestamp_offset_datetime(), Some(dt));
+    }
+
+    #[test]
+    fn test_timestamp_rfc3339() {
+        let rfc3339 = |s: &str| s.parse::<SyslogMessage>().unwrap().timestamp_rfc3339();
+        assert_eq!(
+            rfc3339("<1>1 1985-04-12T19:20:50.52-04:00 host - - - -").as_deref(),
+            Some("1985-04-12T19:20:50.52-04:00")
+        );
+        assert_eq!(
+            rfc3339("<1>1 2003-10-11T22:14:15Z host - - - -").as_deref(),
+            Some("2003-10-11T22:14:15Z")
+        );
+        assert_eq!(
+            rfc3339("<1>1 2016-12-31T23:59:60Z host - - - -").as_deref(),
+            Some("2016-12-31T23:59:60Z")
+        );
+        assert_eq!(rfc3339("<1>1 - host - - - -"), None);
+
+        let mut msg = "<1>1 - host - - - -".parse::<SyslogMessage>().unwrap();
+        msg.timestamp = Some(Timestamp::new(1065910455, 123_456_789, 5 * 3600 + 1800));
+        assert_eq!(
+            msg.timestamp_rfc3339().as_deref(),
+            Some("2003-10-12T03:44:15.123456789+05:30")
+        );
+        // the wire format is limited to microseconds
+        assert!(msg.to_string().contains("T03:44:15.123456+05:30"));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono_timestamp() {
+        let mut msg = "<1>1 1985-04-12T19:20:50.52-04:00 host - - - -"
+            .parse::<SyslogMessage>()
+            .expect("Should parse empty message");
+        let dt = msg.timestamp_datetime().expect("should have a timestamp");
+        assert_eq!(dt.to_rfc3339(), "1985-04-12T19:20:50.520-04:00");
+
+        let dt = chrono::DateTime::parse_from_rfc3339("2003-08-24T05:14:15.000003+07:00").unwrap();
+        msg.set_timestamp_datetime(&dt);
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1061676855));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(3000));
+        assert_eq!(msg.timestamp.map(|t| t.offset), Some(7 * 3600));
+        assert_eq!(msg.timestamp_datetime(), Some(dt));
+
+        msg.timestamp = None;
+        assert_eq!(msg.timestamp_datetime(), None);
+    }
+
+    #[test]
+    fn test_structured_data_order() {
+        let mut s = StructuredData::new_ordered();
+        s.insert_tuple("zzz", "b", "1");
+        s.insert_tuple("aaa", "y", "2");
+        s.insert_tuple("zzz", "a", "3");
+        s.entry("aaa").insert("x".to_string(), "4".to_string());
+        let ordered = s
+            .iter_ordered()
+            .map(|(id, params)| {
+                let names = params.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>();
+                (id.as_str(), names)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ordered,
+            vec![("zzz", vec!["b", "a"]), ("aaa", vec!["y", "x"])]
+        );
+
+        let mut unordered = StructuredData::new_empty();
+        unordered.insert_tuple("aaa", "x", "4");
+        unordered.insert_tuple("aaa", "y", "2");
+        unordered.insert_tuple("zzz", "a", "3");
+        unordered.insert_tuple("zzz", "b", "1");
+        assert_eq!(s, unordered);
+        assert_eq!(unordered.iter_ordered().next().unwrap().0, "aaa");
+
+        // removing something forgets where it was, so putting it back puts it at the end
+        s.remove_tuple("zzz", "b");
+        s.insert_tuple("zzz", "b", "5");
+        s.insert_tuple("zzz", "c", "6");
+        s.retain(|_, name, _| name != "a");
+        s.insert_tuple("zzz", "a", "7");
+        s.remove_sdid("aaa");
+        s.insert_tuple("aaa", "y", "8");
+        let ordered = s
+            .iter_ordered()
+            .map(|(id, params)| {
+                let names = params.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>();
+                (id.as_str(), names)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ordered,
+            vec![("zzz", vec!["b", "c", "a"]), ("aaa", vec!["y"])]
+        );
+    }
+
+    #[test]
+    fn test_try_insert_tuple() {
+        use crate::validation::{Field, Violation};
+
+        let mut s = StructuredData::new_empty();
+        s.try_insert_tuple("meta", "seq", "1")
+            .expect("should be valid");
+        let errors = s
+            .try_insert_tuple("bad id", "x".repeat(33), "v")
+            .expect_err("should be invalid");
+        assert_eq!(
+            errors,
+            vec![
+                Violation::InvalidSdNameChar {
+                    field: Field::SdId(String::from("bad id")),
+                    found: ' '
+                },
+                Violation::TooLong {
+                    field: Field::SdParamName(String::from("bad id"), "x".repeat(33)),
+                    length: 33,
+                    max: 32
+                },
+            ]
+        );
+        assert_eq!(s.len(), 1);
+    }
+
+    #[test]
+    fn test_structured_data_mutation() {
+        let mut s = StructuredData::new_empty();
+        s.insert_tuple("meta", "seq", "1");
+        s.insert_tuple("meta", "secret", "hunter2");
+        s.insert_tuple("origin", "ip", "192.0.2.1");
+
+        *s.find_tuple_mut("meta", "seq").unwrap() = String::from("2");
+        assert_eq!(
+            s.remove_tuple("meta", "secret"),
+            Some(String::from("hunter2"))
+        );
+        assert_eq!(s.remove_tuple("meta", "secret"), None);
+        assert!(s.remove_sdid("origin").is_some());
+        assert_eq!(
+            s.iter_tuples().collect::<Vec<_>>(),
+            vec![("meta", "seq", "2")]
+        );
+
+        let mut other = StructuredData::new_empty();
+        other.insert_tuple("meta", "seq", "3");
+        other.insert_tuple("relay", "hop", "1");
+        other.insert_tuple("relay", "host", "r1");
+        s.merge(other);
+        s.retain(|_, name, _| name != "host");
+        assert_eq!(
+            s.iter_tuples().collect::<Vec<_>>(),
+            vec![("meta", "seq", "3"), ("relay", "hop", "1")]
+        );
+        s.find_sdid_mut("relay").unwrap().clear();
+        assert_eq!(s.len(), 2);
+    }
+
+    #[test]
+    fn test_multi_structured_data() {
+        let m = crate::parse_message_ref(r#"<1>1 - - - - - [foo bar="baz" bar="bing"][foo a="b"]"#)
+            .expect("should parse");
+        let multi = m.sd.to_multi();
+        assert_eq!(multi.find_tuples("foo", "bar"), ["baz", "bing"]);
+        assert_eq!(multi.find_tuples("foo", "a"), ["b"]);
+        assert!(multi.find_tuples("foo", "missing").is_empty());
+        assert_eq!(multi.to_structured_data(), m.sd.to_owned());
+        assert_eq!(
+            MultiStructuredData::from(&m.sd.to_owned()).find_tuples("foo", "bar"),
+            ["bing"]
+        );
+    }
+
+    #[test]
+    fn test_procid_ordering() {
+        let mut ids = vec![
+            ProcId::Name(String::from("b")),
+            ProcId::PID(10),
+            ProcId::Name(String::from("a")),
+            ProcId::PID(2),
+        ];
+        ids.sort();
+        assert_eq!(
+            ids,
+            vec![
+                ProcId::PID(2),
+                ProcId::PID(10),
+                ProcId::Name(String::from("a")),
+                ProcId::Name(String::from("b")),
+            ]
+        );
+        let rendered = ids.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(rendered, vec!["2", "10", "a", "b"]);
+    }
+
+    #[test]
+    fn test_systemtime_timestamp() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut msg = "<1>1 1985-04-12T23:20:50.52Z host - - - -"
+            .parse::<SyslogMessage>()
+            .expect("should parse");
+        let t = msg.timestamp_systemtime().expect("should have a timestamp");
+        assert_eq!(t, UNIX_EPOCH + Duration::from_millis(482196050520));
+
+        let before_epoch = UNIX_EPOCH - Duration::from_millis(1500);
+        msg.set_timestamp_systemtime(before_epoch);
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(-2));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(500_000_000));
+        assert_eq!(msg.timestamp_systemtime(), Some(before_epoch));
+        assert_eq!(msg.to_string(), "<1>1 1969-12-31T23:59:58.5Z host - - - -");
+
+        msg.timestamp = None;
+        assert_eq!(msg.timestamp_systemtime(), None);
+    }
+
     #[test]
     fn test_fromstr() {
         let msg = "<1>1 1985-04-12T23:20:50.52Z host - - - -"
             .parse::<SyslogMessage>()
             .expect("Should parse empty message");
-        assert_eq!(msg.timestamp, Some(482196050));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(482196050));
+        assert_eq!(msg.timestamp.map(|t| t.offset), Some(0));
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    #[test]
+    fn test_serde_timestamp() {
+        let m = "<1>1 2003-10-11T22:14:15.003-07:00 - - - - -"
+            .parse::<SyslogMessage>()
+            .expect("should parse");
+        let json = serde_json::to_string(&m).expect("should serialize");
+        assert!(json
+            .contains(r#""timestamp":1065935655,"timestamp_nanos":3000000,"utc_offset":-25200,"#));
+        assert_eq!(
+            serde_json::from_str::<SyslogMessage>(&json).expect("should deserialize"),
+            m
+        );
+    }
+
+    #[test]
+    fn test_msg_bytes() {
+        let mut m = SyslogMessage::builder()
+            .no_timestamp()
+            .msg_bytes(&b"ok"[..])
+            .build_unchecked();
+        assert_eq!(m.msg, "ok");
+        assert_eq!(m.msg_bytes, None);
+        assert_eq!(m.msg_as_bytes(), b"ok");
+
+        m.set_msg_bytes(b"\x00\xff\xfe payload".to_vec());
+        assert_eq!(m.msg, "\u{0}\u{fffd}\u{fffd} payload");
+        assert_eq!(m.msg_as_bytes(), b"\x00\xff\xfe payload");
+        let mut wire = Vec::new();
+        m.write_to(&mut wire).unwrap();
+        assert_eq!(wire, b"<13>1 - - - - - - \x00\xff\xfe payload");
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    #[test]
+    fn test_serde_msg_bytes() {
+        let mut m = "<1>1 - - - - - -".parse::<SyslogMessage>().unwrap();
+        m.set_msg_bytes(vec![0xff, 0x00, b'x']);
+        let json = serde_json::to_string(&m).expect("should serialize");
+        assert!(json.contains(r#""msg_bytes":"/wB4""#), "{}", json);
+        assert_eq!(
+            serde_json::from_str::<SyslogMessage>(&json).expect("should deserialize"),
+            m
+        );
+
+        let old = json.replace(r#""/wB4""#, "[255,0,120]");
+        assert_eq!(serde_json::from_str::<SyslogMessage>(&old).unwrap(), m);
+        let null = json.replace(r#""/wB4""#, "null");
+        assert_eq!(
+            serde_json::from_str::<SyslogMessage>(&null)
+                .unwrap()
+                .msg_bytes,
+            None
+        );
+        let bad = json.replace(r#""/wB4""#, r#""not base64!""#);
+        assert!(serde_json::from_str::<SyslogMessage>(&bad).is_err());
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    #[test]
+    fn test_serde_compact() {
+        let inputs = [
+            r#"<165>1 2003-10-11T22:14:15.003-07:00 host app 4242 ID7 [a@1 b="c"][d@1 e="f"] hi"#,
+            "<1>1 - - sshd - - -",
+        ];
+        for input in &inputs {
+            let mut m = input.parse::<SyslogMessage>().expect("should parse");
+            let bytes = postcard::to_allocvec(&m).expect("should serialize");
+            assert_eq!(
+                postcard::from_bytes::<SyslogMessage>(&bytes).expect("should deserialize"),
+                m
+            );
+
+            m.procid = Some(ProcId::Name(String::from("worker")));
+            m.msg_bytes = Some(vec![0xff, b'x']);
+            m.msg_is_utf8 = true;
+            m.truncated = true;
+            let bytes = postcard::to_allocvec(&m).expect("should serialize");
+            assert_eq!(
+                postcard::from_bytes::<SyslogMessage>(&bytes).expect("should deserialize"),
+                m
+            );
+            assert!(postcard::from_bytes::<SyslogMessage>(&bytes[..bytes.len() - 1]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let input = r#"<78>1 2016-01-15T00:04:01.5-08:00 host1 CROND 10391 - [meta a="x\"y"] hi"#;
+        let msg = input.parse::<SyslogMessage>().expect("should parse");
+        assert_eq!(msg.to_string(), input);
+        assert_eq!(msg.to_string().parse::<SyslogMessage>().unwrap(), msg);
+
+        let mut s = String::new();
+        msg.write_to_fmt(&mut s).unwrap();
+        assert_eq!(s, input);
+    }
+
+    #[test]
+    fn test_mutation_helpers() {
+        let mut m: SyslogMessage = "<1>1 2016-12-31T23:59:60Z host - - - - hi".parse().unwrap();
+        assert!(m.leap_second);
+        m.set_timestamp_now();
+        assert!(!m.leap_second);
+        assert!(m.timestamp.unwrap().secs > 1_600_000_000);
+
+        m.prepend_msg("[relayed] ");
+        assert_eq!(m.msg, "[relayed] hi");
+        m.msg = String::from("\u{feff}hi");
+        m.prepend_msg("> ");
+        assert_eq!(m.msg, "\u{feff}> hi");
+        m.set_msg_bytes(b"\xffhi".to_vec());
+        m.prepend_msg("> ");
+        assert_eq!(m.msg_bytes.as_deref(), Some(&b"> \xffhi"[..]));
+        assert_eq!(m.msg, "> \u{fffd}hi");
+
+        m.tag_sd("relay@32473", "hop", 2);
+        m.tag_sd("relay@32473", "via", "relay1");
+        assert_eq!(m.sd.find_tuple("relay@32473", "hop").unwrap(), "2");
+        assert_eq!(m.sd.find_tuple("relay@32473", "via").unwrap(), "relay1");
+    }
+
+    #[cfg(feature = "hostname")]
+    #[test]
+    fn test_set_hostname_local() {
+        let mut m: SyslogMessage = "<1>1 - - - - - - hi".parse().unwrap();
+        m.set_hostname_local().unwrap();
+        assert!(!m.hostname.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_write_to() {
+        let input = b"<1>1 - host - - - - caf\xe9";
+        let msg = crate::parser::parse_message_bytes(input, crate::parser::Utf8Policy::Preserve)
+            .expect("should parse");
+        let mut out = Vec::new();
+        msg.write_to(&mut out).expect("should write");
+        assert_eq!(&out[..], &input[..]);
+
+        let mut full = [0u8; 8];
+        let err = msg
+            .write_to(&mut &mut full[..])
+            .expect_err("should run out of space");
+        assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let config = crate::parser::ParserConfig::new()
+            .with_preserve_sd_order(true)
+            .with_strip_bom(false);
+        let normalized = |s: &str| {
+            let mut m = crate::parser::parse_message_with(&config, s).unwrap();
+            let fingerprint = m.fingerprint();
+            m.normalize();
+            assert_eq!(m.fingerprint(), fingerprint);
+            m.to_string()
+        };
+        let canonical = r#"<14>1 2020-01-01T00:00:00Z host app 12 ID [a@1 x="1" y="2"][b@1] hi"#;
+        assert_eq!(normalized(canonical), canonical);
+        assert_eq!(
+            normalized(
+                "<14>1 2020-01-01T01:00:00+01:00 HoSt app 12 ID [b@1][a@1 y=\"2\" x=\"1\"] \u{feff}hi"
+            ),
+            canonical
+        );
+
+        let mut m = "<14>1 - - - - - - hi".parse::<SyslogMessage>().unwrap();
+        m.hostname = Some("".into());
+        m.appname = Some("".into());
+        m.procid = Some(ProcId::Name(String::new()));
+        m.msgid = Some("".into());
+        m.msg_is_utf8 = true;
+        m.normalize();
+        assert_eq!(m, "<14>1 - - - - - - hi".parse::<SyslogMessage>().unwrap());
+        assert_eq!(m.to_string(), "<14>1 - - - - - - hi");
+
+        m.set_msg_bytes(b"\xef\xbb\xbfcaf\xe9".to_vec());
+        m.normalize();
+        assert_eq!(m.msg_as_bytes(), b"caf\xe9");
+        assert_eq!(m.msg, "caf\u{fffd}");
     }
 }
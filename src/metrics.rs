@@ -0,0 +1,203 @@
+//! Counting what the parser does.
+//!
+//! A `ParserMetrics` set on a `ParserConfig` with `ParserConfig::with_metrics` is told about every
+//! message parsed with that config: how long the input was, and whether it parsed or why it
+//! didn't. This covers `parse_message_with` (and so `parse_messages`, `parser::Parser::parse` and
+//! `parse_messages_par_with`), `parse_message_bytes_with`, `parse_message_with_raw` and
+//! `parse_message_lazy`.
+//!
+//! `AtomicParserMetrics` keeps counts of messages, bytes and failures by `ErrorClass`, and can be
+//! shared between threads; closures work too, for feeding some other metrics library.
+//!
+//! # Example
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use syslog_rfc5424::metrics::{AtomicParserMetrics, ErrorClass};
+//! use syslog_rfc5424::parser::{parse_message_with, ParserConfig};
+//!
+//! let metrics = Arc::new(AtomicParserMetrics::new());
+//! let config = ParserConfig::new().with_metrics(metrics.clone());
+//! assert!(parse_message_with(&config, "<1>1 - - - - - - hi").is_ok());
+//! assert!(parse_message_with(&config, "<1>1 2020-13-01T00:00:00Z - - - - - hi").is_err());
+//!
+//! let stats = metrics.snapshot();
+//! assert_eq!(stats.messages, 2);
+//! assert_eq!(stats.failures, 1);
+//! assert_eq!(stats.errors(ErrorClass::Timestamp), 1);
+//! ```
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+pub use crate::parser::ErrorClass;
+use crate::parser::ParseErr;
+
+/// Something which is told about each message a parser handles
+pub trait ParserMetrics: Send + Sync {
+    /// Called once per message, with the length of the input in bytes and the outcome
+    fn record(&self, len: usize, outcome: Result<(), &ParseErr>);
+}
+
+impl<F> ParserMetrics for F
+where
+    F: Fn(usize, Result<(), &ParseErr>) + Send + Sync,
+{
+    fn record(&self, len: usize, outcome: Result<(), &ParseErr>) {
+        self(len, outcome)
+    }
+}
+
+#[derive(Clone)]
+/// The `ParserMetrics` held by a `ParserConfig`
+pub(crate) struct MetricsHandle(pub(crate) Arc<dyn ParserMetrics>);
+
+impl fmt::Debug for MetricsHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ParserMetrics")
+    }
+}
+
+#[derive(Debug, Default)]
+/// A `ParserMetrics` which keeps running counts in atomics
+pub struct AtomicParserMetrics {
+    messages: AtomicU64,
+    failures: AtomicU64,
+    bytes: AtomicU64,
+    errors: [AtomicU64; ErrorClass::ALL.len()],
+}
+
+impl AtomicParserMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The counts so far
+    pub fn snapshot(&self) -> ParserStats {
+        let mut errors = [0; ErrorClass::ALL.len()];
+        for (count, counter) in errors.iter_mut().zip(&self.errors) {
+            *count = counter.load(Ordering::Relaxed);
+        }
+        ParserStats {
+            messages: self.messages.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            errors,
+        }
+    }
+
+    /// Set every count back to zero
+    pub fn reset(&self) {
+        self.messages.store(0, Ordering::Relaxed);
+        self.failures.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+        for counter in &self.errors {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl ParserMetrics for AtomicParserMetrics {
+    fn record(&self, len: usize, outcome: Result<(), &ParseErr>) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(len as u64, Ordering::Relaxed);
+        if let Err(e) = outcome {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+            self.errors[e.class() as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// A snapshot of the counts in an `AtomicParserMetrics`
+pub struct ParserStats {
+    /// Messages parsed, whether successfully or not
+    pub messages: u64,
+    /// Messages which failed to parse
+    pub failures: u64,
+    /// Total length of every message's input, in bytes
+    pub bytes: u64,
+    errors: [u64; ErrorClass::ALL.len()],
+}
+
+impl ParserStats {
+    /// Messages which failed to parse with an error of this class
+    pub fn errors(&self, class: ErrorClass) -> u64 {
+        self.errors[class as usize]
+    }
+
+    /// The fraction of messages which failed to parse, or zero if there were none
+    pub fn error_rate(&self) -> f64 {
+        if self.messages == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.messages as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::{AtomicParserMetrics, ErrorClass, ParserMetrics};
+    use crate::parser::{
+        parse_message_bytes_with, parse_message_lazy, parse_message_with, parse_message_with_raw,
+        ParseErr, ParserConfig, Utf8Policy,
+    };
+
+    #[test]
+    fn test_atomic_metrics() {
+        let metrics = Arc::new(AtomicParserMetrics::new());
+        let config = ParserConfig::new()
+            .with_max_message_length(Some(64))
+            .with_metrics(metrics.clone());
+        let ok = "<1>1 - - - - - - hi";
+        assert!(parse_message_with(&config, ok).is_ok());
+        assert!(parse_message_with_raw(&config, ok).is_ok());
+        assert!(parse_message_lazy(&config, ok).is_ok());
+        assert!(
+            parse_message_bytes_with(&config, b"<1>1 - - - - - - \xff", Utf8Policy::Lossy).is_ok()
+        );
+        assert!(parse_message_with(&config, "<200>1 - - - - - - hi").is_err());
+        assert!(parse_message_with(&config, "<1>2 - - - - - - hi").is_ok());
+        assert!(parse_message_with(&config, "<1>1 - - - - - [a").is_err());
+        assert!(parse_message_with(&config, "x".repeat(100)).is_err());
+        assert!(
+            parse_message_bytes_with(&config, b"<1>1 - \xff - - - - hi", Utf8Policy::Strict)
+                .is_err()
+        );
+
+        let stats = metrics.snapshot();
+        assert_eq!(stats.messages, 9);
+        assert_eq!(stats.failures, 4);
+        assert_eq!(stats.bytes, 19 * 4 + 18 + 21 + 17 + 100 + 19);
+        assert_eq!(stats.errors(ErrorClass::Pri), 1);
+        assert_eq!(stats.errors(ErrorClass::Sd), 1);
+        assert_eq!(stats.errors(ErrorClass::Limit), 1);
+        assert_eq!(stats.errors(ErrorClass::Header), 1);
+        assert_eq!(stats.error_rate(), 4.0 / 9.0);
+
+        metrics.reset();
+        assert_eq!(metrics.snapshot(), Default::default());
+    }
+
+    #[test]
+    fn test_closure_metrics() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let metrics: Arc<dyn ParserMetrics> =
+            Arc::new(move |len: usize, outcome: Result<(), &ParseErr>| {
+                assert!(outcome.is_ok());
+                counter.fetch_add(len, Ordering::Relaxed);
+            });
+        let config = ParserConfig::new().with_metrics(metrics);
+        let parser = crate::parser::Parser::new().with_config(config.clone());
+        parser.parse("<1>1 - - - - - - hi").unwrap();
+        parse_message_with(&config, "<1>1 - - - - - - hello").unwrap();
+        assert_eq!(count.load(Ordering::Relaxed), 19 + 22);
+    }
+}
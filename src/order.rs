@@ -0,0 +1,161 @@
+//! Putting messages back into chronological order.
+//!
+//! Messages received over UDP, or merged from several relays, often arrive out of order. These
+//! helpers sort batches by their TIMESTAMP and notice when a message turns up later than it
+//! should have.
+//!
+//! `SyslogMessage` deliberately doesn't implement `PartialOrd`, since two different messages can
+//! have the same timestamp; use `cmp_by_time` instead.
+
+use std::cmp::Ordering;
+use std::time::Duration;
+
+use crate::message::SyslogMessage;
+use crate::timestamp::Timestamp;
+
+/// Compare two messages by when they were sent, whatever the sender's UTC offset
+///
+/// Messages without a timestamp come after all of those with one, and compare equal to each
+/// other, so a stable sort leaves them in the order they were received.
+pub fn cmp_by_time(a: &SyslogMessage, b: &SyslogMessage) -> Ordering {
+    cmp_timestamps(a.timestamp.as_ref(), b.timestamp.as_ref())
+}
+
+fn cmp_timestamps(a: Option<&Timestamp>, b: Option<&Timestamp>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => (a.secs, a.nanos).cmp(&(b.secs, b.nanos)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Sort messages by `cmp_by_time`, keeping the received order of messages sent at the same
+/// time (or without a timestamp)
+pub fn sort_by_time(messages: &mut [SyslogMessage]) {
+    messages.sort_by(cmp_by_time);
+}
+
+/// Whether `messages` is already in chronological order, as `sort_by_time` would leave it
+pub fn is_sorted_by_time(messages: &[SyslogMessage]) -> bool {
+    messages
+        .windows(2)
+        .all(|pair| cmp_by_time(&pair[0], &pair[1]) != Ordering::Greater)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// How a message's timestamp relates to the ones which came before it; see `OrderChecker`
+pub enum Arrival {
+    /// No earlier than any message seen so far
+    InOrder,
+    /// Earlier than the latest message seen so far, by no more than the allowed skew
+    WithinSkew(Duration),
+    /// Earlier than the latest message seen so far, by more than the allowed skew
+    OutOfOrder(Duration),
+    /// The message has no timestamp
+    Untimed,
+}
+
+#[derive(Clone, Debug)]
+/// Spots messages which arrive out of order
+///
+/// Each message is compared against the latest timestamp seen so far. A little disorder is normal
+/// (senders' clocks and network paths differ), so messages up to `max_skew` behind are reported
+/// separately from ones which are further out of order.
+pub struct OrderChecker {
+    max_skew: Duration,
+    latest: Option<Timestamp>,
+}
+
+impl OrderChecker {
+    pub fn new(max_skew: Duration) -> Self {
+        OrderChecker {
+            max_skew,
+            latest: None,
+        }
+    }
+
+    /// The latest timestamp seen so far
+    pub fn latest(&self) -> Option<Timestamp> {
+        self.latest
+    }
+
+    /// Check the next message to arrive
+    pub fn check(&mut self, m: &SyslogMessage) -> Arrival {
+        let ts = match m.timestamp {
+            Some(ts) => ts,
+            None => return Arrival::Untimed,
+        };
+        let latest = match self.latest {
+            Some(latest) if cmp_timestamps(Some(&ts), Some(&latest)) == Ordering::Less => latest,
+            _ => {
+                self.latest = Some(ts);
+                return Arrival::InOrder;
+            }
+        };
+        let mut secs = latest.secs - ts.secs;
+        let mut nanos = i64::from(latest.nanos) - i64::from(ts.nanos);
+        if nanos < 0 {
+            secs -= 1;
+            nanos += 1_000_000_000;
+        }
+        let behind = Duration::new(secs as u64, nanos as u32);
+        if behind <= self.max_skew {
+            Arrival::WithinSkew(behind)
+        } else {
+            Arrival::OutOfOrder(behind)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{cmp_by_time, is_sorted_by_time, sort_by_time, Arrival, OrderChecker};
+    use crate::parse_message;
+
+    #[test]
+    fn test_sort_by_time() {
+        let mut messages = [
+            "<1>1 2020-01-01T00:00:02Z a - - - - 1",
+            "<1>1 - b - - - - 2",
+            // the same instant as the first message
+            "<1>1 2020-01-01T01:00:02+01:00 c - - - - 3",
+            "<1>1 2020-01-01T00:00:01.5Z d - - - - 4",
+            "<1>1 - e - - - - 5",
+        ]
+        .iter()
+        .map(|s| parse_message(s).unwrap())
+        .collect::<Vec<_>>();
+        assert!(!is_sorted_by_time(&messages));
+        sort_by_time(&mut messages);
+        let order: Vec<_> = messages.iter().map(|m| m.msg.as_str()).collect();
+        assert_eq!(order, vec!["4", "1", "3", "2", "5"]);
+        assert!(is_sorted_by_time(&messages));
+        assert!(cmp_by_time(&messages[1], &messages[2]).is_eq());
+    }
+
+    #[test]
+    fn test_order_checker() {
+        let mut checker = OrderChecker::new(Duration::from_secs(1));
+        let mut check = |s: &str| checker.check(&parse_message(s).unwrap());
+        assert_eq!(
+            check("<1>1 2020-01-01T00:00:10Z - - - - -"),
+            Arrival::InOrder
+        );
+        assert_eq!(
+            check("<1>1 2020-01-01T00:00:09.25Z - - - - -"),
+            Arrival::WithinSkew(Duration::from_millis(750))
+        );
+        assert_eq!(
+            check("<1>1 2020-01-01T00:00:07.5Z - - - - -"),
+            Arrival::OutOfOrder(Duration::from_millis(2500))
+        );
+        assert_eq!(check("<1>1 - - - - - -"), Arrival::Untimed);
+        assert_eq!(
+            check("<1>1 2020-01-01T00:00:10Z - - - - -"),
+            Arrival::InOrder
+        );
+    }
+}
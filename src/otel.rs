@@ -0,0 +1,306 @@
+//! Conversion to and from OpenTelemetry log records (requires the `otel` feature).
+//!
+//! `SyslogMessage::to_log_record` fills in a `LogRecord` from any OpenTelemetry `Logger`, and
+//! `resource_attributes` gives the attributes of the `Resource` it belongs to.
+//! `SyslogMessage::from_log_record` goes the other way, for an `opentelemetry_sdk` record and its
+//! resource. The fields are mapped as in the OpenTelemetry log data model's appendix for syslog,
+//! and named as the Collector's syslog receiver names them:
+//!
+//! | Message field       | OpenTelemetry                                              |
+//! |---------------------|------------------------------------------------------------|
+//! | TIMESTAMP           | `Timestamp` (the UTC offset is lost)                       |
+//! | severity            | `SeverityNumber` (see `severity_number`), `SeverityText`   |
+//! | MSG                 | `Body`                                                     |
+//! | HOSTNAME            | resource `host.name`                                       |
+//! | APP-NAME            | resource `service.name`                                    |
+//! | PROCID              | resource `process.pid` if it's numeric, else `proc_id`     |
+//! | facility            | `facility`                                                 |
+//! | VERSION             | `version`                                                  |
+//! | MSGID               | `msg_id`                                                   |
+//! | STRUCTURED-DATA     | `structured_data`, a map of SD-IDs to maps of SD-PARAMs    |
+//!
+//! When converting back, `hostname`, `appname` and `proc_id` attributes are used if the resource
+//! doesn't have the corresponding attributes, as the Collector puts everything in attributes.
+//!
+//! # Example
+//!
+//! ```
+//! use opentelemetry::logs::{Logger, LoggerProvider};
+//! use opentelemetry_sdk::logs::SdkLoggerProvider;
+//! use opentelemetry_sdk::Resource;
+//! use syslog_rfc5424::message::SyslogMessage;
+//! use syslog_rfc5424::otel::resource_attributes;
+//! use syslog_rfc5424::parse_message;
+//!
+//! let m = parse_message("<11>1 2020-01-01T00:00:00Z web1 nginx 42 - - upstream timed out").unwrap();
+//! let resource = Resource::builder_empty().with_attributes(resource_attributes(&m)).build();
+//! let provider = SdkLoggerProvider::builder().with_resource(resource.clone()).build();
+//! let record = m.to_log_record(&provider.logger("syslog"));
+//!
+//! assert_eq!(SyslogMessage::from_log_record(&record, &resource), m);
+//! ```
+
+use std::collections::HashMap;
+
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, Severity};
+use opentelemetry::{Key, KeyValue, Value};
+use opentelemetry_sdk::logs::SdkLogRecord;
+use opentelemetry_sdk::Resource;
+
+use crate::facility::SyslogFacility;
+use crate::message::{ProcId, StructuredData, SyslogMessage};
+use crate::severity::SyslogSeverity;
+use crate::timestamp::Timestamp;
+
+/// The severity number a syslog severity maps to
+pub fn severity_number(severity: SyslogSeverity) -> Severity {
+    match severity {
+        SyslogSeverity::SEV_EMERG => Severity::Fatal,
+        SyslogSeverity::SEV_ALERT => Severity::Error3,
+        SyslogSeverity::SEV_CRIT => Severity::Error2,
+        SyslogSeverity::SEV_ERR => Severity::Error,
+        SyslogSeverity::SEV_WARNING => Severity::Warn,
+        SyslogSeverity::SEV_NOTICE => Severity::Info2,
+        SyslogSeverity::SEV_INFO => Severity::Info,
+        SyslogSeverity::SEV_DEBUG => Severity::Debug,
+    }
+}
+
+/// The syslog severity a severity number maps to
+///
+/// This is the inverse of `severity_number`, with the numbers in between rounded to the nearest
+/// syslog severity below them; TRACE becomes `SEV_DEBUG`.
+pub fn syslog_severity(number: Severity) -> SyslogSeverity {
+    match number as i32 {
+        1..=8 => SyslogSeverity::SEV_DEBUG,
+        9 => SyslogSeverity::SEV_INFO,
+        10..=12 => SyslogSeverity::SEV_NOTICE,
+        13..=16 => SyslogSeverity::SEV_WARNING,
+        17 => SyslogSeverity::SEV_ERR,
+        18 => SyslogSeverity::SEV_CRIT,
+        19 | 20 => SyslogSeverity::SEV_ALERT,
+        _ => SyslogSeverity::SEV_EMERG,
+    }
+}
+
+/// The attributes of the `Resource` a message's log record belongs to
+///
+/// These are `host.name`, `service.name` and `process.pid`, for whichever of HOSTNAME, APP-NAME
+/// and a numeric PROCID aren't NIL.
+pub fn resource_attributes(m: &SyslogMessage) -> Vec<KeyValue> {
+    let mut attributes = Vec::new();
+    if let Some(ref hostname) = m.hostname {
+        attributes.push(KeyValue::new("host.name", hostname.to_string()));
+    }
+    if let Some(ref appname) = m.appname {
+        attributes.push(KeyValue::new("service.name", appname.to_string()));
+    }
+    if let Some(ProcId::PID(pid)) = m.procid {
+        attributes.push(KeyValue::new("process.pid", i64::from(pid)));
+    }
+    attributes
+}
+
+pub(crate) fn to_log_record<L: Logger>(m: &SyslogMessage, logger: &L) -> L::LogRecord {
+    let mut record = logger.create_log_record();
+    if let Some(t) = m.timestamp_systemtime() {
+        record.set_timestamp(t);
+    }
+    record.set_severity_number(severity_number(m.severity));
+    record.set_severity_text(m.severity.as_str());
+    record.set_body(AnyValue::from(m.msg.clone()));
+    record.add_attribute("facility", m.facility as i64);
+    record.add_attribute("version", i64::from(m.version));
+    if let Some(ProcId::Name(ref name)) = m.procid {
+        record.add_attribute("proc_id", name.clone());
+    }
+    if let Some(ref msgid) = m.msgid {
+        record.add_attribute("msg_id", msgid.to_string());
+    }
+    if !m.sd.is_empty() {
+        let sd: HashMap<Key, AnyValue> = m
+            .sd
+            .iter()
+            .map(|(sd_id, params)| {
+                let params = params
+                    .iter()
+                    .map(|(name, value)| (Key::from(name.clone()), AnyValue::from(value.clone())))
+                    .collect::<HashMap<_, _>>();
+                (Key::from(sd_id.clone()), AnyValue::Map(Box::new(params)))
+            })
+            .collect();
+        record.add_attribute("structured_data", AnyValue::Map(Box::new(sd)));
+    }
+    record
+}
+
+/// A string attribute value, rendering anything else
+fn any_to_string(value: &AnyValue) -> String {
+    match value {
+        AnyValue::String(s) => s.to_string(),
+        AnyValue::Int(i) => i.to_string(),
+        AnyValue::Double(d) => d.to_string(),
+        AnyValue::Boolean(b) => b.to_string(),
+        AnyValue::Bytes(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn any_to_int(value: &AnyValue) -> Option<i64> {
+    match value {
+        AnyValue::Int(i) => Some(*i),
+        AnyValue::String(s) => s.as_str().parse().ok(),
+        _ => None,
+    }
+}
+
+pub(crate) fn from_log_record(record: &SdkLogRecord, resource: &Resource) -> SyslogMessage {
+    let attribute = |key: &str| {
+        record
+            .attributes_iter()
+            .find(|(k, _)| k.as_str() == key)
+            .map(|(_, v)| v)
+    };
+    let resource_string = |key: &'static str, fallback: &str| match resource.get(&Key::new(key)) {
+        Some(value) => Some(value.to_string()),
+        None => attribute(fallback).map(any_to_string),
+    };
+
+    let severity = match (record.severity_number(), record.severity_text()) {
+        (Some(number), _) => syslog_severity(number),
+        (None, Some(text)) => SyslogSeverity::from_str(text).unwrap_or(SyslogSeverity::SEV_INFO),
+        (None, None) => SyslogSeverity::SEV_INFO,
+    };
+    let facility = attribute("facility")
+        .and_then(any_to_int)
+        .and_then(|f| SyslogFacility::from_int(f as i32))
+        .unwrap_or(SyslogFacility::LOG_USER);
+    let procid = match resource.get(&Key::new("process.pid")) {
+        Some(Value::I64(pid)) => Some(ProcId::PID(pid as i32)),
+        _ => attribute("proc_id").map(|p| match any_to_int(p) {
+            Some(pid) => ProcId::PID(pid as i32),
+            None => ProcId::Name(any_to_string(p)),
+        }),
+    };
+    let mut sd = StructuredData::new_empty();
+    if let Some(AnyValue::Map(elements)) = attribute("structured_data") {
+        for (sd_id, params) in elements.iter() {
+            sd.entry(sd_id.as_str());
+            if let AnyValue::Map(params) = params {
+                for (name, value) in params.iter() {
+                    sd.insert_tuple(sd_id.as_str(), name.as_str(), any_to_string(value));
+                }
+            }
+        }
+    }
+
+    SyslogMessage {
+        severity,
+        facility,
+        version: attribute("version")
+            .and_then(any_to_int)
+            .map_or(1, |v| v as i32),
+        timestamp: record
+            .timestamp()
+            .or_else(|| record.observed_timestamp())
+            .map(Timestamp::from),
+        timestamp_precision_lost: false,
+        leap_second: false,
+        hostname: resource_string("host.name", "hostname"),
+        appname: resource_string("service.name", "appname"),
+        procid,
+        msgid: attribute("msg_id").map(any_to_string),
+        sd,
+        msg: record.body().map(any_to_string).unwrap_or_default(),
+        msg_is_utf8: false,
+        msg_bytes: None,
+        truncated: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::logs::{AnyValue, LogRecord, Logger, LoggerProvider, Severity};
+    use opentelemetry::KeyValue;
+    use opentelemetry_sdk::logs::SdkLoggerProvider;
+    use opentelemetry_sdk::Resource;
+
+    use super::{resource_attributes, severity_number, syslog_severity};
+    use crate::message::{ProcId, SyslogMessage};
+    use crate::parse_message;
+    use crate::severity::SyslogSeverity;
+
+    fn resource(m: &SyslogMessage) -> Resource {
+        Resource::builder_empty()
+            .with_attributes(resource_attributes(m))
+            .build()
+    }
+
+    #[test]
+    fn test_severity() {
+        for i in 0..8 {
+            let severity = SyslogSeverity::from_int(i).unwrap();
+            assert_eq!(syslog_severity(severity_number(severity)), severity);
+        }
+        assert_eq!(severity_number(SyslogSeverity::SEV_NOTICE), Severity::Info2);
+        assert_eq!(syslog_severity(Severity::Trace), SyslogSeverity::SEV_DEBUG);
+        assert_eq!(
+            syslog_severity(Severity::Warn4),
+            SyslogSeverity::SEV_WARNING
+        );
+        assert_eq!(syslog_severity(Severity::Fatal4), SyslogSeverity::SEV_EMERG);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let provider = SdkLoggerProvider::builder().build();
+        let logger = provider.logger("test");
+        for s in [
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine evntslog 12 ID47 [a@1 x="1" y="2"][b@1] An application event"#,
+            "<14>1 - - - worker - - hi",
+            "<14>1 - - - - - -",
+        ] {
+            let m = parse_message(s).unwrap();
+            let record = m.to_log_record(&logger);
+            assert_eq!(
+                SyslogMessage::from_log_record(&record, &resource(&m)),
+                m,
+                "{}",
+                s
+            );
+        }
+
+        let m = parse_message("<14>1 2020-01-01T00:00:00+02:00 host app 12 - - hi").unwrap();
+        let record = m.to_log_record(&logger);
+        assert_eq!(record.severity_number(), Some(Severity::Info));
+        assert_eq!(record.severity_text(), Some("info"));
+        assert_eq!(record.body(), Some(&AnyValue::from("hi")));
+        let back = SyslogMessage::from_log_record(&record, &resource(&m));
+        assert_eq!(back.timestamp.unwrap().secs, m.timestamp.unwrap().secs);
+        assert_eq!(back.timestamp.unwrap().offset, 0);
+    }
+
+    #[test]
+    fn test_from_collector_attributes() {
+        let provider = SdkLoggerProvider::builder().build();
+        let mut record = provider.logger("test").create_log_record();
+        record.set_severity_text("warning");
+        record.set_body(AnyValue::Int(7));
+        record.add_attribute("hostname", "host");
+        record.add_attribute("appname", "app");
+        record.add_attribute("proc_id", "8");
+        let empty = Resource::builder_empty().build();
+        let m = SyslogMessage::from_log_record(&record, &empty);
+        assert_eq!(m.severity, SyslogSeverity::SEV_WARNING);
+        assert_eq!(m.hostname.as_deref(), Some("host"));
+        assert_eq!(m.appname.as_deref(), Some("app"));
+        assert_eq!(m.procid, Some(ProcId::PID(8)));
+        assert_eq!(m.msg, "7");
+
+        let resource = Resource::builder_empty()
+            .with_attribute(KeyValue::new("host.name", "resource-host"))
+            .build();
+        let m = SyslogMessage::from_log_record(&record, &resource);
+        assert_eq!(m.hostname.as_deref(), Some("resource-host"));
+    }
+}
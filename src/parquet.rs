@@ -0,0 +1,185 @@
+//! Archiving messages as Apache Parquet files (requires the `parquet` feature).
+//!
+//! `ParquetWriter` is a `Sink` which collects messages into Arrow record batches with an
+//! `arrow::RecordBatchBuilder`, and writes them out as Parquet row groups. The file's schema is
+//! `arrow::schema`, so it can be read back as Arrow by anything which reads Parquet:
+//!
+//! | Column       | Parquet type                                       |
+//! |--------------|----------------------------------------------------|
+//! | `timestamp`  | `INT64 (TIMESTAMP(NANOS, true))`                   |
+//! | `utc_offset` | `INT32`                                            |
+//! | `hostname`   | `BYTE_ARRAY (STRING)`                              |
+//! | `appname`    | `BYTE_ARRAY (STRING)`                              |
+//! | `procid`     | `BYTE_ARRAY (STRING)`                              |
+//! | `msgid`      | `BYTE_ARRAY (STRING)`                              |
+//! | `severity`   | `BYTE_ARRAY (STRING)`, dictionary-encoded          |
+//! | `facility`   | `BYTE_ARRAY (STRING)`, dictionary-encoded          |
+//! | `sd`         | `MAP<STRING, MAP<STRING, STRING>>`                 |
+//! | `msg`        | `BYTE_ARRAY (STRING)`                              |
+//!
+//! Files are compressed with zstd by default; pass your own `WriterProperties` to
+//! `ParquetWriter::with_properties` to change that, or the size of row groups. Messages are
+//! buffered until there's a batch's worth of them (see `with_batch_size`) or `flush` is called,
+//! and the file isn't readable until `close` has written its footer.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::parquet::ParquetWriter;
+//! use syslog_rfc5424::parse_message;
+//! use syslog_rfc5424::sink::Sink;
+//!
+//! let mut writer = ParquetWriter::new(Vec::new()).unwrap();
+//! writer.send(&parse_message("<11>1 2020-01-01T00:00:00Z web1 nginx - - - timed out").unwrap()).unwrap();
+//! let file: Vec<u8> = writer.close().unwrap();
+//! assert_eq!(&file[..4], b"PAR1");
+//! ```
+
+use std::io::{self, Write};
+
+use ::parquet::arrow::ArrowWriter;
+use ::parquet::basic::{Compression, ZstdLevel};
+use ::parquet::errors::ParquetError;
+use ::parquet::file::properties::WriterProperties;
+
+use crate::arrow::{schema, RecordBatchBuilder};
+use crate::message::SyslogMessage;
+use crate::sink::Sink;
+
+/// The number of messages buffered before they're handed to Parquet, by default
+pub const DEFAULT_BATCH_SIZE: usize = 8192;
+
+/// A `Sink` which writes messages to an `io::Write` as a Parquet file
+pub struct ParquetWriter<W: Write + Send> {
+    writer: ArrowWriter<W>,
+    builder: RecordBatchBuilder,
+    batch_size: usize,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    /// Start writing a file to `writer`, compressed with zstd
+    pub fn new(writer: W) -> Result<Self, ParquetError> {
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+        ParquetWriter::with_properties(writer, properties)
+    }
+
+    /// Start writing a file to `writer`, with the given compression, row group size, etc.
+    pub fn with_properties(writer: W, properties: WriterProperties) -> Result<Self, ParquetError> {
+        Ok(ParquetWriter {
+            writer: ArrowWriter::try_new(writer, schema(), Some(properties))?,
+            builder: RecordBatchBuilder::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+        })
+    }
+
+    /// Set how many messages are buffered before they're handed to Parquet (default:
+    /// `DEFAULT_BATCH_SIZE`)
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Write a message; the same as `Sink::send`, but without converting the error
+    pub fn write(&mut self, m: &SyslogMessage) -> Result<(), ParquetError> {
+        self.builder.append(m);
+        if self.builder.len() >= self.batch_size {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<(), ParquetError> {
+        if !self.builder.is_empty() {
+            self.writer.write(&self.builder.finish())?;
+        }
+        Ok(())
+    }
+
+    /// Write out any buffered messages, and end the current row group
+    pub fn flush(&mut self) -> Result<(), ParquetError> {
+        self.write_batch()?;
+        self.writer.flush()
+    }
+
+    /// The number of bytes written to the underlying writer so far
+    pub fn bytes_written(&self) -> usize {
+        self.writer.bytes_written()
+    }
+
+    /// Write out any buffered messages and the file's footer, and return the underlying writer
+    pub fn close(mut self) -> Result<W, ParquetError> {
+        self.write_batch()?;
+        self.writer.into_inner()
+    }
+}
+
+impl<W: Write + Send> Sink for ParquetWriter<W> {
+    fn send(&mut self, m: &SyslogMessage) -> io::Result<()> {
+        self.write(m).map_err(io::Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use ::parquet::file::properties::WriterProperties;
+    use bytes::Bytes;
+
+    use super::ParquetWriter;
+    use crate::arrow::{schema, to_record_batch};
+    use crate::parse_message;
+    use crate::sink::Sink;
+
+    #[test]
+    fn test_round_trip() {
+        let messages = vec![
+            parse_message(
+                r#"<165>1 2003-10-11T22:14:15.003-07:00 mymachine evntslog 12 ID47 [exampleSDID@32473 iut="3"] An application event"#,
+            )
+            .unwrap(),
+            parse_message("<14>1 - - - - - - hi").unwrap(),
+            parse_message(r#"<11>1 - web1 nginx - - [origin ip="10.0.0.1"] timed out"#).unwrap(),
+        ];
+        let mut writer = ParquetWriter::new(Vec::new()).unwrap().with_batch_size(2);
+        for m in &messages {
+            writer.send(m).unwrap();
+        }
+        let file = Bytes::from(writer.close().unwrap());
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].schema(), schema());
+        assert_eq!(batches[0], to_record_batch(&messages));
+    }
+
+    #[test]
+    fn test_row_groups() {
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(2)
+            .build();
+        let mut writer = ParquetWriter::with_properties(Vec::new(), properties).unwrap();
+        let m = parse_message("<14>1 - - - - - - hi").unwrap();
+        for _ in 0..3 {
+            writer.send(&m).unwrap();
+        }
+        writer.flush().unwrap();
+        assert!(writer.bytes_written() > 0);
+        writer.send(&m).unwrap();
+        let file = Bytes::from(writer.close().unwrap());
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        assert_eq!(builder.metadata().num_row_groups(), 3);
+        let rows = builder
+            .build()
+            .unwrap()
+            .map(|batch| batch.unwrap().num_rows())
+            .sum::<usize>();
+        assert_eq!(rows, 4);
+    }
+}
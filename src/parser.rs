@@ -1,47 +1,365 @@
 use std::borrow::Cow;
+use std::cell::OnceCell;
 use std::convert::TryFrom;
+use std::io;
+use std::mem;
 use std::num;
+use std::ops::Range;
 use std::str;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use thiserror::Error;
 
+use crate::borrowed::{ProcIdRef, StructuredDataRef, SyslogMessageRef};
 use crate::facility;
-use crate::message::{ProcId, StructuredData, SyslogMessage};
+use crate::intern::{Cache, InternedMessage, Interner};
+use crate::message::{StructuredData, SyslogMessage};
+use crate::metrics::{MetricsHandle, ParserMetrics};
+use crate::scan;
 use crate::severity;
+use crate::timestamp::Timestamp;
+use crate::validation::{self, Violation};
 
 #[derive(Debug, Error)]
+#[non_exhaustive]
+/// Why a message couldn't be parsed
+///
+/// Most variants name the field which failed, along with the byte offset in the input at which
+/// that field starts and a snippet of the input from there; `ParseErr::kind` says what was wrong
+/// with it. The kind is part of the error's message, so it isn't also returned as its `source`.
 pub enum ParseErr {
-    #[error("regular expression does not parse")]
-    RegexDoesNotMatchErr,
-    #[error("bad severity in message")]
-    BadSeverityInPri,
-    #[error("bad facility in message")]
-    BadFacilityInPri,
-    #[error("unexpected eof")]
+    #[error("invalid PRI at byte {offset} ({found:?}): {kind}")]
+    Pri {
+        offset: usize,
+        found: String,
+        kind: ErrorKind,
+    },
+    #[error("invalid VERSION at byte {offset} ({found:?}): {kind}")]
+    Version {
+        offset: usize,
+        found: String,
+        kind: ErrorKind,
+    },
+    #[error("invalid TIMESTAMP at byte {offset} ({found:?}): {kind}")]
+    Timestamp {
+        offset: usize,
+        found: String,
+        kind: ErrorKind,
+    },
+    #[error("invalid HOSTNAME at byte {offset} ({found:?}): {kind}")]
+    Hostname {
+        offset: usize,
+        found: String,
+        kind: ErrorKind,
+    },
+    #[error("invalid APP-NAME at byte {offset} ({found:?}): {kind}")]
+    AppName {
+        offset: usize,
+        found: String,
+        kind: ErrorKind,
+    },
+    #[error("invalid PROCID at byte {offset} ({found:?}): {kind}")]
+    ProcId {
+        offset: usize,
+        found: String,
+        kind: ErrorKind,
+    },
+    #[error("invalid MSGID at byte {offset} ({found:?}): {kind}")]
+    MsgId {
+        offset: usize,
+        found: String,
+        kind: ErrorKind,
+    },
+    /// Malformed STRUCTURED-DATA, with the SD-ID of the SD-ELEMENT and the name of the SD-PARAM
+    /// which failed, if it got that far
+    #[error("invalid STRUCTURED-DATA{} at byte {offset} ({found:?}): {kind}", sd_location(.element, .param))]
+    Sd {
+        offset: usize,
+        found: String,
+        element: Option<String>,
+        param: Option<String>,
+        kind: ErrorKind,
+    },
+    #[error("invalid MSG at byte {offset}: {kind}")]
+    Msg { offset: usize, kind: ErrorKind },
+    /// The message is over one of the limits in `ParserConfig`
+    #[error("{what} exceeds the configured limit of {limit}")]
+    TooLong { what: &'static str, limit: usize },
+    #[error("message does not conform to RFC 5424 ({} violations)", .0.len())]
+    Nonconformant(Vec<Violation>),
+    #[error("I/O error: {0}")]
+    Io(io::Error),
+}
+
+impl From<io::Error> for ParseErr {
+    fn from(e: io::Error) -> Self {
+        ParseErr::Io(e)
+    }
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+/// What was wrong with the field named by a `ParseErr`
+pub enum ErrorKind {
+    #[error("unexpected end of input")]
     UnexpectedEndOfInput,
-    #[error("too few digits in numeric field")]
+    #[error("expected {0:?}")]
+    ExpectedToken(char),
+    #[error("unexpected character {0:?}")]
+    UnexpectedChar(char),
+    #[error("too few digits")]
     TooFewDigits,
-    #[error("too many digits in numeric field")]
+    #[error("too many digits")]
     TooManyDigits,
-    #[error("invalid UTC offset")]
-    InvalidUTCOffset,
-    #[error("unicode error: {0}")]
-    BaseUnicodeError(#[from] str::Utf8Error),
-    #[error("unicode error: {0}")]
-    UnicodeError(#[from] std::string::FromUtf8Error),
-    #[error("unexpected input at character {0}")]
-    ExpectedTokenErr(char),
-    #[error("integer conversion error: {0}")]
-    IntConversionErr(#[from] num::ParseIntError),
-    #[error("missing field {0}")]
-    MissingField(&'static str),
+    #[error("integer conversion error")]
+    InvalidInteger(#[from] num::ParseIntError),
+    #[error("bad severity")]
+    BadSeverity,
+    #[error("bad facility")]
+    BadFacility,
+    #[error("unsupported protocol version {0}")]
+    UnsupportedVersion(i32),
     #[error("invalid month number {0}")]
     InvalidMonth(u8),
     #[error("date had invalid field {0}")]
     InvalidDate(String),
-    #[error("date had invalid UTC offset")]
+    #[error("invalid UTC offset")]
     InvalidOffset,
+    #[error("NIL where a value is required")]
+    Nil,
+    #[error("invalid escape sequence \\{0} in SD-PARAM value")]
+    InvalidEscape(char),
+    #[error("invalid UTF-8")]
+    InvalidUtf8(#[from] str::Utf8Error),
+    /// Only seen while parsing; this becomes `ParseErr::TooLong`
+    #[error("{0} exceeds the configured limit of {1}")]
+    LimitExceeded(&'static str, usize),
+}
+
+/// How much of the input to include in a `ParseErr`
+const MAX_ERROR_CONTEXT: usize = 32;
+
+/// Describe where in the STRUCTURED-DATA a `ParseErr::Sd` happened
+fn sd_location(element: &Option<String>, param: &Option<String>) -> String {
+    match (element, param) {
+        (Some(element), Some(param)) => format!(" in SD-PARAM {}/{}", element, param),
+        (Some(element), None) => format!(" in SD-ELEMENT {}", element),
+        _ => String::new(),
+    }
+}
+
+/// A failure inside the parser, before we know which field it belongs to
+#[derive(Debug)]
+struct Failure {
+    kind: ErrorKind,
+    /// The SD-ID of the SD-ELEMENT being parsed, if any
+    element: Option<String>,
+    /// The name of the SD-PARAM being parsed, if any
+    param: Option<String>,
+}
+
+impl From<ErrorKind> for Failure {
+    fn from(kind: ErrorKind) -> Self {
+        Failure {
+            kind,
+            element: None,
+            param: None,
+        }
+    }
+}
+
+impl ParseErr {
+    /// Blame `failure` on `field`, which starts at `offset` in `input`
+    fn new(input: &str, field: MessageField, offset: usize, failure: Failure) -> Self {
+        let kind = match failure.kind {
+            ErrorKind::LimitExceeded(what, limit) => return ParseErr::TooLong { what, limit },
+            kind => kind,
+        };
+        let rest = &input[offset..];
+        let mut end = rest.find(' ').unwrap_or(rest.len());
+        if field == MessageField::StructuredData {
+            // SD contains spaces, so show up to the end of the element instead
+            end = rest.find(']').map_or(rest.len(), |i| i + 1);
+        }
+        if end > MAX_ERROR_CONTEXT {
+            end = MAX_ERROR_CONTEXT;
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+        }
+        let found = String::from(&rest[..end]);
+        match field {
+            MessageField::Pri => ParseErr::Pri {
+                offset,
+                found,
+                kind,
+            },
+            MessageField::Version => ParseErr::Version {
+                offset,
+                found,
+                kind,
+            },
+            MessageField::Timestamp => ParseErr::Timestamp {
+                offset,
+                found,
+                kind,
+            },
+            MessageField::Hostname => ParseErr::Hostname {
+                offset,
+                found,
+                kind,
+            },
+            MessageField::AppName => ParseErr::AppName {
+                offset,
+                found,
+                kind,
+            },
+            MessageField::ProcId => ParseErr::ProcId {
+                offset,
+                found,
+                kind,
+            },
+            MessageField::MsgId => ParseErr::MsgId {
+                offset,
+                found,
+                kind,
+            },
+            MessageField::StructuredData => ParseErr::Sd {
+                offset,
+                found,
+                element: failure.element,
+                param: failure.param,
+                kind,
+            },
+            MessageField::Msg => ParseErr::Msg { offset, kind },
+        }
+    }
+
+    /// The field which failed to parse, if the error is about a single field
+    pub fn field(&self) -> Option<MessageField> {
+        Some(match *self {
+            ParseErr::Pri { .. } => MessageField::Pri,
+            ParseErr::Version { .. } => MessageField::Version,
+            ParseErr::Timestamp { .. } => MessageField::Timestamp,
+            ParseErr::Hostname { .. } => MessageField::Hostname,
+            ParseErr::AppName { .. } => MessageField::AppName,
+            ParseErr::ProcId { .. } => MessageField::ProcId,
+            ParseErr::MsgId { .. } => MessageField::MsgId,
+            ParseErr::Sd { .. } => MessageField::StructuredData,
+            ParseErr::Msg { .. } => MessageField::Msg,
+            _ => return None,
+        })
+    }
+
+    /// The byte offset into the input of the field which failed to parse, if known
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            ParseErr::Pri { offset, .. }
+            | ParseErr::Version { offset, .. }
+            | ParseErr::Timestamp { offset, .. }
+            | ParseErr::Hostname { offset, .. }
+            | ParseErr::AppName { offset, .. }
+            | ParseErr::ProcId { offset, .. }
+            | ParseErr::MsgId { offset, .. }
+            | ParseErr::Sd { offset, .. }
+            | ParseErr::Msg { offset, .. } => Some(offset),
+            _ => None,
+        }
+    }
+
+    /// The input from the start of the failed field, cut off at the end of it (or after 32 bytes)
+    pub fn found(&self) -> Option<&str> {
+        match *self {
+            ParseErr::Pri { ref found, .. }
+            | ParseErr::Version { ref found, .. }
+            | ParseErr::Timestamp { ref found, .. }
+            | ParseErr::Hostname { ref found, .. }
+            | ParseErr::AppName { ref found, .. }
+            | ParseErr::ProcId { ref found, .. }
+            | ParseErr::MsgId { ref found, .. }
+            | ParseErr::Sd { ref found, .. } => Some(found),
+            _ => None,
+        }
+    }
+
+    /// The byte range of the input covered by `found`, or just `offset` if there's no `found`
+    pub fn span(&self) -> Option<Range<usize>> {
+        let offset = self.offset()?;
+        Some(offset..offset + self.found().map_or(0, str::len))
+    }
+
+    /// What was wrong with the field which failed to parse
+    pub fn kind(&self) -> Option<&ErrorKind> {
+        match *self {
+            ParseErr::Pri { ref kind, .. }
+            | ParseErr::Version { ref kind, .. }
+            | ParseErr::Timestamp { ref kind, .. }
+            | ParseErr::Hostname { ref kind, .. }
+            | ParseErr::AppName { ref kind, .. }
+            | ParseErr::ProcId { ref kind, .. }
+            | ParseErr::MsgId { ref kind, .. }
+            | ParseErr::Sd { ref kind, .. }
+            | ParseErr::Msg { ref kind, .. } => Some(kind),
+            _ => None,
+        }
+    }
+
+    /// What sort of problem this is, for counting errors; see `metrics`
+    pub fn class(&self) -> ErrorClass {
+        match *self {
+            ParseErr::Pri { .. } => ErrorClass::Pri,
+            ParseErr::Version { .. } => ErrorClass::Version,
+            ParseErr::Timestamp { .. } => ErrorClass::Timestamp,
+            ParseErr::Hostname { .. }
+            | ParseErr::AppName { .. }
+            | ParseErr::ProcId { .. }
+            | ParseErr::MsgId { .. } => ErrorClass::Header,
+            ParseErr::Sd { .. } => ErrorClass::Sd,
+            ParseErr::Msg { .. } => ErrorClass::Msg,
+            ParseErr::TooLong { .. } => ErrorClass::Limit,
+            ParseErr::Nonconformant(_) => ErrorClass::Nonconformant,
+            ParseErr::Io(_) => ErrorClass::Io,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// A broad category of `ParseErr`, by where the problem was
+pub enum ErrorClass {
+    /// The PRI is malformed, or its severity or facility is out of range
+    Pri,
+    /// The VERSION is malformed, or above `ParserConfig::with_max_version`
+    Version,
+    /// The TIMESTAMP isn't a valid date, time or UTC offset
+    Timestamp,
+    /// HOSTNAME, APP-NAME, PROCID or MSGID is malformed
+    Header,
+    /// The STRUCTURED-DATA is malformed
+    Sd,
+    /// MSG isn't valid UTF-8
+    Msg,
+    /// The message is over one of the limits in `ParserConfig`
+    Limit,
+    /// The message parsed, but `ParserConfig::with_strict` rejected it
+    Nonconformant,
+    /// Reading the input failed
+    Io,
+}
+
+impl ErrorClass {
+    /// Every class, in order
+    pub const ALL: [ErrorClass; 9] = [
+        ErrorClass::Pri,
+        ErrorClass::Version,
+        ErrorClass::Timestamp,
+        ErrorClass::Header,
+        ErrorClass::Sd,
+        ErrorClass::Msg,
+        ErrorClass::Limit,
+        ErrorClass::Nonconformant,
+        ErrorClass::Io,
+    ];
 }
 
 // We parse with this super-duper-dinky hand-coded recursive descent parser because we don't really
@@ -76,15 +394,342 @@ macro_rules! take_item {
 
 type ParseResult<T> = Result<T, ParseErr>;
 
+/// The result of parsing part of a field
+type FieldResult<T> = Result<T, ErrorKind>;
+
+/// The result of parsing a run of fields, which may need context about the SD
+type InnerResult<T> = Result<T, Failure>;
+
+/// The byte order mark which RFC 5424 uses to flag a UTF-8 MSG
+pub const BOM: char = '\u{feff}';
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+/// What to do with a timestamp which falls on a leap second, like `2016-12-31T23:59:60Z`
+///
+/// Whatever the policy, messages parsed from such a timestamp have `leap_second` set.
+pub enum LeapSecondPolicy {
+    /// Fail with `ErrorKind::InvalidDate`
+    Reject,
+    /// Represent the leap second as the last possible instant of the preceding second
+    /// (`23:59:59.999999999`)
+    #[default]
+    Clamp,
+    /// Represent the leap second as the first second of the following minute (`00:00:00`),
+    /// keeping any fractional part
+    Rollover,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+/// Device-specific deviations from RFC 5424 which the parser should put up with
+///
+/// Each profile turns on the handful of workarounds needed for messages from that sender:
+///
+/// | Profile    | Tolerates |
+/// |------------|-----------|
+/// | `Rsyslog`  | missing STRUCTURED-DATA, trailing newline |
+/// | `SyslogNg` | missing STRUCTURED-DATA, trailing newline |
+/// | `CiscoIos` | sequence numbers (`123: `) before or after the PRI, `*` or `.` clock-sync markers before the timestamp, and everything `lenient` does |
+/// | `Fortinet` | missing STRUCTURED-DATA, and everything `lenient` does |
+/// | `Nxlog`    | whitespace before the PRI, missing STRUCTURED-DATA, trailing newline |
+///
+/// When STRUCTURED-DATA is missing, MSG follows right after MSGID and `sd` is left empty.
+pub enum Quirks {
+    /// No quirks; parse according to the RFC (and `ParserConfig::with_lenient`)
+    #[default]
+    None,
+    Rsyslog,
+    SyslogNg,
+    CiscoIos,
+    Fortinet,
+    Nxlog,
+}
+
+impl Quirks {
+    fn lenient(self) -> bool {
+        matches!(self, Quirks::CiscoIos | Quirks::Fortinet)
+    }
+
+    fn optional_sd(self) -> bool {
+        matches!(
+            self,
+            Quirks::Rsyslog | Quirks::SyslogNg | Quirks::Fortinet | Quirks::Nxlog
+        )
+    }
+
+    fn trailing_newline(self) -> bool {
+        matches!(self, Quirks::Rsyslog | Quirks::SyslogNg | Quirks::Nxlog)
+    }
+
+    fn sequence_numbers(self) -> bool {
+        self == Quirks::CiscoIos
+    }
+
+    fn leading_whitespace(self) -> bool {
+        self == Quirks::Nxlog
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+/// What to do with characters outside of printable ASCII in HOSTNAME, APP-NAME or MSGID
+///
+/// RFC 5424 only allows PRINTUSASCII in these fields, but some senders put Latin-1 or UTF-8 into
+/// them; see `ParserConfig::with_field_utf8_policy`.
+pub enum FieldUtf8Policy {
+    /// Fail the message, as RFC 5424 says to
+    #[default]
+    Reject,
+    /// Keep non-ASCII characters, replacing any invalid UTF-8 with U+FFFD REPLACEMENT CHARACTER
+    Replace,
+    /// Percent-encode every byte outside of PRINTUSASCII, so that `caf\xe9` becomes `caf%E9` and
+    /// the field stays ASCII
+    PercentEncode,
+}
+
+#[derive(Clone, Debug)]
+/// Knobs controlling how the parser treats its input
+///
+/// The default configuration parses strictly according to RFC 5424.
+///
+/// # Example
+///
+/// ```
+/// use syslog_rfc5424::parser::{parse_message_with, ParserConfig};
+///
+/// let config = ParserConfig::new().with_lenient(true);
+/// let message = parse_message_with(&config, "<34>Oct 11 22:14:15 mymachine su - - - hi").unwrap();
+/// assert_eq!(message.hostname, Some(String::from("mymachine")));
+/// ```
+pub struct ParserConfig {
+    lenient: bool,
+    pub(crate) strict: bool,
+    leap_second_policy: LeapSecondPolicy,
+    strip_bom: bool,
+    quirks: Quirks,
+    max_version: Option<i32>,
+    pub(crate) preserve_sd_order: bool,
+    strict_sd_escapes: bool,
+    unknown_facility: Option<facility::SyslogFacility>,
+    max_message_length: Option<usize>,
+    max_sd_elements: Option<usize>,
+    max_sd_params: Option<usize>,
+    max_sd_param_value_length: Option<usize>,
+    lazy_sd: bool,
+    metrics: Option<MetricsHandle>,
+    hostname_utf8: FieldUtf8Policy,
+    appname_utf8: FieldUtf8Policy,
+    msgid_utf8: FieldUtf8Policy,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        ParserConfig {
+            lenient: false,
+            strict: false,
+            leap_second_policy: LeapSecondPolicy::default(),
+            strip_bom: true,
+            quirks: Quirks::None,
+            max_version: None,
+            preserve_sd_order: false,
+            strict_sd_escapes: false,
+            unknown_facility: None,
+            max_message_length: None,
+            max_sd_elements: None,
+            max_sd_params: None,
+            max_sd_param_value_length: None,
+            lazy_sd: true,
+            metrics: None,
+            hostname_utf8: FieldUtf8Policy::Reject,
+            appname_utf8: FieldUtf8Policy::Reject,
+            msgid_utf8: FieldUtf8Policy::Reject,
+        }
+    }
+}
+
+impl ParserConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tolerate common real-world deviations from RFC 5424
+    ///
+    /// Currently, this means:
+    ///
+    ///  * A missing VERSION is assumed to be 1
+    ///  * RFC 3164-style timestamps (`Oct 11 22:14:15`) are accepted and assumed to be UTC (so
+    ///    their `offset` will be zero) and within the last year
+    ///  * The space after VERSION or TIMESTAMP may be omitted
+    ///  * Lowercase `t` and `z` are accepted in RFC 3339 timestamps
+    ///  * A comma may be used instead of a period before the fractional seconds of a timestamp
+    ///  * STRUCTURED-DATA which is cut off by the end of the input is accepted (keeping every
+    ///    complete SD-PARAM), and the message is marked as `truncated`
+    pub fn with_lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Reject messages which parse, but which don't conform to the field rules in RFC 5424
+    ///
+    /// See `validation::validate` for what's checked; the violations are returned in
+    /// `ParseErr::Nonconformant`.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Choose how timestamps falling on a leap second are represented (default: `Clamp`)
+    pub fn with_leap_second_policy(mut self, leap_second_policy: LeapSecondPolicy) -> Self {
+        self.leap_second_policy = leap_second_policy;
+        self
+    }
+
+    /// Whether to remove the BOM from the start of MSG (default: true)
+    ///
+    /// Either way, its presence is recorded in `msg_is_utf8`.
+    pub fn with_strip_bom(mut self, strip_bom: bool) -> Self {
+        self.strip_bom = strip_bom;
+        self
+    }
+
+    /// Put up with the deviations from RFC 5424 made by a particular kind of sender
+    ///
+    /// See `Quirks` for what each profile allows.
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Reject messages whose VERSION is greater than this (default: no limit)
+    ///
+    /// Whatever the VERSION, the rest of the message is parsed as if it were version 1, which is
+    /// the only version defined so far; check `SyslogMessage::version` if you need to treat later
+    /// versions differently. Rejected messages fail with `ErrorKind::UnsupportedVersion`.
+    pub fn with_max_version(mut self, max_version: Option<i32>) -> Self {
+        self.max_version = max_version;
+        self
+    }
+
+    /// Have `SyslogMessage::sd` remember the order of SD-ELEMENTs and SD-PARAMs (default: false)
+    ///
+    /// See `StructuredData::iter_ordered`; serializing the message then keeps the original order.
+    /// This only affects owned messages, since `SyslogMessageRef` always keeps the input order.
+    pub fn with_preserve_sd_order(mut self, preserve_sd_order: bool) -> Self {
+        self.preserve_sd_order = preserve_sd_order;
+        self
+    }
+
+    /// Reject SD-PARAM values with a backslash before anything but `"`, `\` or `]`
+    ///
+    /// By default, such a backslash is kept as part of the value, as RFC 5424 says it should be.
+    /// Rejected messages fail with `ErrorKind::InvalidEscape`.
+    pub fn with_strict_sd_escapes(mut self, strict_sd_escapes: bool) -> Self {
+        self.strict_sd_escapes = strict_sd_escapes;
+        self
+    }
+
+    /// Map facility numbers above 23 (PRI values above 191) to this facility (default: `None`)
+    ///
+    /// Some older devices use facility numbers which RFC 5424 doesn't define; by default, these
+    /// messages fail with `ErrorKind::BadFacility`. The severity is kept either way.
+    pub fn with_unknown_facility(mut self, facility: Option<facility::SyslogFacility>) -> Self {
+        self.unknown_facility = facility;
+        self
+    }
+
+    /// Reject messages longer than this many bytes (default: no limit)
+    ///
+    /// This and the other limits protect receivers from untrusted senders; messages over a limit
+    /// fail with `ParseErr::TooLong`.
+    pub fn with_max_message_length(mut self, max_message_length: Option<usize>) -> Self {
+        self.max_message_length = max_message_length;
+        self
+    }
+
+    /// Reject messages with more than this many SD-ELEMENTs (default: no limit)
+    pub fn with_max_sd_elements(mut self, max_sd_elements: Option<usize>) -> Self {
+        self.max_sd_elements = max_sd_elements;
+        self
+    }
+
+    /// Reject messages with more than this many SD-PARAMs in any one SD-ELEMENT (default: no
+    /// limit)
+    pub fn with_max_sd_params(mut self, max_sd_params: Option<usize>) -> Self {
+        self.max_sd_params = max_sd_params;
+        self
+    }
+
+    /// Reject messages with an SD-PARAM value longer than this many bytes, once unescaped
+    /// (default: no limit)
+    pub fn with_max_sd_param_value_length(mut self, max_length: Option<usize>) -> Self {
+        self.max_sd_param_value_length = max_length;
+        self
+    }
+
+    /// Whether `parse_message_lazy` leaves STRUCTURED-DATA unparsed until it's first asked for
+    /// (default: true)
+    ///
+    /// With this off, the SD is parsed straight away, so malformed SD fails the parse as it would
+    /// in `parse_message_with`.
+    pub fn with_lazy_sd(mut self, lazy_sd: bool) -> Self {
+        self.lazy_sd = lazy_sd;
+        self
+    }
+
+    /// Report every message parsed with this config to `metrics` (default: none)
+    ///
+    /// See the `metrics` module for which functions do this.
+    pub fn with_metrics(mut self, metrics: Arc<dyn ParserMetrics>) -> Self {
+        self.metrics = Some(MetricsHandle(metrics));
+        self
+    }
+
+    /// What to do with non-ASCII characters in one of HOSTNAME, APP-NAME or MSGID (default:
+    /// `Reject`)
+    ///
+    /// This lets a stray high byte in a HOSTNAME through without losing the whole message. It
+    /// applies to `parse_message_bytes` as well as to `str`s, whatever its `Utf8Policy`. Other
+    /// fields are always strict, and are ignored here. `parse_header` returns the fields as they
+    /// appear in the input, without any percent-encoding.
+    pub fn with_field_utf8_policy(mut self, field: MessageField, policy: FieldUtf8Policy) -> Self {
+        match field {
+            MessageField::Hostname => self.hostname_utf8 = policy,
+            MessageField::AppName => self.appname_utf8 = policy,
+            MessageField::MsgId => self.msgid_utf8 = policy,
+            _ => {}
+        }
+        self
+    }
+
+    fn field_utf8_policy(&self, field: MessageField) -> FieldUtf8Policy {
+        match field {
+            MessageField::Hostname => self.hostname_utf8,
+            MessageField::AppName => self.appname_utf8,
+            MessageField::MsgId => self.msgid_utf8,
+            _ => FieldUtf8Policy::Reject,
+        }
+    }
+
+    fn is_lenient(&self) -> bool {
+        self.lenient || self.quirks.lenient()
+    }
+
+    /// Pass the outcome of parsing `len` bytes on to the metrics, if there are any
+    pub(crate) fn observe<T>(&self, len: usize, result: ParseResult<T>) -> ParseResult<T> {
+        if let Some(MetricsHandle(ref metrics)) = self.metrics {
+            metrics.record(len, result.as_ref().map(|_| ()));
+        }
+        result
+    }
+}
+
 macro_rules! take_char {
     ($e: expr, $c:expr) => {{
         $e = match $e.chars().next() {
             Some($c) => &$e[1..],
             Some(_) => {
-                return Err(ParseErr::ExpectedTokenErr($c));
+                return Err(ErrorKind::ExpectedToken($c).into());
             }
             None => {
-                return Err(ParseErr::UnexpectedEndOfInput);
+                return Err(ErrorKind::UnexpectedEndOfInput.into());
             }
         }
     }};
@@ -105,70 +750,110 @@ where
     ("", None)
 }
 
-fn parse_sd_id(input: &str) -> ParseResult<(String, &str)> {
-    let (res, rest) = take_while(input, |c| c != ' ' && c != '=' && c != ']', 128);
-    Ok((
-        String::from(res),
-        match rest {
-            Some(s) => s,
-            None => return Err(ParseErr::UnexpectedEndOfInput),
-        },
-    ))
+fn parse_sd_id(input: &str) -> FieldResult<(&str, &str)> {
+    const MAX_LENGTH: usize = 128;
+    let idx = match memchr::memchr3(b' ', b'=', b']', input.as_bytes()) {
+        Some(idx) if idx <= MAX_LENGTH => idx,
+        _ if input.len() > MAX_LENGTH => {
+            // too long; cut it off, and let the caller fail on whatever comes next
+            (0..=MAX_LENGTH)
+                .rev()
+                .find(|i| input.is_char_boundary(*i))
+                .unwrap_or(0)
+        }
+        _ => return Err(ErrorKind::UnexpectedEndOfInput),
+    };
+    Ok((&input[..idx], &input[idx..]))
 }
 
 /** Parse a `param_value`... a.k.a. a quoted string */
-fn parse_param_value(input: &str) -> ParseResult<(Cow<str>, &str)> {
+fn parse_param_value(input: &str, strict_escapes: bool) -> FieldResult<(Cow<str>, &str)> {
     let mut rest = input;
     take_char!(rest, '"');
     // Can't do a 0-copy &str slice here because we need to un-escape escaped quotes
     // in the string. :-(
     let mut result = String::new();
-
     let mut saw_any_escapes = false;
-    let mut escaped = false;
+    // everything before `pos` has been copied into `result`, if there were any escapes
+    let mut pos = 0;
 
-    for (idx, chr) in rest.char_indices() {
-        if escaped {
-            escaped = false
-        } else {
-            if chr == '\\' {
-                escaped = true;
-                if !saw_any_escapes {
-                    result.push_str(&rest[..idx]);
-                }
-                saw_any_escapes = true;
-                continue;
+    while let Some(found) = memchr::memchr2(b'"', b'\\', &rest.as_bytes()[pos..]) {
+        let idx = pos + found;
+        if rest.as_bytes()[idx] == b'"' {
+            let res_cow = if saw_any_escapes {
+                result.push_str(&rest[pos..idx]);
+                Cow::Owned(result)
+            } else {
+                Cow::Borrowed(&rest[..idx])
+            };
+            return Ok((res_cow, &rest[(idx + 1)..]));
+        }
+        saw_any_escapes = true;
+        result.push_str(&rest[pos..idx]);
+        match rest[idx + 1..].chars().next() {
+            Some(chr @ ('"' | '\\' | ']')) => {
+                result.push(chr);
+                pos = idx + 2;
             }
-            if chr == '"' {
-                let res_cow = if saw_any_escapes {
-                    Cow::Owned(result)
-                } else {
-                    Cow::Borrowed(&rest[..idx])
-                };
-                return Ok((res_cow, &rest[(idx + 1)..]));
+            // RFC 5424 §6.3.3: a backslash before anything else is just a backslash
+            Some(chr) if strict_escapes => return Err(ErrorKind::InvalidEscape(chr)),
+            Some(_) => {
+                result.push('\\');
+                pos = idx + 1;
             }
-        }
-        if saw_any_escapes {
-            result.push(chr);
+            None => break,
         }
     }
 
-    Err(ParseErr::UnexpectedEndOfInput)
+    Err(ErrorKind::UnexpectedEndOfInput)
+}
+
+/// Fail with `ErrorKind::LimitExceeded` if `value` is over `limit`
+fn check_limit(what: &'static str, value: usize, limit: Option<usize>) -> FieldResult<()> {
+    match limit {
+        Some(limit) if value > limit => Err(ErrorKind::LimitExceeded(what, limit)),
+        _ => Ok(()),
+    }
 }
 
-type ParsedSDParams = Vec<(String, String)>;
+type ParsedSDParams<'a> = Vec<(&'a str, Cow<'a, str>)>;
+
+/// Parse the `="value"` of an SD-PARAM
+fn parse_sd_param_value<'a>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> FieldResult<(Cow<'a, str>, &'a str)> {
+    let mut rest = input;
+    take_char!(rest, '=');
+    let value = take_item!(parse_param_value(rest, config.strict_sd_escapes), rest);
+    check_limit(
+        "SD-PARAM value length",
+        value.len(),
+        config.max_sd_param_value_length,
+    )?;
+    Ok((value, rest))
+}
 
-fn parse_sd_params(input: &str) -> ParseResult<(ParsedSDParams, &str)> {
+fn parse_sd_params<'a>(
+    input: &'a str,
+    config: &ParserConfig,
+) -> InnerResult<(ParsedSDParams<'a>, &'a str)> {
     let mut params = Vec::new();
     let mut top = input;
     loop {
         if let Some(rest2) = maybe_expect_char!(top, ' ') {
+            check_limit("SD-PARAM count", params.len() + 1, config.max_sd_params)?;
             let mut rest = rest2;
             let param_name = take_item!(parse_sd_id(rest), rest);
-            take_char!(rest, '=');
-            let param_value = take_item!(parse_param_value(rest), rest);
-            // is there an uglier modifier than &*
-            params.push((param_name, String::from(&*param_value)));
+            let param_value = take_item!(
+                parse_sd_param_value(rest, config).map_err(|kind| Failure {
+                    kind,
+                    element: None,
+                    param: Some(param_name.to_string()),
+                }),
+                rest
+            );
+            params.push((param_name, param_value));
             top = rest;
         } else {
             return Ok((params, top));
@@ -176,111 +861,330 @@ fn parse_sd_params(input: &str) -> ParseResult<(ParsedSDParams, &str)> {
     }
 }
 
-fn parse_sde(sde: &str) -> ParseResult<((String, ParsedSDParams), &str)> {
+fn parse_sde<'a>(
+    sde: &'a str,
+    config: &ParserConfig,
+) -> InnerResult<((&'a str, ParsedSDParams<'a>), &'a str)> {
     let mut rest = sde;
     take_char!(rest, '[');
     let id = take_item!(parse_sd_id(rest), rest);
-    let params = take_item!(parse_sd_params(rest), rest);
-    take_char!(rest, ']');
-    Ok(((id, params), rest))
+    let body = parse_sd_params(rest, config).and_then(|(params, mut rest)| {
+        take_char!(rest, ']');
+        Ok((params, rest))
+    });
+    match body {
+        Ok((params, rest)) => Ok(((id, params), rest)),
+        Err(failure) => Err(Failure {
+            element: Some(id.to_string()),
+            ..failure
+        }),
+    }
+}
+
+/// The SD-ID and complete SD-PARAMs of an SD-ELEMENT which was cut off by the end of the input
+fn parse_truncated_sde<'a>(
+    sde: &'a str,
+    config: &ParserConfig,
+) -> Option<(&'a str, ParsedSDParams<'a>)> {
+    let (id, mut rest) = parse_sd_id(sde.strip_prefix('[')?).ok()?;
+    let mut params = Vec::new();
+    while let Some(r) = rest.strip_prefix(' ') {
+        let param = parse_sd_id(r).and_then(|(name, r)| {
+            let r = r.strip_prefix('=').ok_or(ErrorKind::UnexpectedEndOfInput)?;
+            let (value, r) = parse_param_value(r, config.strict_sd_escapes)?;
+            Ok(((name, value), r))
+        });
+        match param {
+            Ok((param, r)) => {
+                params.push(param);
+                rest = r;
+            }
+            Err(_) => break,
+        }
+    }
+    Some((id, params))
 }
 
-fn parse_sd(structured_data_raw: &str) -> ParseResult<(StructuredData, &str)> {
-    let mut sd = StructuredData::new_empty();
+/// Parse STRUCTURED-DATA, and whether it was cut off by the end of the input
+///
+/// Truncated SD is only accepted in lenient mode; the complete SD-PARAMs of the last SD-ELEMENT
+/// are kept.
+fn parse_sd<'a>(
+    structured_data_raw: &'a str,
+    config: &ParserConfig,
+) -> InnerResult<((StructuredDataRef<'a>, bool), &'a str)> {
+    let mut sd = StructuredDataRef::new_empty();
     if let Some(rest) = structured_data_raw.strip_prefix('-') {
-        return Ok((sd, rest));
+        return Ok(((sd, false), rest));
     }
     let mut rest = structured_data_raw;
     while !rest.is_empty() {
-        let (sd_id, params) = take_item!(parse_sde(rest), rest);
-        let sub_map = sd.entry(sd_id.clone());
-        for (sd_param_id, sd_param_value) in params {
-            sub_map.insert(sd_param_id, sd_param_value);
+        check_limit("SD-ELEMENT count", sd.len() + 1, config.max_sd_elements)?;
+        match parse_sde(rest, config) {
+            Ok(((sd_id, params), r)) => {
+                sd.push(sd_id, params);
+                rest = r;
+            }
+            Err(Failure {
+                kind: ErrorKind::UnexpectedEndOfInput,
+                ..
+            }) if config.is_lenient() => {
+                if let Some((sd_id, params)) = parse_truncated_sde(rest, config) {
+                    sd.push(sd_id, params);
+                }
+                return Ok(((sd, true), ""));
+            }
+            Err(e) => return Err(e),
         }
         if rest.starts_with(' ') {
             break;
         }
     }
-    Ok((sd, rest))
+    Ok(((sd, false), rest))
 }
 
-fn parse_pri_val(pri: i32) -> ParseResult<(severity::SyslogSeverity, facility::SyslogFacility)> {
-    let sev = severity::SyslogSeverity::from_int(pri & 0x7).ok_or(ParseErr::BadSeverityInPri)?;
-    let fac = facility::SyslogFacility::from_int(pri >> 3).ok_or(ParseErr::BadFacilityInPri)?;
+fn parse_pri_val(
+    pri: i32,
+    config: &ParserConfig,
+) -> FieldResult<(severity::SyslogSeverity, facility::SyslogFacility)> {
+    let sev = severity::SyslogSeverity::from_int(pri & 0x7).ok_or(ErrorKind::BadSeverity)?;
+    let fac = facility::SyslogFacility::from_int(pri >> 3)
+        .or(config.unknown_facility)
+        .ok_or(ErrorKind::BadFacility)?;
     Ok((sev, fac))
 }
 
 /// Parse an i32
-fn parse_num(s: &str, min_digits: usize, max_digits: usize) -> ParseResult<(i32, &str)> {
+fn parse_num(s: &str, min_digits: usize, max_digits: usize) -> FieldResult<(i32, &str)> {
     let (res, rest1) = take_while(s, |c| ('0'..='9').contains(&c), max_digits);
-    let rest = rest1.ok_or(ParseErr::UnexpectedEndOfInput)?;
+    let rest = rest1.ok_or(ErrorKind::UnexpectedEndOfInput)?;
     if res.len() < min_digits {
-        Err(ParseErr::TooFewDigits)
+        Err(ErrorKind::TooFewDigits)
     } else if res.len() > max_digits {
-        Err(ParseErr::TooManyDigits)
+        Err(ErrorKind::TooManyDigits)
     } else {
-        Ok((
-            i32::from_str(res).map_err(ParseErr::IntConversionErr)?,
-            rest,
-        ))
+        Ok((i32::from_str(res)?, rest))
     }
 }
 
 /// Parse an i32
-fn parse_num_generic<NT>(s: &str, min_digits: usize, max_digits: usize) -> ParseResult<(NT, &str)>
+fn parse_num_generic<NT>(s: &str, min_digits: usize, max_digits: usize) -> FieldResult<(NT, &str)>
 where
     NT: FromStr<Err = num::ParseIntError>,
 {
     let (res, rest1) = take_while(s, |c| ('0'..='9').contains(&c), max_digits);
-    let rest = rest1.ok_or(ParseErr::UnexpectedEndOfInput)?;
+    let rest = rest1.ok_or(ErrorKind::UnexpectedEndOfInput)?;
     if res.len() < min_digits {
-        Err(ParseErr::TooFewDigits)
+        Err(ErrorKind::TooFewDigits)
     } else if res.len() > max_digits {
-        Err(ParseErr::TooManyDigits)
+        Err(ErrorKind::TooManyDigits)
     } else {
-        Ok((NT::from_str(res).map_err(ParseErr::IntConversionErr)?, rest))
+        Ok((NT::from_str(res)?, rest))
+    }
+}
+
+/// Parse TIME-SECFRAC into nanoseconds
+///
+/// RFC 5424 only allows 6 digits, but plenty of senders emit more. We keep up to 9 and report
+/// whether there were any more than that which had to be dropped.
+fn parse_secfrac(d: &str) -> FieldResult<((u32, bool), &str)> {
+    let digits = d.bytes().take_while(u8::is_ascii_digit).count();
+    if digits == 0 {
+        return Err(ErrorKind::TooFewDigits);
     }
+    let mut nanos = 0;
+    for b in d[..digits.min(9)].bytes() {
+        nanos = nanos * 10 + u32::from(b - b'0');
+    }
+    for _ in digits..9 {
+        nanos *= 10;
+    }
+    Ok(((nanos, digits > 9), &d[digits..]))
 }
 
-fn parse_decimal(d: &str, min_digits: usize, max_digits: usize) -> ParseResult<(i32, &str)> {
-    parse_num(d, min_digits, max_digits).map(|(val, s)| {
-        let mut multiplicand = 1;
-        let z = 10 - (d.len() - s.len());
+/// A parsed TIMESTAMP
+struct EventTime {
+    dt: time::OffsetDateTime,
+    precision_lost: bool,
+    leap_second: bool,
+}
 
-        for _i in 1..(z) {
-            multiplicand *= 10;
+/// Build the TIME part of a timestamp, returning whether it was a leap second
+///
+/// Leap seconds are represented as the preceding second; it's up to the caller to apply
+/// `LeapSecondPolicy::Rollover` once it has a full datetime to add a second to.
+fn make_time(
+    hour: u8,
+    minute: u8,
+    second: u8,
+    nano: u32,
+    config: &ParserConfig,
+) -> FieldResult<(time::Time, bool)> {
+    let leap_second = second == 60;
+    let (second, nano) = match (leap_second, config.leap_second_policy) {
+        (false, _) => (second, nano),
+        (true, LeapSecondPolicy::Reject) => {
+            return Err(ErrorKind::InvalidDate(String::from("second")))
         }
-        (val * multiplicand, s)
-    })
+        (true, LeapSecondPolicy::Clamp) => (59, 999_999_999),
+        (true, LeapSecondPolicy::Rollover) => (59, nano),
+    };
+    let time = time::Time::from_hms_nano(hour, minute, second, nano)
+        .map_err(|e| ErrorKind::InvalidDate(e.name().to_string()))?;
+    Ok((time, leap_second))
+}
+
+/// Check that a leap second falls at the end of a UTC day, and apply the leap second policy
+fn apply_leap_second(
+    dt: time::OffsetDateTime,
+    config: &ParserConfig,
+) -> FieldResult<time::OffsetDateTime> {
+    let utc = dt.to_offset(time::UtcOffset::UTC);
+    if (utc.hour(), utc.minute()) != (23, 59) {
+        return Err(ErrorKind::InvalidDate(String::from("second")));
+    }
+    if config.leap_second_policy == LeapSecondPolicy::Rollover {
+        Ok(dt + time::Duration::SECOND)
+    } else {
+        Ok(dt)
+    }
+}
+
+/// Consume the SP between two header fields
+///
+/// Only use this where the end of the previous field is unambiguous, since in lenient mode the
+/// space is optional.
+fn take_sp<'a>(rest: &'a str, config: &ParserConfig) -> FieldResult<&'a str> {
+    match rest.chars().next() {
+        Some(' ') => Ok(&rest[1..]),
+        Some(_) if config.is_lenient() => Ok(rest),
+        Some(_) => Err(ErrorKind::ExpectedToken(' ')),
+        None => Err(ErrorKind::UnexpectedEndOfInput),
+    }
+}
+
+fn parse_version<'a>(m: &'a str, config: &ParserConfig) -> FieldResult<(i32, &'a str)> {
+    let (version, rest) = if config.is_lenient() {
+        let digits = m.bytes().take_while(u8::is_ascii_digit).count();
+        let len = if digits <= 2 && m[digits..].starts_with(' ') {
+            digits
+        } else {
+            // VERSION run straight into the timestamp, as in `1-` or `12003-10-11T...`
+            (1..=digits.min(2))
+                .find(|&len| starts_timestamp(&m[len..]))
+                .unwrap_or(0)
+        };
+        if len == 0 {
+            // no VERSION at all; the timestamp follows right after the PRI
+            return Ok((1, m.strip_prefix(' ').unwrap_or(m)));
+        }
+        parse_num(m, len, len)?
+    } else {
+        parse_num(m, 1, 2)?
+    };
+    match config.max_version {
+        Some(max_version) if version > max_version => Err(ErrorKind::UnsupportedVersion(version)),
+        _ => Ok((version, rest)),
+    }
 }
 
-fn parse_timestamp(m: &str) -> ParseResult<(Option<time::OffsetDateTime>, &str)> {
+/// Whether `s` starts with a NIL or RFC 3339 timestamp (as far as its year)
+fn starts_timestamp(s: &str) -> bool {
+    let b = s.as_bytes();
+    s.starts_with('-') || (b.len() > 4 && b[..4].iter().all(u8::is_ascii_digit) && b[4] == b'-')
+}
+
+const RFC3164_MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parse a BSD-style `Mmm dd hh:mm:ss` timestamp
+///
+/// These don't have a year or timezone, so we assume UTC and pick the year which puts the
+/// timestamp at most a day in the future.
+fn parse_rfc3164_timestamp<'a>(
+    m: &'a str,
+    config: &ParserConfig,
+) -> FieldResult<(Option<EventTime>, &'a str)> {
+    let month_idx = RFC3164_MONTHS
+        .iter()
+        .position(|name| m.starts_with(name))
+        .ok_or(ErrorKind::InvalidDate(String::from("month")))?;
+    // safe to unwrap: there are only 12 entries in RFC3164_MONTHS
+    let month = time::Month::try_from(month_idx as u8 + 1).unwrap();
+    let mut rest = &m[3..];
+    take_char!(rest, ' ');
+    if rest.starts_with(' ') {
+        rest = &rest[1..];
+    }
+    let mday = take_item!(parse_num_generic(rest, 1, 2), rest);
+    take_char!(rest, ' ');
+    let hour = take_item!(parse_num_generic(rest, 2, 2), rest);
+    take_char!(rest, ':');
+    let minute = take_item!(parse_num_generic(rest, 2, 2), rest);
+    take_char!(rest, ':');
+    let second = take_item!(parse_num_generic(rest, 2, 2), rest);
+    let (time, leap_second) = make_time(hour, minute, second, 0, config)?;
+    let in_year = |year| {
+        time::Date::from_calendar_date(year, month, mday)
+            .map(|date| time::PrimitiveDateTime::new(date, time).assume_utc())
+            .map_err(|e| ErrorKind::InvalidDate(e.name().to_string()))
+    };
+    let now = time::OffsetDateTime::now_utc();
+    let mut dt = in_year(now.year())?;
+    if dt > now + time::Duration::DAY {
+        dt = in_year(now.year() - 1)?;
+    }
+    if leap_second {
+        dt = apply_leap_second(dt, config)?;
+    }
+    Ok((
+        Some(EventTime {
+            dt,
+            precision_lost: false,
+            leap_second,
+        }),
+        rest,
+    ))
+}
+
+fn parse_timestamp<'a>(
+    m: &'a str,
+    config: &ParserConfig,
+) -> FieldResult<(Option<EventTime>, &'a str)> {
     let mut rest = m;
     if let Some(rest) = rest.strip_prefix('-') {
         return Ok((None, rest));
     }
+    if config.is_lenient() && rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return parse_rfc3164_timestamp(rest, config);
+    }
     let year = take_item!(parse_num(rest, 4, 4), rest);
     take_char!(rest, '-');
     let month_num = take_item!(parse_num_generic(rest, 2, 2), rest);
-    let month = time::Month::try_from(month_num).map_err(|_| ParseErr::InvalidMonth(month_num))?;
+    let month = time::Month::try_from(month_num).map_err(|_| ErrorKind::InvalidMonth(month_num))?;
     take_char!(rest, '-');
     let mday = take_item!(parse_num_generic(rest, 2, 2), rest);
     let date = time::Date::from_calendar_date(year, month, mday)
-        .map_err(|e| ParseErr::InvalidDate(e.name().to_string()))?;
-    take_char!(rest, 'T');
+        .map_err(|e| ErrorKind::InvalidDate(e.name().to_string()))?;
+    if config.is_lenient() && rest.starts_with('t') {
+        rest = &rest[1..];
+    } else {
+        take_char!(rest, 'T');
+    }
     let hour = take_item!(parse_num_generic(rest, 2, 2), rest);
     take_char!(rest, ':');
     let minute = take_item!(parse_num_generic(rest, 2, 2), rest);
     take_char!(rest, ':');
     let second = take_item!(parse_num_generic(rest, 2, 2), rest);
-    let nano = if rest.starts_with('.') {
-        take_char!(rest, '.');
-        take_item!(parse_decimal(rest, 1, 6), rest) as u32
-    } else {
-        0
-    };
-    let time = time::Time::from_hms_nano(hour, minute, second, nano)
-        .map_err(|e| ParseErr::InvalidDate(e.name().to_string()))?;
+    let (nano, precision_lost) =
+        if rest.starts_with('.') || (config.is_lenient() && rest.starts_with(',')) {
+            rest = &rest[1..];
+            take_item!(parse_secfrac(rest), rest)
+        } else {
+            (0, false)
+        };
+    let (time, leap_second) = make_time(hour, minute, second, nano, config)?;
     // Tm::utcoff is totally broken, don't use it.
     let utc_offset = match rest.chars().next() {
         None => None,
@@ -288,130 +1192,1407 @@ fn parse_timestamp(m: &str) -> ParseResult<(Option<time::OffsetDateTime>, &str)>
             rest = &rest[1..];
             None
         }
+        Some('z') if config.is_lenient() => {
+            rest = &rest[1..];
+            None
+        }
         Some(c) => {
             let (sign, irest) = match c {
                 // Note: signs are backwards as per RFC3339
                 '-' => (-1, &rest[1..]),
                 '+' => (1, &rest[1..]),
                 _ => {
-                    return Err(ParseErr::InvalidUTCOffset);
+                    return Err(ErrorKind::InvalidOffset);
                 }
             };
-            let hours = i8::from_str(&irest[0..2]).map_err(ParseErr::IntConversionErr)?;
-            let minutes = i8::from_str(&irest[3..5]).map_err(ParseErr::IntConversionErr)?;
+            // `HH:MM`; checked up front so that the slicing below can't panic
+            let b = irest.as_bytes();
+            if b.len() < 5 || b[2] != b':' || !b[..2].iter().chain(&b[3..5]).all(u8::is_ascii_digit)
+            {
+                return Err(ErrorKind::InvalidOffset);
+            }
+            let hours = i8::from_str(&irest[0..2])?;
+            let minutes = i8::from_str(&irest[3..5])?;
             rest = &irest[5..];
             Some(
                 time::UtcOffset::from_hms(hours * sign, minutes * sign, 0)
-                    .map_err(|_| ParseErr::InvalidOffset)?,
+                    .map_err(|_| ErrorKind::InvalidOffset)?,
             )
         }
     };
     let naive_dt = time::PrimitiveDateTime::new(date, time);
-    let dt = if let Some(utc_offset) = utc_offset {
+    let mut dt = if let Some(utc_offset) = utc_offset {
         naive_dt.assume_offset(utc_offset)
     } else {
         naive_dt.assume_utc()
     };
-    Ok((Some(dt), rest))
+    if leap_second {
+        dt = apply_leap_second(dt, config)?;
+    }
+    Ok((
+        Some(EventTime {
+            dt,
+            precision_lost,
+            leap_second,
+        }),
+        rest,
+    ))
+}
+
+/// Parse an RFC 3339 timestamp, such as `2003-10-11T22:14:15.003-07:00`, on its own
+///
+/// This is the parser used for the TIMESTAMP field, so it accepts exactly what a message would,
+/// up to nine digits of fractional seconds. The whole of `s` has to be the timestamp. Leap
+/// seconds are handled as in the default `ParserConfig`.
+///
+/// ```
+/// use syslog_rfc5424::parser::parse_rfc3339_timestamp;
+///
+/// let ts = parse_rfc3339_timestamp("2003-10-11T22:14:15.003-07:00").unwrap();
+/// assert_eq!(ts.secs, 1065935655);
+/// assert_eq!(ts.nanos, 3_000_000);
+/// assert_eq!(ts.offset, -7 * 3600);
+/// ```
+pub fn parse_rfc3339_timestamp(s: &str) -> ParseResult<Timestamp> {
+    let result = match parse_timestamp(s, &ParserConfig::default()) {
+        Ok((None, _)) => Err(ErrorKind::Nil),
+        Ok((Some(t), "")) => Ok(Timestamp::from(t.dt)),
+        Ok((Some(_), rest)) => Err(ErrorKind::UnexpectedChar(rest.chars().next().unwrap())),
+        Err(kind) => Err(kind),
+    };
+    result.map_err(|kind| ParseErr::new(s, MessageField::Timestamp, 0, kind.into()))
 }
 
 fn parse_term(
     m: &str,
     min_length: usize,
     max_length: usize,
-) -> ParseResult<(Option<String>, &str)> {
+    policy: FieldUtf8Policy,
+) -> FieldResult<(Option<&str>, &str)> {
     if m.starts_with('-') && (m.len() <= 1 || m.as_bytes()[1] == 0x20) {
         return Ok((None, &m[1..]));
     }
-    let byte_ary = m.as_bytes();
-    for (idx, chr) in byte_ary.iter().enumerate() {
-        if *chr < 33 || *chr > 126 {
-            if idx < min_length {
-                return Err(ParseErr::TooFewDigits);
+    let mut len = scan::printusascii_len(m.as_bytes(), max_length);
+    if policy != FieldUtf8Policy::Reject {
+        // carry on through any non-ASCII characters
+        while let Some(c) = m[len..].chars().next().filter(|c| !c.is_ascii()) {
+            if len + c.len_utf8() > max_length {
+                break;
             }
-            let utf8_ary = str::from_utf8(&byte_ary[..idx]).map_err(ParseErr::BaseUnicodeError)?;
-            return Ok((Some(String::from(utf8_ary)), &m[idx..]));
+            len += c.len_utf8();
+            len += scan::printusascii_len(&m.as_bytes()[len..], max_length - len);
         }
-        if idx >= max_length {
-            let utf8_ary = str::from_utf8(&byte_ary[..idx]).map_err(ParseErr::BaseUnicodeError)?;
-            return Ok((Some(String::from(utf8_ary)), &m[idx..]));
+    }
+    if len == m.len() {
+        Err(ErrorKind::UnexpectedEndOfInput)
+    } else if len < min_length {
+        // safe to unwrap: `len` is short of the end of `m`
+        Err(ErrorKind::UnexpectedChar(m[len..].chars().next().unwrap()))
+    } else {
+        // the run ends on an ASCII character, so `len` is on a char boundary
+        Ok((Some(&m[..len]), &m[len..]))
+    }
+}
+
+/// Apply a `FieldUtf8Policy::PercentEncode` to a HOSTNAME, APP-NAME or MSGID
+fn encode_field<'a>(value: Cow<'a, str>, policy: FieldUtf8Policy) -> Cow<'a, str> {
+    if policy != FieldUtf8Policy::PercentEncode || value.is_ascii() {
+        return value;
+    }
+    Cow::Owned(percent_encode(value.as_bytes()))
+}
+
+/// Percent-encode every byte of `b` which isn't PRINTUSASCII
+fn percent_encode(b: &[u8]) -> String {
+    let mut encoded = String::with_capacity(b.len());
+    for &byte in b {
+        if (33..=126).contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
         }
     }
-    Err(ParseErr::UnexpectedEndOfInput)
+    encoded
+}
+
+pub(crate) fn parse_message_s<'a>(
+    m: &'a str,
+    config: &ParserConfig,
+) -> ParseResult<SyslogMessageRef<'a>> {
+    parse_message_spanned(m, config).map(|(msg, _)| msg)
+}
+
+fn parse_message_spanned<'a>(
+    m: &'a str,
+    config: &ParserConfig,
+) -> ParseResult<(SyslogMessageRef<'a>, FieldSpans)> {
+    let mut spans = FieldSpans::default();
+    match parse_message_spanned_inner(m, config, &mut spans) {
+        Ok(msg) => Ok((msg, spans)),
+        Err(failure) => Err(spans.error(m, failure)),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The HEADER of a message, borrowed from the input; see `parse_header`
+pub struct HeaderFields<'a> {
+    pub severity: severity::SyslogSeverity,
+    pub facility: facility::SyslogFacility,
+    pub version: i32,
+    pub timestamp: Option<Timestamp>,
+    pub timestamp_precision_lost: bool,
+    pub leap_second: bool,
+    pub hostname: Option<&'a str>,
+    pub appname: Option<&'a str>,
+    pub procid: Option<ProcIdRef<'a>>,
+    pub msgid: Option<&'a str>,
 }
 
-fn parse_message_s(m: &str) -> ParseResult<SyslogMessage> {
+/// Parse PRI through MSGID, returning the rest of the input (starting with STRUCTURED-DATA)
+fn parse_header_spanned<'a>(
+    m: &'a str,
+    config: &ParserConfig,
+    spans: &mut FieldSpans,
+) -> FieldResult<(HeaderFields<'a>, &'a str)> {
+    let pos = |r: &str| m.len() - r.len();
+    let quirks = config.quirks;
     let mut rest = m;
+    if quirks.leading_whitespace() {
+        rest = rest.trim_start();
+    }
+    if quirks.sequence_numbers() {
+        rest = strip_sequence_number(rest);
+    }
+    spans.pri.start = pos(rest);
     take_char!(rest, '<');
     let prival = take_item!(parse_num(rest, 1, 3), rest);
     take_char!(rest, '>');
-    let (sev, fac) = parse_pri_val(prival)?;
-    let version = take_item!(parse_num(rest, 1, 2), rest);
-    take_char!(rest, ' ');
-    let event_time = take_item!(parse_timestamp(rest), rest);
+    spans.pri.end = pos(rest);
+    let (sev, fac) = parse_pri_val(prival, config)?;
+    if quirks.sequence_numbers() {
+        rest = strip_sequence_number(rest);
+    }
+    spans.version.start = pos(rest);
+    let version = take_item!(parse_version(rest, config), rest);
+    spans.version.end = pos(rest);
+    rest = take_sp(rest, config)?;
+    if quirks.sequence_numbers() {
+        // IOS flags timestamps from an unsynchronized clock with `*` and ones from a clock which
+        // has lost sync with `.`
+        rest = rest.trim_start_matches(['*', '.']);
+    }
+    spans.timestamp.start = pos(rest);
+    let event_time = take_item!(parse_timestamp(rest, config), rest);
+    spans.timestamp.end = pos(rest);
+    rest = take_sp(rest, config)?;
+    spans.hostname.start = pos(rest);
+    let hostname = take_item!(parse_term(rest, 1, 255, config.hostname_utf8), rest);
+    spans.hostname.end = pos(rest);
     take_char!(rest, ' ');
-    let hostname = take_item!(parse_term(rest, 1, 255), rest);
+    spans.appname.start = pos(rest);
+    let appname = take_item!(parse_term(rest, 1, 48, config.appname_utf8), rest);
+    spans.appname.end = pos(rest);
     take_char!(rest, ' ');
-    let appname = take_item!(parse_term(rest, 1, 48), rest);
-    take_char!(rest, ' ');
-    let procid = take_item!(parse_term(rest, 1, 128), rest).map(|s| match i32::from_str(&s) {
-        Ok(n) => ProcId::PID(n),
-        Err(_) => ProcId::Name(s),
+    spans.procid.start = pos(rest);
+    let procid = take_item!(parse_term(rest, 1, 128, FieldUtf8Policy::Reject), rest).map(|s| {
+        match i32::from_str(s) {
+            Ok(n) => ProcIdRef::PID(n),
+            Err(_) => ProcIdRef::Name(s),
+        }
     });
+    spans.procid.end = pos(rest);
     take_char!(rest, ' ');
-    let msgid = take_item!(parse_term(rest, 1, 32), rest);
+    spans.msgid.start = pos(rest);
+    let msgid = take_item!(parse_term(rest, 1, 32, config.msgid_utf8), rest);
+    spans.msgid.end = pos(rest);
     take_char!(rest, ' ');
-    let sd = take_item!(parse_sd(rest), rest);
-    rest = match maybe_expect_char!(rest, ' ') {
-        Some(r) => r,
-        None => rest,
-    };
-    let msg = String::from(rest);
 
-    Ok(SyslogMessage {
+    let mut header = HeaderFields {
         severity: sev,
         facility: fac,
         version,
-        timestamp: event_time.map(|t| t.unix_timestamp()),
-        timestamp_nanos: event_time.map(|t| t.time().nanosecond()),
+        timestamp: None,
+        timestamp_precision_lost: false,
+        leap_second: false,
         hostname,
         appname,
         procid,
         msgid,
+    };
+    if let Some(t) = event_time {
+        header.timestamp = Some(Timestamp::from(t.dt));
+        header.timestamp_precision_lost = t.precision_lost;
+        header.leap_second = t.leap_second;
+    }
+    Ok((header, rest))
+}
+
+fn parse_message_spanned_inner<'a>(
+    m: &'a str,
+    config: &ParserConfig,
+    spans: &mut FieldSpans,
+) -> InnerResult<SyslogMessageRef<'a>> {
+    check_limit("message length", m.len(), config.max_message_length)?;
+    let pos = |r: &str| m.len() - r.len();
+    let quirks = config.quirks;
+    let (header, mut rest) = parse_header_spanned(m, config, spans)?;
+    spans.sd.start = pos(rest);
+    let (sd, truncated) = if quirks.optional_sd() && !rest.starts_with(['-', '[']) {
+        (StructuredDataRef::new_empty(), false)
+    } else {
+        take_item!(parse_sd(rest, config), rest)
+    };
+    spans.sd.end = pos(rest);
+    let msg_start = pos(rest) + usize::from(rest.starts_with(' '));
+    let rest = take_msg(rest, config);
+    spans.msg = msg_start..msg_start + rest.len();
+    let (msg, msg_is_utf8) = split_bom(rest, config);
+
+    let mut m = SyslogMessageRef {
+        msg_is_utf8,
+        sd,
+        msg: Cow::Borrowed(msg),
+        truncated,
+        ..header.into()
+    };
+    m.encode_fields(config);
+    Ok(m)
+}
+
+impl<'a> From<HeaderFields<'a>> for SyslogMessageRef<'a> {
+    /// A message with this header, no STRUCTURED-DATA and an empty MSG
+    fn from(h: HeaderFields<'a>) -> Self {
+        SyslogMessageRef {
+            severity: h.severity,
+            facility: h.facility,
+            version: h.version,
+            timestamp: h.timestamp,
+            timestamp_precision_lost: h.timestamp_precision_lost,
+            leap_second: h.leap_second,
+            msg_is_utf8: false,
+            hostname: h.hostname.map(Cow::Borrowed),
+            appname: h.appname.map(Cow::Borrowed),
+            procid: h.procid,
+            msgid: h.msgid.map(Cow::Borrowed),
+            sd: StructuredDataRef::new_empty(),
+            msg: Cow::Borrowed(""),
+            truncated: false,
+        }
+    }
+}
+
+/// Everything after STRUCTURED-DATA: skip the SP before MSG, and any newline the quirks allow for
+fn take_msg<'a>(rest: &'a str, config: &ParserConfig) -> &'a str {
+    let mut rest = match maybe_expect_char!(rest, ' ') {
+        Some(r) => r,
+        None => rest,
+    };
+    if config.quirks.trailing_newline() {
+        rest = rest.strip_suffix('\n').unwrap_or(rest);
+        rest = rest.strip_suffix('\r').unwrap_or(rest);
+    }
+    rest
+}
+
+/// Skip a Cisco-style `123: ` sequence number, if there is one
+fn strip_sequence_number(s: &str) -> &str {
+    let digits = s.bytes().take_while(u8::is_ascii_digit).count();
+    if digits > 0 {
+        if let Some(rest) = s[digits..].strip_prefix(": ") {
+            return rest;
+        }
+    }
+    s
+}
+
+/// Strip the BOM from MSG (if so configured), returning whether there was one
+///
+/// RFC 5424 says that a MSG starting with a BOM is UTF-8; anything else could be anything.
+fn split_bom<'a>(msg: &'a str, config: &ParserConfig) -> (&'a str, bool) {
+    let msg_is_utf8 = msg.starts_with(BOM);
+    if msg_is_utf8 && config.strip_bom {
+        (&msg[BOM.len_utf8()..], true)
+    } else {
+        (msg, msg_is_utf8)
+    }
+}
+
+impl<'a> SyslogMessageRef<'a> {
+    /// Percent-encode HOSTNAME, APP-NAME and MSGID, if so configured
+    fn encode_fields(&mut self, config: &ParserConfig) {
+        let encode = |value: &mut Option<Cow<'a, str>>, field| {
+            *value = value
+                .take()
+                .map(|v| encode_field(v, config.field_utf8_policy(field)));
+        };
+        encode(&mut self.hostname, MessageField::Hostname);
+        encode(&mut self.appname, MessageField::AppName);
+        encode(&mut self.msgid, MessageField::MsgId);
+    }
+
+    fn set_event_time(&mut self, event_time: Option<EventTime>) {
+        if let Some(t) = event_time {
+            self.timestamp = Some(Timestamp::from(t.dt));
+            self.timestamp_precision_lost = t.precision_lost;
+            self.leap_second = t.leap_second;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The parts of a syslog message, in the order they appear on the wire
+pub enum MessageField {
+    Pri,
+    Version,
+    Timestamp,
+    Hostname,
+    AppName,
+    ProcId,
+    MsgId,
+    StructuredData,
+    Msg,
+}
+
+impl MessageField {
+    /// The field's name, as RFC 5424 writes it (`APP-NAME`, `STRUCTURED-DATA`, ...)
+    pub fn name(self) -> &'static str {
+        match self {
+            MessageField::Pri => "PRI",
+            MessageField::Version => "VERSION",
+            MessageField::Timestamp => "TIMESTAMP",
+            MessageField::Hostname => "HOSTNAME",
+            MessageField::AppName => "APP-NAME",
+            MessageField::ProcId => "PROCID",
+            MessageField::MsgId => "MSGID",
+            MessageField::StructuredData => "STRUCTURED-DATA",
+            MessageField::Msg => "MSG",
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Something which went wrong during `parse_message_lossy`
+pub struct ParseWarning {
+    /// The field which couldn't be parsed (and was left at its default)
+    pub field: MessageField,
+    /// The byte offset into the input at which the field started
+    pub offset: usize,
+    pub error: ParseErr,
+}
+
+/// Skip past the next SP, for resynchronizing after a field fails to parse
+fn skip_token(s: &str) -> &str {
+    match s.find(' ') {
+        Some(idx) => &s[idx + 1..],
+        None => "",
+    }
+}
+
+fn parse_pri<'a>(
+    m: &'a str,
+    config: &ParserConfig,
+) -> FieldResult<(
+    (severity::SyslogSeverity, facility::SyslogFacility),
+    &'a str,
+)> {
+    let mut rest = m;
+    take_char!(rest, '<');
+    let prival = take_item!(parse_num(rest, 1, 3), rest);
+    take_char!(rest, '>');
+    Ok((parse_pri_val(prival, config)?, rest))
+}
+
+/// Parse as much of a message as possible, noting anything that had to be skipped
+///
+/// Fields which can't be parsed are left as NIL (or, for the PRI and VERSION, at the RFC 3164
+/// default of `user.notice` and 1), and a `ParseWarning` is recorded for each of them. Malformed
+/// structured data elements are dropped, keeping any well-formed ones which came before them. If
+/// the input doesn't even start with a PRI, the whole thing is treated as MSG. Input longer than
+/// `ParserConfig::with_max_message_length` is cut short, with a warning for MSG.
+///
+/// # Example
+///
+/// ```
+/// use syslog_rfc5424::parser::{parse_message_lossy, MessageField, ParserConfig};
+///
+/// let input = r#"<78>1 2016-01-15T25:04:01Z host1 CROND 10391 - [meta sequenceId="29] some_message"#;
+/// let (message, warnings) = parse_message_lossy(&ParserConfig::default(), input);
+///
+/// assert_eq!(message.hostname.as_deref(), Some("host1"));
+/// assert_eq!(message.timestamp, None);
+/// let fields: Vec<_> = warnings.iter().map(|w| w.field).collect();
+/// assert_eq!(fields, vec![MessageField::Timestamp, MessageField::StructuredData]);
+/// ```
+pub fn parse_message_lossy<S: AsRef<str>>(
+    config: &ParserConfig,
+    s: S,
+) -> (SyslogMessage, Vec<ParseWarning>) {
+    let (m, warnings) = parse_message_lossy_s(s.as_ref(), config);
+    let mut owned = m.to_owned();
+    if config.preserve_sd_order {
+        owned.sd = m.sd.to_owned_ordered();
+    }
+    (owned, warnings)
+}
+
+fn parse_message_lossy_s<'a>(
+    m: &'a str,
+    config: &ParserConfig,
+) -> (SyslogMessageRef<'a>, Vec<ParseWarning>) {
+    let mut warnings = Vec::new();
+    // keep as much as is allowed, and drop the rest
+    let (m, cut_to_length) = match config.max_message_length {
+        Some(max) if m.len() > max => {
+            let mut end = max;
+            while !m.is_char_boundary(end) {
+                end -= 1;
+            }
+            warnings.push(ParseWarning {
+                field: MessageField::Msg,
+                offset: end,
+                error: ParseErr::TooLong {
+                    what: "message length",
+                    limit: max,
+                },
+            });
+            (&m[..end], true)
+        }
+        _ => (m, false),
+    };
+    let pos = |r: &str| m.len() - r.len();
+    let mut rest = m;
+
+    // Run a field parser; on failure, record a warning, skip the field and use the default
+    macro_rules! lossy_item {
+        ($field:expr, $e:expr, $default:expr) => {{
+            let start = rest;
+            match $e {
+                Ok((v, r)) => {
+                    rest = r;
+                    v
+                }
+                Err(_) if start.is_empty() => $default,
+                Err(failure) => {
+                    warnings.push(ParseWarning {
+                        field: $field,
+                        offset: pos(start),
+                        error: ParseErr::new(m, $field, pos(start), failure.into()),
+                    });
+                    rest = skip_token(start);
+                    $default
+                }
+            }
+        }};
+    }
+
+    let mut msg = SyslogMessageRef {
+        severity: severity::SyslogSeverity::SEV_NOTICE,
+        facility: facility::SyslogFacility::LOG_USER,
+        version: 1,
+        timestamp: None,
+        timestamp_precision_lost: false,
+        leap_second: false,
+        msg_is_utf8: false,
+        hostname: None,
+        appname: None,
+        procid: None,
+        msgid: None,
+        sd: StructuredDataRef::new_empty(),
+        msg: Cow::Borrowed(""),
+        truncated: cut_to_length,
+    };
+
+    if !rest.starts_with('<') {
+        warnings.push(ParseWarning {
+            field: MessageField::Pri,
+            offset: 0,
+            error: ParseErr::new(
+                m,
+                MessageField::Pri,
+                0,
+                ErrorKind::ExpectedToken('<').into(),
+            ),
+        });
+        let (body, msg_is_utf8) = split_bom(rest, config);
+        msg.msg = Cow::Borrowed(body);
+        msg.msg_is_utf8 = msg_is_utf8;
+        return (msg, warnings);
+    }
+    let pri_start = rest;
+    match parse_pri(rest, config) {
+        Ok(((sev, fac), r)) => {
+            msg.severity = sev;
+            msg.facility = fac;
+            rest = r;
+        }
+        Err(kind) => {
+            warnings.push(ParseWarning {
+                field: MessageField::Pri,
+                offset: 0,
+                error: ParseErr::new(m, MessageField::Pri, 0, kind.into()),
+            });
+            rest = match pri_start.find('>') {
+                Some(idx) => &pri_start[idx + 1..],
+                None => skip_token(pri_start),
+            };
+        }
+    }
+    msg.version = lossy_item!(
+        MessageField::Version,
+        parse_version(rest, config).and_then(|(v, r)| Ok((v, take_sp(r, config)?))),
+        1
+    );
+    let event_time = lossy_item!(
+        MessageField::Timestamp,
+        parse_timestamp(rest, config).and_then(|(v, r)| Ok((v, take_sp(r, config)?))),
+        None
+    );
+    msg.set_event_time(event_time);
+    let terms = [
+        (MessageField::Hostname, 255),
+        (MessageField::AppName, 48),
+        (MessageField::ProcId, 128),
+        (MessageField::MsgId, 32),
+    ];
+    let mut values = [None; 4];
+    for (value, (field, max_length)) in values.iter_mut().zip(terms.iter()) {
+        *value = lossy_item!(
+            *field,
+            parse_term(rest, 1, *max_length, config.field_utf8_policy(*field)).and_then(
+                |(v, mut r)| {
+                    take_char!(r, ' ');
+                    Ok((v, r))
+                }
+            ),
+            None
+        );
+    }
+    msg.hostname = values[0].map(Cow::Borrowed);
+    msg.appname = values[1].map(Cow::Borrowed);
+    msg.procid = values[2].map(|s| match i32::from_str(s) {
+        Ok(n) => ProcIdRef::PID(n),
+        Err(_) => ProcIdRef::Name(s),
+    });
+    msg.msgid = values[3].map(Cow::Borrowed);
+    msg.encode_fields(config);
+
+    if let Some(r) = rest.strip_prefix('-') {
+        rest = r;
+    } else {
+        while rest.starts_with('[') {
+            let sde = check_limit("SD-ELEMENT count", msg.sd.len() + 1, config.max_sd_elements)
+                .map_err(Failure::from)
+                .and_then(|_| parse_sde(rest, config));
+            match sde {
+                Ok(((sd_id, params), r)) => {
+                    msg.sd.push(sd_id, params);
+                    rest = r;
+                }
+                Err(failure) => {
+                    let cut_off = matches!(failure.kind, ErrorKind::UnexpectedEndOfInput);
+                    warnings.push(ParseWarning {
+                        field: MessageField::StructuredData,
+                        offset: pos(rest),
+                        error: ParseErr::new(m, MessageField::StructuredData, pos(rest), failure),
+                    });
+                    // The best we can do is look for something which looks like the end of an
+                    // element followed by MSG; failing that, either the message was cut off part
+                    // of the way through this element, or we keep the rest as MSG so that
+                    // nothing is lost.
+                    if let Some(idx) = rest.find("] ") {
+                        rest = &rest[idx + 1..];
+                    } else if cut_off {
+                        if let Some((sd_id, params)) = parse_truncated_sde(rest, config) {
+                            msg.sd.push(sd_id, params);
+                        }
+                        msg.truncated = true;
+                        rest = "";
+                    }
+                    break;
+                }
+            }
+        }
+    }
+    rest = rest.strip_prefix(' ').unwrap_or(rest);
+    let (body, msg_is_utf8) = split_bom(rest, config);
+    msg.msg = Cow::Borrowed(body);
+    msg.msg_is_utf8 = msg_is_utf8;
+    (msg, warnings)
+}
+
+/// Parse a string into a `SyslogMessage` object
+///
+/// # Arguments
+///
+///  * `s`: Anything convertible to a string
+///
+/// # Returns
+///
+///  * `ParseErr` if the string is not parseable as an RFC5424 message
+///
+/// # Example
+///
+/// ```
+/// use syslog_rfc5424::parse_message;
+///
+/// let message = parse_message("<78>1 2016-01-15T00:04:01+00:00 host1 CROND 10391 - [meta sequenceId=\"29\"] some_message").unwrap();
+///
+/// assert!(message.hostname.unwrap() == "host1");
+/// ```
+pub fn parse_message<S: AsRef<str>>(s: S) -> ParseResult<SyslogMessage> {
+    parse_message_with(&ParserConfig::default(), s)
+}
+
+/// Parse a string into a `SyslogMessage` object, using the given `ParserConfig`
+pub fn parse_message_with<S: AsRef<str>>(
+    config: &ParserConfig,
+    s: S,
+) -> ParseResult<SyslogMessage> {
+    let s = s.as_ref();
+    config.observe(s.len(), parse_owned(s, config))
+}
+
+fn parse_owned(s: &str, config: &ParserConfig) -> ParseResult<SyslogMessage> {
+    finish_owned(parse_message_s(s, config)?, config)
+}
+
+/// Parse just the HEADER of a message (PRI through MSGID), for when that's all you need
+///
+/// This is much quicker than parsing the whole message if it has much STRUCTURED-DATA, which is
+/// useful for routing on the facility, HOSTNAME or APP-NAME. The rest of the input, starting at
+/// the STRUCTURED-DATA, is returned unparsed.
+///
+/// ```
+/// use syslog_rfc5424::parser::parse_header;
+///
+/// let (header, rest) = parse_header(r#"<78>1 - host1 CROND 10391 - [meta sequenceId="29"] hi"#).unwrap();
+/// assert_eq!(header.hostname, Some("host1"));
+/// assert_eq!(header.appname, Some("CROND"));
+/// assert_eq!(rest, r#"[meta sequenceId="29"] hi"#);
+/// ```
+pub fn parse_header(s: &str) -> ParseResult<(HeaderFields<'_>, &str)> {
+    parse_header_with(&ParserConfig::default(), s)
+}
+
+/// Like `parse_header`, but with a `ParserConfig`
+pub fn parse_header_with<'a>(
+    config: &ParserConfig,
+    s: &'a str,
+) -> ParseResult<(HeaderFields<'a>, &'a str)> {
+    let mut spans = FieldSpans::default();
+    check_limit("message length", s.len(), config.max_message_length)
+        .and_then(|_| parse_header_spanned(s, config, &mut spans))
+        .map_err(|kind| spans.error(s, kind.into()))
+}
+
+/// Parse a standalone STRUCTURED-DATA string, such as `[id a="b"][id2 c="d"]`, or `-` for none
+///
+/// This is for other formats which reuse RFC 5424's SD syntax, like JSON logs with an SD field.
+/// The whole of `s` has to be STRUCTURED-DATA; anything after the last SD-ELEMENT is an error.
+///
+/// ```
+/// use syslog_rfc5424::parser::parse_structured_data;
+///
+/// let sd = parse_structured_data(r#"[id a="b"][id2 c="d\"e"]"#).unwrap();
+/// assert_eq!(sd.find_tuple("id2", "c"), Some("d\"e"));
+/// ```
+pub fn parse_structured_data(s: &str) -> ParseResult<StructuredDataRef<'_>> {
+    parse_structured_data_with(&ParserConfig::default(), s)
+}
+
+/// Like `parse_structured_data`, but with a `ParserConfig`
+///
+/// The SD limits, escape handling and strictness apply as they would in a message. A lenient
+/// config accepts SD which is cut off part of the way through, keeping whatever was complete.
+pub fn parse_structured_data_with<'a>(
+    config: &ParserConfig,
+    s: &'a str,
+) -> ParseResult<StructuredDataRef<'a>> {
+    let sd = parse_sd_exact(s, config)
+        .map_err(|failure| ParseErr::new(s, MessageField::StructuredData, 0, failure))?;
+    if config.strict {
+        let mut violations = Vec::new();
+        for (sd_id, params) in sd.iter() {
+            let id_field = validation::Field::SdId(sd_id.to_string());
+            if let Err(v) = validation::validate_sd_name(id_field, sd_id) {
+                violations.extend(v);
+            }
+            for (name, _) in params {
+                let field = validation::Field::SdParamName(sd_id.to_string(), name.to_string());
+                if let Err(v) = validation::validate_sd_name(field, name) {
+                    violations.extend(v);
+                }
+            }
+        }
+        if !violations.is_empty() {
+            return Err(ParseErr::Nonconformant(violations));
+        }
+    }
+    Ok(sd)
+}
+
+/// Parse STRUCTURED-DATA which takes up the whole of `s`
+fn parse_sd_exact<'a>(s: &'a str, config: &ParserConfig) -> InnerResult<StructuredDataRef<'a>> {
+    if s.is_empty() {
+        return Err(ErrorKind::UnexpectedEndOfInput.into());
+    }
+    let ((sd, _), rest) = parse_sd(s, config)?;
+    if !rest.is_empty() {
+        return Err(ErrorKind::ExpectedToken('[').into());
+    }
+    Ok(sd)
+}
+
+/// Convert a freshly-parsed message into an owned one, applying any post-parse checks
+fn finish_owned(m: SyslogMessageRef<'_>, config: &ParserConfig) -> ParseResult<SyslogMessage> {
+    let mut owned = m.to_owned();
+    if config.preserve_sd_order {
+        owned.sd = m.sd.to_owned_ordered();
+    }
+    let m = owned;
+    if config.strict {
+        validation::validate(&m).map_err(ParseErr::Nonconformant)?;
+    }
+    Ok(m)
+}
+
+#[derive(Clone, Debug, Default)]
+/// A reusable parser, which keeps its `ParserConfig` and a string cache between messages
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use syslog_rfc5424::parser::Parser;
+///
+/// let mut parser = Parser::new();
+/// let first = parser.parse_interned("<1>1 - host1 app - - - one").unwrap();
+/// let second = parser.parse_interned("<1>1 - host1 app - - - two").unwrap();
+/// assert!(Arc::ptr_eq(
+///     first.hostname.as_ref().unwrap(),
+///     second.hostname.as_ref().unwrap()
+/// ));
+/// ```
+pub struct Parser<C = Interner> {
+    config: ParserConfig,
+    cache: C,
+}
+
+impl Parser<Interner> {
+    pub fn new() -> Self {
+        Parser::with_cache(Interner::new())
+    }
+}
+
+impl<C: Cache> Parser<C> {
+    /// Use your own `Cache` for the interned fields
+    pub fn with_cache(cache: C) -> Self {
+        Parser {
+            config: ParserConfig::default(),
+            cache,
+        }
+    }
+
+    pub fn with_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &ParserConfig {
+        &self.config
+    }
+
+    pub fn cache(&self) -> &C {
+        &self.cache
+    }
+
+    pub fn cache_mut(&mut self) -> &mut C {
+        &mut self.cache
+    }
+
+    /// Parse a message, exactly as `parse_message_with` would
+    pub fn parse(&self, s: &str) -> ParseResult<SyslogMessage> {
+        parse_message_with(&self.config, s)
+    }
+
+    /// Parse a message, sharing its HOSTNAME, APP-NAME and MSGID through the cache
+    pub fn parse_interned(&mut self, s: &str) -> ParseResult<InternedMessage> {
+        let mut m = parse_message_s(s, &self.config)?;
+        if self.config.strict {
+            // validation needs the whole message, so this can't skip the copies
+            let m = finish_owned(m, &self.config)?;
+            return Ok(InternedMessage::from_message(m, &mut self.cache));
+        }
+        let hostname = m.hostname.take().map(|s| self.cache.intern(&s));
+        let appname = m.appname.take().map(|s| self.cache.intern(&s));
+        let msgid = m.msgid.take().map(|s| self.cache.intern(&s));
+        Ok(InternedMessage {
+            hostname,
+            appname,
+            msgid,
+            message: finish_owned(m, &self.config)?,
+        })
+    }
+}
+
+/// Parse a string into a `SyslogMessageRef` which borrows from the input
+///
+/// This avoids allocating a new `String` for every field, which matters if you're parsing a lot
+/// of messages and only looking at a few fields of each.
+///
+/// # Example
+///
+/// ```
+/// use syslog_rfc5424::parse_message_ref;
+///
+/// let input = "<78>1 2016-01-15T00:04:01+00:00 host1 CROND 10391 - [meta sequenceId=\"29\"] some_message";
+/// let message = parse_message_ref(input).unwrap();
+///
+/// assert_eq!(message.hostname.as_deref(), Some("host1"));
+/// assert_eq!(message.sd.find_tuple("meta", "sequenceId"), Some("29"));
+/// ```
+pub fn parse_message_ref(s: &str) -> ParseResult<SyslogMessageRef<'_>> {
+    parse_message_s(s, &ParserConfig::default())
+}
+
+#[derive(Clone, Debug)]
+/// Iterator over the messages in a newline-delimited buffer; see `parse_messages`
+pub struct ParseMessages<'a> {
+    rest: &'a str,
+    config: ParserConfig,
+}
+
+impl<'a> ParseMessages<'a> {
+    /// Parse each message with the given `ParserConfig` instead of the default
+    pub fn with_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Skip ahead to the next line which looks like the start of a message
+    fn resync(&mut self) {
+        while !self.rest.is_empty() && !self.rest.starts_with('<') {
+            self.rest = match self.rest.find('\n') {
+                Some(idx) => &self.rest[idx + 1..],
+                None => "",
+            };
+        }
+    }
+}
+
+impl<'a> Iterator for ParseMessages<'a> {
+    type Item = ParseResult<SyslogMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.rest.is_empty() {
+                return None;
+            }
+            let (line, rest) = match self.rest.find('\n') {
+                Some(idx) => (&self.rest[..idx], &self.rest[idx + 1..]),
+                None => (self.rest, ""),
+            };
+            self.rest = rest;
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if line.is_empty() {
+                continue;
+            }
+            let result = parse_message_with(&self.config, line);
+            if result.is_err() {
+                self.resync();
+            }
+            return Some(result);
+        }
+    }
+}
+
+/// Parse a buffer containing many newline-delimited messages (like a log file)
+///
+/// Blank lines are skipped, and a trailing CR is removed from each line. Each message which fails
+/// to parse is yielded as an `Err`, after which any following lines which don't start with `<`
+/// are skipped, so that a garbled multi-line message only produces one error.
+///
+/// # Example
+///
+/// ```
+/// use syslog_rfc5424::parse_messages;
+///
+/// let input = "<1>1 - host1 - - - - one\ngarbage\n  more garbage\n<1>1 - host2 - - - - two\n";
+/// let results: Vec<_> = parse_messages(input).collect();
+///
+/// assert_eq!(results.len(), 3);
+/// assert_eq!(results[0].as_ref().unwrap().msg, "one");
+/// assert!(results[1].is_err());
+/// assert_eq!(results[2].as_ref().unwrap().msg, "two");
+/// ```
+pub fn parse_messages(s: &str) -> ParseMessages<'_> {
+    ParseMessages {
+        rest: s,
+        config: ParserConfig::default(),
+    }
+}
+
+/// Parse many messages at once, spread across all cores with rayon (requires the `rayon` feature)
+///
+/// Each input is parsed on its own, exactly as `parse_message` would, and the results come back in
+/// the same order as `lines`. Parsing keeps no state between messages, so this is as fast as
+/// splitting the input up and parsing it on separate threads yourself.
+///
+/// # Example
+///
+/// ```
+/// use syslog_rfc5424::parse_messages_par;
+///
+/// let lines = vec!["<1>1 - host1 - - - - one", "garbage", "<1>1 - host2 - - - - two"];
+/// let results = parse_messages_par(&lines);
+///
+/// assert_eq!(results[0].as_ref().unwrap().msg, "one");
+/// assert!(results[1].is_err());
+/// assert_eq!(results[2].as_ref().unwrap().msg, "two");
+/// ```
+#[cfg(feature = "rayon")]
+pub fn parse_messages_par<S: AsRef<str> + Sync>(lines: &[S]) -> Vec<ParseResult<SyslogMessage>> {
+    parse_messages_par_with(&ParserConfig::default(), lines)
+}
+
+/// `parse_messages_par`, using the given `ParserConfig` (requires the `rayon` feature)
+#[cfg(feature = "rayon")]
+pub fn parse_messages_par_with<S: AsRef<str> + Sync>(
+    config: &ParserConfig,
+    lines: &[S],
+) -> Vec<ParseResult<SyslogMessage>> {
+    use rayon::prelude::*;
+
+    lines
+        .par_iter()
+        .map(|line| parse_message_with(config, line))
+        .collect()
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// The byte range of each field within the original input
+///
+/// NIL fields cover the `-`, `pri` covers the angle brackets, and `msg` covers any BOM.
+pub struct FieldSpans {
+    pub pri: Range<usize>,
+    pub version: Range<usize>,
+    pub timestamp: Range<usize>,
+    pub hostname: Range<usize>,
+    pub appname: Range<usize>,
+    pub procid: Range<usize>,
+    pub msgid: Range<usize>,
+    pub sd: Range<usize>,
+    pub msg: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A parsed message along with the exact input it was parsed from
+pub struct RawSyslogMessage {
+    pub raw: String,
+    pub message: SyslogMessage,
+    pub spans: FieldSpans,
+}
+
+impl FieldSpans {
+    /// The last field we got to, and where it starts
+    fn last_field(&self) -> (MessageField, usize) {
+        // fields we didn't get to start at 0, as does everything up to the PRI
+        [
+            (MessageField::Pri, self.pri.start),
+            (MessageField::Version, self.version.start),
+            (MessageField::Timestamp, self.timestamp.start),
+            (MessageField::Hostname, self.hostname.start),
+            (MessageField::AppName, self.appname.start),
+            (MessageField::ProcId, self.procid.start),
+            (MessageField::MsgId, self.msgid.start),
+            (MessageField::StructuredData, self.sd.start),
+            (MessageField::Msg, self.msg.start),
+        ]
+        .iter()
+        .copied()
+        .rev()
+        .find(|&(_, start)| start > 0)
+        .unwrap_or((MessageField::Pri, self.pri.start))
+    }
+
+    /// Blame `failure` on the last field we got to in `input`
+    fn error(&self, input: &str, failure: Failure) -> ParseErr {
+        let (field, offset) = self.last_field();
+        ParseErr::new(input, field, offset, failure)
+    }
+}
+
+impl RawSyslogMessage {
+    /// The untouched text of one field, given its span from `spans`
+    pub fn raw_field(&self, span: &Range<usize>) -> &str {
+        &self.raw[span.clone()]
+    }
+}
+
+/// Parse a string into a `SyslogMessage`, keeping a copy of the original input and the location
+/// of each field within it
+///
+/// This is useful for forwarders which need to archive messages verbatim while also indexing
+/// their parsed fields.
+///
+/// # Example
+///
+/// ```
+/// use syslog_rfc5424::parser::{parse_message_with_raw, ParserConfig};
+///
+/// let input = "<78>1 2016-01-15T00:04:01+00:00 host1 CROND 10391 - - some_message";
+/// let raw = parse_message_with_raw(&ParserConfig::default(), input).unwrap();
+///
+/// assert_eq!(raw.raw, input);
+/// assert_eq!(raw.raw_field(&raw.spans.timestamp), "2016-01-15T00:04:01+00:00");
+/// assert_eq!(raw.message.hostname.as_deref(), Some("host1"));
+/// ```
+pub fn parse_message_with_raw<S: Into<String>>(
+    config: &ParserConfig,
+    s: S,
+) -> ParseResult<RawSyslogMessage> {
+    let raw = s.into();
+    let result = parse_message_spanned(&raw, config)
+        .and_then(|(m, spans)| Ok((finish_owned(m, config)?, spans)));
+    let (message, spans) = config.observe(raw.len(), result)?;
+    Ok(RawSyslogMessage {
+        raw,
+        message,
+        spans,
+    })
+}
+
+#[derive(Clone, Debug)]
+/// A message whose STRUCTURED-DATA is only parsed when it's first asked for; see
+/// `parse_message_lazy`
+pub struct LazySyslogMessage {
+    message: SyslogMessage,
+    raw_sd: String,
+    sd: OnceCell<StructuredData>,
+    config: ParserConfig,
+}
+
+impl LazySyslogMessage {
+    /// Every field of the message except `sd`, which is left empty
+    pub fn message(&self) -> &SyslogMessage {
+        &self.message
+    }
+
+    /// The STRUCTURED-DATA as it appeared in the input
+    pub fn raw_sd(&self) -> &str {
+        &self.raw_sd
+    }
+
+    /// The STRUCTURED-DATA, which is parsed the first time this is called
+    ///
+    /// If the SD is malformed, every call fails, with offsets relative to `raw_sd`.
+    pub fn sd(&self) -> ParseResult<&StructuredData> {
+        if let Some(sd) = self.sd.get() {
+            return Ok(sd);
+        }
+        let sd = parse_raw_sd(&self.raw_sd, &self.config)?;
+        Ok(self.sd.get_or_init(|| sd))
+    }
+
+    /// Whether the STRUCTURED-DATA has been parsed yet
+    pub fn is_sd_parsed(&self) -> bool {
+        self.sd.get().is_some()
+    }
+
+    /// The whole message, parsing its STRUCTURED-DATA if that hasn't happened yet
+    pub fn into_message(self) -> ParseResult<SyslogMessage> {
+        let mut message = self.message;
+        message.sd = match self.sd.into_inner() {
+            Some(sd) => sd,
+            None => parse_raw_sd(&self.raw_sd, &self.config)?,
+        };
+        Ok(message)
+    }
+}
+
+fn parse_raw_sd(raw_sd: &str, config: &ParserConfig) -> ParseResult<StructuredData> {
+    if raw_sd.is_empty() {
+        return Ok(StructuredData::new_empty());
+    }
+    let sd = parse_structured_data_with(config, raw_sd)?;
+    Ok(if config.preserve_sd_order {
+        sd.to_owned_ordered()
+    } else {
+        sd.into_owned()
+    })
+}
+
+/// Find where the STRUCTURED-DATA at the start of `s` ends, without parsing it
+///
+/// Only the brackets and quoting are looked at, so the SD can still turn out to be malformed.
+fn split_sd(s: &str) -> FieldResult<(&str, &str)> {
+    if s.starts_with('-') {
+        return Ok(s.split_at(1));
+    }
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() && bytes[i] != b' ' {
+        if bytes[i] != b'[' {
+            return Err(ErrorKind::ExpectedToken('['));
+        }
+        let mut in_value = false;
+        i += 1;
+        loop {
+            match bytes.get(i) {
+                None => return Err(ErrorKind::UnexpectedEndOfInput),
+                Some(b'\\') if in_value => i += 1,
+                Some(b'"') => in_value = !in_value,
+                Some(b']') if !in_value => break,
+                _ => {}
+            }
+            i += 1;
+        }
+        i += 1;
+    }
+    // every byte we stopped on was ASCII, so this is a char boundary
+    Ok(s.split_at(i))
+}
+
+/// Parse a string into a `LazySyslogMessage`, leaving its STRUCTURED-DATA to be parsed later
+///
+/// Only the extent of the SD is found up front, so messages whose SD is never looked at skip the
+/// cost of building it. Malformed SD is then reported by `LazySyslogMessage::sd`, rather than by
+/// this; see `ParserConfig::with_lazy_sd` to parse it straight away instead.
+///
+/// # Example
+///
+/// ```
+/// use syslog_rfc5424::parser::{parse_message_lazy, ParserConfig};
+///
+/// let input = r#"<78>1 - host1 CROND 10391 - [meta sequenceId="29"] some_message"#;
+/// let message = parse_message_lazy(&ParserConfig::default(), input).unwrap();
+///
+/// assert_eq!(message.message().hostname.as_deref(), Some("host1"));
+/// assert!(!message.is_sd_parsed());
+/// assert_eq!(
+///     message.sd().unwrap().find_tuple("meta", "sequenceId"),
+///     Some(&"29".to_string())
+/// );
+/// ```
+pub fn parse_message_lazy<S: AsRef<str>>(
+    config: &ParserConfig,
+    s: S,
+) -> ParseResult<LazySyslogMessage> {
+    let s = s.as_ref();
+    let mut spans = FieldSpans::default();
+    let result = parse_message_lazy_inner(s, config, &mut spans)
+        .map_err(|failure| spans.error(s, failure))
+        .and_then(|(m, raw_sd)| Ok((finish_owned(m, config)?, raw_sd)));
+    let (mut message, raw_sd) = config.observe(s.len(), result)?;
+    let sd = OnceCell::new();
+    if !config.lazy_sd {
+        let _ = sd.set(mem::replace(&mut message.sd, StructuredData::new_empty()));
+    }
+    Ok(LazySyslogMessage {
+        message,
+        raw_sd: raw_sd.to_string(),
         sd,
-        msg,
+        config: config.clone(),
     })
 }
 
-/// Parse a string into a `SyslogMessage` object
-///
-/// # Arguments
-///
-///  * `s`: Anything convertible to a string
-///
-/// # Returns
+/// Parse everything but the STRUCTURED-DATA (unless `lazy_sd` is off), returning the raw SD too
+fn parse_message_lazy_inner<'a>(
+    m: &'a str,
+    config: &ParserConfig,
+    spans: &mut FieldSpans,
+) -> InnerResult<(SyslogMessageRef<'a>, &'a str)> {
+    check_limit("message length", m.len(), config.max_message_length)?;
+    let (header, rest) = parse_header_spanned(m, config, spans)?;
+    spans.sd.start = m.len() - rest.len();
+    let (raw_sd, rest, truncated) = if config.quirks.optional_sd() && !rest.starts_with(['-', '['])
+    {
+        ("", rest, false)
+    } else {
+        match split_sd(rest) {
+            Ok((raw_sd, rest)) => (raw_sd, rest, false),
+            Err(ErrorKind::UnexpectedEndOfInput) if config.is_lenient() => (rest, "", true),
+            Err(kind) => return Err(kind.into()),
+        }
+    };
+    // strictness is checked along with the rest of the message, by `finish_owned`
+    let sd = if config.lazy_sd || raw_sd.is_empty() {
+        StructuredDataRef::new_empty()
+    } else {
+        parse_sd_exact(raw_sd, config)?
+    };
+    let (msg, msg_is_utf8) = split_bom(take_msg(rest, config), config);
+    let mut m = SyslogMessageRef {
+        msg_is_utf8,
+        sd,
+        msg: Cow::Borrowed(msg),
+        truncated,
+        ..header.into()
+    };
+    m.encode_fields(config);
+    Ok((m, raw_sd))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// What to do with input which isn't valid UTF-8
+pub enum Utf8Policy {
+    /// Fail with `ErrorKind::InvalidUtf8`, blamed on whichever field the invalid bytes are in
+    Strict,
+    /// Replace any invalid sequences with U+FFFD REPLACEMENT CHARACTER
+    Lossy,
+    /// Keep the original bytes of MSG in `SyslogMessage::msg_bytes` (with a lossy copy in `msg`)
+    ///
+    /// The header fields must still be valid UTF-8; only MSG is allowed to be arbitrary bytes.
+    Preserve,
+}
+
+/// Parse a byte slice into a `SyslogMessage` object
 ///
-///  * `ParseErr` if the string is not parseable as an RFC5424 message
+/// Many devices put Latin-1 or other arbitrary bytes into MSG, so this lets you pick what happens
+/// if the input isn't UTF-8 rather than having to convert it yourself first.
 ///
 /// # Example
 ///
 /// ```
-/// use syslog_rfc5424::parse_message;
+/// use syslog_rfc5424::parser::{parse_message_bytes, Utf8Policy};
 ///
-/// let message = parse_message("<78>1 2016-01-15T00:04:01+00:00 host1 CROND 10391 - [meta sequenceId=\"29\"] some_message").unwrap();
+/// let input = b"<1>1 - host app - - - caf\xe9";
+/// assert!(parse_message_bytes(input, Utf8Policy::Strict).is_err());
 ///
-/// assert!(message.hostname.unwrap() == "host1");
+/// let message = parse_message_bytes(input, Utf8Policy::Preserve).unwrap();
+/// assert_eq!(message.msg, "caf\u{fffd}");
+/// assert_eq!(message.msg_bytes, Some(b"caf\xe9".to_vec()));
 /// ```
-pub fn parse_message<S: AsRef<str>>(s: S) -> ParseResult<SyslogMessage> {
-    parse_message_s(s.as_ref())
+pub fn parse_message_bytes(b: &[u8], policy: Utf8Policy) -> ParseResult<SyslogMessage> {
+    parse_message_bytes_with(&ParserConfig::default(), b, policy)
+}
+
+/// Parse a byte slice into a `SyslogMessage` object, using the given `ParserConfig`
+pub fn parse_message_bytes_with(
+    config: &ParserConfig,
+    b: &[u8],
+    policy: Utf8Policy,
+) -> ParseResult<SyslogMessage> {
+    config.observe(b.len(), parse_bytes(config, b, policy))
+}
+
+fn parse_bytes(config: &ParserConfig, b: &[u8], policy: Utf8Policy) -> ParseResult<SyslogMessage> {
+    // before any lossy conversion, which can make the input longer
+    if let Err(ErrorKind::LimitExceeded(what, limit)) =
+        check_limit("message length", b.len(), config.max_message_length)
+    {
+        return Err(ParseErr::TooLong { what, limit });
+    }
+    let mut b = Cow::Borrowed(b);
+    let err = loop {
+        let err = match str::from_utf8(&b) {
+            Ok(s) => return parse_owned(s, config),
+            Err(e) => e,
+        };
+        match repair_field(&b, err, config) {
+            Some(repaired) => b = Cow::Owned(repaired),
+            None => break err,
+        }
+    };
+    let b = &b[..];
+    match policy {
+        Utf8Policy::Strict => {
+            let (_, spans) = parse_valid_prefix(b, err, config)?;
+            Err(ParseErr::Msg {
+                offset: spans.msg.start,
+                kind: ErrorKind::InvalidUtf8(err),
+            })
+        }
+        Utf8Policy::Lossy => parse_owned(&String::from_utf8_lossy(b), config),
+        Utf8Policy::Preserve => {
+            let (m, _) = parse_valid_prefix(b, err, config)?;
+            let valid = &b[..err.valid_up_to()];
+            let msg_start = m.msg.as_ptr() as usize - valid.as_ptr() as usize;
+            let msg_bytes = b[msg_start..].to_vec();
+            let mut m = finish_owned(m, config)?;
+            m.msg = String::from_utf8_lossy(&msg_bytes).into_owned();
+            m.msg_bytes = Some(msg_bytes);
+            Ok(m)
+        }
+    }
+}
+
+/// If the first invalid UTF-8 in `b` is in a field with a `FieldUtf8Policy` which allows it,
+/// return a copy of `b` with that field's bytes made valid
+fn repair_field(b: &[u8], err: str::Utf8Error, config: &ParserConfig) -> Option<Vec<u8>> {
+    // safe to unwrap: everything up to `valid_up_to` is valid
+    let valid = str::from_utf8(&b[..err.valid_up_to()]).unwrap();
+    let mut spans = FieldSpans::default();
+    match parse_message_spanned_inner(valid, config, &mut spans) {
+        Err(Failure {
+            kind: ErrorKind::UnexpectedEndOfInput,
+            ..
+        }) => {}
+        _ => return None,
+    }
+    let (field, start) = spans.last_field();
+    let end = memchr::memchr(b' ', &b[start..]).map_or(b.len(), |i| start + i);
+    let token = &b[start..end];
+    let repaired = match config.field_utf8_policy(field) {
+        FieldUtf8Policy::Reject => return None,
+        FieldUtf8Policy::Replace => String::from_utf8_lossy(token).into_owned(),
+        FieldUtf8Policy::PercentEncode => percent_encode(token),
+    };
+    let mut fixed = Vec::with_capacity(b.len() + repaired.len());
+    fixed.extend_from_slice(&b[..start]);
+    fixed.extend_from_slice(repaired.as_bytes());
+    fixed.extend_from_slice(&b[end..]);
+    Some(fixed)
+}
+
+/// Parse the input up to the first invalid UTF-8 in it, which is at `err`
+///
+/// If all of the bad bytes are in MSG, then everything before the first one is a complete message
+/// with a (possibly truncated) MSG, and tells us where MSG starts. Otherwise, the header is cut
+/// off part of the way through the field with the bad bytes, and that field gets the blame.
+fn parse_valid_prefix<'a>(
+    b: &'a [u8],
+    err: str::Utf8Error,
+    config: &ParserConfig,
+) -> ParseResult<(SyslogMessageRef<'a>, FieldSpans)> {
+    // safe to unwrap: everything up to `valid_up_to` is valid
+    let valid = str::from_utf8(&b[..err.valid_up_to()]).unwrap();
+    let mut spans = FieldSpans::default();
+    match parse_message_spanned_inner(valid, config, &mut spans) {
+        // the bad bytes come straight after STRUCTURED-DATA, without the SP before MSG
+        Ok(_) if spans.sd.end == valid.len() => {
+            let expected = if valid.ends_with(']') { '[' } else { ' ' };
+            Err(ParseErr::new(
+                valid,
+                MessageField::StructuredData,
+                spans.sd.start,
+                ErrorKind::ExpectedToken(expected).into(),
+            ))
+        }
+        Ok(m) => Ok((m, spans)),
+        Err(Failure {
+            kind: ErrorKind::UnexpectedEndOfInput,
+            ..
+        }) => Err(spans.error(valid, ErrorKind::InvalidUtf8(err).into())),
+        Err(failure) => Err(spans.error(valid, failure)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
+    use std::error::Error;
     use std::mem;
 
-    use super::{parse_message, ParseErr};
+    use super::{
+        parse_header, parse_header_with, parse_message, parse_message_bytes,
+        parse_message_bytes_with, parse_message_lazy, parse_message_lossy, parse_message_with,
+        parse_message_with_raw, parse_messages, parse_rfc3339_timestamp, parse_structured_data,
+        parse_structured_data_with, ErrorClass, ErrorKind, FieldUtf8Policy, LeapSecondPolicy,
+        MessageField, ParseErr, ParserConfig, Quirks, Utf8Policy,
+    };
+    use crate::borrowed::{ProcIdRef, SyslogMessageRef};
     use crate::message;
 
     use crate::facility::SyslogFacility;
@@ -434,33 +2615,42 @@ mod tests {
     fn test_with_time_zulu() {
         let msg = parse_message("<1>1 2015-01-01T00:00:00Z host - - - -")
             .expect("Should parse empty message");
-        assert_eq!(msg.timestamp, Some(1420070400));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1420070400));
     }
 
     #[test]
     fn test_with_time_offset() {
         let msg = parse_message("<1>1 2015-01-01T00:00:00+00:00 - - - - -")
             .expect("Should parse empty message");
-        assert_eq!(msg.timestamp, Some(1420070400));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1420070400));
     }
 
     #[test]
     fn test_with_time_offset_nonzero() {
         let msg = parse_message("<1>1 2015-01-01T00:00:00-10:00 - - - - -")
             .expect("Should parse empty message");
-        assert_eq!(msg.timestamp, Some(1420106400));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1420106400));
+        assert_eq!(msg.timestamp.map(|t| t.offset), Some(-36000));
         // example from RFC 3339
         let msg1 = parse_message("<1>1 2015-01-01T18:50:00-04:00 - - - - -")
             .expect("Should parse empty message");
         let msg2 = parse_message("<1>1 2015-01-01T22:50:00Z - - - - -")
             .expect("Should parse empty message");
-        assert_eq!(msg1.timestamp, msg2.timestamp);
+        assert_eq!(
+            msg1.timestamp.map(|t| t.secs),
+            msg2.timestamp.map(|t| t.secs)
+        );
         // example with fractional minutes
         let msg1 = parse_message("<1>1 2019-01-20T00:46:39+05:45 - - - - -")
             .expect("Should parse empty message");
         let msg2 = parse_message("<1>1 2019-01-19T11:01:39-08:00 - - - - -")
             .expect("Should parse empty message");
-        assert_eq!(msg1.timestamp, msg2.timestamp);
+        assert_eq!(
+            msg1.timestamp.map(|t| t.secs),
+            msg2.timestamp.map(|t| t.secs)
+        );
+        assert_eq!(msg1.timestamp.map(|t| t.offset), Some(5 * 3600 + 45 * 60));
+        assert_eq!(msg2.timestamp.map(|t| t.offset), Some(-8 * 3600));
     }
 
     #[test]
@@ -472,7 +2662,7 @@ mod tests {
         assert_eq!(msg.appname, Some(String::from("CROND")));
         assert_eq!(msg.procid, Some(message::ProcId::PID(10391)));
         assert_eq!(msg.msg, String::from("some_message"));
-        assert_eq!(msg.timestamp, Some(1452816241));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1452816241));
         assert_eq!(msg.sd.len(), 1);
         let v = msg
             .sd
@@ -493,7 +2683,7 @@ mod tests {
         assert_eq!(msg.appname, Some(String::from("CROND")));
         assert_eq!(msg.procid, Some(message::ProcId::PID(10391)));
         assert_eq!(msg.msg, String::from("some_message"));
-        assert_eq!(msg.timestamp, Some(1452816241));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1452816241));
         assert_eq!(msg.sd.len(), 1);
         assert_eq!(
             msg.sd
@@ -513,7 +2703,7 @@ mod tests {
         assert_eq!(msg.appname, Some(String::from("CROND")));
         assert_eq!(msg.procid, Some(message::ProcId::PID(10391)));
         assert_eq!(msg.msg, String::from("some_message"));
-        assert_eq!(msg.timestamp, Some(1452816241));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1452816241));
         assert_eq!(msg.sd.len(), 2);
         assert_eq!(
             msg.sd.find_sdid("meta").expect("should contain meta").len(),
@@ -545,6 +2735,30 @@ mod tests {
         assert!(msg.is_err());
     }
 
+    #[test]
+    fn test_bad_time_offset() {
+        // found by fuzzing; these used to panic rather than fail
+        for input in &[
+            "<1>1 2015-01-01T00:00:00-0",
+            "<1>1 2015-01-01T00:00:00+05:",
+            "<1>1 2015-01-01T00:00:00+05:\u{e9}0 - - - - -",
+            "<1>1 2015-01-01T00:00:00-08x00 - - - - -",
+        ] {
+            let err = parse_message(input).expect_err("should fail");
+            assert!(
+                matches!(
+                    err,
+                    ParseErr::Timestamp {
+                        kind: ErrorKind::InvalidOffset,
+                        ..
+                    }
+                ),
+                "{:?}",
+                input
+            );
+        }
+    }
+
     #[test]
     fn test_bad_match() {
         // we shouldn't be able to parse RFC3164 messages
@@ -552,32 +2766,67 @@ mod tests {
         assert!(msg.is_err());
     }
 
+    #[test]
+    fn test_parse_rfc3339_timestamp() {
+        let ts = parse_rfc3339_timestamp("1985-04-12T23:20:50.123456789Z").expect("should parse");
+        assert_eq!((ts.secs, ts.nanos, ts.offset), (482196050, 123_456_789, 0));
+        let ts = parse_rfc3339_timestamp("1985-04-12T19:20:50.52-04:00").unwrap();
+        assert_eq!(
+            (ts.secs, ts.nanos, ts.offset),
+            (482196050, 520_000_000, -4 * 3600)
+        );
+
+        assert!(matches!(
+            parse_rfc3339_timestamp("-").unwrap_err().kind(),
+            Some(ErrorKind::Nil)
+        ));
+        assert!(matches!(
+            parse_rfc3339_timestamp("1985-04-12T23:20:50Z host").unwrap_err(),
+            ParseErr::Timestamp {
+                kind: ErrorKind::UnexpectedChar(' '),
+                ..
+            }
+        ));
+        assert!(parse_rfc3339_timestamp("").is_err());
+        assert!(parse_rfc3339_timestamp("1985-04-12").is_err());
+        assert!(parse_rfc3339_timestamp("Oct 11 22:14:15").is_err());
+    }
+
     #[test]
     fn test_example_timestamps() {
         // these are the example timestamps in the rfc
 
         let msg = parse_message("<1>1 1985-04-12T23:20:50.52Z host - - - -")
             .expect("Should parse empty message");
-        assert_eq!(msg.timestamp, Some(482196050));
-        assert_eq!(msg.timestamp_nanos, Some(520000000));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(482196050));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(520000000));
 
         let msg = parse_message("<1>1 1985-04-12T19:20:50.52+04:00 host - - - -")
             .expect("Should parse empty message");
-        assert_eq!(msg.timestamp, Some(482167250));
-        assert_eq!(msg.timestamp_nanos, Some(520000000));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(482167250));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(520000000));
 
         let msg = parse_message("<1>1 1985-04-12T19:20:50+04:00 host - - - -")
             .expect("Should parse empty message");
-        assert_eq!(msg.timestamp, Some(482167250));
-        assert_eq!(msg.timestamp_nanos, Some(0));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(482167250));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(0));
 
         let msg = parse_message("<1>1 2003-08-24T05:14:15.000003+07:00 host - - - -")
             .expect("Should parse empty message");
-        assert_eq!(msg.timestamp, Some(1061676855));
-        assert_eq!(msg.timestamp_nanos, Some(3000));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1061676855));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(3000));
+
+        // more precision than the RFC allows, but we might as well keep it
+        let msg = parse_message("<1>1 2003-08-24T05:14:15.000000003+07:00 host - - - -")
+            .expect("Should parse empty message");
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1061676855));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(3));
+        assert!(!msg.timestamp_precision_lost);
 
-        let msg = parse_message("<1>1 2003-08-24T05:14:15.000000003+07:00 host - - - -");
-        assert!(msg.is_err(), "expected parse fail");
+        let msg = parse_message("<1>1 2003-08-24T05:14:15.1234567891234+07:00 host - - - -")
+            .expect("Should parse empty message");
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(123456789));
+        assert!(msg.timestamp_precision_lost);
     }
 
     #[test]
@@ -589,8 +2838,8 @@ mod tests {
         assert_eq!(msg.appname, Some(String::from("mgd")));
         assert_eq!(msg.procid, Some(message::ProcId::PID(13894)));
         assert_eq!(msg.msg, String::from(""));
-        assert_eq!(msg.timestamp, Some(1526286181));
-        assert_eq!(msg.timestamp_nanos, Some(520000000));
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1526286181));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(520000000));
         assert_eq!(msg.sd.len(), 1);
         let sd = msg
             .sd
@@ -605,7 +2854,7 @@ mod tests {
             expected
                 .into_iter()
                 .map(|(k, v)| (k.to_string(), v.to_string()))
-                .collect::<BTreeMap<_, _>>()
+                .collect::<message::StructuredDataElement>()
         };
         assert_eq!(sd, &expected);
     }
@@ -628,13 +2877,805 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bytes_utf8_policies() {
+        let input = b"<1>1 - host app - - [meta a=\"b\"] caf\xe9 \xff";
+        let err = parse_message_bytes(input, Utf8Policy::Strict).expect_err("should fail");
+        match err {
+            ParseErr::Msg {
+                offset,
+                kind: ErrorKind::InvalidUtf8(e),
+            } => {
+                assert_eq!(offset, 33);
+                assert_eq!(e.valid_up_to(), 36);
+            }
+            other => panic!("expected an error in MSG, got {:?}", other),
+        }
+
+        let msg = parse_message_bytes(input, Utf8Policy::Lossy).expect("should parse");
+        assert_eq!(msg.msg, "caf\u{fffd} \u{fffd}");
+        assert_eq!(msg.msg_bytes, None);
+
+        let msg = parse_message_bytes(input, Utf8Policy::Preserve).expect("should parse");
+        assert_eq!(msg.hostname, Some(String::from("host")));
+        assert_eq!(msg.sd.find_tuple("meta", "a"), Some(&String::from("b")));
+        assert_eq!(msg.msg, "caf\u{fffd} \u{fffd}");
+        assert_eq!(msg.msg_bytes, Some(b"caf\xe9 \xff".to_vec()));
+
+        // valid input doesn't get msg_bytes, whatever the policy
+        let msg = parse_message_bytes(b"<1>1 - - - - - - hi", Utf8Policy::Preserve)
+            .expect("should parse");
+        assert_eq!(msg.msg, "hi");
+        assert_eq!(msg.msg_bytes, None);
+    }
+
+    #[test]
+    fn test_bytes_preserve_needs_space() {
+        let err = parse_message_bytes(b"<1>1 - - - - - [a@1 b=\"c\"]\xffhi", Utf8Policy::Preserve)
+            .expect_err("should fail");
+        assert!(matches!(
+            err,
+            ParseErr::Sd {
+                offset: 15,
+                kind: ErrorKind::ExpectedToken('['),
+                ..
+            }
+        ));
+        // the same as if the byte were valid
+        assert!(matches!(
+            parse_message("<1>1 - - - - - [a@1 b=\"c\"]xhi").unwrap_err(),
+            ParseErr::Sd {
+                offset: 15,
+                kind: ErrorKind::ExpectedToken('['),
+                ..
+            }
+        ));
+        let msg = parse_message_bytes(b"<1>1 - - - - - [a@1 b=\"c\"] \xffhi", Utf8Policy::Preserve)
+            .expect("should parse");
+        assert_eq!(msg.msg_bytes, Some(b"\xffhi".to_vec()));
+    }
+
+    #[test]
+    fn test_bytes_preserve_bad_header() {
+        let err = parse_message_bytes(b"<1>1 - h\xffst app - - - msg", Utf8Policy::Preserve)
+            .expect_err("should fail");
+        assert!(matches!(
+            err,
+            ParseErr::Hostname {
+                offset: 7,
+                kind: ErrorKind::InvalidUtf8(_),
+                ..
+            }
+        ));
+        let err = parse_message_bytes(b"<1>1 - h\xffst app - - - msg", Utf8Policy::Strict)
+            .expect_err("should fail");
+        assert!(matches!(err, ParseErr::Hostname { .. }));
+        // other problems in the header come first
+        let err = parse_message_bytes(b"<1>x - h\xffst app - - - msg", Utf8Policy::Preserve)
+            .expect_err("should fail");
+        assert!(matches!(
+            err,
+            ParseErr::Version {
+                kind: ErrorKind::TooFewDigits,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_field_utf8_policy() {
+        let input = "<1>1 - caf\u{e9} app - - - hi";
+        assert!(matches!(
+            parse_message(input).unwrap_err(),
+            ParseErr::Hostname {
+                kind: ErrorKind::ExpectedToken(' '),
+                ..
+            }
+        ));
+        let replace = ParserConfig::new()
+            .with_field_utf8_policy(MessageField::Hostname, FieldUtf8Policy::Replace);
+        let msg = parse_message_with(&replace, input).expect("should parse");
+        assert_eq!(msg.hostname.as_deref(), Some("caf\u{e9}"));
+        let encode = ParserConfig::new()
+            .with_field_utf8_policy(MessageField::Hostname, FieldUtf8Policy::PercentEncode);
+        let msg = parse_message_with(&encode, input).expect("should parse");
+        assert_eq!(msg.hostname.as_deref(), Some("caf%C3%A9"));
+        assert_eq!(msg.appname.as_deref(), Some("app"));
+        // only the configured field is relaxed
+        assert!(parse_message_with(&encode, "<1>1 - host caf\u{e9} - - - hi").is_err());
+
+        let (msg, warnings) = parse_message_lossy(&encode, input);
+        assert!(warnings.is_empty());
+        assert_eq!(msg.hostname.as_deref(), Some("caf%C3%A9"));
+        let lazy = parse_message_lazy(&encode, input).expect("should parse");
+        assert_eq!(lazy.message().hostname.as_deref(), Some("caf%C3%A9"));
+
+        let input = b"<1>1 - caf\xe9 app - - - hi";
+        assert!(parse_message_bytes(input, Utf8Policy::Strict).is_err());
+        let msg = parse_message_bytes_with(&replace, input, Utf8Policy::Strict).unwrap();
+        assert_eq!(msg.hostname.as_deref(), Some("caf\u{fffd}"));
+        let msg = parse_message_bytes_with(&encode, input, Utf8Policy::Strict).unwrap();
+        assert_eq!(msg.hostname.as_deref(), Some("caf%E9"));
+
+        // MSG is still up to the Utf8Policy
+        let input = b"<1>1 - caf\xe9 app - - - h\xefi";
+        assert!(parse_message_bytes_with(&encode, input, Utf8Policy::Strict).is_err());
+        let msg = parse_message_bytes_with(&encode, input, Utf8Policy::Preserve).unwrap();
+        assert_eq!(msg.hostname.as_deref(), Some("caf%E9"));
+        assert_eq!(msg.msg_bytes, Some(b"h\xefi".to_vec()));
+    }
+
+    #[test]
+    fn test_lenient_missing_version() {
+        let config = ParserConfig::new().with_lenient(true);
+        for input in &[
+            "<34> 2003-10-11T22:14:15.003Z mymachine su - ID47 - hi",
+            "<34>2003-10-11T22:14:15.003Z mymachine su - ID47 - hi",
+        ] {
+            assert!(parse_message(input).is_err());
+            let msg = parse_message_with(&config, input).expect("should parse leniently");
+            assert_eq!(msg.version, 1);
+            assert_eq!(msg.timestamp.map(|t| t.secs), Some(1065910455));
+            assert_eq!(msg.hostname, Some(String::from("mymachine")));
+            assert_eq!(msg.msgid, Some(String::from("ID47")));
+            assert_eq!(msg.msg, "hi");
+        }
+    }
+
+    #[test]
+    fn test_lenient_version_without_space() {
+        let config = ParserConfig::new().with_lenient(true);
+        let input = "<34>1- host su - - - hi";
+        assert!(parse_message(input).is_err());
+        let msg = parse_message_with(&config, input).expect("should parse leniently");
+        assert_eq!(msg.version, 1);
+        assert!(msg.timestamp.is_none());
+        assert_eq!(msg.hostname.as_deref(), Some("host"));
+        assert_eq!(msg.msg, "hi");
+
+        let input = "<34>12003-10-11T22:14:15.003Z mymachine su - ID47 - hi";
+        assert!(parse_message(input).is_err());
+        let msg = parse_message_with(&config, input).expect("should parse leniently");
+        assert_eq!(msg.version, 1);
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1065910455));
+        assert_eq!(msg.hostname.as_deref(), Some("mymachine"));
+
+        let msg = parse_message_with(&config, "<34>22003-10-11T22:14:15Z host su - - - hi")
+            .expect("should parse leniently");
+        assert_eq!(msg.version, 2);
+    }
+
+    #[test]
+    fn test_lenient_rfc3164_timestamp() {
+        let config = ParserConfig::new().with_lenient(true);
+        let input = "<34>1 Jan  2 03:04:05 mymachine su - - - hi";
+        assert!(parse_message(input).is_err());
+        let msg = parse_message_with(&config, input).expect("should parse leniently");
+        let ts = time::OffsetDateTime::from_unix_timestamp(msg.timestamp.unwrap().secs).unwrap();
+        assert_eq!(ts.month(), time::Month::January);
+        assert_eq!(ts.day(), 2);
+        assert_eq!((ts.hour(), ts.minute(), ts.second()), (3, 4, 5));
+        assert!(ts <= time::OffsetDateTime::now_utc() + time::Duration::DAY);
+        assert_eq!(msg.hostname, Some(String::from("mymachine")));
+
+        let msg = parse_message_with(&config, "<34>Oct 11 22:14:15 mymachine su - - - hi")
+            .expect("should parse leniently");
+        assert_eq!(msg.version, 1);
+        assert_eq!(msg.appname, Some(String::from("su")));
+    }
+
+    #[test]
+    fn test_lenient_missing_space_and_lowercase() {
+        let config = ParserConfig::new().with_lenient(true);
+        let input = "<34>1 2003-10-11t22:14:15.003zmymachine su - ID47 - hi";
+        assert!(parse_message(input).is_err());
+        let msg = parse_message_with(&config, input).expect("should parse leniently");
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1065910455));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(3000000));
+        assert_eq!(msg.hostname, Some(String::from("mymachine")));
+    }
+
+    #[test]
+    fn test_lenient_comma_secfrac() {
+        let config = ParserConfig::new().with_lenient(true);
+        let input = "<34>1 2024-01-02T03:04:05,123Z host su - - - hi";
+        assert!(matches!(
+            parse_message(input),
+            Err(ParseErr::Timestamp { .. })
+        ));
+        let msg = parse_message_with(&config, input).expect("should parse leniently");
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1704164645));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(123000000));
+        assert_eq!(msg.hostname.as_deref(), Some("host"));
+        assert!(msg.to_string().contains("2024-01-02T03:04:05.123Z host"));
+    }
+
+    #[test]
+    fn test_leap_second() {
+        let input = "<1>1 2016-12-31T23:59:60.5Z host - - - -";
+        let msg = parse_message(input).expect("should parse leap second");
+        assert!(msg.leap_second);
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1483228799));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(999_999_999));
+
+        let config = ParserConfig::new().with_leap_second_policy(LeapSecondPolicy::Rollover);
+        let msg = parse_message_with(&config, input).expect("should parse leap second");
+        assert!(msg.leap_second);
+        assert_eq!(msg.timestamp.map(|t| t.secs), Some(1483228800));
+        assert_eq!(msg.timestamp.map(|t| t.nanos), Some(500_000_000));
+
+        let config = ParserConfig::new().with_leap_second_policy(LeapSecondPolicy::Reject);
+        let err = parse_message_with(&config, input).expect_err("should reject leap second");
+        assert!(matches!(err.kind(), Some(ErrorKind::InvalidDate(_))));
+
+        let msg = parse_message("<1>1 2016-12-31T23:59:59Z host - - - -").expect("should parse");
+        assert!(!msg.leap_second);
+        assert!(parse_message("<1>1 2016-12-31T23:59:61Z host - - - -").is_err());
+
+        // leap seconds only happen in the last minute of a UTC day
+        let msg =
+            parse_message("<1>1 2017-01-01T05:29:60+05:30 host - - - -").expect("should parse");
+        assert!(msg.leap_second);
+        for input in [
+            "<1>1 2016-06-15T12:30:60Z host - - - -",
+            "<1>1 2016-06-15T12:59:60Z host - - - -",
+            "<1>1 2016-12-31T23:59:60+01:00 host - - - -",
+        ] {
+            let err = parse_message(input).expect_err("should reject leap second");
+            assert!(matches!(err.kind(), Some(ErrorKind::InvalidDate(_))));
+        }
+    }
+
+    #[test]
+    fn test_bom() {
+        let msg = parse_message("<1>1 - - - - - - \u{feff}hello").expect("should parse");
+        assert!(msg.msg_is_utf8);
+        assert_eq!(msg.msg, "hello");
+
+        let config = ParserConfig::new().with_strip_bom(false);
+        let msg =
+            parse_message_with(&config, "<1>1 - - - - - - \u{feff}hello").expect("should parse");
+        assert!(msg.msg_is_utf8);
+        assert_eq!(msg.msg, "\u{feff}hello");
+
+        let msg = parse_message("<1>1 - - - - - - hello").expect("should parse");
+        assert!(!msg.msg_is_utf8);
+        assert_eq!(msg.msg, "hello");
+
+        let msg = parse_message_bytes(
+            b"<1>1 - - - - - - \xef\xbb\xbfcaf\xe9",
+            Utf8Policy::Preserve,
+        )
+        .expect("should parse");
+        assert!(msg.msg_is_utf8);
+        assert_eq!(msg.msg_bytes, Some(b"caf\xe9".to_vec()));
+    }
+
+    #[test]
+    fn test_raw_spans() {
+        let input =
+            r#"<78>1 2016-01-15T00:04:01Z host1 CROND 10391 - [meta sequenceId="29"] some_message"#;
+        let raw = parse_message_with_raw(&ParserConfig::default(), input).expect("should parse");
+        assert_eq!(raw.raw, input);
+        assert_eq!(raw.message, parse_message(input).unwrap());
+        let fields = [
+            (&raw.spans.pri, "<78>"),
+            (&raw.spans.version, "1"),
+            (&raw.spans.timestamp, "2016-01-15T00:04:01Z"),
+            (&raw.spans.hostname, "host1"),
+            (&raw.spans.appname, "CROND"),
+            (&raw.spans.procid, "10391"),
+            (&raw.spans.msgid, "-"),
+            (&raw.spans.sd, r#"[meta sequenceId="29"]"#),
+            (&raw.spans.msg, "some_message"),
+        ];
+        for (span, expected) in fields.iter() {
+            assert_eq!(raw.raw_field(span), *expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let input = "<1>1 - host1 - - - - one\r\n\n<1>1 - host2 - - - - two\n<1>x - - - - - bad\n continued\n<1>1 - host3 - - - - three";
+        let results = parse_messages(input).collect::<Vec<_>>();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().msg, "one");
+        assert_eq!(results[1].as_ref().unwrap().msg, "two");
+        assert!(results[2].is_err());
+        assert_eq!(
+            results[3].as_ref().unwrap().hostname.as_deref(),
+            Some("host3")
+        );
+
+        let config = ParserConfig::new().with_lenient(true);
+        let results = parse_messages("<1>Oct 11 22:14:15 host - - - - hi\n")
+            .with_config(config)
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_ok());
+        assert_eq!(parse_messages("\n\n").count(), 0);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parse_messages_par() {
+        let lines = (0..1000)
+            .map(|i| {
+                if i % 10 == 0 {
+                    format!("<1>x - host{} - - - - bad", i)
+                } else {
+                    format!("<1>1 - host{} - - - - msg {}", i, i)
+                }
+            })
+            .collect::<Vec<_>>();
+        let results = super::parse_messages_par(&lines);
+        assert_eq!(results.len(), lines.len());
+        for (line, result) in lines.iter().zip(&results) {
+            match (result, parse_message(line)) {
+                (Ok(m), Ok(expected)) => assert_eq!(m, &expected),
+                (Err(_), Err(_)) => {}
+                (result, expected) => panic!("{:?} != {:?}", result, expected),
+            }
+        }
+
+        let config = ParserConfig::new().with_lenient(true);
+        let results =
+            super::parse_messages_par_with(&config, &["<1>Oct 11 22:14:15 host - - - - hi"]);
+        assert!(results[0].is_ok());
+        assert!(super::parse_messages_par::<&str>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_facility() {
+        let input = "<203>1 - host - - - - hi";
+        let err = parse_message(input).expect_err("should reject facility 25");
+        assert!(matches!(
+            err,
+            ParseErr::Pri {
+                kind: ErrorKind::BadFacility,
+                ..
+            }
+        ));
+
+        let config = ParserConfig::new().with_unknown_facility(Some(SyslogFacility::LOG_USER));
+        let msg = parse_message_with(&config, input).expect("should parse");
+        assert_eq!(msg.facility, SyslogFacility::LOG_USER);
+        assert_eq!(msg.severity, SyslogSeverity::SEV_ERR);
+        let msg = parse_message_with(&config, "<165>1 - - - - - -").expect("should parse");
+        assert_eq!(msg.facility, SyslogFacility::LOG_LOCAL4);
+    }
+
+    #[test]
+    fn test_max_version() {
+        let input = "<34>2 2016-01-15T00:04:01Z host1 su - - - hi";
+        let msg = parse_message(input).expect("should parse");
+        assert_eq!(msg.version, 2);
+        assert_eq!(msg.hostname.as_deref(), Some("host1"));
+
+        let config = ParserConfig::new().with_max_version(Some(1));
+        let err = parse_message_with(&config, input).expect_err("should reject version 2");
+        assert!(matches!(err.kind(), Some(ErrorKind::UnsupportedVersion(2))));
+        assert_eq!(err.offset(), Some(4));
+        let msg = parse_message_with(&config, "<34>1 - host1 su - - - hi").expect("should parse");
+        assert_eq!(msg.version, 1);
+
+        let (msg, warnings) = parse_message_lossy(&config, input);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(msg.hostname.as_deref(), Some("host1"));
+    }
+
+    #[test]
+    fn test_preserve_sd_order() {
+        let input = r#"<1>1 - - - - - [zzz b="1" a="2"][aaa c="3"] hi"#;
+        let config = ParserConfig::new().with_preserve_sd_order(true);
+        let msg = parse_message_with(&config, input).expect("should parse");
+        assert!(msg.sd.preserves_order());
+        assert_eq!(msg.to_string(), input);
+
+        let msg = parse_message(input).expect("should parse");
+        assert!(!msg.sd.preserves_order());
+        assert_eq!(
+            msg.to_string(),
+            r#"<1>1 - - - - - [aaa c="3"][zzz a="2" b="1"] hi"#
+        );
+    }
+
+    #[test]
+    fn test_quirks_optional_sd() {
+        let input = "<78>1 2016-01-15T00:04:01Z host1 CROND 10391 - some message\n";
+        let err = parse_message(input).expect_err("should not parse without quirks");
+        assert!(matches!(
+            err,
+            ParseErr::Sd {
+                kind: ErrorKind::ExpectedToken('['),
+                ..
+            }
+        ));
+        let config = ParserConfig::new().with_quirks(Quirks::Rsyslog);
+        let msg = parse_message_with(&config, input).expect("should parse with quirks");
+        assert!(msg.sd.is_empty());
+        assert_eq!(msg.msg, "some message");
+
+        // well-formed structured data is still parsed
+        let input = r#"<78>1 - host1 CROND - - [meta sequenceId="29"] hi"#;
+        let msg = parse_message_with(&config, input).expect("should parse with quirks");
+        assert_eq!(
+            msg.sd.find_tuple("meta", "sequenceId"),
+            Some(&String::from("29"))
+        );
+        assert_eq!(msg.msg, "hi");
+    }
+
+    #[test]
+    fn test_quirks_cisco_sequence_numbers() {
+        let config = ParserConfig::new().with_quirks(Quirks::CiscoIos);
+        for input in &[
+            "000123: <189>1 *2016-01-15T00:04:01Z router - - - - %SYS-5-CONFIG_I",
+            "<189>123: 1 .2016-01-15T00:04:01Z router - - - - %SYS-5-CONFIG_I",
+            "<189>123: *Jan 15 00:04:01 router - - - - %SYS-5-CONFIG_I",
+        ] {
+            let msg = parse_message_with(&config, input).expect("should parse with quirks");
+            assert_eq!(msg.facility, SyslogFacility::LOG_LOCAL7);
+            assert_eq!(msg.hostname.as_deref(), Some("router"));
+            assert_eq!(msg.msg, "%SYS-5-CONFIG_I");
+        }
+    }
+
+    #[test]
+    fn test_quirks_nxlog_whitespace() {
+        let config = ParserConfig::new().with_quirks(Quirks::Nxlog);
+        let input = "\r\n <14>1 - host app - - message\r\n";
+        let raw = parse_message_with_raw(&config, input).expect("should parse with quirks");
+        assert_eq!(raw.message.msg, "message");
+        assert_eq!(raw.raw_field(&raw.spans.pri), "<14>");
+        assert_eq!(raw.raw_field(&raw.spans.msg), "message");
+    }
+
+    #[test]
+    fn test_lossy() {
+        let config = ParserConfig::default();
+        let input = r#"<78>1 2016-01-15T00:04:01Z host1 CROND 10391 - [meta sequenceId="29"] msg"#;
+        let (msg, warnings) = parse_message_lossy(&config, input);
+        assert!(warnings.is_empty());
+        assert_eq!(msg, parse_message(input).unwrap());
+
+        let input = r#"<78>x 2016-13-15T00:04:01Z host1 CROND 10391 - [a b="c"][meta sequenceId="29] some message"#;
+        let (msg, warnings) = parse_message_lossy(&config, input);
+        let fields = warnings.iter().map(|w| w.field).collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                MessageField::Version,
+                MessageField::Timestamp,
+                MessageField::StructuredData
+            ]
+        );
+        assert_eq!(warnings[1].offset, 6);
+        assert_eq!(msg.facility, SyslogFacility::LOG_CRON);
+        assert_eq!(msg.version, 1);
+        assert_eq!(msg.timestamp, None);
+        assert_eq!(msg.hostname.as_deref(), Some("host1"));
+        assert_eq!(msg.procid, Some(message::ProcId::PID(10391)));
+        assert_eq!(msg.sd.find_tuple("a", "b"), Some(&String::from("c")));
+        assert_eq!(msg.msg, "some message");
+    }
+
+    #[test]
+    fn test_lossy_garbage() {
+        let config = ParserConfig::default();
+        let (msg, warnings) = parse_message_lossy(&config, "just some text");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].field, MessageField::Pri);
+        assert_eq!(msg.facility, SyslogFacility::LOG_USER);
+        assert_eq!(msg.severity, SyslogSeverity::SEV_NOTICE);
+        assert_eq!(msg.msg, "just some text");
+
+        let (msg, warnings) = parse_message_lossy(&config, "<999>1 - host app");
+        let fields = warnings.iter().map(|w| w.field).collect::<Vec<_>>();
+        assert_eq!(fields, vec![MessageField::Pri, MessageField::AppName]);
+        assert_eq!(msg.hostname.as_deref(), Some("host"));
+        assert_eq!(msg.appname, None);
+        assert_eq!(msg.msg, "");
+    }
+
+    #[test]
+    fn test_strict() {
+        let input = r#"<1>1 - host app 12 ID47 [this-sd-id-is-much-too-long-for-the-rfc a="b"] hi"#;
+        parse_message(input).expect("should parse");
+        let config = ParserConfig::new().with_strict(true);
+        match parse_message_with(&config, input) {
+            Err(ParseErr::Nonconformant(v)) => assert_eq!(v.len(), 1),
+            other => panic!("expected Nonconformant, got {:?}", other),
+        }
+        parse_message_with(&config, "<1>1 - host app 12 ID47 - hi").expect("should parse");
+    }
+
+    #[test]
+    fn test_parse_structured_data() {
+        let sd =
+            parse_structured_data(r#"[a@1 x="1" y="\]"][b@1][a@1 x="2"]"#).expect("should parse");
+        assert_eq!(sd.len(), 3);
+        let sd = sd.into_owned();
+        assert_eq!(sd.find_tuple("a@1", "x"), Some(&"2".to_string()));
+        assert_eq!(sd.find_tuple("a@1", "y"), Some(&"]".to_string()));
+        assert!(sd.find_sdid("b@1").is_some());
+        assert_eq!(
+            r#"[c@1 d="e"]"#.parse::<message::StructuredData>().unwrap(),
+            message::StructuredData::parse(r#"[c@1 d="e"]"#).unwrap()
+        );
+        assert!(parse_structured_data("-").unwrap().is_empty());
+
+        assert!(matches!(
+            parse_structured_data("").unwrap_err().kind(),
+            Some(ErrorKind::UnexpectedEndOfInput)
+        ));
+        assert!(matches!(
+            parse_structured_data(r#"[a@1 x="1"] trailing"#)
+                .unwrap_err()
+                .kind(),
+            Some(ErrorKind::ExpectedToken('['))
+        ));
+        assert!(parse_structured_data("- ").is_err());
+        assert!(parse_structured_data(r#"a@1 x="1"]"#).is_err());
+
+        let truncated = r#"[a@1 x="1"][b@1 y="2" z="3"#;
+        assert!(parse_structured_data(truncated).is_err());
+        let lenient = ParserConfig::new().with_lenient(true);
+        let sd = parse_structured_data_with(&lenient, truncated).expect("should parse");
+        assert_eq!(sd.find_tuple("b@1", "y"), Some("2"));
+
+        let strict = ParserConfig::new().with_strict(true);
+        let long_name = r#"[a@1 this-param-name-is-far-too-long-for-rfc-5424="1"]"#;
+        assert!(parse_structured_data(long_name).is_ok());
+        assert!(matches!(
+            parse_structured_data_with(&strict, long_name),
+            Err(ParseErr::Nonconformant(_))
+        ));
+        let limited = ParserConfig::new().with_max_sd_elements(Some(1));
+        assert!(matches!(
+            parse_structured_data_with(&limited, r#"[a@1][b@1]"#),
+            Err(ParseErr::TooLong { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let input = r#"<78>1 2016-01-15T00:04:01.5+01:00 host1 CROND 10391 ID47 [a@1 x="1"] hello"#;
+        let (header, rest) = parse_header(input).expect("should parse");
+        assert_eq!(rest, r#"[a@1 x="1"] hello"#);
+        assert_eq!(header.facility, SyslogFacility::LOG_CRON);
+        assert_eq!(header.severity, SyslogSeverity::SEV_INFO);
+        assert_eq!(header.hostname, Some("host1"));
+        assert_eq!(header.appname, Some("CROND"));
+        assert_eq!(header.procid, Some(ProcIdRef::PID(10391)));
+        assert_eq!(header.msgid, Some("ID47"));
+
+        let m = parse_message(input).unwrap();
+        assert_eq!(header.timestamp, m.timestamp);
+        let empty = SyslogMessageRef::from(header).to_owned();
+        assert_eq!(empty.hostname, m.hostname);
+        assert!(empty.sd.is_empty());
+        assert_eq!(empty.msg, "");
+
+        // nothing after MSGID is looked at
+        let (header, rest) = parse_header("<1>1 - - - - - [oops").unwrap();
+        assert_eq!(header.hostname, None);
+        assert_eq!(rest, "[oops");
+
+        match parse_header("<1>1 - host app") {
+            Err(ParseErr::AppName { offset, .. }) => assert_eq!(offset, 12),
+            other => panic!("expected an error, got {:?}", other),
+        }
+        let cisco = ParserConfig::new().with_quirks(Quirks::CiscoIos);
+        let (header, _) = parse_header_with(&cisco, "12: <1>1 - host - - - -").unwrap();
+        assert_eq!(header.hostname, Some("host"));
+    }
+
+    #[test]
+    fn test_parse_message_lazy() {
+        let default = ParserConfig::default();
+        for input in [
+            r#"<1>1 - host app - - [a@1 x="]" y="\"]"][b@1] hi [there]"#,
+            "<1>1 - host app - - - hi",
+            "<1>1 - host app - - -",
+            r#"<1>1 - host app - - [a@1 x="1"]"#,
+        ] {
+            let lazy = parse_message_lazy(&default, input).expect("should parse");
+            assert!(!lazy.is_sd_parsed());
+            assert!(lazy.message().sd.is_empty());
+            let eager = parse_message(input).unwrap();
+            assert_eq!(lazy.sd().unwrap(), &eager.sd);
+            assert!(lazy.is_sd_parsed());
+            assert_eq!(lazy.into_message().unwrap(), eager);
+        }
+
+        let lazy =
+            parse_message_lazy(&default, r#"<1>1 - host app - - [a@1 x="1"][b@1] hi"#).unwrap();
+        assert_eq!(lazy.raw_sd(), r#"[a@1 x="1"][b@1]"#);
+        assert_eq!(lazy.message().msg, "hi");
+
+        // the SD is only looked at closely once it's asked for
+        let malformed = r#"<1>1 - host app - - [a@1 x=1] hi"#;
+        let lazy = parse_message_lazy(&default, malformed).expect("should parse");
+        assert!(lazy.sd().is_err());
+        assert!(lazy.into_message().is_err());
+        let eager = ParserConfig::new().with_lazy_sd(false);
+        assert!(parse_message_lazy(&eager, malformed).is_err());
+        let lazy = parse_message_lazy(&eager, r#"<1>1 - host app - - [a@1 x="1"] hi"#).unwrap();
+        assert!(lazy.is_sd_parsed());
+
+        assert!(parse_message_lazy(&default, r#"<1>1 - host app - - [a@1 x="1"#).is_err());
+        assert!(parse_message_lazy(&default, "<1>1 - host app - - x hi").is_err());
+        let lenient = ParserConfig::new().with_lenient(true);
+        let lazy = parse_message_lazy(&lenient, r#"<1>1 - host app - - [a@1 x="1" y="2"#).unwrap();
+        assert!(lazy.message().truncated);
+        assert_eq!(
+            lazy.sd().unwrap().find_tuple("a@1", "x"),
+            Some(&"1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let input = r#"<1>1 - host app - - [a@1 x="1" y="22"][b@1 z="333"] hello"#;
+        let limited = |config: ParserConfig| match parse_message_with(&config, input) {
+            Err(ParseErr::TooLong { what, .. }) => what,
+            Err(other) => panic!("expected TooLong, got {:?}", other),
+            Ok(m) => panic!("expected an error, got {:?}", m),
+        };
+        let config = ParserConfig::new()
+            .with_max_message_length(Some(input.len()))
+            .with_max_sd_elements(Some(2))
+            .with_max_sd_params(Some(2))
+            .with_max_sd_param_value_length(Some(3));
+        parse_message_with(&config, input).expect("should be within the limits");
+        assert_eq!(
+            limited(config.clone().with_max_message_length(Some(10))),
+            "message length"
+        );
+        assert_eq!(
+            limited(config.clone().with_max_sd_elements(Some(1))),
+            "SD-ELEMENT count"
+        );
+        assert_eq!(
+            limited(config.clone().with_max_sd_params(Some(1))),
+            "SD-PARAM count"
+        );
+        assert_eq!(
+            limited(config.clone().with_max_sd_param_value_length(Some(2))),
+            "SD-PARAM value length"
+        );
+
+        let (m, warnings) = parse_message_lossy(
+            &config
+                .with_max_sd_elements(Some(1))
+                .with_max_message_length(Some(55)),
+            input,
+        );
+        assert_eq!(m.sd.len(), 1);
+        assert_eq!(m.msg, "hel");
+        assert!(m.truncated);
+        let fields: Vec<_> = warnings.iter().map(|w| w.field).collect();
+        assert_eq!(
+            fields,
+            vec![MessageField::Msg, MessageField::StructuredData]
+        );
+    }
+
     #[test]
     fn test_truncated() {
         let err =
             parse_message("<39>1 2018-05-15T20:56:58+00:00 -web1west -").expect_err("should fail");
+        assert_eq!(err.field(), Some(MessageField::AppName));
+        assert_eq!(
+            mem::discriminant(err.kind().unwrap()),
+            mem::discriminant(&ErrorKind::UnexpectedEndOfInput)
+        );
+    }
+
+    #[test]
+    fn test_truncated_sd() {
+        let input = r#"<1>1 - host app - - [a@1 x="1"][b@1 y="2" z="cut of"#;
+        let err = parse_message(input).expect_err("should fail");
+        match err {
+            ParseErr::Sd {
+                element,
+                param,
+                kind: ErrorKind::UnexpectedEndOfInput,
+                ..
+            } => {
+                assert_eq!(element.as_deref(), Some("b@1"));
+                assert_eq!(param.as_deref(), Some("z"));
+            }
+            other => panic!("expected an SD error, got {:?}", other),
+        }
+
+        let config = ParserConfig::new().with_lenient(true);
+        let msg = parse_message_with(&config, input).expect("should parse");
+        assert!(msg.truncated);
+        assert_eq!(msg.sd.find_tuple("a@1", "x"), Some(&"1".to_string()));
+        assert_eq!(msg.sd.find_tuple("b@1", "y"), Some(&"2".to_string()));
+        assert_eq!(msg.sd.find_tuple("b@1", "z"), None);
+        assert_eq!(msg.msg, "");
+        let msg = parse_message_with(&config, r#"<1>1 - host app - - [a@1 x="1"] hi"#).unwrap();
+        assert!(!msg.truncated);
+
+        let (msg, warnings) = parse_message_lossy(&ParserConfig::default(), input);
+        assert!(msg.truncated);
+        assert_eq!(msg.sd.len(), 2);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_error_position() {
+        let input = "<39>1 2018-05-15T25:56:58+00:00 web1west - - - - msg";
+        let err = parse_message(input).expect_err("should fail");
+        assert_eq!(err.offset(), Some(6));
+        match err {
+            ParseErr::Timestamp {
+                ref found,
+                kind: ErrorKind::InvalidDate(_),
+                ..
+            } => assert_eq!(found, "2018-05-15T25:56:58+00:00"),
+            ref other => panic!("expected a TIMESTAMP error, got {:?}", other),
+        }
+        // the kind is in the message, so it isn't repeated as the source
+        assert!(err.source().is_none());
+        assert!(
+            err.to_string().ends_with(": date had invalid field hour"),
+            "{}",
+            err
+        );
+
+        let err = parse_message("<39>1 - web1west - - - [meta").expect_err("should fail");
+        assert_eq!(err.offset(), Some(23));
+        assert!(err.to_string().contains("\"[meta\""));
+    }
+
+    #[test]
+    fn test_error_fields() {
+        let field = |input: &str| parse_message(input).unwrap_err().field();
+        assert_eq!(field("1>1 - - - - - -"), Some(MessageField::Pri));
+        assert_eq!(field("<1>x - - - - - -"), Some(MessageField::Version));
+        assert_eq!(field("<1>1x - - - - - -"), Some(MessageField::Version));
+        assert_eq!(
+            field("<1>1 2020-13 - - - - -"),
+            Some(MessageField::Timestamp)
+        );
+        assert_eq!(field("<1>1 -  - - - - -"), Some(MessageField::Hostname));
+        assert_eq!(field("<1>1 - host - - -"), Some(MessageField::MsgId));
         assert_eq!(
-            mem::discriminant(&err),
-            mem::discriminant(&ParseErr::UnexpectedEndOfInput)
+            field("<1>1 - - - - - [a b]"),
+            Some(MessageField::StructuredData)
         );
+
+        let err = parse_message(r#"<1>1 - - - - - [a x="1""#).unwrap_err();
+        assert!(err.to_string().contains("in SD-ELEMENT a "), "{}", err);
+        let config = ParserConfig::new().with_strict_sd_escapes(true);
+        let err = parse_message_with(&config, r#"<1>1 - - - - - [a b="c\n"] hi"#).unwrap_err();
+        match err {
+            ParseErr::Sd {
+                ref element,
+                ref param,
+                kind: ErrorKind::InvalidEscape('n'),
+                ..
+            } => {
+                assert_eq!(element.as_deref(), Some("a"));
+                assert_eq!(param.as_deref(), Some("b"));
+            }
+            ref other => panic!("expected an SD error, got {:?}", other),
+        }
+        assert!(err.to_string().contains("in SD-PARAM a/b"), "{}", err);
+        assert_eq!(err.class(), ErrorClass::Sd);
+
+        let err = parse_message(format!("<1>1 - {} - - - -", "h".repeat(300))).unwrap_err();
+        assert!(matches!(
+            err,
+            ParseErr::Hostname {
+                kind: ErrorKind::ExpectedToken(' '),
+                ..
+            }
+        ));
+        assert_eq!(err.found().map(str::len), Some(32));
     }
 }
@@ -0,0 +1,85 @@
+//! The PRI value at the start of every message.
+
+use std::fmt;
+
+use crate::facility::SyslogFacility;
+use crate::message::SyslogMessage;
+use crate::severity::SyslogSeverity;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A PRI value: a facility and severity packed into one number (`facility * 8 + severity`)
+///
+/// Displays as the bare number, without the angle brackets.
+pub struct Pri(u8);
+
+impl Pri {
+    pub fn new(facility: SyslogFacility, severity: SyslogSeverity) -> Self {
+        Pri((facility as u8) << 3 | severity as u8)
+    }
+
+    /// Wrap a raw PRI value, if it corresponds to a known facility
+    pub fn from_raw(raw: u8) -> Option<Self> {
+        SyslogFacility::from_int(i32::from(raw >> 3)).map(|_| Pri(raw))
+    }
+
+    /// The raw PRI value
+    pub fn raw(self) -> u8 {
+        self.0
+    }
+
+    pub fn facility(self) -> SyslogFacility {
+        SyslogFacility::from_int(i32::from(self.0 >> 3)).expect("checked on construction")
+    }
+
+    pub fn severity(self) -> SyslogSeverity {
+        SyslogSeverity::from_int(i32::from(self.0 & 0x7)).expect("all severities are valid")
+    }
+}
+
+impl From<Pri> for u8 {
+    fn from(pri: Pri) -> u8 {
+        pri.0
+    }
+}
+
+impl fmt::Display for Pri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl SyslogMessage {
+    /// The facility and severity as a `Pri`
+    pub fn pri(&self) -> Pri {
+        Pri::new(self.facility, self.severity)
+    }
+
+    /// The raw PRI value, `facility * 8 + severity`
+    pub fn priority(&self) -> u8 {
+        self.pri().raw()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pri;
+    use crate::{SyslogFacility, SyslogSeverity};
+
+    #[test]
+    fn test_pri() {
+        let pri = Pri::new(SyslogFacility::LOG_LOCAL4, SyslogSeverity::SEV_NOTICE);
+        assert_eq!(pri.raw(), 165);
+        assert_eq!(Pri::from_raw(165), Some(pri));
+        assert_eq!(pri.facility(), SyslogFacility::LOG_LOCAL4);
+        assert_eq!(pri.severity(), SyslogSeverity::SEV_NOTICE);
+        assert_eq!(
+            Pri::from_raw(191).map(Pri::facility),
+            Some(SyslogFacility::LOG_LOCAL7)
+        );
+        assert_eq!(Pri::from_raw(192), None);
+
+        let m = crate::parse_message("<165>1 - - - - - -").unwrap();
+        assert_eq!(m.priority(), 165);
+        assert_eq!(m.pri().to_string(), "165");
+    }
+}
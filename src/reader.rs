@@ -0,0 +1,109 @@
+//! Parsing newline-delimited messages from anything which implements `BufRead`.
+//!
+//! # Example
+//!
+//! ```
+//! use std::io::Cursor;
+//!
+//! use syslog_rfc5424::reader::SyslogReader;
+//!
+//! let input = Cursor::new("<1>1 - host1 - - - - one\n<1>1 - host2 - - - - two\n");
+//! let bodies: Vec<String> = SyslogReader::new(input)
+//!     .map(|m| m.expect("should parse").msg)
+//!     .collect();
+//! assert_eq!(bodies, vec!["one", "two"]);
+//! ```
+
+use std::io::BufRead;
+
+use crate::message::SyslogMessage;
+use crate::parser::{parse_message_bytes, ParseErr, Utf8Policy};
+
+#[derive(Debug)]
+/// An iterator which reads one message per line from a `BufRead`
+///
+/// Blank lines are skipped, and the trailing LF or CRLF is removed from each line. A line which
+/// fails to parse is yielded as an `Err` and iteration carries on with the next line; errors from
+/// the underlying reader are yielded as `ParseErr::Io`.
+pub struct SyslogReader<R> {
+    reader: R,
+    buf: Vec<u8>,
+    utf8_policy: Utf8Policy,
+}
+
+impl<R: BufRead> SyslogReader<R> {
+    pub fn new(reader: R) -> Self {
+        SyslogReader {
+            reader,
+            buf: Vec::new(),
+            utf8_policy: Utf8Policy::Strict,
+        }
+    }
+
+    /// Set how lines which aren't valid UTF-8 are handled
+    pub fn with_utf8_policy(mut self, utf8_policy: Utf8Policy) -> Self {
+        self.utf8_policy = utf8_policy;
+        self
+    }
+
+    /// Discard this reader, returning the underlying `BufRead`
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: BufRead> Iterator for SyslogReader<R> {
+    type Item = Result<SyslogMessage, ParseErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(ParseErr::Io(e))),
+            }
+            let mut line = &self.buf[..];
+            line = line.strip_suffix(b"\n").unwrap_or(line);
+            line = line.strip_suffix(b"\r").unwrap_or(line);
+            if !line.is_empty() {
+                return Some(parse_message_bytes(line, self.utf8_policy));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufReader, Cursor, Read};
+
+    use super::SyslogReader;
+    use crate::parser::{ParseErr, Utf8Policy};
+
+    #[test]
+    fn test_lines_and_errors() {
+        let input =
+            Cursor::new(&b"<1>1 - - - - - - one\r\n\nnot syslog\n<1>1 - - - - - - caf\xe9"[..]);
+        let results = SyslogReader::new(input)
+            .with_utf8_policy(Utf8Policy::Lossy)
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().msg, "one");
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap().msg, "caf\u{fffd}");
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("boom"))
+        }
+    }
+
+    #[test]
+    fn test_io_error() {
+        let mut reader = SyslogReader::new(BufReader::new(FailingReader));
+        assert!(matches!(reader.next(), Some(Err(ParseErr::Io(_)))));
+    }
+}
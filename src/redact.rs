@@ -0,0 +1,324 @@
+//! Masking sensitive data in messages (requires the `redact` feature).
+//!
+//! A `Redactor` replaces the values of chosen SD-PARAMs, and anything in MSG or in SD-PARAM values
+//! which matches a pattern or one of the built-in `Detector`s, with a mask. It's meant for
+//! scrubbing personal data from messages before they leave the ingest tier.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::parse_message;
+//! use syslog_rfc5424::redact::{Detector, Redactor};
+//!
+//! let redactor = Redactor::new()
+//!     .with_sd_param("password")
+//!     .with_detector(Detector::Email)
+//!     .with_detector(Detector::Ipv4);
+//!
+//! let m = parse_message(
+//!     r#"<38>1 - host sshd - - [auth@32473 user="bob" password="hunter2"] login by bob@example.com from 192.0.2.7"#,
+//! ).unwrap();
+//! let scrubbed = redactor.redact(&m);
+//!
+//! assert_eq!(scrubbed.sd.find_tuple("auth@32473", "password"), Some(&"***".to_string()));
+//! assert_eq!(scrubbed.sd.find_tuple("auth@32473", "user"), Some(&"bob".to_string()));
+//! assert_eq!(scrubbed.msg, "login by *** from ***");
+//! ```
+
+use std::net::Ipv6Addr;
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::message::SyslogMessage;
+
+/// What a `Redactor` replaces sensitive data with, unless told otherwise
+pub const DEFAULT_MASK: &str = "***";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Built-in patterns for common kinds of personal data
+pub enum Detector {
+    /// Dotted-quad IPv4 addresses
+    Ipv4,
+    /// IPv6 addresses, in any of their textual forms
+    Ipv6,
+    /// Email addresses
+    Email,
+    /// Payment card numbers: 13 to 19 digits, optionally grouped with spaces or dashes, which
+    /// pass the Luhn check
+    CreditCard,
+}
+
+/// Given the whole string and the bounds of a match, whether to mask it
+type Accept = fn(&str, usize, usize) -> bool;
+
+impl Detector {
+    fn matcher(self) -> Matcher {
+        let (pattern, accept): (&str, Accept) = match self {
+            Detector::Ipv4 => (
+                r"\b(?:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\b",
+                is_ipv4,
+            ),
+            Detector::Ipv6 => (r"[0-9A-Fa-f:]*:[0-9A-Fa-f:.]*[0-9A-Fa-f:]", is_ipv6),
+            Detector::Email => (
+                r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b",
+                |_, _, _| true,
+            ),
+            Detector::CreditCard => (r"\b\d(?:[ -]?\d){12,18}\b", |s, start, end| {
+                luhn(&s[start..end])
+            }),
+        };
+        Matcher {
+            regex: Regex::new(pattern).expect("built-in patterns are valid"),
+            accept,
+        }
+    }
+}
+
+/// Whether `s[start..end]` isn't just part of a longer dotted number, like a version
+fn is_ipv4(s: &str, start: usize, end: usize) -> bool {
+    fn dotted<'a>(mut rest: impl Iterator<Item = &'a u8>) -> bool {
+        rest.next() == Some(&b'.') && rest.next().is_some_and(u8::is_ascii_digit)
+    }
+    let bytes = s.as_bytes();
+    !dotted(bytes[..start].iter().rev()) && !dotted(bytes[end..].iter())
+}
+
+/// Whether `s[start..end]` is a whole IPv6 address, rather than part of e.g. a Rust path
+fn is_ipv6(s: &str, start: usize, end: usize) -> bool {
+    let adjacent = |c: Option<char>| matches!(c, Some(c) if c.is_alphanumeric() || c == ':');
+    !adjacent(s[..start].chars().next_back())
+        && !adjacent(s[end..].chars().next())
+        && Ipv6Addr::from_str(&s[start..end]).is_ok()
+}
+
+/// Whether the digits in `s` pass the Luhn check
+fn luhn(s: &str) -> bool {
+    let sum: u32 = s
+        .bytes()
+        .rev()
+        .filter(u8::is_ascii_digit)
+        .map(|b| u32::from(b - b'0'))
+        .enumerate()
+        .map(|(i, d)| match (i % 2, d * 2) {
+            (0, _) => d,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+#[derive(Clone, Debug)]
+struct Matcher {
+    regex: Regex,
+    accept: Accept,
+}
+
+#[derive(Clone, Debug)]
+/// Scrubs sensitive data from messages; see the module docs
+pub struct Redactor {
+    mask: String,
+    /// (SD-ID, if only one element's param is meant, SD-PARAM name)
+    sd_params: Vec<(Option<String>, String)>,
+    matchers: Vec<Matcher>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Redactor::new()
+    }
+}
+
+impl Redactor {
+    /// A `Redactor` which doesn't mask anything yet
+    pub fn new() -> Self {
+        Redactor {
+            mask: DEFAULT_MASK.to_string(),
+            sd_params: Vec::new(),
+            matchers: Vec::new(),
+        }
+    }
+
+    /// Replace sensitive data with `mask` instead of `DEFAULT_MASK`
+    pub fn with_mask<S: Into<String>>(mut self, mask: S) -> Self {
+        self.mask = mask.into();
+        self
+    }
+
+    /// Mask the whole value of every SD-PARAM called `name`, in any SD-ELEMENT
+    pub fn with_sd_param<S: Into<String>>(mut self, name: S) -> Self {
+        self.sd_params.push((None, name.into()));
+        self
+    }
+
+    /// Mask the whole value of SD-PARAM `name`, in SD-ELEMENT `sd_id` only
+    pub fn with_sd_tuple<SI: Into<String>, S: Into<String>>(mut self, sd_id: SI, name: S) -> Self {
+        self.sd_params.push((Some(sd_id.into()), name.into()));
+        self
+    }
+
+    /// Mask anything matching `pattern`, in MSG and in SD-PARAM values
+    pub fn with_pattern(mut self, pattern: Regex) -> Self {
+        self.matchers.push(Matcher {
+            regex: pattern,
+            accept: |_, _, _| true,
+        });
+        self
+    }
+
+    /// Mask anything found by `detector`, in MSG and in SD-PARAM values
+    pub fn with_detector(mut self, detector: Detector) -> Self {
+        self.matchers.push(detector.matcher());
+        self
+    }
+
+    /// Return a scrubbed copy of `m`
+    pub fn redact(&self, m: &SyslogMessage) -> SyslogMessage {
+        let mut m = m.clone();
+        self.redact_in_place(&mut m);
+        m
+    }
+
+    /// Scrub `m` in place, returning whether anything was masked
+    ///
+    /// If MSG is changed, the original bytes in `msg_bytes` are dropped too.
+    pub fn redact_in_place(&self, m: &mut SyslogMessage) -> bool {
+        let mut changed = false;
+        for (sd_id, name, value) in m.sd.iter_tuples_mut() {
+            let by_name = self
+                .sd_params
+                .iter()
+                .any(|(id, n)| n == name && id.iter().all(|id| id == sd_id));
+            if by_name {
+                if *value != self.mask {
+                    *value = self.mask.clone();
+                    changed = true;
+                }
+            } else if let Some(masked) = self.mask_matches(value) {
+                *value = masked;
+                changed = true;
+            }
+        }
+        if let Some(masked) = self.mask_matches(&m.msg) {
+            m.msg = masked;
+            m.msg_bytes = None;
+            changed = true;
+        }
+        changed
+    }
+
+    /// `s` with every match masked, or `None` if nothing matched
+    pub fn mask_matches(&self, s: &str) -> Option<String> {
+        let mut spans = self
+            .matchers
+            .iter()
+            .flat_map(|matcher| {
+                matcher
+                    .regex
+                    .find_iter(s)
+                    .filter(move |found| (matcher.accept)(s, found.start(), found.end()))
+                    .map(|found| (found.start(), found.end()))
+            })
+            .filter(|(start, end)| start < end)
+            .collect::<Vec<_>>();
+        if spans.is_empty() {
+            return None;
+        }
+        spans.sort_unstable();
+        let mut masked = String::with_capacity(s.len());
+        let mut pos = 0;
+        for (start, end) in spans {
+            // overlapping matches are masked together
+            if end <= pos {
+                continue;
+            }
+            if start >= pos {
+                masked.push_str(&s[pos..start]);
+                masked.push_str(&self.mask);
+            }
+            pos = end;
+        }
+        masked.push_str(&s[pos..]);
+        Some(masked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::{luhn, Detector, Redactor};
+    use crate::parse_message;
+
+    fn masked(detector: Detector, s: &str) -> String {
+        Redactor::new()
+            .with_detector(detector)
+            .mask_matches(s)
+            .unwrap_or_else(|| s.to_string())
+    }
+
+    #[test]
+    fn test_detectors() {
+        assert_eq!(masked(Detector::Ipv4, "from 10.0.0.255:22"), "from ***:22");
+        assert_eq!(
+            masked(Detector::Ipv4, "version 1.2.3.4.5"),
+            "version 1.2.3.4.5"
+        );
+        assert_eq!(masked(Detector::Ipv4, "not 256.1.1.1"), "not 256.1.1.1");
+        assert_eq!(
+            masked(Detector::Ipv6, "from 2001:db8::1 and ::ffff:192.0.2.1."),
+            "from *** and ***."
+        );
+        assert_eq!(
+            masked(Detector::Ipv6, "in std::io::Error"),
+            "in std::io::Error"
+        );
+        assert_eq!(masked(Detector::Ipv6, "at 12:34:56"), "at 12:34:56");
+        assert_eq!(
+            masked(Detector::Email, "mail a.b+c@mail.example.co.uk now"),
+            "mail *** now"
+        );
+        assert_eq!(
+            masked(
+                Detector::CreditCard,
+                "card 4111 1111 1111 1111, order 4111111111111112"
+            ),
+            "card ***, order 4111111111111112"
+        );
+        assert!(luhn("79927398713"));
+        assert!(!luhn("79927398710"));
+    }
+
+    #[test]
+    fn test_redact_message() {
+        let m = parse_message(
+            r#"<38>1 - host app - - [a@1 token="t1" ip="192.0.2.1"][b@1 token="t2"] user=alice token=abc"#,
+        )
+        .unwrap();
+        let redactor = Redactor::new()
+            .with_mask("[x]")
+            .with_sd_tuple("a@1", "token")
+            .with_detector(Detector::Ipv4)
+            .with_pattern(Regex::new(r"user=\w+").unwrap())
+            .with_pattern(Regex::new(r"token=\w+").unwrap());
+        let scrubbed = redactor.redact(&m);
+        assert_eq!(
+            scrubbed.sd.find_tuple("a@1", "token"),
+            Some(&"[x]".to_string())
+        );
+        assert_eq!(
+            scrubbed.sd.find_tuple("a@1", "ip"),
+            Some(&"[x]".to_string())
+        );
+        assert_eq!(
+            scrubbed.sd.find_tuple("b@1", "token"),
+            Some(&"t2".to_string())
+        );
+        assert_eq!(scrubbed.msg, "[x] [x]");
+
+        let mut clean = parse_message("<38>1 - host app - - - nothing to see").unwrap();
+        assert!(!redactor.redact_in_place(&mut clean));
+        assert_eq!(clean.msg, "nothing to see");
+    }
+}
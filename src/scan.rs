@@ -0,0 +1,62 @@
+//! Fast byte scanning for the tokenizer.
+//!
+//! Delimiters are found with `memchr`, and runs of PRINTUSASCII (`%d33-126`) are checked eight
+//! bytes at a time with SWAR ("SIMD within a register") arithmetic on `u64`s.
+
+use std::convert::TryInto;
+
+const ONES: u64 = u64::from_ne_bytes([0x01; 8]);
+const HIGHS: u64 = u64::from_ne_bytes([0x80; 8]);
+
+/// Whether any of the eight bytes in `word` is outside of PRINTUSASCII
+#[inline]
+fn has_non_printusascii(word: u64) -> bool {
+    // a byte below 33 borrows when 33 is subtracted, setting its top bit where it wasn't set
+    let below = word.wrapping_sub(ONES * 33) & !word;
+    // a byte above 126 has its top bit set once 1 is added, or already had it set
+    let above = word.wrapping_add(ONES) | word;
+    (below | above) & HIGHS != 0
+}
+
+/// The length of the run of PRINTUSASCII characters at the start of `bytes`, looking no further
+/// than `max` bytes
+pub(crate) fn printusascii_len(bytes: &[u8], max: usize) -> usize {
+    let bytes = &bytes[..bytes.len().min(max)];
+    let mut chunks = bytes.chunks_exact(8);
+    let mut len = 0;
+    for chunk in &mut chunks {
+        let word = u64::from_ne_bytes(chunk.try_into().expect("chunks are eight bytes"));
+        if has_non_printusascii(word) {
+            break;
+        }
+        len += 8;
+    }
+    len + bytes[len..]
+        .iter()
+        .take_while(|b| (33..=126).contains(*b))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::printusascii_len;
+
+    #[test]
+    fn test_printusascii_len() {
+        assert_eq!(printusascii_len(b"", 10), 0);
+        assert_eq!(printusascii_len(b"host1 app", 255), 5);
+        assert_eq!(printusascii_len(b"abcdefghijklmnop", 255), 16);
+        assert_eq!(printusascii_len(b"abcdefghijklmnop", 10), 10);
+        assert_eq!(printusascii_len(b"abcdefgh\x7fjkl", 255), 8);
+        assert_eq!(printusascii_len("abcdefg\u{e9}".as_bytes(), 255), 7);
+        // every byte value, in every position of a word
+        for b in 0..=255u8 {
+            for pos in 0..16 {
+                let mut input = [b'x'; 16];
+                input[pos] = b;
+                let expected = if (33..=126).contains(&b) { 16 } else { pos };
+                assert_eq!(printusascii_len(&input, 255), expected, "{} at {}", b, pos);
+            }
+        }
+    }
+}
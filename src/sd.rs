@@ -0,0 +1,491 @@
+//! Typed views of structured data: SD-IDs, and the elements registered in RFC 5424 §7.
+//!
+//! `StructuredData` stores every SD-ID and SD-PARAM as a string; `SdId` splits an SD-ID into its
+//! name and enterprise number, and the accessors here pick out the `timeQuality`, `origin` and
+//! `meta` elements and check their params against the RFC.
+//!
+//! # Example
+//!
+//! ```
+//! let m = syslog_rfc5424::parse_message(
+//!     r#"<1>1 - - - - - [timeQuality tzKnown="1" isSynced="1" syncAccuracy="60000"] hi"#,
+//! )
+//! .unwrap();
+//! let tq = m.time_quality().unwrap().unwrap();
+//! assert_eq!(tq.is_synced, Some(true));
+//! assert_eq!(tq.sync_accuracy, Some(60000));
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::message::{StructuredData, SyslogMessage};
+use crate::sd_map::{sorted_entries, SdParamMap};
+
+/// The SD-ID of the `timeQuality` element (RFC 5424 §7.1)
+pub const TIME_QUALITY: &str = "timeQuality";
+/// The SD-ID of the `origin` element (RFC 5424 §7.2)
+pub const ORIGIN: &str = "origin";
+/// The SD-ID of the `meta` element (RFC 5424 §7.3)
+pub const META: &str = "meta";
+
+/// The SD-IDs registered with IANA, which are the only ones allowed without an `@` suffix
+pub const REGISTERED_SD_IDS: &[&str] = &[TIME_QUALITY, ORIGIN, META];
+
+/// Escape `"`, `\` and `]` in an SD-PARAM value, as RFC 5424 §6.3.3 requires
+///
+/// Borrows if there's nothing to escape.
+pub fn escape_param_value(value: &str) -> Cow<'_, str> {
+    if !value.contains(['"', '\\', ']']) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 2);
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    Cow::Owned(escaped)
+}
+
+/// Undo `escape_param_value`
+///
+/// As RFC 5424 §6.3.3 says, a backslash before any other character (or at the end) is left as
+/// it is. Borrows if there's nothing to unescape.
+pub fn unescape_param_value(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            // take the escaped character now, so that an escaped backslash can't escape another
+            Some(&next @ ('"' | '\\' | ']')) if c == '\\' => {
+                unescaped.push(next);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum SdIdError {
+    #[error("SD-ID has an empty name")]
+    EmptyName,
+    #[error("SD-ID {0:?} isn't registered with IANA, so it needs an @enterprise suffix")]
+    MissingEnterpriseNumber(String),
+    #[error("invalid enterprise number {0:?} in SD-ID")]
+    InvalidEnterpriseNumber(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// An SD-ID split into its name and enterprise number, e.g. `exampleSDID@32473`
+pub struct SdId<'a> {
+    name: &'a str,
+    enterprise_number: Option<&'a str>,
+}
+
+impl<'a> SdId<'a> {
+    /// Split an SD-ID, checking that it's either registered or has an enterprise number
+    ///
+    /// The enterprise number is a private enterprise code assigned by IANA, optionally followed
+    /// by more dot-separated numbers.
+    pub fn parse(sd_id: &'a str) -> Result<Self, SdIdError> {
+        let id = Self::split(sd_id);
+        if id.name.is_empty() {
+            return Err(SdIdError::EmptyName);
+        }
+        match id.enterprise_number {
+            Some(number) if !number.split('.').all(is_digits) => {
+                Err(SdIdError::InvalidEnterpriseNumber(number.to_string()))
+            }
+            None if !id.is_registered() => {
+                Err(SdIdError::MissingEnterpriseNumber(sd_id.to_string()))
+            }
+            _ => Ok(id),
+        }
+    }
+
+    /// Split an SD-ID at its first `@` without checking anything
+    pub fn split(sd_id: &'a str) -> Self {
+        match sd_id.find('@') {
+            Some(at) => SdId {
+                name: &sd_id[..at],
+                enterprise_number: Some(&sd_id[at + 1..]),
+            },
+            None => SdId {
+                name: sd_id,
+                enterprise_number: None,
+            },
+        }
+    }
+
+    /// The part before the `@`
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// The part after the `@`, if there is one
+    pub fn enterprise_number(&self) -> Option<&'a str> {
+        self.enterprise_number
+    }
+
+    /// The private enterprise code, without any sub-identifiers
+    pub fn private_enterprise_code(&self) -> Option<u32> {
+        self.enterprise_number?.split('.').next()?.parse().ok()
+    }
+
+    /// Whether this is one of the IANA-registered SD-IDs
+    pub fn is_registered(&self) -> bool {
+        self.enterprise_number.is_none() && REGISTERED_SD_IDS.contains(&self.name)
+    }
+}
+
+impl<'a> fmt::Display for SdId<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name)?;
+        if let Some(number) = self.enterprise_number {
+            write!(f, "@{}", number)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum SdParamError {
+    #[error("invalid value {value:?} for {sd_id} param {name}")]
+    InvalidValue {
+        sd_id: &'static str,
+        name: &'static str,
+        value: String,
+    },
+    #[error("timeQuality has a syncAccuracy, but isSynced is 0")]
+    SyncAccuracyWithoutSync,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// The `timeQuality` element, describing how much the timestamp can be trusted
+pub struct TimeQuality {
+    /// Whether the sender knows its time zone
+    pub tz_known: Option<bool>,
+    /// Whether the sender's clock is synchronized to a reliable external source
+    pub is_synced: Option<bool>,
+    /// How accurate the sender thinks its clock is, in microseconds
+    pub sync_accuracy: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// The `origin` element, describing where the message was first generated
+pub struct Origin {
+    /// The sender's IP addresses
+    ///
+    /// The RFC allows `ip` to be repeated, but `StructuredData` only keeps the last value; see
+    /// `MultiStructuredData` if you need all of them.
+    pub ip: Vec<IpAddr>,
+    /// The sender's SMI Private Enterprise Code, possibly followed by more dotted numbers
+    pub enterprise_id: Option<String>,
+    pub software: Option<String>,
+    pub sw_version: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// The `meta` element, with information about the message itself
+pub struct Meta {
+    /// A counter of messages sent since the sender started, between 1 and 2147483647
+    pub sequence_id: Option<u32>,
+    /// The sender's SNMP `sysUpTime`, in hundredths of a second
+    pub sys_up_time: Option<u64>,
+    /// The language of MSG, as a BCP 47 tag
+    pub language: Option<String>,
+}
+
+fn parse_param<T: FromStr, P: SdParamMap>(
+    element: &P,
+    sd_id: &'static str,
+    name: &'static str,
+    valid: impl Fn(&str) -> bool,
+) -> Result<Option<T>, SdParamError> {
+    let value = match element.get(name) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let invalid = || SdParamError::InvalidValue {
+        sd_id,
+        name,
+        value: value.clone(),
+    };
+    if !valid(value) {
+        return Err(invalid());
+    }
+    value.parse().map(Some).map_err(|_| invalid())
+}
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn parse_flag<P: SdParamMap>(
+    element: &P,
+    sd_id: &'static str,
+    name: &'static str,
+) -> Result<Option<bool>, SdParamError> {
+    let flag: Option<u8> = parse_param(element, sd_id, name, |v| v == "0" || v == "1")?;
+    Ok(flag.map(|f| f == 1))
+}
+
+/// Every way of splitting `path` into an SD-ID and a PARAM-NAME at `separator`
+fn split_path(path: &str, separator: char) -> impl Iterator<Item = (&str, &str)> {
+    path.match_indices(separator)
+        .map(move |(i, sep)| (&path[..i], &path[i + sep.len()..]))
+}
+
+impl<P: SdParamMap> StructuredData<P> {
+    /// Find the elements whose SD-ID has the given name, whatever its enterprise number
+    ///
+    /// They come out in alphabetical order of SD-ID, whatever the map.
+    pub fn find_by_name<'b>(
+        &'b self,
+        name: &'b str,
+    ) -> impl Iterator<Item = (SdId<'b>, &'b P)> + 'b {
+        sorted_entries(&**self)
+            .map(|(sd_id, element)| (SdId::split(sd_id), element))
+            .filter(move |(sd_id, _)| sd_id.name() == name)
+    }
+
+    /// Look up an SD-PARAM by a `<SD-ID>/<PARAM-NAME>` path, like `exampleSDID@32473/eventID`
+    pub fn get_path(&self, path: &str) -> Option<&str> {
+        self.get_path_with(path, '/')
+    }
+
+    /// Like `get_path`, with a different separator between the SD-ID and PARAM-NAME
+    ///
+    /// SD-IDs and PARAM-NAMEs may contain the separator themselves, so every place the path
+    /// could be split is tried in turn.
+    pub fn get_path_with(&self, path: &str, separator: char) -> Option<&str> {
+        split_path(path, separator)
+            .find_map(|(sd_id, name)| self.find_tuple(sd_id, name).map(String::as_str))
+    }
+
+    /// Like `get_path`, but matching only the name of the SD-ID, whatever its enterprise number
+    ///
+    /// `exampleSDID/eventID` (or `exampleSDID@1/eventID`) finds `eventID` in the first element
+    /// called `exampleSDID` which has it, such as `exampleSDID@32473`.
+    pub fn get_path_by_name(&self, path: &str) -> Option<&str> {
+        self.get_path_by_name_with(path, '/')
+    }
+
+    /// Like `get_path_by_name`, with a different separator
+    pub fn get_path_by_name_with(&self, path: &str, separator: char) -> Option<&str> {
+        split_path(path, separator).find_map(|(sd_id, name)| {
+            let sd_id = SdId::split(sd_id).name();
+            sorted_entries(&**self)
+                .filter(|(id, _)| SdId::split(id).name() == sd_id)
+                .find_map(|(_, element)| element.get(name))
+                .map(String::as_str)
+        })
+    }
+
+    /// The `timeQuality` element, if there is one
+    pub fn time_quality(&self) -> Result<Option<TimeQuality>, SdParamError> {
+        let element = match self.find_sdid(TIME_QUALITY) {
+            Some(element) => element,
+            None => return Ok(None),
+        };
+        let tq = TimeQuality {
+            tz_known: parse_flag(element, TIME_QUALITY, "tzKnown")?,
+            is_synced: parse_flag(element, TIME_QUALITY, "isSynced")?,
+            sync_accuracy: parse_param(element, TIME_QUALITY, "syncAccuracy", is_digits)?,
+        };
+        if tq.sync_accuracy.is_some() && tq.is_synced == Some(false) {
+            return Err(SdParamError::SyncAccuracyWithoutSync);
+        }
+        Ok(Some(tq))
+    }
+
+    /// The `origin` element, if there is one
+    pub fn origin(&self) -> Result<Option<Origin>, SdParamError> {
+        let element = match self.find_sdid(ORIGIN) {
+            Some(element) => element,
+            None => return Ok(None),
+        };
+        let is_enterprise_id = |v: &str| v.split('.').all(is_digits);
+        Ok(Some(Origin {
+            ip: parse_param(element, ORIGIN, "ip", |_| true)?
+                .into_iter()
+                .collect(),
+            enterprise_id: parse_param(element, ORIGIN, "enterpriseId", is_enterprise_id)?,
+            software: parse_param(element, ORIGIN, "software", |v| v.chars().count() <= 48)?,
+            sw_version: parse_param(element, ORIGIN, "swVersion", |v| v.chars().count() <= 32)?,
+        }))
+    }
+
+    /// The `meta` element, if there is one
+    pub fn meta(&self) -> Result<Option<Meta>, SdParamError> {
+        let element = match self.find_sdid(META) {
+            Some(element) => element,
+            None => return Ok(None),
+        };
+        let is_sequence_id = |v: &str| is_digits(v) && matches!(v.parse::<i32>(), Ok(n) if n >= 1);
+        Ok(Some(Meta {
+            sequence_id: parse_param(element, META, "sequenceId", is_sequence_id)?,
+            sys_up_time: parse_param(element, META, "sysUpTime", is_digits)?,
+            language: parse_param(element, META, "language", |v| !v.is_empty())?,
+        }))
+    }
+}
+
+impl SyslogMessage {
+    /// The `timeQuality` SD element, if there is one; see `StructuredData::time_quality`
+    pub fn time_quality(&self) -> Result<Option<TimeQuality>, SdParamError> {
+        self.sd.time_quality()
+    }
+
+    /// The `origin` SD element, if there is one; see `StructuredData::origin`
+    pub fn origin(&self) -> Result<Option<Origin>, SdParamError> {
+        self.sd.origin()
+    }
+
+    /// The `meta` SD element, if there is one; see `StructuredData::meta`
+    pub fn meta(&self) -> Result<Option<Meta>, SdParamError> {
+        self.sd.meta()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        escape_param_value, unescape_param_value, Meta, Origin, SdId, SdIdError, SdParamError,
+    };
+    use crate::parse_message;
+    use crate::parser::{parse_message_with, ErrorKind, ParserConfig};
+
+    #[test]
+    fn test_get_path() {
+        let m = parse_message(
+            r#"<1>1 - - - - - [exampleSDID@32473 eventID="1011"][a/b c/d="1"][x.y z="2"] hi"#,
+        )
+        .unwrap();
+        assert_eq!(m.sd.get_path("exampleSDID@32473/eventID"), Some("1011"));
+        assert_eq!(m.sd.get_path("exampleSDID/eventID"), None);
+        assert_eq!(m.sd.get_path("exampleSDID@32473/other"), None);
+        assert_eq!(m.sd.get_path("exampleSDID@32473"), None);
+        assert_eq!(m.sd.get_path("a/b/c/d"), Some("1"));
+        assert_eq!(m.sd.get_path_with("x.y.z", '.'), Some("2"));
+        assert_eq!(m.sd.get_path_by_name("exampleSDID/eventID"), Some("1011"));
+        assert_eq!(m.sd.get_path_by_name("exampleSDID@1/eventID"), Some("1011"));
+        assert_eq!(
+            m.sd.get_path_by_name_with("exampleSDID::eventID", ':'),
+            None
+        );
+        assert_eq!(
+            m.sd.get_path_by_name_with("exampleSDID:eventID", ':'),
+            Some("1011")
+        );
+    }
+
+    #[test]
+    fn test_escaping() {
+        let raw = r#"a "quoted" \path] \n"#;
+        let escaped = escape_param_value(raw);
+        assert_eq!(escaped, r#"a \"quoted\" \\path\] \\n"#);
+        assert_eq!(unescape_param_value(&escaped), raw);
+        assert_eq!(unescape_param_value(r#"\n\"#), r#"\n\"#);
+        assert_eq!(unescape_param_value(r#"\\\""#), r#"\""#);
+
+        let input = format!(r#"<1>1 - - - - - [x@1 a="{}"] hi"#, escaped);
+        let m = parse_message(&input).expect("should parse");
+        assert_eq!(m.sd.find_tuple("x@1", "a").map(String::as_str), Some(raw));
+        assert_eq!(m.to_string(), input);
+
+        let config = ParserConfig::new().with_strict_sd_escapes(true);
+        let err = parse_message_with(&config, r#"<1>1 - - - - - [x@1 a="\n"] hi"#)
+            .expect_err("should reject \\n");
+        assert!(matches!(err.kind(), Some(ErrorKind::InvalidEscape('n'))));
+    }
+
+    #[test]
+    fn test_sd_id() {
+        let id = SdId::parse("exampleSDID@32473.1").expect("should parse");
+        assert_eq!(id.name(), "exampleSDID");
+        assert_eq!(id.enterprise_number(), Some("32473.1"));
+        assert_eq!(id.private_enterprise_code(), Some(32473));
+        assert_eq!(id.to_string(), "exampleSDID@32473.1");
+        assert!(SdId::parse("origin").unwrap().is_registered());
+        assert_eq!(
+            SdId::parse("custom"),
+            Err(SdIdError::MissingEnterpriseNumber(String::from("custom")))
+        );
+        assert_eq!(
+            SdId::parse("custom@acme"),
+            Err(SdIdError::InvalidEnterpriseNumber(String::from("acme")))
+        );
+        assert_eq!(SdId::parse("@1"), Err(SdIdError::EmptyName));
+
+        let m = parse_message(r#"<1>1 - - - - - [ex@1 a="1"][ex@2 a="2"][other@1 a="3"] hi"#)
+            .expect("should parse");
+        let found =
+            m.sd.find_by_name("ex")
+                .map(|(id, e)| (id.private_enterprise_code(), e["a"].as_str()))
+                .collect::<Vec<_>>();
+        assert_eq!(found, vec![(Some(1), "1"), (Some(2), "2")]);
+    }
+
+    #[test]
+    fn test_standard_elements() {
+        let m = parse_message(
+            r#"<1>1 - - - - - [origin ip="192.0.2.1" enterpriseId="32473.1" software="x"][meta sequenceId="29" language="en-US"] hi"#,
+        )
+        .expect("should parse");
+        assert_eq!(m.time_quality(), Ok(None));
+        assert_eq!(
+            m.origin(),
+            Ok(Some(Origin {
+                ip: vec!["192.0.2.1".parse().unwrap()],
+                enterprise_id: Some(String::from("32473.1")),
+                software: Some(String::from("x")),
+                sw_version: None,
+            }))
+        );
+        assert_eq!(
+            m.meta(),
+            Ok(Some(Meta {
+                sequence_id: Some(29),
+                sys_up_time: None,
+                language: Some(String::from("en-US")),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let m =
+            parse_message(r#"<1>1 - - - - - [meta sequenceId="0"][timeQuality isSynced="yes"] hi"#)
+                .expect("should parse");
+        assert!(matches!(
+            m.meta(),
+            Err(SdParamError::InvalidValue {
+                name: "sequenceId",
+                ..
+            })
+        ));
+        assert!(matches!(
+            m.time_quality(),
+            Err(SdParamError::InvalidValue {
+                name: "isSynced",
+                ..
+            })
+        ));
+
+        let m = parse_message(r#"<1>1 - - - - - [timeQuality isSynced="0" syncAccuracy="5"] hi"#)
+            .expect("should parse");
+        assert_eq!(m.time_quality(), Err(SdParamError::SyncAccuracyWithoutSync));
+    }
+}
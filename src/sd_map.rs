@@ -0,0 +1,423 @@
+//! The maps `StructuredData` can keep its SD-IDs and SD-PARAMs in.
+//!
+//! `StructuredData` is a `BTreeMap` of `BTreeMap`s unless told otherwise, so lookups and
+//! iteration through `Deref` are alphabetical. Its type parameter is the map of one SD-ELEMENT's
+//! params (`SdParamMap`), which also picks the map of SD-IDs. Besides `BTreeMap`, there's
+//! `IndexParamMap` (with the `sd-indexmap` feature), which iterates in insertion order, and
+//! `FxParamMap` (with the `sd-fxhash` feature), which is fastest for messages with many params
+//! but iterates in no particular order.
+//!
+//! Only lookups and iterating through `Deref` depend on the map; `iter_tuples`, `iter_ordered`,
+//! serde, `Hash` and the typed accessors in `sd` all come out the same whichever map is used.
+//! Parsed messages always use the default map; `StructuredData::convert` moves one into another.
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(feature = "sd-fxhash")]
+//! # {
+//! use syslog_rfc5424::message::StructuredData;
+//! use syslog_rfc5424::sd_map::FxParamMap;
+//!
+//! let m = syslog_rfc5424::parse_message(r#"<1>1 - - - - - [a@1 x="1" y="2"] hi"#).unwrap();
+//! let sd: StructuredData<FxParamMap> = m.sd.convert();
+//! assert_eq!(sd.find_tuple("a@1", "y").map(String::as_str), Some("2"));
+//! assert_eq!(sd.iter_tuples().collect::<Vec<_>>(), vec![("a@1", "x", "1"), ("a@1", "y", "2")]);
+//! # }
+//! ```
+
+use std::collections::{btree_map, BTreeMap};
+#[cfg(feature = "sd-fxhash")]
+use std::collections::{hash_map, HashMap};
+use std::fmt;
+
+use crate::message::{SDParamIDType, SDParamValueType};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A map from names to `V`s which `StructuredData` can be kept in
+///
+/// This is sealed; it's implemented for `BTreeMap`, `IndexParamMap` and `FxParamMap` (and their
+/// maps of SD-IDs).
+pub trait SdMap<V>: private::Sealed + Clone + fmt::Debug + Default + PartialEq + Eq {
+    /// Whether iteration is already in key order
+    const SORTED: bool;
+
+    type Iter<'a>: ExactSizeIterator<Item = (&'a String, &'a V)>
+    where
+        Self: 'a,
+        V: 'a;
+    type IterMut<'a>: Iterator<Item = (&'a String, &'a mut V)>
+    where
+        Self: 'a,
+        V: 'a;
+    type IntoEntries: Iterator<Item = (String, V)>;
+
+    /// An empty map with room for `capacity` entries, if it has a capacity
+    fn with_capacity(capacity: usize) -> Self;
+    /// Make room for `additional` more entries, if the map has a capacity
+    fn reserve(&mut self, additional: usize);
+    /// Free any spare capacity
+    fn shrink_to_fit(&mut self);
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn get(&self, key: &str) -> Option<&V>;
+    fn get_mut(&mut self, key: &str) -> Option<&mut V>;
+    fn insert(&mut self, key: String, value: V) -> Option<V>;
+    /// The value for `key`, inserting the default first if there isn't one
+    fn get_or_default(&mut self, key: String) -> &mut V
+    where
+        V: Default;
+    /// Remove `key`, keeping the order of everything else
+    fn remove(&mut self, key: &str) -> Option<V>;
+    fn retain<F: FnMut(&String, &mut V) -> bool>(&mut self, f: F);
+    fn iter(&self) -> Self::Iter<'_>;
+    fn iter_mut(&mut self) -> Self::IterMut<'_>;
+    fn into_entries(self) -> Self::IntoEntries;
+}
+
+/// A map of one SD-ELEMENT's params, which `StructuredData` can be kept in
+pub trait SdParamMap: SdMap<SDParamValueType> {
+    /// The same kind of map, from SD-IDs to their params
+    type SdIdMap: SdMap<Self>;
+}
+
+impl<V> private::Sealed for BTreeMap<String, V> {}
+
+impl<V: Clone + fmt::Debug + PartialEq + Eq> SdMap<V> for BTreeMap<String, V> {
+    const SORTED: bool = true;
+
+    type Iter<'a>
+        = btree_map::Iter<'a, String, V>
+    where
+        V: 'a;
+    type IterMut<'a>
+        = btree_map::IterMut<'a, String, V>
+    where
+        V: 'a;
+    type IntoEntries = btree_map::IntoIter<String, V>;
+
+    fn with_capacity(_capacity: usize) -> Self {
+        BTreeMap::new()
+    }
+
+    fn reserve(&mut self, _additional: usize) {}
+
+    fn shrink_to_fit(&mut self) {}
+
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+
+    fn get(&self, key: &str) -> Option<&V> {
+        BTreeMap::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut V> {
+        BTreeMap::get_mut(self, key)
+    }
+
+    fn insert(&mut self, key: String, value: V) -> Option<V> {
+        BTreeMap::insert(self, key, value)
+    }
+
+    fn get_or_default(&mut self, key: String) -> &mut V
+    where
+        V: Default,
+    {
+        self.entry(key).or_default()
+    }
+
+    fn remove(&mut self, key: &str) -> Option<V> {
+        BTreeMap::remove(self, key)
+    }
+
+    fn retain<F: FnMut(&String, &mut V) -> bool>(&mut self, f: F) {
+        BTreeMap::retain(self, f)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        BTreeMap::iter(self)
+    }
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        BTreeMap::iter_mut(self)
+    }
+
+    fn into_entries(self) -> Self::IntoEntries {
+        IntoIterator::into_iter(self)
+    }
+}
+
+impl SdParamMap for BTreeMap<SDParamIDType, SDParamValueType> {
+    type SdIdMap = BTreeMap<String, Self>;
+}
+
+/// An `indexmap::IndexMap` of params, which iterates in insertion order (requires the
+/// `sd-indexmap` feature)
+#[cfg(feature = "sd-indexmap")]
+pub type IndexParamMap = indexmap::IndexMap<SDParamIDType, SDParamValueType>;
+
+#[cfg(feature = "sd-indexmap")]
+impl<V> private::Sealed for indexmap::IndexMap<String, V> {}
+
+#[cfg(feature = "sd-indexmap")]
+impl<V: Clone + fmt::Debug + PartialEq + Eq> SdMap<V> for indexmap::IndexMap<String, V> {
+    const SORTED: bool = false;
+
+    type Iter<'a>
+        = indexmap::map::Iter<'a, String, V>
+    where
+        V: 'a;
+    type IterMut<'a>
+        = indexmap::map::IterMut<'a, String, V>
+    where
+        V: 'a;
+    type IntoEntries = indexmap::map::IntoIter<String, V>;
+
+    fn with_capacity(capacity: usize) -> Self {
+        indexmap::IndexMap::with_capacity(capacity)
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        indexmap::IndexMap::reserve(self, additional)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        indexmap::IndexMap::shrink_to_fit(self)
+    }
+
+    fn len(&self) -> usize {
+        indexmap::IndexMap::len(self)
+    }
+
+    fn get(&self, key: &str) -> Option<&V> {
+        indexmap::IndexMap::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut V> {
+        indexmap::IndexMap::get_mut(self, key)
+    }
+
+    fn insert(&mut self, key: String, value: V) -> Option<V> {
+        indexmap::IndexMap::insert(self, key, value)
+    }
+
+    fn get_or_default(&mut self, key: String) -> &mut V
+    where
+        V: Default,
+    {
+        self.entry(key).or_default()
+    }
+
+    fn remove(&mut self, key: &str) -> Option<V> {
+        self.shift_remove(key)
+    }
+
+    fn retain<F: FnMut(&String, &mut V) -> bool>(&mut self, f: F) {
+        indexmap::IndexMap::retain(self, f)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        indexmap::IndexMap::iter(self)
+    }
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        indexmap::IndexMap::iter_mut(self)
+    }
+
+    fn into_entries(self) -> Self::IntoEntries {
+        IntoIterator::into_iter(self)
+    }
+}
+
+#[cfg(feature = "sd-indexmap")]
+impl SdParamMap for IndexParamMap {
+    type SdIdMap = indexmap::IndexMap<String, Self>;
+}
+
+/// A `HashMap` of params using `rustc_hash::FxBuildHasher` (requires the `sd-fxhash` feature)
+#[cfg(feature = "sd-fxhash")]
+pub type FxParamMap = HashMap<SDParamIDType, SDParamValueType, rustc_hash::FxBuildHasher>;
+
+#[cfg(feature = "sd-fxhash")]
+impl<V> private::Sealed for HashMap<String, V, rustc_hash::FxBuildHasher> {}
+
+#[cfg(feature = "sd-fxhash")]
+impl<V: Clone + fmt::Debug + PartialEq + Eq> SdMap<V>
+    for HashMap<String, V, rustc_hash::FxBuildHasher>
+{
+    const SORTED: bool = false;
+
+    type Iter<'a>
+        = hash_map::Iter<'a, String, V>
+    where
+        V: 'a;
+    type IterMut<'a>
+        = hash_map::IterMut<'a, String, V>
+    where
+        V: 'a;
+    type IntoEntries = hash_map::IntoIter<String, V>;
+
+    fn with_capacity(capacity: usize) -> Self {
+        HashMap::with_capacity_and_hasher(capacity, Default::default())
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        HashMap::reserve(self, additional)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        HashMap::shrink_to_fit(self)
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+
+    fn get(&self, key: &str) -> Option<&V> {
+        HashMap::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut V> {
+        HashMap::get_mut(self, key)
+    }
+
+    fn insert(&mut self, key: String, value: V) -> Option<V> {
+        HashMap::insert(self, key, value)
+    }
+
+    fn get_or_default(&mut self, key: String) -> &mut V
+    where
+        V: Default,
+    {
+        self.entry(key).or_default()
+    }
+
+    fn remove(&mut self, key: &str) -> Option<V> {
+        HashMap::remove(self, key)
+    }
+
+    fn retain<F: FnMut(&String, &mut V) -> bool>(&mut self, f: F) {
+        HashMap::retain(self, f)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        HashMap::iter(self)
+    }
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        HashMap::iter_mut(self)
+    }
+
+    fn into_entries(self) -> Self::IntoEntries {
+        IntoIterator::into_iter(self)
+    }
+}
+
+#[cfg(feature = "sd-fxhash")]
+impl SdParamMap for FxParamMap {
+    type SdIdMap = HashMap<String, Self, rustc_hash::FxBuildHasher>;
+}
+
+/// The entries of an `SdMap` in key order; see `sorted_entries`
+pub(crate) enum SortedEntries<'a, M: SdMap<V> + 'a, V: 'a> {
+    InOrder(M::Iter<'a>),
+    Sorted(std::vec::IntoIter<(&'a String, &'a V)>),
+}
+
+impl<'a, M: SdMap<V>, V> Iterator for SortedEntries<'a, M, V> {
+    type Item = (&'a String, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            SortedEntries::InOrder(iter) => iter.next(),
+            SortedEntries::Sorted(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            SortedEntries::InOrder(iter) => iter.size_hint(),
+            SortedEntries::Sorted(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<M: SdMap<V>, V> ExactSizeIterator for SortedEntries<'_, M, V> {}
+
+/// Iterate over `map` in key order, sorting it first unless it already is
+pub(crate) fn sorted_entries<M: SdMap<V>, V>(map: &M) -> SortedEntries<'_, M, V> {
+    if M::SORTED {
+        return SortedEntries::InOrder(map.iter());
+    }
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_unstable_by_key(|(key, _)| *key);
+    SortedEntries::Sorted(entries.into_iter())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sorted_entries, SdMap};
+    use crate::message::StructuredData;
+
+    #[test]
+    fn test_sorted_entries() {
+        let sd = StructuredData::parse(r#"[b@1 y="2" x="1"][a@1 z="3"]"#).unwrap();
+        let ids: Vec<_> = sorted_entries(&*sd).map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["a@1", "b@1"]);
+        assert_eq!(sorted_entries(&*sd).len(), 2);
+        assert!(SdMap::get(&*sd, "a@1").is_some());
+    }
+
+    #[cfg(all(feature = "sd-indexmap", feature = "sd-fxhash"))]
+    #[test]
+    fn test_other_maps() {
+        use super::{FxParamMap, IndexParamMap};
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let hash = |sd: &dyn Fn(&mut DefaultHasher)| {
+            let mut h = DefaultHasher::new();
+            sd(&mut h);
+            h.finish()
+        };
+        let sd = StructuredData::parse(
+            r#"[b@1 y="2" x="1"][a@1 z="3"][exampleSDID@32473 e="4"][exampleSDID@1 e="5"]"#,
+        )
+        .unwrap();
+        let indexed: StructuredData<IndexParamMap> = sd.clone().convert();
+        let hashed: StructuredData<FxParamMap> = sd.clone().convert();
+        let tuples: Vec<_> = sd.iter_tuples().collect();
+        assert_eq!(indexed.iter_tuples().collect::<Vec<_>>(), tuples);
+        assert_eq!(hashed.iter_tuples().collect::<Vec<_>>(), tuples);
+        assert_eq!(hash(&|h| indexed.hash(h)), hash(&|h| sd.hash(h)));
+        assert_eq!(hash(&|h| hashed.hash(h)), hash(&|h| sd.hash(h)));
+        // whichever element comes first alphabetically, not first in the hash order
+        assert_eq!(hashed.get_path_by_name("exampleSDID/e"), Some("5"));
+        let found: Vec<_> = hashed
+            .find_by_name("exampleSDID")
+            .map(|(sd_id, _)| sd_id.enterprise_number())
+            .collect();
+        assert_eq!(found, vec![Some("1"), Some("32473")]);
+        assert_eq!(
+            hashed.clone().convert::<std::collections::BTreeMap<_, _>>(),
+            sd
+        );
+        #[cfg(feature = "serde-serialize")]
+        assert_eq!(
+            serde_json::to_string(&hashed).unwrap(),
+            serde_json::to_string(&sd).unwrap()
+        );
+
+        let mut ordered = StructuredData::<IndexParamMap>::empty_ordered();
+        ordered.insert_tuple("z@1", "b", "1");
+        ordered.insert_tuple("a@1", "c", "2");
+        ordered.remove_sdid("z@1");
+        assert_eq!(ordered.keys().collect::<Vec<_>>(), vec!["a@1"]);
+    }
+}
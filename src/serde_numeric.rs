@@ -0,0 +1,119 @@
+//! Serializing severities and facilities as their integer codes (requires the `serde-serialize`
+//! feature).
+//!
+//! `SyslogSeverity` and `SyslogFacility` serialize as their names. For a field of either type
+//! which should be written as a number instead, point `serde(with)` at this module. Reading
+//! accepts a name or a code from self-describing formats like JSON, and a code from anything
+//! else.
+//!
+//! To write a whole `SyslogMessage` with numeric codes, use
+//! `serialize::SerializeOptions::with_numeric_codes`.
+//!
+//! # Example
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//! use syslog_rfc5424::SyslogSeverity;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Alert {
+//!     #[serde(with = "syslog_rfc5424::serde_numeric")]
+//!     severity: SyslogSeverity,
+//! }
+//!
+//! let json = serde_json::to_string(&Alert { severity: SyslogSeverity::SEV_ERR }).unwrap();
+//! assert_eq!(json, r#"{"severity":3}"#);
+//! let alert: Alert = serde_json::from_str(r#"{"severity":"warning"}"#).unwrap();
+//! assert_eq!(alert.severity, SyslogSeverity::SEV_WARNING);
+//! ```
+
+use std::convert::TryFrom;
+
+use serde::de::{Deserialize, Deserializer, Error, Unexpected};
+use serde::ser::Serializer;
+
+use crate::facility::SyslogFacility;
+use crate::severity::SyslogSeverity;
+
+/// Something with an integer code; implemented for `SyslogSeverity` and `SyslogFacility`
+pub trait NumericCode: Sized {
+    /// The integer code
+    fn code(&self) -> u8;
+
+    /// The value with this integer code, if there is one
+    fn from_code(code: u8) -> Option<Self>;
+}
+
+impl NumericCode for SyslogSeverity {
+    fn code(&self) -> u8 {
+        *self as u8
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        SyslogSeverity::try_from(i32::from(code)).ok()
+    }
+}
+
+impl NumericCode for SyslogFacility {
+    fn code(&self) -> u8 {
+        *self as u8
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        SyslogFacility::try_from(i32::from(code)).ok()
+    }
+}
+
+/// Serialize `value` as its integer code
+pub fn serialize<T: NumericCode, S: Serializer>(value: &T, ser: S) -> Result<S::Ok, S::Error> {
+    ser.serialize_u8(value.code())
+}
+
+/// Deserialize a name or an integer code, or only a code if the format isn't self-describing
+pub fn deserialize<'de, T, D>(des: D) -> Result<T, D::Error>
+where
+    T: NumericCode + Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    if des.is_human_readable() {
+        return T::deserialize(des);
+    }
+    let code = u8::deserialize(des)?;
+    T::from_code(code)
+        .ok_or_else(|| D::Error::invalid_value(Unexpected::Unsigned(code.into()), &"a valid code"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{SyslogFacility, SyslogSeverity};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Codes {
+        #[serde(with = "super")]
+        severity: SyslogSeverity,
+        #[serde(with = "super")]
+        facility: SyslogFacility,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let codes = Codes {
+            severity: SyslogSeverity::SEV_NOTICE,
+            facility: SyslogFacility::LOG_LOCAL4,
+        };
+        let json = serde_json::to_string(&codes).unwrap();
+        assert_eq!(json, r#"{"severity":5,"facility":20}"#);
+        assert_eq!(serde_json::from_str::<Codes>(&json).unwrap(), codes);
+        assert_eq!(
+            serde_json::from_str::<Codes>(r#"{"severity":"notice","facility":"local4"}"#).unwrap(),
+            codes
+        );
+
+        let bytes = postcard::to_allocvec(&codes).unwrap();
+        assert_eq!(bytes, vec![5, 20]);
+        assert_eq!(postcard::from_bytes::<Codes>(&bytes).unwrap(), codes);
+        assert!(postcard::from_bytes::<Codes>(&[8, 20]).is_err());
+    }
+}
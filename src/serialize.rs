@@ -0,0 +1,283 @@
+//! Customizing how messages are serialized (requires the `serde-serialize` feature).
+//!
+//! The `serde` representation of `SyslogMessage` has fixed, snake_case keys. Wrapping a message
+//! with `SerializeOptions::wrap` (or `SyslogMessage::serialize_with`) gives something which
+//! serializes with the same values, but can:
+//!
+//!  * change the case of every key (`FieldCase::Camel` turns `timestamp_nanos` into
+//!    `timestampNanos`)
+//!  * rename individual keys, like `hostname` to `host`; renames take precedence over the case
+//!  * leave out NIL fields, rather than writing them as `null`
+//!  * flatten the structured data into `sd.<SD-ID>.<PARAM-NAME>` keys
+//!  * write the severity and facility as integer codes rather than names
+//!
+//! Only serialization is affected; use the plain representation for anything which needs to be
+//! deserialized again.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::parse_message;
+//! use syslog_rfc5424::serialize::{FieldCase, SerializeOptions};
+//!
+//! let m = parse_message(r#"<11>1 - web1 nginx - - [req@32473 status="500"] hi"#).unwrap();
+//! let options = SerializeOptions::new()
+//!     .with_case(FieldCase::Camel)
+//!     .with_rename("hostname", "host")
+//!     .with_skip_nulls(true)
+//!     .with_flattened_sd(true);
+//! let json = serde_json::to_value(options.wrap(&m)).unwrap();
+//! assert_eq!(json["host"], "web1");
+//! assert_eq!(json["sd.req@32473.status"], "500");
+//! assert!(json.get("msgid").is_none());
+//! ```
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::message::{msg_bytes_base64, SyslogMessage};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+/// The case keys are written in
+pub enum FieldCase {
+    /// `timestamp_nanos`, as in the plain representation
+    #[default]
+    Snake,
+    /// `timestampNanos`
+    Camel,
+    /// `TimestampNanos`
+    Pascal,
+    /// `timestamp-nanos`
+    Kebab,
+}
+
+impl FieldCase {
+    /// Convert a snake_case field name into this case
+    fn apply(self, name: &'static str) -> Cow<'static, str> {
+        let mut words = name.split('_');
+        let capitalize = |word: &str| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_ascii_uppercase().to_string() + chars.as_str()
+            })
+        };
+        match self {
+            FieldCase::Snake => Cow::Borrowed(name),
+            FieldCase::Kebab => Cow::Owned(name.replace('_', "-")),
+            FieldCase::Camel => {
+                let first = words.next().unwrap_or_default().to_string();
+                Cow::Owned(words.fold(first, |s, word| s + &capitalize(word)))
+            }
+            FieldCase::Pascal => Cow::Owned(words.map(capitalize).collect()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// How to serialize a message; see the module documentation
+pub struct SerializeOptions {
+    case: FieldCase,
+    renames: BTreeMap<String, String>,
+    skip_nulls: bool,
+    flatten_sd: bool,
+    numeric_codes: bool,
+}
+
+impl SerializeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the case of the keys (default: `FieldCase::Snake`)
+    pub fn with_case(mut self, case: FieldCase) -> Self {
+        self.case = case;
+        self
+    }
+
+    /// Write the field which is normally called `field` (in snake_case, like `hostname` or
+    /// `timestamp_nanos`) as `name` instead
+    pub fn with_rename<F: Into<String>, N: Into<String>>(mut self, field: F, name: N) -> Self {
+        self.renames.insert(field.into(), name.into());
+        self
+    }
+
+    /// Leave out NIL fields instead of writing them as `null` (default: false)
+    pub fn with_skip_nulls(mut self, skip_nulls: bool) -> Self {
+        self.skip_nulls = skip_nulls;
+        self
+    }
+
+    /// Write each SD-PARAM as a top-level `sd.<SD-ID>.<PARAM-NAME>` key, rather than as a nested
+    /// `sd` map (default: false)
+    ///
+    /// The `sd` prefix is renamed and recased like any other key.
+    pub fn with_flattened_sd(mut self, flatten_sd: bool) -> Self {
+        self.flatten_sd = flatten_sd;
+        self
+    }
+
+    /// Write the severity and facility as their integer codes, like `3` rather than `"err"`
+    /// (default: false)
+    ///
+    /// See the `serde_numeric` module for doing the same to a field of your own.
+    pub fn with_numeric_codes(mut self, numeric_codes: bool) -> Self {
+        self.numeric_codes = numeric_codes;
+        self
+    }
+
+    /// Pair a message with these options, for serializing
+    pub fn wrap<'a>(&'a self, m: &'a SyslogMessage) -> WithOptions<'a> {
+        WithOptions { m, options: self }
+    }
+
+    fn key(&self, field: &'static str) -> Cow<'_, str> {
+        match self.renames.get(field) {
+            Some(name) => Cow::Borrowed(name),
+            None => self.case.apply(field),
+        }
+    }
+
+    /// Write a field which may be NIL
+    fn nullable<M: SerializeMap, T: Serialize>(
+        &self,
+        map: &mut M,
+        field: &'static str,
+        value: Option<T>,
+    ) -> Result<(), M::Error> {
+        match value {
+            Some(value) => map.serialize_entry(&self.key(field), &value),
+            None if self.skip_nulls => Ok(()),
+            None => map.serialize_entry(&self.key(field), &()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+/// A message which serializes according to some `SerializeOptions`
+pub struct WithOptions<'a> {
+    m: &'a SyslogMessage,
+    options: &'a SerializeOptions,
+}
+
+/// `msg_bytes`, written as base64 like the plain representation does
+struct MsgBytes<'a>(&'a Option<Vec<u8>>);
+
+impl<'a> Serialize for MsgBytes<'a> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        msg_bytes_base64::serialize(self.0, ser)
+    }
+}
+
+impl<'a> Serialize for WithOptions<'a> {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let (m, options) = (self.m, self.options);
+        let mut map = ser.serialize_map(None)?;
+        if options.numeric_codes {
+            map.serialize_entry(&options.key("severity"), &(m.severity as u8))?;
+            map.serialize_entry(&options.key("facility"), &(m.facility as u8))?;
+        } else {
+            map.serialize_entry(&options.key("severity"), &m.severity)?;
+            map.serialize_entry(&options.key("facility"), &m.facility)?;
+        }
+        map.serialize_entry(&options.key("version"), &m.version)?;
+        options.nullable(&mut map, "timestamp", m.timestamp.map(|t| t.secs))?;
+        options.nullable(&mut map, "timestamp_nanos", m.timestamp.map(|t| t.nanos))?;
+        if let Some(t) = m.timestamp {
+            map.serialize_entry(&options.key("utc_offset"), &t.offset)?;
+        }
+        if m.timestamp_precision_lost {
+            map.serialize_entry(&options.key("timestamp_precision_lost"), &true)?;
+        }
+        if m.leap_second {
+            map.serialize_entry(&options.key("leap_second"), &true)?;
+        }
+        options.nullable(&mut map, "hostname", m.hostname.as_ref())?;
+        options.nullable(&mut map, "appname", m.appname.as_ref())?;
+        options.nullable(&mut map, "procid", m.procid.as_ref())?;
+        options.nullable(&mut map, "msgid", m.msgid.as_ref())?;
+        if options.flatten_sd {
+            let prefix = options.key("sd");
+            for (sd_id, sd_param_id, value) in m.sd.iter_tuples() {
+                let key = format!("{}.{}.{}", prefix, sd_id, sd_param_id);
+                map.serialize_entry(&key, value)?;
+            }
+        } else {
+            map.serialize_entry(&options.key("sd"), &m.sd)?;
+        }
+        map.serialize_entry(&options.key("msg"), &m.msg)?;
+        if m.msg_is_utf8 {
+            map.serialize_entry(&options.key("msg_is_utf8"), &true)?;
+        }
+        if m.msg_bytes.is_some() {
+            map.serialize_entry(&options.key("msg_bytes"), &MsgBytes(&m.msg_bytes))?;
+        }
+        if m.truncated {
+            map.serialize_entry(&options.key("truncated"), &true)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FieldCase, SerializeOptions};
+    use crate::parse_message;
+
+    #[test]
+    fn test_default_matches_plain_representation() {
+        for input in [
+            "<1>1 - - - - - - hi",
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine evntslog 10 ID47 [exampleSDID@32473 iut="3" eventSource="Application"][b a="1"] \u{feff}hi"#,
+        ] {
+            let m = parse_message(input).unwrap();
+            assert_eq!(
+                serde_json::to_string(&SerializeOptions::new().wrap(&m)).unwrap(),
+                serde_json::to_string(&m).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_options() {
+        let m = parse_message(
+            "<1>1 2003-10-11T22:14:15.003+01:00 - - 10 - [a@1 x=\"1\" y=\"2\"][b@1 z=\"3\"] hi",
+        )
+        .unwrap();
+        let options = SerializeOptions::new()
+            .with_case(FieldCase::Camel)
+            .with_rename("sd", "structured")
+            .with_rename("utc_offset", "tz")
+            .with_skip_nulls(true)
+            .with_flattened_sd(true);
+        assert_eq!(
+            serde_json::to_value(m.serialize_with(&options)).unwrap(),
+            serde_json::json!({
+                "severity": "alert",
+                "facility": "kern",
+                "version": 1,
+                "timestamp": 1065906855,
+                "timestampNanos": 3000000,
+                "tz": 3600,
+                "procid": 10,
+                "structured.a@1.x": "1",
+                "structured.a@1.y": "2",
+                "structured.b@1.z": "3",
+                "msg": "hi",
+            })
+        );
+
+        let options = SerializeOptions::new().with_case(FieldCase::Kebab);
+        let value = serde_json::to_value(options.wrap(&m)).unwrap();
+        assert_eq!(value["timestamp-nanos"], 3000000);
+        assert!(value["hostname"].is_null());
+        assert_eq!(value["sd"]["a@1"]["y"], "2");
+        assert_eq!(FieldCase::Pascal.apply("msg_is_utf8"), "MsgIsUtf8");
+
+        let options = SerializeOptions::new().with_numeric_codes(true);
+        let value = serde_json::to_value(options.wrap(&m)).unwrap();
+        assert_eq!(value["severity"], 1);
+        assert_eq!(value["facility"], 0);
+    }
+}
@@ -1,13 +1,15 @@
 use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
 
 #[cfg(feature = "serde-serialize")]
 use serde::{de::Visitor, Deserialize, Serialize, Serializer};
 
 use thiserror::Error;
 
-use crate::parser::ParseErr;
+use crate::parser::{ErrorKind, ParseErr};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[allow(non_camel_case_types)]
 /// Syslog Severities from RFC 5424.
 pub enum SyslogSeverity {
@@ -70,21 +72,49 @@ impl SyslogSeverity {
     }
 
     /// Convert a string to a syslog severity
+    ///
+    /// Matching is case-insensitive, and also accepts a `log_` or `sev_` prefix (as in
+    /// `LOG_ERR`), the deprecated syslog.conf keywords `panic`, `error` and `warn`, and the names
+    /// used in RFC 5424 (`emergency`, `critical`, `error` and `informational`).
     pub fn from_str(v: &str) -> Result<SyslogSeverity, ParseErr> {
+        let v = v.to_ascii_lowercase();
+        let v = v
+            .strip_prefix("log_")
+            .or_else(|| v.strip_prefix("sev_"))
+            .unwrap_or(&v);
         match v {
-            "emerg" => Ok(SyslogSeverity::SEV_EMERG),
+            "emerg" | "panic" | "emergency" => Ok(SyslogSeverity::SEV_EMERG),
             "alert" => Ok(SyslogSeverity::SEV_ALERT),
-            "crit" => Ok(SyslogSeverity::SEV_CRIT),
-            "err" => Ok(SyslogSeverity::SEV_ERR),
-            "warning" => Ok(SyslogSeverity::SEV_WARNING),
+            "crit" | "critical" => Ok(SyslogSeverity::SEV_CRIT),
+            "err" | "error" => Ok(SyslogSeverity::SEV_ERR),
+            "warning" | "warn" => Ok(SyslogSeverity::SEV_WARNING),
             "notice" => Ok(SyslogSeverity::SEV_NOTICE),
-            "info" => Ok(SyslogSeverity::SEV_INFO),
+            "info" | "informational" => Ok(SyslogSeverity::SEV_INFO),
             "debug" => Ok(SyslogSeverity::SEV_DEBUG),
-            &_ => Err(ParseErr::BadSeverityInPri),
+            &_ => Err(ParseErr::Pri {
+                offset: 0,
+                found: v.to_string(),
+                kind: ErrorKind::BadSeverity,
+            }),
         }
     }
 }
 
+impl fmt::Display for SyslogSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SyslogSeverity {
+    type Err = ParseErr;
+
+    /// Same as the inherent `from_str`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SyslogSeverity::from_str(s)
+    }
+}
+
 #[cfg(feature = "serde-serialize")]
 impl Serialize for SyslogSeverity {
     fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
@@ -100,15 +130,35 @@ impl<'de> Visitor<'de> for SyslogSeverityVisitor {
     type Value = SyslogSeverity;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("a string")
+        formatter.write_str("a severity name or integer code")
     }
 
-    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
         SyslogSeverity::from_str(v).map_err(|err| E::custom(err.to_string()))
     }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        i32::try_from(v)
+            .ok()
+            .and_then(|v| SyslogSeverity::try_from(v).ok())
+            .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Signed(v), &self))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        i32::try_from(v)
+            .ok()
+            .and_then(|v| SyslogSeverity::try_from(v).ok())
+            .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+    }
 }
 
 #[cfg(feature = "serde-serialize")]
@@ -117,7 +167,12 @@ impl<'de> Deserialize<'de> for SyslogSeverity {
     where
         D: serde::Deserializer<'de>,
     {
-        des.deserialize_str(SyslogSeverityVisitor)
+        // accept either form from self-describing formats; other formats get what we'd write
+        if des.is_human_readable() {
+            des.deserialize_any(SyslogSeverityVisitor)
+        } else {
+            des.deserialize_str(SyslogSeverityVisitor)
+        }
     }
 }
 
@@ -136,4 +191,34 @@ mod tests {
         assert_eq!(SyslogSeverity::SEV_INFO.as_str(), "info");
         assert_eq!(SyslogSeverity::SEV_DEBUG.as_str(), "debug");
     }
+
+    #[test]
+    fn test_display_fromstr() {
+        assert_eq!(SyslogSeverity::SEV_WARNING.to_string(), "warning");
+        assert_eq!(
+            "notice".parse::<SyslogSeverity>().unwrap(),
+            SyslogSeverity::SEV_NOTICE
+        );
+        assert!("bogus".parse::<SyslogSeverity>().is_err());
+    }
+
+    #[test]
+    fn test_from_str_aliases() {
+        for (name, severity) in [
+            ("ERR", SyslogSeverity::SEV_ERR),
+            ("error", SyslogSeverity::SEV_ERR),
+            ("LOG_ERR", SyslogSeverity::SEV_ERR),
+            ("Warn", SyslogSeverity::SEV_WARNING),
+            ("panic", SyslogSeverity::SEV_EMERG),
+            ("SEV_DEBUG", SyslogSeverity::SEV_DEBUG),
+            ("informational", SyslogSeverity::SEV_INFO),
+        ] {
+            assert_eq!(
+                SyslogSeverity::from_str(name).unwrap(),
+                severity,
+                "{}",
+                name
+            );
+        }
+    }
 }
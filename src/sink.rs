@@ -0,0 +1,67 @@
+//! Destinations for outgoing messages.
+//!
+//! `Sink` is what the emitting side of this crate (`transport::relay::Relay`, and the `tracing` and
+//! `log` integrations) writes messages to. It's implemented for the senders in `transport`, for
+//! `WriteSink`, and for closures, so messages can go anywhere else too.
+
+use std::io::{self, Write};
+
+use crate::message::SyslogMessage;
+
+/// Somewhere messages can be delivered to
+pub trait Sink {
+    fn send(&mut self, m: &SyslogMessage) -> io::Result<()>;
+}
+
+impl<F> Sink for F
+where
+    F: FnMut(&SyslogMessage) -> io::Result<()>,
+{
+    fn send(&mut self, m: &SyslogMessage) -> io::Result<()> {
+        self(m)
+    }
+}
+
+#[derive(Debug)]
+/// A `Sink` which writes each message to an `io::Write`, followed by a newline
+///
+/// This is the format `reader::SyslogReader` reads, and what most tools expect in a file.
+pub struct WriteSink<W> {
+    writer: W,
+}
+
+impl<W: Write> WriteSink<W> {
+    pub fn new(writer: W) -> Self {
+        WriteSink { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Sink for WriteSink<W> {
+    fn send(&mut self, m: &SyslogMessage) -> io::Result<()> {
+        m.write_to(&mut self.writer)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sink, WriteSink};
+    use crate::parse_message;
+
+    #[test]
+    fn test_write_sink() {
+        let mut sink = WriteSink::new(Vec::new());
+        let m = parse_message("<14>1 - host app - - - hi").unwrap();
+        sink.send(&m).unwrap();
+        sink.send(&m).unwrap();
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "<14>1 - host app - - - hi\n<14>1 - host app - - - hi\n"
+        );
+    }
+}
@@ -0,0 +1,547 @@
+//! Signed syslog messages (RFC 5848).
+//!
+//! RFC 5848 lets a sender prove that its messages weren't forged, altered or dropped. Every so
+//! often it sends a Signature Block: a message whose `ssign` SD-ELEMENT holds the hashes of the
+//! messages it has sent since the last one, signed with its private key. The matching public key is
+//! sent in Certificate Blocks (`ssign-cert`), split into fragments if it doesn't fit in one message.
+//!
+//! This module reads both kinds of block. Checking signatures and hashes needs the `ssign` feature,
+//! which adds `verifier::StreamVerifier`.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::parse_message;
+//! use syslog_rfc5424::ssign::{SignatureBlock, SignatureGroup};
+//!
+//! let m = parse_message(
+//!     r#"<110>1 2024-01-01T00:00:00Z host app - - [ssign VER="0111" RSID="1" SG="0" SPRI="0" GBC="3" FMN="5" CNT="2" HB="aGFzaDE= aGFzaDI=" SIGN="c2ln"]"#,
+//! )
+//! .unwrap();
+//! let block = SignatureBlock::from_message(&m).unwrap();
+//! assert_eq!(block.signature_group, SignatureGroup::Global);
+//! assert_eq!(block.message_numbers(), 5..7);
+//! ```
+
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::message::{StructuredData, SyslogMessage};
+use crate::parser::{parse_rfc3339_timestamp, ParseErr};
+use crate::timestamp::Timestamp;
+
+#[cfg(feature = "ssign")]
+pub mod verifier;
+
+/// The SD-ID of a Signature Block
+pub const SSIGN_SD_ID: &str = "ssign";
+/// The SD-ID of a Certificate Block
+pub const SSIGN_CERT_SD_ID: &str = "ssign-cert";
+/// The longest Payload Block `PayloadAssembler` will put together, unless told otherwise
+pub const DEFAULT_MAX_PAYLOAD_LENGTH: usize = 64 * 1024;
+
+#[derive(Debug, Error)]
+pub enum SsignError {
+    #[error("message has no {0} SD-ELEMENT")]
+    MissingElement(&'static str),
+    #[error("missing SD-PARAM {0}")]
+    MissingParam(&'static str),
+    #[error("invalid value for SD-PARAM {0}")]
+    InvalidParam(&'static str),
+    #[error("unsupported signed syslog version {0:?}")]
+    UnsupportedVersion(String),
+    #[error("invalid Payload Block")]
+    InvalidPayload,
+    #[error("signature does not match")]
+    BadSignature,
+    #[error("could not parse message: {0}")]
+    Parse(#[from] ParseErr),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// The hash algorithm a block's hashes and signature use
+pub enum HashAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// The VER SD-PARAM
+///
+/// RFC 5848 defines version `01` of the protocol, with SHA-1 or SHA-256 hashes and OpenPGP DSA
+/// signatures, so the hash algorithm is the only thing which varies.
+pub struct Version {
+    pub hash_algorithm: HashAlgorithm,
+}
+
+impl FromStr for Version {
+    type Err = SsignError;
+
+    fn from_str(s: &str) -> Result<Self, SsignError> {
+        let hash_algorithm = match s {
+            "0111" => HashAlgorithm::Sha1,
+            "0121" => HashAlgorithm::Sha256,
+            _ => return Err(SsignError::UnsupportedVersion(s.to_string())),
+        };
+        Ok(Version { hash_algorithm })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.hash_algorithm {
+            HashAlgorithm::Sha1 => f.write_str("0111"),
+            HashAlgorithm::Sha256 => f.write_str("0121"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Which of the sender's messages a block covers (the SG and SPRI SD-PARAMs)
+///
+/// Messages are numbered separately in each group.
+pub enum SignatureGroup {
+    /// `SG="0"`: every message
+    Global,
+    /// `SG="1"`: messages with this PRI value
+    Pri(u8),
+    /// `SG="2"`: messages with a PRI value up to this one, and above the next group down
+    PriRange(u8),
+    /// `SG="3"`: a group of the sender's choosing
+    Custom(u8),
+}
+
+impl SignatureGroup {
+    /// The SG and SPRI values
+    pub fn to_params(self) -> (u8, u8) {
+        match self {
+            SignatureGroup::Global => (0, 0),
+            SignatureGroup::Pri(pri) => (1, pri),
+            SignatureGroup::PriRange(pri) => (2, pri),
+            SignatureGroup::Custom(pri) => (3, pri),
+        }
+    }
+
+    fn from_params(sg: u8, spri: u8) -> Result<Self, SsignError> {
+        if spri > 191 {
+            return Err(SsignError::InvalidParam("SPRI"));
+        }
+        match sg {
+            0 => Ok(SignatureGroup::Global),
+            1 => Ok(SignatureGroup::Pri(spri)),
+            2 => Ok(SignatureGroup::PriRange(spri)),
+            3 => Ok(SignatureGroup::Custom(spri)),
+            _ => Err(SsignError::InvalidParam("SG")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The `ssign` SD-ELEMENT of a Signature Block
+pub struct SignatureBlock {
+    pub version: Version,
+    /// RSID: changes whenever the sender restarts, which starts the numbering over
+    pub reboot_session_id: u64,
+    pub signature_group: SignatureGroup,
+    /// GBC: counts the blocks the sender has sent in this reboot session
+    pub global_block_counter: u64,
+    /// FMN: the message number of the first hash in `hashes`
+    pub first_message_number: u64,
+    /// HB: the base64 hashes of consecutive messages in the group (as many as CNT says)
+    pub hashes: Vec<String>,
+    /// SIGN: the base64 signature of the whole block message
+    pub signature: String,
+}
+
+impl SignatureBlock {
+    /// Read the `ssign` SD-ELEMENT from STRUCTURED-DATA
+    pub fn from_sd(sd: &StructuredData) -> Result<Self, SsignError> {
+        let params = Params::new(sd, SSIGN_SD_ID)?;
+        let count: usize = params.number("CNT", 2)?;
+        let hashes: Vec<String> = params
+            .get("HB")?
+            .split(' ')
+            .filter(|h| !h.is_empty())
+            .map(String::from)
+            .collect();
+        if count == 0 || hashes.len() != count {
+            return Err(SsignError::InvalidParam("CNT"));
+        }
+        Ok(SignatureBlock {
+            version: params.version()?,
+            reboot_session_id: params.number("RSID", 10)?,
+            signature_group: params.signature_group()?,
+            global_block_counter: params.number("GBC", 10)?,
+            first_message_number: params.number("FMN", 10)?,
+            hashes,
+            signature: params.get("SIGN")?.to_string(),
+        })
+    }
+
+    pub fn from_message(m: &SyslogMessage) -> Result<Self, SsignError> {
+        Self::from_sd(&m.sd)
+    }
+
+    /// The numbers of the messages `hashes` are for
+    pub fn message_numbers(&self) -> Range<u64> {
+        self.first_message_number..self.first_message_number + self.hashes.len() as u64
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The `ssign-cert` SD-ELEMENT of a Certificate Block
+pub struct CertificateBlock {
+    pub version: Version,
+    pub reboot_session_id: u64,
+    pub signature_group: SignatureGroup,
+    /// GBC: counts the blocks the sender has sent in this reboot session
+    pub global_block_counter: u64,
+    /// TPBL: the length of the whole Payload Block
+    pub total_payload_length: usize,
+    /// INDEX: where `fragment` starts in the Payload Block, counting from 1
+    pub index: usize,
+    /// FRAG: this piece of the Payload Block
+    pub fragment: String,
+    /// SIGN: the base64 signature of the whole block message
+    pub signature: String,
+}
+
+impl CertificateBlock {
+    /// Read the `ssign-cert` SD-ELEMENT from STRUCTURED-DATA
+    pub fn from_sd(sd: &StructuredData) -> Result<Self, SsignError> {
+        let params = Params::new(sd, SSIGN_CERT_SD_ID)?;
+        let total_payload_length = params.number("TPBL", 8)?;
+        let index: usize = params.number("INDEX", 8)?;
+        let fragment_length: usize = params.number("FLEN", 4)?;
+        let fragment = params.get("FRAG")?;
+        if fragment.len() != fragment_length {
+            return Err(SsignError::InvalidParam("FLEN"));
+        }
+        if index == 0 || index - 1 + fragment_length > total_payload_length {
+            return Err(SsignError::InvalidParam("INDEX"));
+        }
+        Ok(CertificateBlock {
+            version: params.version()?,
+            reboot_session_id: params.number("RSID", 10)?,
+            signature_group: params.signature_group()?,
+            global_block_counter: params.number("GBC", 10)?,
+            total_payload_length,
+            index,
+            fragment: fragment.to_string(),
+            signature: params.get("SIGN")?.to_string(),
+        })
+    }
+
+    pub fn from_message(m: &SyslogMessage) -> Result<Self, SsignError> {
+        Self::from_sd(&m.sd)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// What kind of key a Payload Block carries
+pub enum KeyBlobType {
+    /// `C`: a PKIX certificate
+    PkixCertificate,
+    /// `P`: an OpenPGP certificate
+    OpenPgpCertificate,
+    /// `K`: a bare public key, as a DER-encoded SubjectPublicKeyInfo
+    PublicKey,
+    /// `N`: no key; the receiver has to get it some other way
+    None,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A Payload Block, reassembled from the fragments in a sender's Certificate Blocks
+pub struct Payload {
+    /// When the sender started using this key
+    pub timestamp: Timestamp,
+    pub key_blob_type: KeyBlobType,
+    /// The base64 key (empty for `KeyBlobType::None`)
+    pub key_blob: String,
+}
+
+impl FromStr for Payload {
+    type Err = SsignError;
+
+    fn from_str(s: &str) -> Result<Self, SsignError> {
+        let mut parts = s.splitn(3, ' ');
+        let (timestamp, key_blob_type, key_blob) = match (parts.next(), parts.next(), parts.next())
+        {
+            (Some(t), Some(k), b) => (t, k, b.unwrap_or("")),
+            _ => return Err(SsignError::InvalidPayload),
+        };
+        let key_blob_type = match key_blob_type {
+            "C" => KeyBlobType::PkixCertificate,
+            "P" => KeyBlobType::OpenPgpCertificate,
+            "K" => KeyBlobType::PublicKey,
+            "N" => KeyBlobType::None,
+            _ => return Err(SsignError::InvalidPayload),
+        };
+        Ok(Payload {
+            timestamp: parse_rfc3339_timestamp(timestamp)?,
+            key_blob_type,
+            key_blob: key_blob.to_string(),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Puts a Payload Block back together from the fragments in Certificate Blocks
+///
+/// Certificate Blocks are often sent more than once, and may arrive in any order; repeats are
+/// ignored. Only one Payload Block is assembled at a time, so a block with a different reboot
+/// session, signature group or length starts over. Blocks aren't authenticated until the whole
+/// payload is, so longer Payload Blocks than `DEFAULT_MAX_PAYLOAD_LENGTH` are refused.
+pub struct PayloadAssembler {
+    max_payload_length: usize,
+    current: Option<(u64, SignatureGroup, usize)>,
+    payload: Vec<Option<u8>>,
+}
+
+impl Default for PayloadAssembler {
+    fn default() -> Self {
+        PayloadAssembler {
+            max_payload_length: DEFAULT_MAX_PAYLOAD_LENGTH,
+            current: None,
+            payload: Vec::new(),
+        }
+    }
+}
+
+impl PayloadAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refuse Payload Blocks longer than this (default: `DEFAULT_MAX_PAYLOAD_LENGTH`)
+    pub fn with_max_payload_length(mut self, max_payload_length: usize) -> Self {
+        self.max_payload_length = max_payload_length;
+        self
+    }
+
+    /// Add a fragment, returning the Payload Block once all of it has arrived
+    ///
+    /// A fragment which doesn't fit in the Payload Block fails with `SsignError::InvalidParam`,
+    /// as does a Payload Block longer than the maximum.
+    pub fn add(&mut self, block: &CertificateBlock) -> Option<Result<Payload, SsignError>> {
+        let total = block.total_payload_length;
+        if total > self.max_payload_length {
+            return Some(Err(SsignError::InvalidParam("TPBL")));
+        }
+        let end = match block.index.checked_sub(1) {
+            Some(start) if start <= total && block.fragment.len() <= total - start => {
+                start + block.fragment.len()
+            }
+            _ => return Some(Err(SsignError::InvalidParam("INDEX"))),
+        };
+        let key = (block.reboot_session_id, block.signature_group, total);
+        if self.current != Some(key) {
+            self.current = Some(key);
+            self.payload.clear();
+        }
+        if self.payload.len() < end {
+            self.payload.resize(end, None);
+        }
+        for (slot, b) in self.payload[end - block.fragment.len()..]
+            .iter_mut()
+            .zip(block.fragment.bytes())
+        {
+            *slot = Some(b);
+        }
+        if self.payload.len() < total {
+            return None;
+        }
+        let bytes = self.payload.iter().copied().collect::<Option<Vec<u8>>>()?;
+        self.current = None;
+        self.payload.clear();
+        Some(
+            String::from_utf8(bytes)
+                .map_err(|_| SsignError::InvalidPayload)
+                .and_then(|s| s.parse()),
+        )
+    }
+}
+
+/// The SD-PARAMs of one SD-ELEMENT, for reading blocks
+struct Params<'a> {
+    sd: &'a StructuredData,
+    sd_id: &'static str,
+}
+
+impl<'a> Params<'a> {
+    fn new(sd: &'a StructuredData, sd_id: &'static str) -> Result<Self, SsignError> {
+        if sd.find_sdid(sd_id).is_none() {
+            return Err(SsignError::MissingElement(sd_id));
+        }
+        Ok(Params { sd, sd_id })
+    }
+
+    fn get(&self, name: &'static str) -> Result<&'a str, SsignError> {
+        self.sd
+            .find_tuple(self.sd_id, name)
+            .map(String::as_str)
+            .ok_or(SsignError::MissingParam(name))
+    }
+
+    /// A decimal number of at most `max_digits` digits
+    fn number<T: FromStr>(&self, name: &'static str, max_digits: usize) -> Result<T, SsignError> {
+        let value = self.get(name)?;
+        if value.is_empty()
+            || value.len() > max_digits
+            || !value.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(SsignError::InvalidParam(name));
+        }
+        value.parse().map_err(|_| SsignError::InvalidParam(name))
+    }
+
+    fn version(&self) -> Result<Version, SsignError> {
+        self.get("VER")?.parse()
+    }
+
+    fn signature_group(&self) -> Result<SignatureGroup, SsignError> {
+        SignatureGroup::from_params(self.number("SG", 1)?, self.number("SPRI", 3)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CertificateBlock, HashAlgorithm, KeyBlobType, PayloadAssembler, SignatureBlock,
+        SignatureGroup, SsignError, Version,
+    };
+    use crate::parse_message;
+
+    #[test]
+    fn test_signature_block() {
+        let m = parse_message(
+            r#"<110>1 - host app - - [ssign VER="0121" RSID="1234" SG="1" SPRI="14" GBC="7" FMN="21" CNT="3" HB="YQ== Yg== Yw==" SIGN="c2ln"]"#,
+        )
+        .unwrap();
+        let block = SignatureBlock::from_message(&m).expect("should read");
+        assert_eq!(block.version.hash_algorithm, HashAlgorithm::Sha256);
+        assert_eq!(block.version.to_string(), "0121");
+        assert_eq!(block.reboot_session_id, 1234);
+        assert_eq!(block.signature_group, SignatureGroup::Pri(14));
+        assert_eq!(block.signature_group.to_params(), (1, 14));
+        assert_eq!(block.global_block_counter, 7);
+        assert_eq!(block.message_numbers(), 21..24);
+        assert_eq!(block.hashes, vec!["YQ==", "Yg==", "Yw=="]);
+        assert_eq!(block.signature, "c2ln");
+
+        let read = |sd: &str| {
+            let m = parse_message(format!("<110>1 - host app - - {}", sd)).unwrap();
+            SignatureBlock::from_message(&m)
+        };
+        let valid =
+            r#"VER="0111" RSID="1" SG="0" SPRI="0" GBC="1" FMN="1" CNT="1" HB="YQ==" SIGN="""#;
+        assert!(read(&format!("[ssign {}]", valid)).is_ok());
+        assert!(matches!(
+            read(&format!("[other {}]", valid)),
+            Err(SsignError::MissingElement("ssign"))
+        ));
+        for (from, to, error) in [
+            ("CNT=\"1\"", "CNT=\"2\"", "CNT"),
+            ("SG=\"0\"", "SG=\"4\"", "SG"),
+            ("SPRI=\"0\"", "SPRI=\"192\"", "SPRI"),
+            ("RSID=\"1\"", "RSID=\"12345678901\"", "RSID"),
+            ("GBC=\"1\"", "GBC=\"-1\"", "GBC"),
+        ] {
+            match read(&format!("[ssign {}]", valid.replace(from, to))) {
+                Err(SsignError::InvalidParam(name)) => assert_eq!(name, error),
+                other => panic!("expected InvalidParam({}), got {:?}", error, other),
+            }
+        }
+        assert!(matches!(
+            read(&format!(
+                "[ssign {}]",
+                valid.replace("VER=\"0111\"", "VER=\"0231\"")
+            )),
+            Err(SsignError::UnsupportedVersion(_))
+        ));
+        assert!(matches!(
+            read(&format!("[ssign {}]", valid.replace("FMN=\"1\" ", ""))),
+            Err(SsignError::MissingParam("FMN"))
+        ));
+        assert_eq!("0111".parse::<Version>().unwrap().to_string(), "0111");
+    }
+
+    #[test]
+    fn test_certificate_blocks() {
+        let payload = "2024-01-01T00:00:00Z K MIIBtzCCASsGByqGSM44BAEwggEeAoGBAK==";
+        let (first, second) = payload.split_at(30);
+        let block = |index: usize, frag: &str| {
+            let m = parse_message(format!(
+                r#"<110>1 - host app - - [ssign-cert VER="0111" RSID="1" SG="0" SPRI="0" GBC="2" TPBL="{}" INDEX="{}" FLEN="{}" FRAG="{}" SIGN="c2ln"]"#,
+                payload.len(),
+                index,
+                frag.len(),
+                frag
+            ))
+            .unwrap();
+            CertificateBlock::from_message(&m)
+        };
+        let second = block(31, second).expect("should read");
+        assert_eq!(second.total_payload_length, payload.len());
+        let first = block(1, first).unwrap();
+
+        let mut assembler = PayloadAssembler::new();
+        assert!(assembler.add(&second).is_none());
+        assert!(assembler.add(&second).is_none());
+        let assembled = assembler.add(&first).expect("should be complete").unwrap();
+        assert_eq!(assembled.key_blob_type, KeyBlobType::PublicKey);
+        assert_eq!(assembled.timestamp.secs, 1704067200);
+        assert_eq!(assembled.key_blob, "MIIBtzCCASsGByqGSM44BAEwggEeAoGBAK==");
+
+        assert!(matches!(
+            block(0, "2024"),
+            Err(SsignError::InvalidParam("INDEX"))
+        ));
+        assert!(matches!(
+            block(payload.len(), "2024"),
+            Err(SsignError::InvalidParam("INDEX"))
+        ));
+
+        let mut bad = first.clone();
+        bad.index = 0;
+        assert!(matches!(
+            assembler.add(&bad),
+            Some(Err(SsignError::InvalidParam("INDEX")))
+        ));
+        bad.index = payload.len() + 2;
+        bad.fragment.clear();
+        assert!(matches!(
+            assembler.add(&bad),
+            Some(Err(SsignError::InvalidParam("INDEX")))
+        ));
+        bad.index = payload.len();
+        bad.fragment = "2024".to_string();
+        assert!(matches!(
+            assembler.add(&bad),
+            Some(Err(SsignError::InvalidParam("INDEX")))
+        ));
+        bad.index = 1;
+        bad.total_payload_length = 99_999_999;
+        assert!(matches!(
+            assembler.add(&bad),
+            Some(Err(SsignError::InvalidParam("TPBL")))
+        ));
+        let mut small = PayloadAssembler::new().with_max_payload_length(payload.len() - 1);
+        assert!(matches!(
+            small.add(&first),
+            Some(Err(SsignError::InvalidParam("TPBL")))
+        ));
+        assert!(small.payload.is_empty());
+        assert!("2024-01-01T00:00:00Z X abc"
+            .parse::<super::Payload>()
+            .is_err());
+        assert!("2024-01-01T00:00:00Z N"
+            .parse::<super::Payload>()
+            .unwrap()
+            .key_blob
+            .is_empty());
+    }
+}
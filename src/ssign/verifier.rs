@@ -0,0 +1,412 @@
+//! Checking the signatures and hashes of a signed message stream (requires the `ssign` feature).
+//!
+//! `StreamVerifier` is given every message from a sender, exactly as it was received. Ordinary
+//! messages are held until a Signature Block covering them arrives; the block's signature is
+//! checked against the sender's public key, and then each of its hashes is matched up with a held
+//! message. What's left over tells you what was lost or tampered with.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use dsa::signature::hazmat::PrehashVerifier;
+use dsa::{BigUint, Signature};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+pub use dsa::VerifyingKey;
+
+use super::{
+    CertificateBlock, HashAlgorithm, SignatureBlock, SignatureGroup, SsignError, Version,
+    SSIGN_CERT_SD_ID, SSIGN_SD_ID,
+};
+use crate::parser::{parse_message_with, ParserConfig};
+
+/// How many unverified messages are held, unless told otherwise
+pub const DEFAULT_MAX_PENDING: usize = 10_000;
+
+/// Hash a message as RFC 5848 does, for comparing against a Signature Block
+pub fn hash_message(algorithm: HashAlgorithm, raw: &str) -> Vec<u8> {
+    match algorithm {
+        HashAlgorithm::Sha1 => Sha1::digest(raw).to_vec(),
+        HashAlgorithm::Sha256 => Sha256::digest(raw).to_vec(),
+    }
+}
+
+#[derive(Debug)]
+/// What `StreamVerifier::observe` made of a message
+pub enum Observation {
+    /// An ordinary message, which is held until a Signature Block covers it
+    Pending,
+    /// A Signature Block with a valid signature
+    Block(BlockReport),
+    /// A Certificate Block with a valid signature
+    Certificate(CertificateBlock),
+    /// A block which has already been seen (senders may send each one several times)
+    Duplicate,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The outcome of checking one Signature Block
+pub struct BlockReport {
+    pub block: SignatureBlock,
+    /// The held messages which matched one of the block's hashes, in message number order
+    pub verified: Vec<String>,
+    /// The message numbers of hashes which didn't match any held message
+    pub missing: Vec<u64>,
+    /// Message numbers in this signature group which no block has covered, if this block doesn't
+    /// carry on from the last one
+    pub skipped: Option<Range<u64>>,
+}
+
+impl BlockReport {
+    /// Whether every message the block covers was received intact
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty() && self.skipped.is_none()
+    }
+}
+
+#[derive(Debug)]
+/// Verifies a stream of signed messages from one sender; see the module docs
+///
+/// Messages which no Signature Block has covered yet are held, up to a limit; after that, the
+/// oldest are dropped (and their hashes will turn up in `BlockReport::missing`).
+pub struct StreamVerifier {
+    key: VerifyingKey,
+    config: ParserConfig,
+    max_pending: usize,
+    pending: VecDeque<String>,
+    session: Option<u64>,
+    seen_blocks: HashSet<u64>,
+    next_message: HashMap<SignatureGroup, u64>,
+}
+
+impl StreamVerifier {
+    /// Check signatures against `key`, the sender's public key
+    pub fn new(key: VerifyingKey) -> Self {
+        StreamVerifier {
+            key,
+            config: ParserConfig::default(),
+            max_pending: DEFAULT_MAX_PENDING,
+            pending: VecDeque::new(),
+            session: None,
+            seen_blocks: HashSet::new(),
+            next_message: HashMap::new(),
+        }
+    }
+
+    /// Parse messages with `config`, instead of the default
+    pub fn with_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Hold at most this many unverified messages (default: `DEFAULT_MAX_PENDING`)
+    pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = max_pending;
+        self
+    }
+
+    /// The messages which no Signature Block has covered yet, oldest first
+    pub fn pending(&self) -> impl Iterator<Item = &str> {
+        self.pending.iter().map(String::as_str)
+    }
+
+    /// Check the next message from the sender
+    ///
+    /// `raw` has to be exactly what was sent, without any framing, since that's what the hashes
+    /// and signatures are over. Blocks with a bad signature fail with `SsignError::BadSignature`.
+    pub fn observe(&mut self, raw: &str) -> Result<Observation, SsignError> {
+        let m = parse_message_with(&self.config, raw)?;
+        if m.sd.find_sdid(SSIGN_SD_ID).is_some() {
+            let block = SignatureBlock::from_sd(&m.sd)?;
+            if self.is_duplicate(block.reboot_session_id, block.global_block_counter) {
+                return Ok(Observation::Duplicate);
+            }
+            self.verify_signature(raw, SSIGN_SD_ID, block.version, &block.signature)?;
+            self.start_session(block.reboot_session_id);
+            self.seen_blocks.insert(block.global_block_counter);
+            return Ok(Observation::Block(self.match_hashes(block)?));
+        }
+        if m.sd.find_sdid(SSIGN_CERT_SD_ID).is_some() {
+            let block = CertificateBlock::from_sd(&m.sd)?;
+            if self.is_duplicate(block.reboot_session_id, block.global_block_counter) {
+                return Ok(Observation::Duplicate);
+            }
+            self.verify_signature(raw, SSIGN_CERT_SD_ID, block.version, &block.signature)?;
+            self.start_session(block.reboot_session_id);
+            self.seen_blocks.insert(block.global_block_counter);
+            return Ok(Observation::Certificate(block));
+        }
+        if self.pending.len() >= self.max_pending {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(raw.to_string());
+        Ok(Observation::Pending)
+    }
+
+    /// Whether this reboot session's block has already been seen
+    fn is_duplicate(&self, reboot_session_id: u64, global_block_counter: u64) -> bool {
+        self.session == Some(reboot_session_id) && self.seen_blocks.contains(&global_block_counter)
+    }
+
+    /// Forget the previous reboot session's blocks if the sender has restarted
+    ///
+    /// Only call this once the block's signature has been checked, so that a forged RSID can't
+    /// clear the blocks which have been seen.
+    fn start_session(&mut self, reboot_session_id: u64) {
+        if self.session != Some(reboot_session_id) {
+            self.session = Some(reboot_session_id);
+            self.seen_blocks.clear();
+            self.next_message.clear();
+        }
+    }
+
+    /// Check SIGN, which is over the whole message with SIGN's own value left empty
+    fn verify_signature(
+        &self,
+        raw: &str,
+        sd_id: &str,
+        version: Version,
+        signature: &str,
+    ) -> Result<(), SsignError> {
+        let unsigned = without_signature(raw, sd_id).ok_or(SsignError::InvalidParam("SIGN"))?;
+        let signature = STANDARD
+            .decode(signature)
+            .ok()
+            .and_then(|b| decode_signature(&b))
+            .ok_or(SsignError::InvalidParam("SIGN"))?;
+        let hash = hash_message(version.hash_algorithm, &unsigned);
+        self.key
+            .verify_prehash(&hash, &signature)
+            .map_err(|_| SsignError::BadSignature)
+    }
+
+    fn match_hashes(&mut self, block: SignatureBlock) -> Result<BlockReport, SsignError> {
+        let hashes = block
+            .hashes
+            .iter()
+            .map(|h| STANDARD.decode(h))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| SsignError::InvalidParam("HB"))?;
+        let mut held: Vec<Option<Vec<u8>>> = self
+            .pending
+            .iter()
+            .map(|m| Some(hash_message(block.version.hash_algorithm, m)))
+            .collect();
+        let mut verified = Vec::new();
+        let mut missing = Vec::new();
+        for (number, hash) in block.message_numbers().zip(&hashes) {
+            match held.iter().position(|h| h.as_ref() == Some(hash)) {
+                Some(i) => {
+                    held[i] = None;
+                    verified.push(self.pending[i].clone());
+                }
+                None => missing.push(number),
+            }
+        }
+        let mut held = held.into_iter();
+        self.pending
+            .retain(|_| held.next().is_some_and(|h| h.is_some()));
+
+        let numbers = block.message_numbers();
+        let next = self
+            .next_message
+            .entry(block.signature_group)
+            .or_insert(numbers.start);
+        let skipped = if numbers.start > *next {
+            Some(*next..numbers.start)
+        } else {
+            None
+        };
+        *next = (*next).max(numbers.end);
+        Ok(BlockReport {
+            block,
+            verified,
+            missing,
+            skipped,
+        })
+    }
+}
+
+/// `raw` with the value of the SIGN SD-PARAM in `sd_id`'s SD-ELEMENT removed
+fn without_signature(raw: &str, sd_id: &str) -> Option<String> {
+    let element = raw.find(&format!("[{} ", sd_id))?;
+    // base64 has no quotes or escapes, so the value ends at the next quote
+    let value = element + raw[element..].find(" SIGN=\"")? + " SIGN=\"".len();
+    let end = value + raw[value..].find('"')?;
+    Some(format!("{}{}", &raw[..value], &raw[end..]))
+}
+
+/// Read an OpenPGP DSA signature: two MPIs, `r` and `s`, each a 16-bit bit count followed by
+/// that many bits, big-endian
+fn decode_signature(bytes: &[u8]) -> Option<Signature> {
+    let mut rest = bytes;
+    let mut mpi = || {
+        let bits = usize::from(u16::from_be_bytes([*rest.first()?, *rest.get(1)?]));
+        let len = (bits + 7) / 8;
+        let value = rest.get(2..2 + len)?;
+        rest = &rest[2 + len..];
+        Some(BigUint::from_bytes_be(value))
+    };
+    let r = mpi()?;
+    let s = mpi()?;
+    if !rest.is_empty() {
+        return None;
+    }
+    Signature::from_components(r, s).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use dsa::{BigUint, Components, SigningKey, VerifyingKey};
+    use sha1::Sha1;
+
+    use super::{hash_message, without_signature, Observation, StreamVerifier};
+    use crate::ssign::{HashAlgorithm, SsignError};
+
+    // a 1024-bit key, generated once for these tests
+    const P: &str = "a6889836caaa68eec55ad6f7dc65f8994a0a7cd52d5992ff439efa397fcd9bb3c4cbae8eb6fb2a4adc6f273f904a5b8a2e7e62e5d620d95ea3deed681932e1723b96a3b1f4a078b78917c36f92951b39c2409277978df47a8a9a76704838bf8b4429c830673231c490f757c160851b37d5c966de0b4ba04bcac08d0dc9eeae53";
+    const Q: &str = "f5546d420844bbba017231b5d5904a55b2d38f17";
+    const G: &str = "31e7b808d0f4ddb3544d15a2aef65790c8909f1e2eeca9c8377334f5357be3bd6bf6b5bb31ebfafdf804aa16d614569cbe89df39b45a4ada6ca89a6905f16646f8013da56ccd7611ad4a3d38743dd8bf6ce482ac5b86e4062fbb3d1366320dcb793f3cb6273cb67740439e034c8a035dec51fd2075dd8fbc183ae683852547e6";
+    const Y: &str = "7f9f4a6f9d3583d94286b7c907557828b88ac7e4800fdc741e1c900d20837d33e6fde8f312722ab1082d681d612b9cca6522179a0b5f2608fa89407fbcd94780e6abc3513c380f05d760415ed33eb68e27bebf434b837cd21b120a1fe37016d641a66a0016d1db2f9363c3f3b6b75719120f5cf7d8b6cce345a34df0ea8ce717";
+    const X: &str = "76df6d3287d6c8ae6cb792606d95422179078b9e";
+
+    fn hex(s: &str) -> BigUint {
+        BigUint::parse_bytes(s.as_bytes(), 16).unwrap()
+    }
+
+    fn signing_key() -> SigningKey {
+        let components = Components::from_components(hex(P), hex(Q), hex(G)).unwrap();
+        let verifying_key = VerifyingKey::from_components(components, hex(Y)).unwrap();
+        SigningKey::from_components(verifying_key, hex(X)).unwrap()
+    }
+
+    fn mpi(n: &BigUint) -> Vec<u8> {
+        let mut out = (n.bits() as u16).to_be_bytes().to_vec();
+        out.extend(n.to_bytes_be());
+        out
+    }
+
+    /// Fill in SIGN on a block which has `SIGN=""`
+    fn sign(key: &SigningKey, unsigned: &str) -> String {
+        let hash = hash_message(HashAlgorithm::Sha1, unsigned);
+        let signature = key.sign_prehashed_rfc6979::<Sha1>(&hash).unwrap();
+        let mut encoded = mpi(signature.r());
+        encoded.extend(mpi(signature.s()));
+        unsigned.replace(
+            "SIGN=\"\"",
+            &format!("SIGN=\"{}\"", STANDARD.encode(encoded)),
+        )
+    }
+
+    fn signature_block(key: &SigningKey, gbc: u64, fmn: u64, messages: &[&str]) -> String {
+        let hashes: Vec<String> = messages
+            .iter()
+            .map(|m| STANDARD.encode(hash_message(HashAlgorithm::Sha1, m)))
+            .collect();
+        sign(
+            key,
+            &format!(
+                r#"<110>1 2024-01-01T00:00:00Z host app - - [ssign VER="0111" RSID="1" SG="0" SPRI="0" GBC="{}" FMN="{}" CNT="{}" HB="{}" SIGN=""]"#,
+                gbc,
+                fmn,
+                messages.len(),
+                hashes.join(" ")
+            ),
+        )
+    }
+
+    #[test]
+    fn test_without_signature() {
+        assert_eq!(
+            without_signature(
+                r#"<1>1 - - - - - [a SIGN="x"][ssign CNT="1" SIGN="abc="]"#,
+                "ssign"
+            )
+            .as_deref(),
+            Some(r#"<1>1 - - - - - [a SIGN="x"][ssign CNT="1" SIGN=""]"#)
+        );
+        assert_eq!(without_signature("<1>1 - - - - - [ssign]", "ssign"), None);
+    }
+
+    #[test]
+    fn test_stream_verifier() {
+        let key = signing_key();
+        let mut verifier = StreamVerifier::new(key.verifying_key().clone());
+        let one = "<14>1 2024-01-01T00:00:01Z host app - - - one";
+        let two = "<14>1 2024-01-01T00:00:02Z host app - - - two";
+        let three = "<14>1 2024-01-01T00:00:03Z host app - - - three";
+        for m in [
+            one,
+            "<14>1 2024-01-01T00:00:01Z host app - - - forged",
+            three,
+        ] {
+            assert!(matches!(verifier.observe(m), Ok(Observation::Pending)));
+        }
+
+        let block = signature_block(&key, 1, 1, &[one, two, three]);
+        let report = match verifier.observe(&block) {
+            Ok(Observation::Block(report)) => report,
+            other => panic!("expected a block, got {:?}", other),
+        };
+        assert_eq!(report.verified, vec![one, three]);
+        assert_eq!(report.missing, vec![2]);
+        assert_eq!(report.skipped, None);
+        assert!(!report.is_complete());
+        assert_eq!(
+            verifier.pending().collect::<Vec<_>>(),
+            vec!["<14>1 2024-01-01T00:00:01Z host app - - - forged"]
+        );
+        assert!(matches!(
+            verifier.observe(&block),
+            Ok(Observation::Duplicate)
+        ));
+
+        // messages 4 and 5 were never covered
+        let six = "<14>1 2024-01-01T00:00:06Z host app - - - six";
+        verifier.observe(six).unwrap();
+        let report = match verifier.observe(&signature_block(&key, 3, 6, &[six])) {
+            Ok(Observation::Block(report)) => report,
+            other => panic!("expected a block, got {:?}", other),
+        };
+        assert_eq!(report.verified, vec![six]);
+        assert_eq!(report.skipped, Some(4..6));
+
+        let tampered = signature_block(&key, 4, 7, &[six]).replace("FMN=\"7\"", "FMN=\"8\"");
+        assert!(matches!(
+            verifier.observe(&tampered),
+            Err(SsignError::BadSignature)
+        ));
+        let other_key =
+            SigningKey::from_components(key.verifying_key().clone(), hex("1234567890abcdef"))
+                .unwrap();
+        assert!(matches!(
+            verifier.observe(&signature_block(&other_key, 5, 7, &[six])),
+            Err(SsignError::BadSignature)
+        ));
+
+        // a block with a new RSID and no valid signature doesn't start a new session
+        let forged = signature_block(&other_key, 1, 1, &[one]).replace("RSID=\"1\"", "RSID=\"2\"");
+        assert!(matches!(
+            verifier.observe(&forged),
+            Err(SsignError::BadSignature)
+        ));
+        assert!(matches!(
+            verifier.observe(&block),
+            Ok(Observation::Duplicate)
+        ));
+
+        let cert = sign(
+            &key,
+            r#"<110>1 - host app - - [ssign-cert VER="0111" RSID="1" SG="0" SPRI="0" GBC="2" TPBL="23" INDEX="1" FLEN="23" FRAG="2024-01-01T00:00:00Z N " SIGN=""]"#,
+        );
+        assert!(matches!(
+            verifier.observe(&cert),
+            Ok(Observation::Certificate(_))
+        ));
+    }
+}
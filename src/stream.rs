@@ -0,0 +1,341 @@
+//! Push-based parsing of syslog messages from a byte stream.
+//!
+//! A `StreamingParser` combines one of the decoders from the `framing` module with the message
+//! parser: feed it chunks of bytes exactly as they come off of a socket, and it will tell you
+//! whether it has a complete message yet.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::framing::OctetCountingDecoder;
+//! use syslog_rfc5424::stream::{Parsed, StreamingParser};
+//!
+//! let mut parser = StreamingParser::new(OctetCountingDecoder::new());
+//! parser.push(b"22 <1>1 - host - - - - ");
+//! assert!(matches!(parser.next_message(), Ok(Parsed::NeedMoreData)));
+//! parser.push(b"hi");
+//! match parser.next_message() {
+//!     Ok(Parsed::Message(m)) => assert_eq!(m.msg, "hi"),
+//!     other => panic!("unexpected {:?}", other),
+//! }
+//! ```
+//!
+//! With the `tokio` feature, `MessageStream` does the same for anything implementing
+//! `tokio::io::AsyncRead`, reading from it as needed.
+
+use std::io;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::task::{Context, Poll};
+
+#[cfg(feature = "tokio")]
+use futures_core::Stream;
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, ReadBuf};
+
+use thiserror::Error;
+
+use crate::framing::{FrameDecoder, FramingError};
+use crate::message::SyslogMessage;
+use crate::parser::{parse_message_bytes_with, ParseErr, ParserConfig, Utf8Policy};
+
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error("framing error: {0}")]
+    Framing(#[from] FramingError),
+    #[error("parse error: {0}")]
+    Parse(#[from] ParseErr),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+/// The outcome of asking a `StreamingParser` for its next message
+pub enum Parsed {
+    /// No complete frame is buffered yet; push some more data
+    NeedMoreData,
+    /// A complete message
+    Message(SyslogMessage),
+}
+
+#[derive(Clone, Debug)]
+/// A push-based parser which splits incoming bytes with a `FrameDecoder` and parses each frame
+///
+/// A frame which fails to parse is discarded, so you can keep calling `next_message` after a
+/// `StreamError::Parse`. A `StreamError::Framing` usually means that the stream is out of sync
+/// and the connection should be dropped.
+pub struct StreamingParser<D> {
+    decoder: D,
+    utf8_policy: Utf8Policy,
+    config: ParserConfig,
+}
+
+impl<D: FrameDecoder> StreamingParser<D> {
+    pub fn new(decoder: D) -> Self {
+        StreamingParser {
+            decoder,
+            utf8_policy: Utf8Policy::Strict,
+            config: ParserConfig::default(),
+        }
+    }
+
+    /// Set how frames which aren't valid UTF-8 are handled
+    pub fn with_utf8_policy(mut self, utf8_policy: Utf8Policy) -> Self {
+        self.utf8_policy = utf8_policy;
+        self
+    }
+
+    /// Parse each frame with the given `ParserConfig` instead of the default
+    pub fn with_parser_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Append newly-received bytes
+    pub fn push(&mut self, data: &[u8]) {
+        self.decoder.push(data)
+    }
+
+    /// Parse the next complete message, if there is one
+    pub fn next_message(&mut self) -> Result<Parsed, StreamError> {
+        match self.decoder.next_frame()? {
+            Some(frame) => Ok(Parsed::Message(self.parse_frame(&frame)?)),
+            None => Ok(Parsed::NeedMoreData),
+        }
+    }
+
+    /// Signal that the stream has been closed and parse whatever is left in the buffer
+    ///
+    /// Any complete messages should be drained with `next_message` first.
+    pub fn finish(&mut self) -> Result<Option<SyslogMessage>, StreamError> {
+        match self.decoder.finish()? {
+            Some(frame) => Ok(Some(self.parse_frame(&frame)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Access the underlying frame decoder
+    pub fn decoder(&self) -> &D {
+        &self.decoder
+    }
+
+    /// Discard this parser, returning the underlying frame decoder
+    pub fn into_decoder(self) -> D {
+        self.decoder
+    }
+
+    fn parse_frame(&self, frame: &[u8]) -> Result<SyslogMessage, ParseErr> {
+        parse_message_bytes_with(&self.config, frame, self.utf8_policy)
+    }
+}
+
+/// How much to read from the underlying `AsyncRead` at a time
+#[cfg(feature = "tokio")]
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+/// An asynchronous `Stream` of messages read from an `AsyncRead` (requires the `tokio` feature)
+///
+/// As with `StreamingParser`, a message which fails to parse is yielded as a
+/// `StreamError::Parse` and the stream carries on with the next one. Framing and I/O errors end
+/// the stream.
+///
+/// # Example
+///
+/// ```
+/// use syslog_rfc5424::framing::NonTransparentDecoder;
+/// use syslog_rfc5424::stream::MessageStream;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let input: &[u8] = b"<1>1 - host - - - - one\n<1>1 - host - - - - two\n";
+/// let mut stream = MessageStream::new(input, NonTransparentDecoder::default());
+/// let mut bodies = Vec::new();
+/// while let Some(m) = stream.next_message().await {
+///     bodies.push(m.unwrap().msg);
+/// }
+/// assert_eq!(bodies, vec!["one", "two"]);
+/// # }
+/// ```
+pub struct MessageStream<R, D> {
+    reader: R,
+    parser: StreamingParser<D>,
+    buf: Box<[u8]>,
+    eof: bool,
+    done: bool,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + Unpin, D: FrameDecoder + Unpin> MessageStream<R, D> {
+    pub fn new(reader: R, decoder: D) -> Self {
+        MessageStream {
+            reader,
+            parser: StreamingParser::new(decoder),
+            buf: vec![0; READ_BUFFER_SIZE].into_boxed_slice(),
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Set how frames which aren't valid UTF-8 are handled
+    pub fn with_utf8_policy(mut self, utf8_policy: Utf8Policy) -> Self {
+        self.parser = self.parser.with_utf8_policy(utf8_policy);
+        self
+    }
+
+    /// Parse each frame with the given `ParserConfig` instead of the default
+    pub fn with_parser_config(mut self, config: ParserConfig) -> Self {
+        self.parser = self.parser.with_parser_config(config);
+        self
+    }
+
+    /// Wait for the next message; a convenience for callers without a `StreamExt` to hand
+    pub async fn next_message(&mut self) -> Option<Result<SyslogMessage, StreamError>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    /// Discard this stream, returning the underlying reader
+    ///
+    /// Anything which has been read but not yet parsed is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + Unpin, D: FrameDecoder + Unpin> Stream for MessageStream<R, D> {
+    type Item = Result<SyslogMessage, StreamError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if this.eof {
+                this.done = true;
+                return Poll::Ready(this.parser.finish().transpose());
+            }
+            match this.parser.next_message() {
+                Ok(Parsed::Message(m)) => return Poll::Ready(Some(Ok(m))),
+                Ok(Parsed::NeedMoreData) => {}
+                Err(e @ StreamError::Parse(_)) => return Poll::Ready(Some(Err(e))),
+                Err(e) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+            let mut read_buf = ReadBuf::new(&mut this.buf);
+            match Pin::new(&mut this.reader).poll_read(cx, &mut read_buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(StreamError::Io(e))));
+                }
+                Poll::Ready(Ok(())) if read_buf.filled().is_empty() => this.eof = true,
+                Poll::Ready(Ok(())) => this.parser.push(read_buf.filled()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Parsed, StreamError, StreamingParser};
+    use crate::framing::{NonTransparentDecoder, OctetCountingDecoder};
+
+    #[test]
+    fn test_partial_chunks() {
+        let mut p = StreamingParser::new(NonTransparentDecoder::default());
+        let input =
+            b"<1>1 - host1 - - - - one\n<1>1 - host2 - - - - two\n<1>1 - host3 - - - - three";
+        let mut msgs = Vec::new();
+        for chunk in input.chunks(7) {
+            p.push(chunk);
+            while let Parsed::Message(m) = p.next_message().expect("should parse") {
+                msgs.push(m);
+            }
+        }
+        msgs.push(
+            p.finish()
+                .expect("should parse")
+                .expect("should have a message"),
+        );
+        let bodies = msgs.iter().map(|m| m.msg.as_str()).collect::<Vec<_>>();
+        assert_eq!(bodies, vec!["one", "two", "three"]);
+        assert_eq!(msgs[2].hostname, Some(String::from("host3")));
+    }
+
+    #[test]
+    fn test_recovers_after_bad_message() {
+        let mut p = StreamingParser::new(OctetCountingDecoder::new());
+        p.push(b"3 foo18 <1>1 - - - - - - a");
+        assert!(matches!(p.next_message(), Err(StreamError::Parse(_))));
+        match p.next_message().expect("should parse") {
+            Parsed::Message(m) => assert_eq!(m.msg, "a"),
+            Parsed::NeedMoreData => panic!("should have a message"),
+        }
+        assert!(matches!(p.next_message(), Ok(Parsed::NeedMoreData)));
+        assert!(p.finish().expect("should finish cleanly").is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    struct ChunkedReader {
+        data: &'static [u8],
+        chunk_size: usize,
+    }
+
+    #[cfg(feature = "tokio")]
+    impl tokio::io::AsyncRead for ChunkedReader {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            let n = self.chunk_size.min(self.data.len()).min(buf.remaining());
+            buf.put_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            // make the stream wait on us every so often
+            cx.waker().wake_by_ref();
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_stream() {
+        use super::MessageStream;
+
+        let reader = ChunkedReader {
+            data: b"24 <1>1 - host1 - - - - one3 foo26 <1>1 - host3 - - - - three",
+            chunk_size: 4,
+        };
+        let mut stream = MessageStream::new(reader, OctetCountingDecoder::new());
+        let first = stream.next_message().await.expect("should have a message");
+        assert_eq!(first.expect("should parse").msg, "one");
+        assert!(matches!(
+            stream.next_message().await,
+            Some(Err(StreamError::Parse(_)))
+        ));
+        let third = stream.next_message().await.expect("should have a message");
+        assert_eq!(third.expect("should parse").msg, "three");
+        assert!(stream.next_message().await.is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_stream_truncated() {
+        use super::MessageStream;
+
+        let input: &[u8] = b"30 <1>1 - - - - - -";
+        let mut stream = MessageStream::new(input, OctetCountingDecoder::new());
+        assert!(matches!(
+            stream.next_message().await,
+            Some(Err(StreamError::Framing(_)))
+        ));
+        assert!(stream.next_message().await.is_none());
+    }
+}
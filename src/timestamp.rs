@@ -0,0 +1,175 @@
+//! The TIMESTAMP header field.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "serde-serialize")]
+use serde::{Deserialize, Serialize};
+
+use crate::message::time_t;
+
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// A point in time, along with the UTC offset that the sender wrote it in
+///
+/// `secs` is always in UTC and is floored, and `nanos` counts forwards from it, so half a second
+/// before the epoch is `secs: -1, nanos: 500_000_000`. Ordering compares the instant first.
+pub struct Timestamp {
+    /// Seconds since the Unix epoch
+    pub secs: time_t,
+    /// Nanoseconds after `secs`; always less than one billion
+    pub nanos: u32,
+    /// The sender's offset from UTC, in seconds
+    pub offset: i32,
+}
+
+impl Timestamp {
+    pub fn new(secs: time_t, nanos: u32, offset: i32) -> Self {
+        Timestamp {
+            secs,
+            nanos,
+            offset,
+        }
+    }
+
+    /// Convert to a `time::OffsetDateTime`, in the sender's original UTC offset
+    pub fn to_offset_datetime(self) -> Option<time::OffsetDateTime> {
+        let offset = time::UtcOffset::from_whole_seconds(self.offset).ok()?;
+        let nanos = i128::from(self.secs) * 1_000_000_000 + i128::from(self.nanos);
+        time::OffsetDateTime::from_unix_timestamp_nanos(nanos)
+            .ok()
+            .map(|dt| dt.to_offset(offset))
+    }
+
+    /// Convert to a `std::time::SystemTime`, dropping the UTC offset
+    pub fn to_system_time(self) -> Option<SystemTime> {
+        let nanos = Duration::from_nanos(u64::from(self.nanos));
+        let whole = Duration::from_secs(self.secs.unsigned_abs());
+        if self.secs >= 0 {
+            UNIX_EPOCH.checked_add(whole)?.checked_add(nanos)
+        } else {
+            UNIX_EPOCH.checked_sub(whole)?.checked_add(nanos)
+        }
+    }
+
+    /// Convert to a `chrono::DateTime`, in the sender's original UTC offset
+    #[cfg(feature = "chrono")]
+    pub fn to_datetime(self) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        let offset = chrono::FixedOffset::east_opt(self.offset)?;
+        chrono::DateTime::from_timestamp(self.secs, self.nanos).map(|dt| dt.with_timezone(&offset))
+    }
+
+    #[cfg(feature = "chrono")]
+    pub fn from_datetime<Tz: chrono::TimeZone>(dt: &chrono::DateTime<Tz>) -> Self {
+        use chrono::Offset;
+
+        Timestamp::new(
+            dt.timestamp(),
+            dt.timestamp_subsec_nanos(),
+            dt.offset().fix().local_minus_utc(),
+        )
+    }
+}
+
+impl From<time::OffsetDateTime> for Timestamp {
+    fn from(dt: time::OffsetDateTime) -> Self {
+        Timestamp::new(
+            dt.unix_timestamp(),
+            dt.nanosecond(),
+            dt.offset().whole_seconds(),
+        )
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    /// Convert a `SystemTime`, which is always taken to be in UTC
+    fn from(t: SystemTime) -> Self {
+        let (secs, nanos) = match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => (d.as_secs() as time_t, d.subsec_nanos()),
+            Err(e) => {
+                let d = e.duration();
+                match d.subsec_nanos() {
+                    0 => (-(d.as_secs() as time_t), 0),
+                    n => (-(d.as_secs() as time_t) - 1, 1_000_000_000 - n),
+                }
+            }
+        };
+        Timestamp::new(secs, nanos, 0)
+    }
+}
+
+/// Serializes `SyslogMessage::timestamp` as the flat `timestamp`, `timestamp_nanos` and
+/// `utc_offset` fields that messages had before `Timestamp` existed, so that stored messages
+/// still deserialize
+#[cfg(feature = "serde-serialize")]
+pub(crate) mod serde_compat {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Timestamp;
+    use crate::message::time_t;
+
+    #[derive(Serialize, Deserialize)]
+    struct Flat {
+        #[serde(default)]
+        timestamp: Option<time_t>,
+        #[serde(default)]
+        timestamp_nanos: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        utc_offset: Option<i32>,
+    }
+
+    pub fn serialize<S: Serializer>(ts: &Option<Timestamp>, s: S) -> Result<S::Ok, S::Error> {
+        Flat {
+            timestamp: ts.map(|t| t.secs),
+            timestamp_nanos: ts.map(|t| t.nanos),
+            utc_offset: ts.map(|t| t.offset),
+        }
+        .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Timestamp>, D::Error> {
+        let flat = Flat::deserialize(d)?;
+        Ok(flat.timestamp.map(|secs| {
+            Timestamp::new(
+                secs,
+                flat.timestamp_nanos.unwrap_or(0),
+                flat.utc_offset.unwrap_or(0),
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::Timestamp;
+
+    #[test]
+    fn test_system_time() {
+        let t = UNIX_EPOCH - Duration::from_millis(1500);
+        let ts = Timestamp::from(t);
+        assert_eq!(ts, Timestamp::new(-2, 500_000_000, 0));
+        assert_eq!(ts.to_system_time(), Some(t));
+        assert_eq!(
+            Timestamp::from(UNIX_EPOCH + Duration::new(5, 7)),
+            Timestamp::new(5, 7, 0)
+        );
+    }
+
+    #[test]
+    fn test_offset_datetime() {
+        let dt = time::OffsetDateTime::from_unix_timestamp(1061676855)
+            .unwrap()
+            .to_offset(time::UtcOffset::from_hms(-7, 0, 0).unwrap());
+        let ts = Timestamp::from(dt);
+        assert_eq!(ts, Timestamp::new(1061676855, 0, -7 * 3600));
+        assert_eq!(ts.to_offset_datetime(), Some(dt));
+        assert_eq!(Timestamp::new(0, 0, 100_000).to_offset_datetime(), None);
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(Timestamp::new(-1, 999_999_999, 0) < Timestamp::new(0, 0, 0));
+        assert!(Timestamp::new(10, 5, 3600) < Timestamp::new(10, 6, 0));
+    }
+}
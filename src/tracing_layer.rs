@@ -0,0 +1,242 @@
+//! Emitting `tracing` events as syslog messages (requires the `tracing` feature).
+//!
+//! `SyslogLayer` is a `tracing_subscriber` `Layer` which turns each event into a `SyslogMessage`:
+//! the level becomes the severity, the target becomes the APP-NAME, the `message` field becomes
+//! MSG, and every other field becomes an SD-PARAM in a single SD-ELEMENT. The messages are handed to
+//! a `Sink`, such as a `transport::sender::Sender`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use syslog_rfc5424::sink::WriteSink;
+//! use syslog_rfc5424::tracing_layer::SyslogLayer;
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! let layer = SyslogLayer::new(WriteSink::new(std::io::stderr()))
+//!     .with_hostname("myhost")
+//!     .with_appname("myapp");
+//! tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer)).unwrap();
+//!
+//! tracing::warn!(path = "/var", "disk almost full");
+//! // <12>1 2024-01-01T00:00:00.000000Z myhost myapp 4242 - [tracing@32473 path="/var"] disk almost full
+//! ```
+
+use std::fmt;
+use std::process;
+use std::sync::{Mutex, PoisonError};
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::builder::SyslogMessageBuilder;
+use crate::facility::SyslogFacility;
+use crate::message::{pid_t, SyslogMessage};
+use crate::severity::SyslogSeverity;
+use crate::sink::Sink;
+use crate::validation::{self, MAX_APPNAME_LENGTH};
+
+/// The SD-ID event fields are put under, unless told otherwise
+///
+/// 32473 is the enterprise number reserved for documentation (RFC 5612); use your own with
+/// `SyslogLayer::with_sd_id` if you have one.
+pub const DEFAULT_SD_ID: &str = "tracing@32473";
+
+/// The severity a `tracing` level maps to
+///
+/// `TRACE` and `DEBUG` both become `SEV_DEBUG`.
+pub fn severity_for(level: &Level) -> SyslogSeverity {
+    match *level {
+        Level::ERROR => SyslogSeverity::SEV_ERR,
+        Level::WARN => SyslogSeverity::SEV_WARNING,
+        Level::INFO => SyslogSeverity::SEV_INFO,
+        _ => SyslogSeverity::SEV_DEBUG,
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    params: Vec<(String, String)>,
+}
+
+impl Fields {
+    fn record(&mut self, field: &Field, value: String) {
+        if field.name() == "message" {
+            self.message = value;
+        } else {
+            self.params
+                .push((validation::sanitize_sd_name(field.name()), value));
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, format!("{:?}", value));
+    }
+}
+
+/// A `Layer` which sends every event to a `Sink` as a syslog message; see the module docs
+///
+/// Errors from the sink are ignored, since there's nowhere sensible to report them.
+pub struct SyslogLayer<S> {
+    sink: Mutex<S>,
+    facility: SyslogFacility,
+    hostname: Option<String>,
+    appname: Option<String>,
+    pid: pid_t,
+    sd_id: String,
+}
+
+impl<S> fmt::Debug for SyslogLayer<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SyslogLayer")
+            .field("facility", &self.facility)
+            .field("hostname", &self.hostname)
+            .field("appname", &self.appname)
+            .field("pid", &self.pid)
+            .field("sd_id", &self.sd_id)
+            .finish()
+    }
+}
+
+impl<S: Sink> SyslogLayer<S> {
+    /// Send events to `sink` as `LOG_USER` messages, with NIL HOSTNAME and this process's ID
+    pub fn new(sink: S) -> Self {
+        SyslogLayer {
+            sink: Mutex::new(sink),
+            facility: SyslogFacility::LOG_USER,
+            hostname: None,
+            appname: None,
+            pid: process::id() as pid_t,
+            sd_id: DEFAULT_SD_ID.to_string(),
+        }
+    }
+
+    pub fn with_facility(mut self, facility: SyslogFacility) -> Self {
+        self.facility = facility;
+        self
+    }
+
+    pub fn with_hostname<H: Into<String>>(mut self, hostname: H) -> Self {
+        self.hostname = Some(hostname.into());
+        self
+    }
+
+    /// Use `appname` as the APP-NAME of every message, instead of the event's target
+    pub fn with_appname<A: Into<String>>(mut self, appname: A) -> Self {
+        self.appname = Some(appname.into());
+        self
+    }
+
+    /// Put event fields under `sd_id` instead of `DEFAULT_SD_ID`
+    pub fn with_sd_id<I: Into<String>>(mut self, sd_id: I) -> Self {
+        self.sd_id = sd_id.into();
+        self
+    }
+
+    /// Build the message `event` would be sent as
+    ///
+    /// Field names are made into valid PARAM-NAMEs, and the target into a valid APP-NAME, by
+    /// replacing anything which isn't allowed with `_` and truncating them.
+    pub fn message_for(&self, event: &Event<'_>) -> SyslogMessage {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let mut builder = SyslogMessageBuilder::new()
+            .severity(severity_for(metadata.level()))
+            .facility(self.facility)
+            .pid(self.pid)
+            .msg_is_utf8(!fields.message.is_ascii())
+            .msg(fields.message);
+        if let Some(ref hostname) = self.hostname {
+            builder = builder.hostname(hostname.clone());
+        }
+        match self.appname {
+            Some(ref appname) => builder = builder.appname(appname.clone()),
+            None => {
+                let target = metadata.target();
+                if let Some(appname) = validation::sanitize_header_field(target, MAX_APPNAME_LENGTH)
+                {
+                    builder = builder.appname(appname);
+                }
+            }
+        }
+        if !fields.params.is_empty() {
+            builder = builder.sd_element(&self.sd_id, fields.params);
+        }
+        builder.build_unchecked()
+    }
+}
+
+impl<S, Sub> Layer<Sub> for SyslogLayer<S>
+where
+    S: Sink + Send + 'static,
+    Sub: Subscriber,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, Sub>) {
+        let m = self.message_for(event);
+        let mut sink = self.sink.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = sink.send(&m);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::SyslogLayer;
+    use crate::message::{ProcId, SyslogMessage};
+    use crate::severity::SyslogSeverity;
+
+    #[test]
+    fn test_layer() {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let out = Arc::clone(&messages);
+        let layer = SyslogLayer::new(move |m: &SyslogMessage| {
+            out.lock().unwrap().push(m.clone());
+            Ok(())
+        })
+        .with_hostname("host1")
+        .with_sd_id("fields@32473");
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(user = "bob", count = 3, "disk {} full", "/var");
+            tracing::trace!(target: "my app::db", "caf\u{e9}");
+        });
+
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 2);
+        let m = &messages[0];
+        assert_eq!(m.severity, SyslogSeverity::SEV_WARNING);
+        assert_eq!(m.hostname.as_deref(), Some("host1"));
+        assert_eq!(
+            m.appname.as_deref(),
+            Some("syslog_rfc5424::tracing_layer::tests")
+        );
+        assert_eq!(m.procid, Some(ProcId::PID(std::process::id() as i32)));
+        assert_eq!(m.msg, "disk /var full");
+        assert_eq!(
+            m.sd.find_tuple("fields@32473", "user"),
+            Some(&"bob".to_string())
+        );
+        assert_eq!(
+            m.sd.find_tuple("fields@32473", "count"),
+            Some(&"3".to_string())
+        );
+        assert_eq!(m.validate(), Ok(()));
+
+        let m = &messages[1];
+        assert_eq!(m.severity, SyslogSeverity::SEV_DEBUG);
+        assert_eq!(m.appname.as_deref(), Some("my_app::db"));
+        assert!(m.sd.is_empty());
+        assert!(m.msg_is_utf8);
+    }
+}
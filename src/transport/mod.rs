@@ -0,0 +1,14 @@
+//! Ready-made listeners and senders for the standard syslog transports (requires the `transport`
+//! feature).
+//!
+//! The listeners take care of the socket handling and framing, and hand each parsed message to
+//! your code along with where it came from; `sender::Sender` goes the other way.
+
+pub mod relay;
+pub mod sender;
+pub mod tcp;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod udp;
+#[cfg(unix)]
+pub mod unix;
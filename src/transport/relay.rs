@@ -0,0 +1,334 @@
+//! Forwarding messages from a listener to one or more destinations.
+//!
+//! A `Relay` takes the messages produced by any of the listeners in this module, drops the ones
+//! which don't match its filters, runs the rest through its transforms, and hands a copy to each
+//! of its sinks (see `crate::sink`). Every sink has its own queue, so one destination being down
+//! doesn't hold up the others; messages for it are kept (up to a limit) and retried later.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::sync::mpsc;
+//! use std::thread;
+//!
+//! use syslog_rfc5424::filter::Matcher;
+//! use syslog_rfc5424::transport::relay::Relay;
+//! use syslog_rfc5424::transport::sender::Sender;
+//! use syslog_rfc5424::transport::udp::UdpSyslogServer;
+//!
+//! let mut server = UdpSyslogServer::bind("0.0.0.0:514").unwrap();
+//! let (tx, rx) = mpsc::channel();
+//! thread::spawn(move || server.run_with_channel(tx));
+//!
+//! let mut relay = Relay::new()
+//!     .with_filter("severity >= warning".parse::<Matcher>().unwrap())
+//!     .with_sink(Sender::tcp("central.example.com:601").unwrap())
+//!     .with_sink(Sender::udp("backup.example.com:514").unwrap());
+//! relay.run(rx);
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::filter::Matcher;
+use crate::message::SyslogMessage;
+use crate::sink::Sink;
+
+/// How many messages are kept for a sink which is failing, unless told otherwise
+pub const DEFAULT_BUFFER_SIZE: usize = 1024;
+
+type Transform = Box<dyn FnMut(SyslogMessage) -> Option<SyslogMessage> + Send>;
+
+struct Outbox {
+    sink: Box<dyn Sink + Send>,
+    queue: VecDeque<SyslogMessage>,
+    capacity: usize,
+    retry_at: Option<Instant>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Counts of what a `Relay` has done with the messages it was given
+pub struct RelayStats {
+    /// Messages passed to the relay, including ones which failed to parse
+    pub received: u64,
+    /// Messages which failed to parse, and so weren't forwarded
+    pub invalid: u64,
+    /// Messages dropped by a filter or transform
+    pub filtered: u64,
+    /// Copies of messages thrown away because a sink's queue was full
+    pub dropped: u64,
+}
+
+/// Forwards messages to a set of sinks; see the module docs
+///
+/// When a sink fails, the message stays at the front of its queue, and nothing more is sent to
+/// that sink until the retry interval has passed. Once a queue is full, its oldest message is
+/// dropped to make room. Queued messages are only retried when the relay is given another message,
+/// or when `flush` is called.
+pub struct Relay {
+    filters: Vec<Matcher>,
+    transforms: Vec<Transform>,
+    outboxes: Vec<Outbox>,
+    retry_interval: Duration,
+    stats: RelayStats,
+}
+
+impl fmt::Debug for Relay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Relay")
+            .field("filters", &self.filters)
+            .field("transforms", &self.transforms.len())
+            .field("sinks", &self.outboxes.len())
+            .field("retry_interval", &self.retry_interval)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl Default for Relay {
+    fn default() -> Self {
+        Relay::new()
+    }
+}
+
+impl Relay {
+    /// A `Relay` with no filters, transforms or sinks yet
+    pub fn new() -> Self {
+        Relay {
+            filters: Vec::new(),
+            transforms: Vec::new(),
+            outboxes: Vec::new(),
+            retry_interval: Duration::from_secs(1),
+            stats: RelayStats::default(),
+        }
+    }
+
+    /// Only forward messages which match `filter` (and any other filters)
+    pub fn with_filter(mut self, filter: Matcher) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    /// Pass each message through `transform` before it's forwarded
+    ///
+    /// Transforms run in the order they were added, after the filters. Returning `None` drops the
+    /// message.
+    pub fn with_transform<F>(mut self, transform: F) -> Self
+    where
+        F: FnMut(SyslogMessage) -> Option<SyslogMessage> + Send + 'static,
+    {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Forward messages to `sink`, keeping up to `DEFAULT_BUFFER_SIZE` of them while it's failing
+    pub fn with_sink<S: Sink + Send + 'static>(self, sink: S) -> Self {
+        self.with_buffered_sink(sink, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Forward messages to `sink`, keeping up to `capacity` of them (at least one) while it's
+    /// failing
+    pub fn with_buffered_sink<S: Sink + Send + 'static>(
+        mut self,
+        sink: S,
+        capacity: usize,
+    ) -> Self {
+        self.outboxes.push(Outbox {
+            sink: Box::new(sink),
+            queue: VecDeque::new(),
+            capacity: capacity.max(1),
+            retry_at: None,
+        });
+        self
+    }
+
+    /// Set how long to wait before retrying a sink which failed (default: 1s)
+    pub fn with_retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    pub fn stats(&self) -> RelayStats {
+        self.stats
+    }
+
+    /// How many messages are queued, across all of the sinks
+    pub fn pending(&self) -> usize {
+        self.outboxes.iter().map(|o| o.queue.len()).sum()
+    }
+
+    /// Filter, transform and forward a single message
+    pub fn handle(&mut self, m: SyslogMessage) {
+        self.stats.received += 1;
+        if !self.filters.iter().all(|f| f.matches(&m)) {
+            self.stats.filtered += 1;
+            return;
+        }
+        let mut m = Some(m);
+        for transform in &mut self.transforms {
+            m = m.and_then(&mut **transform);
+        }
+        let m = match m {
+            Some(m) => m,
+            None => {
+                self.stats.filtered += 1;
+                return;
+            }
+        };
+        for outbox in &mut self.outboxes {
+            if outbox.queue.len() >= outbox.capacity {
+                outbox.queue.pop_front();
+                self.stats.dropped += 1;
+            }
+            outbox.queue.push_back(m.clone());
+        }
+        self.deliver(false);
+    }
+
+    /// Try to send every queued message now, even to sinks which are waiting to be retried
+    ///
+    /// Returns whether all of the queues are empty.
+    pub fn flush(&mut self) -> bool {
+        self.deliver(true);
+        self.pending() == 0
+    }
+
+    /// Forward everything from a listener, such as the receiving end of `run_with_channel`
+    ///
+    /// Messages which failed to parse are counted and skipped. Once `messages` runs out, the
+    /// queues are flushed one last time.
+    pub fn run<I, A, E>(&mut self, messages: I)
+    where
+        I: IntoIterator<Item = (A, Result<SyslogMessage, E>)>,
+    {
+        for (_, result) in messages {
+            match result {
+                Ok(m) => self.handle(m),
+                Err(_) => {
+                    self.stats.received += 1;
+                    self.stats.invalid += 1;
+                }
+            }
+        }
+        self.flush();
+    }
+
+    fn deliver(&mut self, force: bool) {
+        let now = Instant::now();
+        for outbox in &mut self.outboxes {
+            if !force && outbox.retry_at.is_some_and(|at| now < at) {
+                continue;
+            }
+            outbox.retry_at = None;
+            while let Some(m) = outbox.queue.front() {
+                if outbox.sink.send(m).is_err() {
+                    outbox.retry_at = Some(now + self.retry_interval);
+                    break;
+                }
+                outbox.queue.pop_front();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{Relay, RelayStats};
+    use crate::filter::Matcher;
+    use crate::message::SyslogMessage;
+    use crate::parse_message;
+    use crate::parser::ParseErr;
+    use crate::transport::sender::Sender;
+    use crate::transport::udp::UdpSyslogServer;
+
+    #[test]
+    fn test_relay() {
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let up = Arc::clone(&delivered);
+        let failures = Arc::new(Mutex::new(2));
+        let flaky = Arc::clone(&failures);
+
+        let mut relay = Relay::new()
+            .with_filter(Matcher::parse("severity >= warning").unwrap())
+            .with_transform(|mut m| {
+                m.hostname = Some("relay".into());
+                Some(m)
+            })
+            .with_transform(|m| if m.msg == "skip" { None } else { Some(m) })
+            .with_sink(move |m: &SyslogMessage| {
+                up.lock().unwrap().push(m.clone());
+                Ok(())
+            })
+            .with_buffered_sink(
+                move |_: &SyslogMessage| {
+                    let mut failures = flaky.lock().unwrap();
+                    if *failures > 0 {
+                        *failures -= 1;
+                        Err(io::Error::other("down"))
+                    } else {
+                        Ok(())
+                    }
+                },
+                1,
+            )
+            .with_retry_interval(Duration::from_secs(3600));
+
+        let message = |s: &str| parse_message(s).unwrap();
+        relay.handle(message("<12>1 - host app - - - one"));
+        relay.handle(message("<14>1 - host app - - - info"));
+        relay.handle(message("<12>1 - host app - - - skip"));
+        // the second sink is still waiting to be retried, and only has room for one message
+        relay.handle(message("<11>1 - host app - - - two"));
+        assert_eq!(relay.pending(), 1);
+
+        let bodies = |v: &Vec<SyslogMessage>| v.iter().map(|m| m.msg.clone()).collect::<Vec<_>>();
+        assert_eq!(bodies(&delivered.lock().unwrap()), vec!["one", "two"]);
+        assert!(delivered
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|m| m.hostname.as_deref() == Some("relay")));
+
+        // fails once more, then succeeds
+        assert!(!relay.flush());
+        assert!(relay.flush());
+        assert_eq!(*failures.lock().unwrap(), 0);
+
+        let errors: Vec<(u8, Result<_, ParseErr>)> = vec![(0, Err(parse_message("").unwrap_err()))];
+        relay.run(errors);
+        assert_eq!(
+            relay.stats(),
+            RelayStats {
+                received: 5,
+                invalid: 1,
+                filtered: 2,
+                dropped: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_relay_udp() {
+        let mut inbound = UdpSyslogServer::bind("127.0.0.1:0").expect("should bind");
+        let mut outbound = UdpSyslogServer::bind("127.0.0.1:0").expect("should bind");
+        let mut client = Sender::udp(inbound.local_addr().unwrap()).unwrap();
+        let mut relay =
+            Relay::new().with_sink(Sender::udp(outbound.local_addr().unwrap()).unwrap());
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || inbound.run_with_channel(tx));
+        let m = parse_message("<14>1 - host app - - - hi").unwrap();
+        client.send(&m).expect("should send");
+        relay.run(rx.iter().take(1));
+
+        let (_, received) = outbound.recv().expect("should receive");
+        assert_eq!(received.expect("should parse"), m);
+    }
+}
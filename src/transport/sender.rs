@@ -0,0 +1,245 @@
+//! Sending messages to a syslog server or the local syslog daemon.
+//!
+//! `Sender` is the reverse of the listeners in this module: it serializes each `SyslogMessage`
+//! and transmits it over UDP, TCP (with octet counting) or a Unix datagram socket. Stream
+//! connections are opened lazily and re-opened if they fail.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use syslog_rfc5424::transport::sender::Sender;
+//!
+//! let mut sender = Sender::tcp("logs.example.com:601").unwrap();
+//! let m = syslog_rfc5424::parse_message("<14>1 - myhost myapp - - - hello").unwrap();
+//! sender.send(&m).unwrap();
+//! ```
+
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::encoder;
+use crate::framing::OctetCountingEncoder;
+use crate::message::SyslogMessage;
+use crate::sink::Sink;
+
+#[derive(Debug)]
+enum Target {
+    Udp(UdpSocket),
+    Tcp {
+        addrs: Vec<SocketAddr>,
+        stream: Option<TcpStream>,
+    },
+    #[cfg(unix)]
+    Unix {
+        path: PathBuf,
+        socket: UnixDatagram,
+    },
+}
+
+#[derive(Debug)]
+/// Serializes messages and transmits them to a syslog receiver
+///
+/// If sending fails, the connection (for TCP) is dropped and re-opened and the message is sent
+/// again, up to `with_retries` times. A TCP write usually succeeds even when the server has gone
+/// away, and only the *next* write fails, so a message can still be lost when a server restarts;
+/// use TLS or an application-level acknowledgement if that matters.
+pub struct Sender {
+    target: Target,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl Sender {
+    fn new(target: Target) -> Self {
+        Sender {
+            target,
+            retries: 1,
+            retry_delay: Duration::from_millis(100),
+        }
+    }
+
+    /// Send each message as a single UDP datagram (RFC 5426)
+    pub fn udp<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let addr = resolve(addr)?[0];
+        let local: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        Ok(Self::new(Target::Udp(socket)))
+    }
+
+    /// Send messages over TCP with octet-counting framing (RFC 6587)
+    ///
+    /// The address is resolved now, but the connection isn't opened until the first `send`.
+    pub fn tcp<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(Self::new(Target::Tcp {
+            addrs: resolve(addr)?,
+            stream: None,
+        }))
+    }
+
+    /// Send each message as a datagram to a Unix socket, such as `/dev/log`
+    #[cfg(unix)]
+    pub fn unix<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(Target::Unix {
+            path: path.as_ref().to_path_buf(),
+            socket: UnixDatagram::unbound()?,
+        }))
+    }
+
+    /// Set how many times a failed send is retried (default: 1)
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Set how long to wait before each retry (default: 100ms)
+    pub fn with_retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Send a single message, reconnecting if necessary
+    pub fn send(&mut self, m: &SyslogMessage) -> io::Result<()> {
+        let body = match self.target {
+            Target::Tcp { .. } => {
+                let mut framed = Vec::new();
+                OctetCountingEncoder::new().encode(m, &mut framed);
+                framed
+            }
+            _ => encoder::to_bytes(m),
+        };
+        let mut attempt = 0;
+        loop {
+            match self.try_send(&body) {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= self.retries => return Err(e),
+                Err(_) => {
+                    attempt += 1;
+                    thread::sleep(self.retry_delay);
+                }
+            }
+        }
+    }
+
+    /// Close the current TCP connection, if any; the next `send` opens a new one
+    pub fn disconnect(&mut self) {
+        if let Target::Tcp { ref mut stream, .. } = self.target {
+            *stream = None;
+        }
+    }
+
+    fn try_send(&mut self, body: &[u8]) -> io::Result<()> {
+        match self.target {
+            Target::Udp(ref socket) => socket.send(body).map(|_| ()),
+            Target::Tcp {
+                ref addrs,
+                ref mut stream,
+            } => {
+                if stream.is_none() {
+                    *stream = Some(TcpStream::connect(&addrs[..])?);
+                }
+                let s = stream.as_mut().expect("connected above");
+                let result = s.write_all(body).and_then(|_| s.flush());
+                if result.is_err() {
+                    *stream = None;
+                }
+                result
+            }
+            #[cfg(unix)]
+            Target::Unix {
+                ref path,
+                ref socket,
+            } => socket.send_to(body, path).map(|_| ()),
+        }
+    }
+}
+
+impl Sink for Sender {
+    fn send(&mut self, m: &SyslogMessage) -> io::Result<()> {
+        Sender::send(self, m)
+    }
+}
+
+fn resolve<A: ToSocketAddrs>(addr: A) -> io::Result<Vec<SocketAddr>> {
+    let addrs = addr.to_socket_addrs()?.collect::<Vec<_>>();
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "address resolved to nothing",
+        ));
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Sender;
+    use crate::parse_message;
+    use crate::transport::tcp::TcpSyslogServer;
+    use crate::transport::udp::UdpSyslogServer;
+
+    #[test]
+    fn test_udp() {
+        let mut server = UdpSyslogServer::bind("127.0.0.1:0").expect("should bind");
+        let mut sender = Sender::udp(server.local_addr().unwrap()).expect("should create");
+        let m = parse_message("<14>1 - host app - - - hi").unwrap();
+        sender.send(&m).expect("should send");
+        let (_, received) = server.recv().expect("should receive");
+        assert_eq!(received.expect("should parse"), m);
+    }
+
+    #[test]
+    fn test_tcp_reconnects() {
+        let server = TcpSyslogServer::bind("127.0.0.1:0").expect("should bind");
+        let mut sender = Sender::tcp(server.local_addr().unwrap())
+            .expect("should create")
+            .with_retry_delay(Duration::from_millis(10));
+        let message = |body| parse_message(format!("<14>1 - host app - - - {}", body)).unwrap();
+
+        sender.send(&message("one")).expect("should send");
+        let mut first = server.accept().expect("should accept");
+        assert_eq!(first.next().unwrap().expect("should parse").msg, "one");
+        drop(first);
+
+        // the first write after the server hangs up may appear to succeed
+        std::thread::sleep(Duration::from_millis(50));
+        let _ = sender.send(&message("two"));
+        std::thread::sleep(Duration::from_millis(50));
+        sender.send(&message("three")).expect("should reconnect");
+        sender.disconnect();
+
+        let second = server.accept().expect("should accept");
+        let bodies = second
+            .map(|r| r.expect("should parse").msg)
+            .collect::<Vec<_>>();
+        assert_eq!(bodies.last().map(String::as_str), Some("three"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix() {
+        use crate::transport::unix::UnixDatagramSyslogServer;
+
+        let path =
+            std::env::temp_dir().join(format!("syslog_rfc5424-sender-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut server = UnixDatagramSyslogServer::bind(&path).expect("should bind");
+        let mut sender = Sender::unix(&path).expect("should create");
+        let m = parse_message("<14>1 - host app - - - hi").unwrap();
+        sender.send(&m).expect("should send");
+        assert_eq!(server.recv().unwrap().expect("should parse"), m);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,438 @@
+//! Receiving syslog over TCP, framed as described in [RFC 6587](https://tools.ietf.org/html/rfc6587).
+//!
+//! `TcpSyslogServer` is the blocking version, which uses a thread per connection. With the
+//! `tokio` feature, `AsyncTcpSyslogServer` does the same with a task per connection.
+//!
+//! By default the framing is detected separately for each connection (see
+//! `framing::DetectingDecoder`); use `with_framing` if you know what your senders use.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use syslog_rfc5424::transport::tcp::TcpSyslogServer;
+//!
+//! let server = TcpSyslogServer::bind("0.0.0.0:601").unwrap();
+//! let err = server.run(|peer, result| match result {
+//!     Ok(m) => println!("{}: {:?}", peer, m.msg),
+//!     Err(e) => eprintln!("{}: {}", peer, e),
+//! });
+//! panic!("listener error: {}", err);
+//! ```
+
+use std::io::{self, Read};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use crate::framing::{FrameDecoder, Framing, DEFAULT_MAX_FRAME_LENGTH};
+use crate::message::SyslogMessage;
+use crate::parser::{ParserConfig, Utf8Policy};
+use crate::stream::{Parsed, StreamError, StreamingParser};
+
+/// How much to read from a connection at a time
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// The outcome of reading a single frame: which peer sent it, and what it parsed to
+pub type Received = (SocketAddr, Result<SyslogMessage, StreamError>);
+
+#[derive(Clone, Debug)]
+pub(super) struct Settings {
+    pub(super) framing: Framing,
+    pub(super) max_frame_length: usize,
+    pub(super) utf8_policy: Utf8Policy,
+    pub(super) parser_config: ParserConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            framing: Framing::Detect,
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+            utf8_policy: Utf8Policy::Strict,
+            parser_config: ParserConfig::default(),
+        }
+    }
+}
+
+impl Settings {
+    fn parser(&self) -> StreamingParser<Box<dyn FrameDecoder + Send>> {
+        StreamingParser::new(self.framing.decoder(self.max_frame_length))
+            .with_utf8_policy(self.utf8_policy)
+            .with_parser_config(self.parser_config.clone())
+    }
+}
+
+/// An iterator over the messages received on a single connection
+///
+/// As with `StreamingParser`, a message which fails to parse is yielded as an error and iteration
+/// carries on; after a framing or I/O error, or once the peer closes the connection, the iterator
+/// ends.
+pub struct Connection<S, A = SocketAddr> {
+    stream: S,
+    peer_addr: A,
+    parser: StreamingParser<Box<dyn FrameDecoder + Send>>,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+/// A plain TCP `Connection`
+pub type TcpConnection = Connection<TcpStream>;
+
+impl<S: Read, A: Clone> Connection<S, A> {
+    pub(super) fn new(stream: S, peer_addr: A, settings: &Settings) -> Self {
+        Connection {
+            stream,
+            peer_addr,
+            parser: settings.parser(),
+            buf: vec![0; READ_BUFFER_SIZE],
+            done: false,
+        }
+    }
+
+    /// The address of the sender
+    pub fn peer_addr(&self) -> A {
+        self.peer_addr.clone()
+    }
+
+    /// Access the underlying stream, e.g. to set a read timeout
+    pub fn stream(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<S: Read, A> Iterator for Connection<S, A> {
+    type Item = Result<SyslogMessage, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done {
+                return None;
+            }
+            match self.parser.next_message() {
+                Ok(Parsed::Message(m)) => return Some(Ok(m)),
+                Ok(Parsed::NeedMoreData) => {}
+                Err(e @ StreamError::Parse(_)) => return Some(Err(e)),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+            match self.stream.read(&mut self.buf) {
+                Ok(0) => {
+                    self.done = true;
+                    return self.parser.finish().transpose();
+                }
+                Ok(n) => self.parser.push(&self.buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(StreamError::Io(e)));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+/// A blocking TCP syslog listener
+pub struct TcpSyslogServer {
+    listener: TcpListener,
+    settings: Settings,
+}
+
+impl TcpSyslogServer {
+    /// Bind a new listener to the given address (the IANA-assigned port is 601, but 514 is common)
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(Self::from_listener(TcpListener::bind(addr)?))
+    }
+
+    /// Accept connections on an existing listener
+    pub fn from_listener(listener: TcpListener) -> Self {
+        TcpSyslogServer {
+            listener,
+            settings: Settings::default(),
+        }
+    }
+
+    /// Set the framing method used by senders (default: `Framing::Detect`)
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.settings.framing = framing;
+        self
+    }
+
+    /// Set the largest frame which will be accepted
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.settings.max_frame_length = max_frame_length;
+        self
+    }
+
+    /// Set how messages which aren't valid UTF-8 are handled
+    pub fn with_utf8_policy(mut self, utf8_policy: Utf8Policy) -> Self {
+        self.settings.utf8_policy = utf8_policy;
+        self
+    }
+
+    /// Parse each message with the given `ParserConfig` instead of the default
+    pub fn with_parser_config(mut self, config: ParserConfig) -> Self {
+        self.settings.parser_config = config;
+        self
+    }
+
+    /// The address the listener is bound to
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Wait for the next connection
+    pub fn accept(&self) -> io::Result<TcpConnection> {
+        let (stream, peer_addr) = self.listener.accept()?;
+        Ok(TcpConnection::new(stream, peer_addr, &self.settings))
+    }
+
+    /// Accept connections forever, handling each one on its own thread
+    ///
+    /// `handler` is called (from the connection's thread) with every message or error. A
+    /// connection isn't read from while its handler is running, so a slow handler pushes back
+    /// on the sender rather than buffering without bound. Only returns if accepting fails.
+    pub fn run<F>(&self, handler: F) -> io::Error
+    where
+        F: Fn(SocketAddr, Result<SyslogMessage, StreamError>) + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        loop {
+            let conn = match self.accept() {
+                Ok(conn) => conn,
+                Err(e) => return e,
+            };
+            let handler = Arc::clone(&handler);
+            thread::spawn(move || {
+                let peer_addr = conn.peer_addr();
+                for result in conn {
+                    handler(peer_addr, result);
+                }
+            });
+        }
+    }
+
+    /// Accept connections forever, sending every message or error down a channel
+    ///
+    /// Use a bounded `sync_channel` to push back on senders when the consumer falls behind.
+    /// Connection threads exit once the receiving end of the channel has been dropped, but this
+    /// only returns if accepting fails.
+    pub fn run_with_channel(&self, sender: mpsc::SyncSender<Received>) -> io::Error {
+        loop {
+            let conn = match self.accept() {
+                Ok(conn) => conn,
+                Err(e) => return e,
+            };
+            let sender = sender.clone();
+            thread::spawn(move || {
+                let peer_addr = conn.peer_addr();
+                for result in conn {
+                    if sender.send((peer_addr, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::asynchronous::AsyncTcpSyslogServer;
+
+#[cfg(feature = "tokio")]
+mod asynchronous {
+    use std::io;
+    use std::net::SocketAddr;
+
+    use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+    use tokio::sync::mpsc;
+
+    use super::{Received, Settings};
+    use crate::framing::{FrameDecoder, Framing};
+    use crate::parser::{ParserConfig, Utf8Policy};
+    use crate::stream::MessageStream;
+
+    #[derive(Debug)]
+    /// An asynchronous TCP syslog listener (requires the `tokio` feature)
+    pub struct AsyncTcpSyslogServer {
+        listener: TcpListener,
+        settings: Settings,
+    }
+
+    impl AsyncTcpSyslogServer {
+        /// Bind a new listener to the given address
+        pub async fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+            Ok(Self::from_listener(TcpListener::bind(addr).await?))
+        }
+
+        /// Accept connections on an existing listener
+        pub fn from_listener(listener: TcpListener) -> Self {
+            AsyncTcpSyslogServer {
+                listener,
+                settings: Settings::default(),
+            }
+        }
+
+        /// Set the framing method used by senders (default: `Framing::Detect`)
+        pub fn with_framing(mut self, framing: Framing) -> Self {
+            self.settings.framing = framing;
+            self
+        }
+
+        /// Set the largest frame which will be accepted
+        pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+            self.settings.max_frame_length = max_frame_length;
+            self
+        }
+
+        /// Set how messages which aren't valid UTF-8 are handled
+        pub fn with_utf8_policy(mut self, utf8_policy: Utf8Policy) -> Self {
+            self.settings.utf8_policy = utf8_policy;
+            self
+        }
+
+        /// Parse each message with the given `ParserConfig` instead of the default
+        pub fn with_parser_config(mut self, config: ParserConfig) -> Self {
+            self.settings.parser_config = config;
+            self
+        }
+
+        /// The address the listener is bound to
+        pub fn local_addr(&self) -> io::Result<SocketAddr> {
+            self.listener.local_addr()
+        }
+
+        /// Wait for the next connection, returning a `Stream` of its messages and the peer address
+        pub async fn accept(
+            &self,
+        ) -> io::Result<(
+            MessageStream<TcpStream, Box<dyn FrameDecoder + Send>>,
+            SocketAddr,
+        )> {
+            let (stream, peer_addr) = self.listener.accept().await?;
+            let decoder = self
+                .settings
+                .framing
+                .decoder(self.settings.max_frame_length);
+            let stream = MessageStream::new(stream, decoder)
+                .with_utf8_policy(self.settings.utf8_policy)
+                .with_parser_config(self.settings.parser_config.clone());
+            Ok((stream, peer_addr))
+        }
+
+        /// Accept connections until the receiving end of the channel is dropped, spawning a task
+        /// to read each one
+        ///
+        /// The channel is bounded, so a consumer which falls behind pushes back on the senders.
+        /// Returns `Ok(())` once the receiver has been dropped, or the error if accepting fails.
+        pub async fn run(self, sender: mpsc::Sender<Received>) -> io::Result<()> {
+            loop {
+                let (mut stream, peer_addr) = tokio::select! {
+                    accepted = self.accept() => accepted?,
+                    _ = sender.closed() => return Ok(()),
+                };
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    while let Some(result) = stream.next_message().await {
+                        if sender.send((peer_addr, result)).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::TcpSyslogServer;
+    use crate::framing::Framing;
+    use crate::stream::StreamError;
+
+    #[test]
+    fn test_accept() {
+        let server = TcpSyslogServer::bind("127.0.0.1:0").expect("should bind");
+        let addr = server.local_addr().unwrap();
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .write_all(b"24 <1>1 - host1 - - - - one3 foo")
+            .unwrap();
+        drop(client);
+
+        let conn = server.accept().expect("should accept");
+        let results = conn.collect::<Vec<_>>();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().msg, "one");
+        assert!(matches!(results[1], Err(StreamError::Parse(_))));
+    }
+
+    #[test]
+    fn test_run_with_channel() {
+        let server = TcpSyslogServer::bind("127.0.0.1:0")
+            .expect("should bind")
+            .with_framing(Framing::NonTransparent(crate::framing::Trailer::LF));
+        let addr = server.local_addr().unwrap();
+        let (tx, rx) = mpsc::sync_channel(1);
+        thread::spawn(move || server.run_with_channel(tx));
+
+        let mut clients = Vec::new();
+        for host in &["host1", "host2"] {
+            let mut client = TcpStream::connect(addr).unwrap();
+            write!(
+                client,
+                "<1>1 - {} - - - - a\n<1>1 - {} - - - - b\n",
+                host, host
+            )
+            .unwrap();
+            clients.push(client);
+        }
+        let mut received = (0..4)
+            .map(|_| {
+                let (_, result) = rx.recv().expect("should receive");
+                let m = result.expect("should parse");
+                format!("{} {}", m.hostname.unwrap(), m.msg)
+            })
+            .collect::<Vec<_>>();
+        received.sort();
+        assert_eq!(received, vec!["host1 a", "host1 b", "host2 a", "host2 b"]);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_run() {
+        use tokio::io::AsyncWriteExt;
+
+        let server = super::AsyncTcpSyslogServer::bind("127.0.0.1:0")
+            .await
+            .expect("should bind")
+            .with_parser_config(crate::ParserConfig::new().with_lenient(true));
+        let addr = server.local_addr().unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let handle = tokio::spawn(server.run(tx));
+
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"<1>1 - host1 - - - - one\n")
+            .await
+            .unwrap();
+        let (peer, result) = rx.recv().await.expect("should receive");
+        assert_eq!(peer, client.local_addr().unwrap());
+        assert_eq!(result.expect("should parse").msg, "one");
+        client
+            .write_all(b"<13>Oct 11 22:14:15 host app 123 - - two\n")
+            .await
+            .unwrap();
+        let (_, result) = rx.recv().await.expect("should receive");
+        assert_eq!(result.expect("should parse leniently").msg, "two");
+        drop(rx);
+        handle.await.unwrap().expect("should stop cleanly");
+    }
+}
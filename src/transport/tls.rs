@@ -0,0 +1,656 @@
+//! Syslog over TLS, as described in [RFC 5425](https://tools.ietf.org/html/rfc5425) (requires the
+//! `tls` feature).
+//!
+//! RFC 5425 always uses octet-counting framing. Peers are authenticated in one of the two ways
+//! described in §5 of the RFC, chosen with `PeerValidation`:
+//!
+//!  * Certification path validation against a set of trust anchors, plus (for a client checking
+//!    a server) matching the server's name against its certificate
+//!  * Matching the fingerprint of the peer's certificate against a configured list, which is how
+//!    self-signed certificates are meant to be used
+//!
+//! Build a `rustls` configuration with `server_config` or `client_config`, then hand it to a
+//! `TlsSyslogServer` or `TlsSender`.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{
+    ClientConfig, ClientConnection, DigitallySignedStruct, DistinguishedName, RootCertStore,
+    ServerConfig, ServerConnection, SignatureScheme, StreamOwned,
+};
+use thiserror::Error;
+
+use super::tcp::{Connection, Settings};
+use crate::framing::{Framing, OctetCountingEncoder};
+use crate::message::SyslogMessage;
+use crate::parser::{ParserConfig, Utf8Policy};
+use crate::sink::Sink;
+use crate::stream::StreamError;
+
+/// The TCP port IANA has assigned to syslog over TLS
+pub const DEFAULT_PORT: u16 = 6514;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FingerprintError {
+    #[error("unsupported fingerprint hash algorithm {0:?}")]
+    UnsupportedAlgorithm(String),
+    #[error("invalid hex in fingerprint")]
+    InvalidHex,
+    #[error("fingerprint is the wrong length for its hash algorithm")]
+    WrongLength,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The hash algorithms which can be used for certificate fingerprints
+pub enum FingerprintAlgorithm {
+    /// SHA-1, which RFC 5425 makes mandatory to implement; prefer SHA-256 where possible
+    Sha1,
+    Sha256,
+}
+
+impl FingerprintAlgorithm {
+    fn digest_algorithm(self) -> &'static ring::digest::Algorithm {
+        match self {
+            FingerprintAlgorithm::Sha1 => &ring::digest::SHA1_FOR_LEGACY_USE_ONLY,
+            FingerprintAlgorithm::Sha256 => &ring::digest::SHA256,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FingerprintAlgorithm::Sha1 => "SHA1",
+            FingerprintAlgorithm::Sha256 => "SHA256",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The fingerprint of a certificate, as described in RFC 5425 §4.2.2
+///
+/// The textual form is the hash algorithm name followed by colon-separated hex bytes, e.g.
+/// `SHA1:E1:2D:53:2B:7C:6B:8A:29:A2:76:C8:64:36:0B:08:4B:7A:F1:9E:9D`. Both `SHA1` and `SHA-1` (and
+/// likewise for SHA-256) are accepted when parsing, in any case.
+pub struct Fingerprint {
+    algorithm: FingerprintAlgorithm,
+    digest: Vec<u8>,
+}
+
+impl Fingerprint {
+    /// Compute the fingerprint of a DER-encoded certificate
+    pub fn of(algorithm: FingerprintAlgorithm, cert: &[u8]) -> Self {
+        Fingerprint {
+            algorithm,
+            digest: ring::digest::digest(algorithm.digest_algorithm(), cert)
+                .as_ref()
+                .to_vec(),
+        }
+    }
+
+    pub fn algorithm(&self) -> FingerprintAlgorithm {
+        self.algorithm
+    }
+
+    fn matches(&self, cert: &[u8]) -> bool {
+        Fingerprint::of(self.algorithm, cert) == *self
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = FingerprintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, hex) = s.split_once(':').ok_or(FingerprintError::InvalidHex)?;
+        let algorithm = match name.to_ascii_uppercase().as_str() {
+            "SHA1" | "SHA-1" => FingerprintAlgorithm::Sha1,
+            "SHA256" | "SHA-256" => FingerprintAlgorithm::Sha256,
+            _ => return Err(FingerprintError::UnsupportedAlgorithm(name.to_string())),
+        };
+        let digest = hex
+            .split(':')
+            .map(|byte| match byte.len() {
+                2 => u8::from_str_radix(byte, 16).map_err(|_| FingerprintError::InvalidHex),
+                _ => Err(FingerprintError::InvalidHex),
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        if digest.len() != algorithm.digest_algorithm().output_len() {
+            return Err(FingerprintError::WrongLength);
+        }
+        Ok(Fingerprint { algorithm, digest })
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.algorithm.name())?;
+        for byte in self.digest.iter() {
+            write!(f, ":{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+/// How to authenticate the other end of a connection
+pub enum PeerValidation {
+    /// Certification path validation (RFC 5425 §5.1) against the given trust anchors
+    CertificatePath(RootCertStore),
+    /// Accept any certificate (self-signed or not) whose fingerprint is in the list
+    /// (RFC 5425 §5.2)
+    Fingerprints(Vec<Fingerprint>),
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn no_matching_fingerprint() -> rustls::Error {
+    rustls::Error::General(String::from(
+        "peer certificate does not match any configured fingerprint",
+    ))
+}
+
+#[derive(Debug)]
+struct FingerprintVerifier {
+    fingerprints: Vec<Fingerprint>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl FingerprintVerifier {
+    fn check(&self, end_entity: &CertificateDer<'_>) -> Result<(), rustls::Error> {
+        if self.fingerprints.iter().any(|f| f.matches(end_entity)) {
+            Ok(())
+        } else {
+            Err(no_matching_fingerprint())
+        }
+    }
+
+    fn tls12(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn tls13(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+impl ServerCertVerifier for FingerprintVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.check(end_entity)?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.tls12(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.tls13(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.schemes()
+    }
+}
+
+impl ClientCertVerifier for FingerprintVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.check(end_entity)?;
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.tls12(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.tls13(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.schemes()
+    }
+}
+
+/// Build a server configuration presenting the given certificate chain
+///
+/// If `client_validation` is `None`, clients aren't asked for a certificate at all; otherwise
+/// they must present one which passes validation.
+pub fn server_config(
+    cert_chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    client_validation: Option<PeerValidation>,
+) -> Result<ServerConfig, rustls::Error> {
+    let provider = provider();
+    let builder = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()?;
+    let builder = match client_validation {
+        None => builder.with_no_client_auth(),
+        Some(PeerValidation::CertificatePath(roots)) => {
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(|e| rustls::Error::General(e.to_string()))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        Some(PeerValidation::Fingerprints(fingerprints)) => {
+            builder.with_client_cert_verifier(Arc::new(FingerprintVerifier {
+                fingerprints,
+                provider,
+            }))
+        }
+    };
+    builder.with_single_cert(cert_chain, key)
+}
+
+/// Build a client configuration, optionally presenting a certificate of our own
+pub fn client_config(
+    server_validation: PeerValidation,
+    client_cert: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+) -> Result<ClientConfig, rustls::Error> {
+    let provider = provider();
+    let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()?;
+    let builder = match server_validation {
+        PeerValidation::CertificatePath(roots) => builder.with_root_certificates(roots),
+        PeerValidation::Fingerprints(fingerprints) => builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(FingerprintVerifier {
+                fingerprints,
+                provider,
+            })),
+    };
+    match client_cert {
+        Some((cert_chain, key)) => builder.with_client_auth_cert(cert_chain, key),
+        None => Ok(builder.with_no_client_auth()),
+    }
+}
+
+/// A TLS stream from a client
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+/// A TLS `Connection`; use `stream().conn.peer_certificates()` to see who's on the other end
+pub type TlsConnection = Connection<TlsStream>;
+
+#[derive(Debug)]
+/// A blocking RFC 5425 listener
+pub struct TlsSyslogServer {
+    listener: TcpListener,
+    config: Arc<ServerConfig>,
+    settings: Settings,
+}
+
+impl TlsSyslogServer {
+    /// Bind a new listener to the given address (see `DEFAULT_PORT`)
+    pub fn bind<A: ToSocketAddrs>(addr: A, config: Arc<ServerConfig>) -> io::Result<Self> {
+        Ok(Self::from_listener(TcpListener::bind(addr)?, config))
+    }
+
+    /// Accept connections on an existing listener
+    pub fn from_listener(listener: TcpListener, config: Arc<ServerConfig>) -> Self {
+        TlsSyslogServer {
+            listener,
+            config,
+            settings: Settings {
+                framing: Framing::OctetCounting,
+                ..Settings::default()
+            },
+        }
+    }
+
+    /// Set the largest frame which will be accepted
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.settings.max_frame_length = max_frame_length;
+        self
+    }
+
+    /// Set how messages which aren't valid UTF-8 are handled
+    pub fn with_utf8_policy(mut self, utf8_policy: Utf8Policy) -> Self {
+        self.settings.utf8_policy = utf8_policy;
+        self
+    }
+
+    /// Parse each message with the given `ParserConfig` instead of the default
+    pub fn with_parser_config(mut self, config: ParserConfig) -> Self {
+        self.settings.parser_config = config;
+        self
+    }
+
+    /// The address the listener is bound to
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Wait for the next connection
+    ///
+    /// The TLS handshake happens on the first read, so handshake failures (including failed
+    /// certificate validation) show up as a `StreamError::Io` from the connection.
+    pub fn accept(&self) -> io::Result<TlsConnection> {
+        let (stream, peer_addr) = self.listener.accept()?;
+        let conn = ServerConnection::new(Arc::clone(&self.config))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Connection::new(
+            StreamOwned::new(conn, stream),
+            peer_addr,
+            &self.settings,
+        ))
+    }
+
+    /// Accept connections forever, handling each one on its own thread
+    ///
+    /// See `TcpSyslogServer::run`.
+    pub fn run<F>(&self, handler: F) -> io::Error
+    where
+        F: Fn(SocketAddr, Result<SyslogMessage, StreamError>) + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        loop {
+            let conn = match self.accept() {
+                Ok(conn) => conn,
+                Err(e) => return e,
+            };
+            let handler = Arc::clone(&handler);
+            thread::spawn(move || {
+                let peer_addr = conn.peer_addr();
+                for result in conn {
+                    handler(peer_addr, result);
+                }
+            });
+        }
+    }
+}
+
+/// A blocking RFC 5425 sender
+pub struct TlsSender {
+    stream: StreamOwned<ClientConnection, TcpStream>,
+}
+
+impl TlsSender {
+    /// Connect to a server, checking its certificate against `server_name`
+    ///
+    /// With `PeerValidation::Fingerprints`, the name isn't checked, but is still sent for SNI.
+    pub fn connect<A: ToSocketAddrs>(
+        addr: A,
+        server_name: &str,
+        config: Arc<ClientConfig>,
+    ) -> io::Result<Self> {
+        let server_name = ServerName::try_from(server_name.to_string())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let conn = ClientConnection::new(config, server_name)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut stream = StreamOwned::new(conn, TcpStream::connect(addr)?);
+        // complete the handshake now, so that validation failures are reported here
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock)?;
+        }
+        Ok(TlsSender { stream })
+    }
+
+    /// Send a single message
+    pub fn send(&mut self, m: &SyslogMessage) -> io::Result<()> {
+        OctetCountingEncoder::new().write(&mut self.stream, m)?;
+        self.stream.flush()
+    }
+
+    /// Close the TLS session cleanly, waiting for the server to hang up
+    pub fn close(mut self) -> io::Result<()> {
+        self.stream.conn.send_close_notify();
+        self.stream.flush()?;
+        // Closing the socket with anything (like a session ticket) left unread makes the kernel
+        // send a RST, which can make the server lose messages it hadn't read yet.
+        let mut buf = [0; 512];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(_) => {}
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::UnexpectedEof
+                        || e.kind() == io::ErrorKind::ConnectionReset =>
+                {
+                    return Ok(())
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Sink for TlsSender {
+    fn send(&mut self, m: &SyslogMessage) -> io::Result<()> {
+        TlsSender::send(self, m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use rustls::RootCertStore;
+
+    use super::{
+        client_config, server_config, Fingerprint, FingerprintAlgorithm, FingerprintError,
+        PeerValidation, TlsSender, TlsSyslogServer,
+    };
+    use crate::parse_message;
+
+    struct Identity {
+        ca: CertificateDer<'static>,
+        cert: CertificateDer<'static>,
+        key: PrivateKeyDer<'static>,
+    }
+
+    fn identity(name: &str) -> Identity {
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let mut ca_params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec![name.to_string()])
+            .unwrap()
+            .signed_by(&key, &ca, &ca_key)
+            .unwrap();
+        Identity {
+            ca: ca.der().clone(),
+            cert: cert.der().clone(),
+            key: PrivateKeyDer::Pkcs8(key.serialize_der().into()),
+        }
+    }
+
+    fn roots(ca: &CertificateDer<'static>) -> RootCertStore {
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.clone()).unwrap();
+        roots
+    }
+
+    #[test]
+    fn test_fingerprint_text() {
+        let text = "SHA1:E1:2D:53:2B:7C:6B:8A:29:A2:76:C8:64:36:0B:08:4B:7A:F1:9E:9D";
+        let f = text.parse::<Fingerprint>().expect("should parse");
+        assert_eq!(f.algorithm(), FingerprintAlgorithm::Sha1);
+        assert_eq!(f.to_string(), text);
+        assert_eq!(
+            "sha-1:e1:2d:53:2b:7c:6b:8a:29:a2:76:c8:64:36:0b:08:4b:7a:f1:9e:9d".parse(),
+            Ok(f)
+        );
+        assert_eq!(
+            "MD5:00".parse::<Fingerprint>(),
+            Err(FingerprintError::UnsupportedAlgorithm(String::from("MD5")))
+        );
+        assert_eq!(
+            "SHA256:E1:2D".parse::<Fingerprint>(),
+            Err(FingerprintError::WrongLength)
+        );
+        assert_eq!(
+            "SHA1:E12D".parse::<Fingerprint>(),
+            Err(FingerprintError::InvalidHex)
+        );
+    }
+
+    fn round_trip(
+        server_id: &Identity,
+        client_validation: Option<PeerValidation>,
+        server_validation: PeerValidation,
+        client_cert: Option<&Identity>,
+    ) -> std::io::Result<Vec<String>> {
+        let config = server_config(
+            vec![server_id.cert.clone()],
+            server_id.key.clone_key(),
+            client_validation,
+        )
+        .unwrap();
+        let server = TlsSyslogServer::bind("127.0.0.1:0", Arc::new(config)).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            server
+                .accept()
+                .unwrap()
+                .map(|r| r.map(|m| m.msg))
+                .collect::<Vec<_>>()
+        });
+
+        let client_cert = client_cert.map(|id| (vec![id.cert.clone()], id.key.clone_key()));
+        let config = client_config(server_validation, client_cert).unwrap();
+        let sent = TlsSender::connect(addr, "localhost", Arc::new(config)).and_then(|mut s| {
+            s.send(&parse_message("<1>1 - - - - - - one").unwrap())?;
+            s.send(&parse_message("<1>1 - - - - - - caf\u{e9}").unwrap())?;
+            s.close()
+        });
+        let received = handle.join().unwrap();
+        sent?;
+        received
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .map_err(|e| std::io::Error::other(e.to_string()))
+    }
+
+    #[test]
+    fn test_certificate_path() {
+        let server_id = identity("localhost");
+        let received = round_trip(
+            &server_id,
+            None,
+            PeerValidation::CertificatePath(roots(&server_id.ca)),
+            None,
+        )
+        .expect("should send");
+        assert_eq!(received, vec!["one", "caf\u{e9}"]);
+
+        let other = identity("localhost");
+        assert!(round_trip(
+            &server_id,
+            None,
+            PeerValidation::CertificatePath(roots(&other.ca)),
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_fingerprints_and_client_auth() {
+        let server_id = identity("syslog.example.com");
+        let client_id = identity("client");
+        let server_fp = Fingerprint::of(FingerprintAlgorithm::Sha256, &server_id.cert);
+        let client_fp = Fingerprint::of(FingerprintAlgorithm::Sha1, &client_id.cert);
+        let received = round_trip(
+            &server_id,
+            Some(PeerValidation::Fingerprints(vec![client_fp])),
+            PeerValidation::Fingerprints(vec![server_fp.clone()]),
+            Some(&client_id),
+        )
+        .expect("should send");
+        assert_eq!(received.len(), 2);
+
+        // a client the server doesn't know
+        let stranger = identity("client");
+        let client_fp = Fingerprint::of(FingerprintAlgorithm::Sha256, &client_id.cert);
+        let result = round_trip(
+            &server_id,
+            Some(PeerValidation::CertificatePath(roots(&client_id.ca))),
+            PeerValidation::Fingerprints(vec![server_fp]),
+            Some(&stranger),
+        );
+        assert!(result.is_err());
+
+        // a server the client doesn't know
+        assert!(round_trip(
+            &server_id,
+            None,
+            PeerValidation::Fingerprints(vec![client_fp]),
+            None
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! Receiving syslog over UDP, as described in [RFC 5426](https://tools.ietf.org/html/rfc5426).
+//!
+//! Every datagram carries exactly one message, so there's no framing to deal with.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use syslog_rfc5424::transport::udp::UdpSyslogServer;
+//!
+//! let mut server = UdpSyslogServer::bind("0.0.0.0:514").unwrap();
+//! let err = server.run(|peer, result| match result {
+//!     Ok(m) => println!("{}: {:?}", peer, m.msg),
+//!     Err(e) => eprintln!("{} sent a bad message: {}", peer, e),
+//! });
+//! panic!("socket error: {}", err);
+//! ```
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc;
+
+use crate::message::SyslogMessage;
+use crate::parser::{parse_message_bytes, ParseErr, Utf8Policy};
+
+/// The largest payload a UDP datagram can carry over IPv4
+pub const MAX_DATAGRAM_SIZE: usize = 65507;
+
+/// The outcome of receiving a single datagram: where it came from, and what it parsed to
+pub type Received = (SocketAddr, Result<SyslogMessage, ParseErr>);
+
+#[derive(Debug)]
+/// A blocking UDP syslog listener
+pub struct UdpSyslogServer {
+    socket: UdpSocket,
+    buf: Vec<u8>,
+    utf8_policy: Utf8Policy,
+}
+
+impl UdpSyslogServer {
+    /// Bind a new socket to the given address (syslog's well-known port is 514)
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(Self::from_socket(UdpSocket::bind(addr)?))
+    }
+
+    /// Receive on an existing socket
+    pub fn from_socket(socket: UdpSocket) -> Self {
+        UdpSyslogServer {
+            socket,
+            buf: vec![0; MAX_DATAGRAM_SIZE],
+            utf8_policy: Utf8Policy::Strict,
+        }
+    }
+
+    /// Set how messages which aren't valid UTF-8 are handled
+    pub fn with_utf8_policy(mut self, utf8_policy: Utf8Policy) -> Self {
+        self.utf8_policy = utf8_policy;
+        self
+    }
+
+    /// Set the largest datagram which will be received in full (default: `MAX_DATAGRAM_SIZE`)
+    ///
+    /// RFC 5426 only requires receivers to handle 480 bytes (over IPv4) and recommends 2048, but
+    /// larger messages are common. Anything longer than this is truncated.
+    pub fn with_max_datagram_size(mut self, max_datagram_size: usize) -> Self {
+        self.buf.resize(max_datagram_size, 0);
+        self
+    }
+
+    /// The address the socket is bound to
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Access the underlying socket, e.g. to set a read timeout
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Wait for the next datagram and parse it
+    ///
+    /// The outer `Result` is for errors on the socket; the inner one is for the message itself.
+    pub fn recv(&mut self) -> io::Result<Received> {
+        let (len, peer) = self.socket.recv_from(&mut self.buf)?;
+        Ok((
+            peer,
+            parse_message_bytes(&self.buf[..len], self.utf8_policy),
+        ))
+    }
+
+    /// Receive datagrams forever, passing each one to `handler`
+    ///
+    /// Only returns if the socket fails.
+    pub fn run<F>(&mut self, mut handler: F) -> io::Error
+    where
+        F: FnMut(SocketAddr, Result<SyslogMessage, ParseErr>),
+    {
+        loop {
+            match self.recv() {
+                Ok((peer, result)) => handler(peer, result),
+                Err(e) => return e,
+            }
+        }
+    }
+
+    /// Receive datagrams, sending each one down a channel
+    ///
+    /// Returns `Ok(())` once the receiving end of the channel has been dropped, or the error if
+    /// the socket fails.
+    pub fn run_with_channel(&mut self, sender: mpsc::Sender<Received>) -> io::Result<()> {
+        loop {
+            if sender.send(self.recv()?).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::UdpSyslogServer;
+
+    #[test]
+    fn test_recv() {
+        let mut server = UdpSyslogServer::bind("127.0.0.1:0").expect("should bind");
+        let addr = server.local_addr().unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.send_to(b"<1>1 - host1 - - - - one", addr).unwrap();
+        client.send_to(b"garbage", addr).unwrap();
+
+        let (peer, result) = server.recv().expect("should receive");
+        assert_eq!(peer, client.local_addr().unwrap());
+        assert_eq!(result.expect("should parse").msg, "one");
+        let (_, result) = server.recv().expect("should receive");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_run_with_channel() {
+        let mut server = UdpSyslogServer::bind("127.0.0.1:0").expect("should bind");
+        let addr = server.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let handle = thread::spawn(move || server.run_with_channel(tx));
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.send_to(b"<1>1 - host1 - - - - one", addr).unwrap();
+        let (_, result) = rx.recv().expect("should receive");
+        assert_eq!(
+            result.expect("should parse").hostname.as_deref(),
+            Some("host1")
+        );
+        drop(rx);
+        // the server only notices the hangup when it next receives something
+        client.send_to(b"<1>1 - - - - - - two", addr).unwrap();
+        handle.join().unwrap().expect("should exit cleanly");
+    }
+}
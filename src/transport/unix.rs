@@ -0,0 +1,252 @@
+//! Receiving syslog on a Unix domain socket, the way a local syslogd does on `/dev/log`.
+//!
+//! Local daemons usually send whatever `syslog(3)` produces, which on most platforms is an
+//! RFC 3164-style `<13>Oct 11 22:14:15 myapp[123]: message` without a hostname; this crate only
+//! parses RFC 5424-shaped messages, so those are delivered as `ParseErr`s. Senders which emit
+//! RFC 5424 (e.g. `logger --rfc5424`, or anything built on this crate) are fine, and
+//! `ParserConfig::with_lenient` takes care of BSD-style timestamps in otherwise RFC 5424-shaped
+//! messages.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use syslog_rfc5424::transport::unix::UnixDatagramSyslogServer;
+//! use syslog_rfc5424::ParserConfig;
+//!
+//! let mut server = UnixDatagramSyslogServer::bind("/run/mylog.sock")
+//!     .unwrap()
+//!     .with_parser_config(ParserConfig::new().with_lenient(true));
+//! let err = server.run(|result| match result {
+//!     Ok(m) => println!("{:?}", m.msg),
+//!     Err(e) => eprintln!("bad message: {}", e),
+//! });
+//! panic!("socket error: {}", err);
+//! ```
+
+use std::io;
+use std::os::unix::net::{SocketAddr, UnixDatagram, UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+use super::tcp::{Connection, Settings};
+use crate::framing::Framing;
+use crate::message::SyslogMessage;
+use crate::parser::{parse_message_bytes_with, ParseErr, ParserConfig, Utf8Policy};
+use crate::stream::StreamError;
+
+/// The largest datagram which will be received in full by default
+pub const DEFAULT_MAX_DATAGRAM_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+/// A listener on a Unix datagram socket, where every datagram is one message
+pub struct UnixDatagramSyslogServer {
+    socket: UnixDatagram,
+    buf: Vec<u8>,
+    config: ParserConfig,
+    utf8_policy: Utf8Policy,
+}
+
+impl UnixDatagramSyslogServer {
+    /// Create and bind a new socket at `path`, which must not already exist
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::from_socket(UnixDatagram::bind(path)?))
+    }
+
+    /// Receive on an existing socket (e.g. one passed in by systemd)
+    pub fn from_socket(socket: UnixDatagram) -> Self {
+        UnixDatagramSyslogServer {
+            socket,
+            buf: vec![0; DEFAULT_MAX_DATAGRAM_SIZE],
+            config: ParserConfig::default(),
+            utf8_policy: Utf8Policy::Strict,
+        }
+    }
+
+    /// Parse each message with the given `ParserConfig` instead of the default
+    pub fn with_parser_config(mut self, config: ParserConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set how messages which aren't valid UTF-8 are handled
+    pub fn with_utf8_policy(mut self, utf8_policy: Utf8Policy) -> Self {
+        self.utf8_policy = utf8_policy;
+        self
+    }
+
+    /// Set the largest datagram which will be received in full; anything longer is truncated
+    pub fn with_max_datagram_size(mut self, max_datagram_size: usize) -> Self {
+        self.buf.resize(max_datagram_size, 0);
+        self
+    }
+
+    /// Access the underlying socket, e.g. to set a read timeout
+    pub fn socket(&self) -> &UnixDatagram {
+        &self.socket
+    }
+
+    /// Wait for the next datagram and parse it
+    ///
+    /// Local senders usually don't bind their end of the socket, so there's no peer address to
+    /// return. A trailing newline, which some senders add, is removed.
+    pub fn recv(&mut self) -> io::Result<Result<SyslogMessage, ParseErr>> {
+        let len = self.socket.recv(&mut self.buf)?;
+        let mut datagram = &self.buf[..len];
+        datagram = datagram.strip_suffix(b"\n").unwrap_or(datagram);
+        Ok(parse_message_bytes_with(
+            &self.config,
+            datagram,
+            self.utf8_policy,
+        ))
+    }
+
+    /// Receive datagrams forever, passing each one to `handler`
+    ///
+    /// Only returns if the socket fails.
+    pub fn run<F>(&mut self, mut handler: F) -> io::Error
+    where
+        F: FnMut(Result<SyslogMessage, ParseErr>),
+    {
+        loop {
+            match self.recv() {
+                Ok(result) => handler(result),
+                Err(e) => return e,
+            }
+        }
+    }
+}
+
+/// A `Connection` on a Unix stream socket
+pub type UnixConnection = Connection<UnixStream, SocketAddr>;
+
+#[derive(Debug)]
+/// A listener on a Unix stream socket
+///
+/// Local senders generally use non-transparent framing with an LF or NUL trailer, so by default
+/// the framing is detected separately for each connection.
+pub struct UnixStreamSyslogServer {
+    listener: UnixListener,
+    settings: Settings,
+}
+
+impl UnixStreamSyslogServer {
+    /// Create and bind a new socket at `path`, which must not already exist
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::from_listener(UnixListener::bind(path)?))
+    }
+
+    /// Accept connections on an existing listener
+    pub fn from_listener(listener: UnixListener) -> Self {
+        UnixStreamSyslogServer {
+            listener,
+            settings: Settings::default(),
+        }
+    }
+
+    /// Set the framing method used by senders (default: `Framing::Detect`)
+    pub fn with_framing(mut self, framing: Framing) -> Self {
+        self.settings.framing = framing;
+        self
+    }
+
+    /// Parse each message with the given `ParserConfig` instead of the default
+    pub fn with_parser_config(mut self, config: ParserConfig) -> Self {
+        self.settings.parser_config = config;
+        self
+    }
+
+    /// Set how messages which aren't valid UTF-8 are handled
+    pub fn with_utf8_policy(mut self, utf8_policy: Utf8Policy) -> Self {
+        self.settings.utf8_policy = utf8_policy;
+        self
+    }
+
+    /// Wait for the next connection
+    pub fn accept(&self) -> io::Result<UnixConnection> {
+        let (stream, peer_addr) = self.listener.accept()?;
+        Ok(Connection::new(stream, peer_addr, &self.settings))
+    }
+
+    /// Accept connections forever, handling each one on its own thread
+    ///
+    /// See `TcpSyslogServer::run`.
+    pub fn run<F>(&self, handler: F) -> io::Error
+    where
+        F: Fn(Result<SyslogMessage, StreamError>) + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        loop {
+            let conn = match self.accept() {
+                Ok(conn) => conn,
+                Err(e) => return e,
+            };
+            let handler = Arc::clone(&handler);
+            thread::spawn(move || {
+                for result in conn {
+                    handler(result);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::os::unix::net::{UnixDatagram, UnixStream};
+    use std::path::PathBuf;
+
+    use super::{UnixDatagramSyslogServer, UnixStreamSyslogServer};
+    use crate::parser::ParserConfig;
+
+    fn socket_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "syslog_rfc5424-{}-{}.sock",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_datagram() {
+        let path = socket_path("dgram");
+        let mut server = UnixDatagramSyslogServer::bind(&path)
+            .expect("should bind")
+            .with_parser_config(ParserConfig::new().with_lenient(true));
+        let client = UnixDatagram::unbound().unwrap();
+        client
+            .send_to(b"<13>Oct 11 22:14:15 host app 123 - - hi\n", &path)
+            .unwrap();
+        client
+            .send_to(b"<13>Oct 11 22:14:15 app[123]: hi", &path)
+            .unwrap();
+
+        let m = server.recv().unwrap().expect("should parse");
+        assert_eq!(m.appname.as_deref(), Some("app"));
+        assert_eq!(m.msg, "hi");
+        assert!(server.recv().unwrap().is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stream() {
+        let path = socket_path("stream");
+        let server = UnixStreamSyslogServer::bind(&path).expect("should bind");
+        let mut client = UnixStream::connect(&path).unwrap();
+        client
+            .write_all(b"<13>1 - host app - - - one\0<13>1 - host app - - - two\0")
+            .unwrap();
+        drop(client);
+
+        let bodies = server
+            .accept()
+            .expect("should accept")
+            .map(|r| r.expect("should parse").msg)
+            .collect::<Vec<_>>();
+        assert_eq!(bodies, vec!["one", "two"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
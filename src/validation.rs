@@ -0,0 +1,355 @@
+//! Checks for conformance with the letter of RFC 5424.
+//!
+//! The parser is fairly forgiving about what it accepts (and `SyslogMessage` has public fields
+//! which can be set to anything), so this module provides a separate pass which reports every way
+//! in which a message falls short of the spec: overlong header fields, characters outside of
+//! `PRINTUSASCII`, and malformed SD-NAMEs.
+//!
+//! `lint` goes further, adding things which are allowed but unhelpful, like a missing
+//! `timeQuality` element or a NIL HOSTNAME, for finding badly-behaved senders without rejecting
+//! their traffic.
+//!
+//! # Example
+//!
+//! ```
+//! use syslog_rfc5424::parse_message;
+//! use syslog_rfc5424::validation::{lint, LintWarning};
+//!
+//! let msg = parse_message("<1>1 2020-01-01T00:00:00Z - app - - - hi").unwrap();
+//! assert_eq!(
+//!     lint(&msg),
+//!     vec![LintWarning::NilHostname, LintWarning::MissingTimeQuality]
+//! );
+//! ```
+
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::message::{ProcId, SyslogMessage};
+use crate::sd::{SdId, SdIdError, SdParamError};
+
+/// Maximum length of the HOSTNAME header field
+pub const MAX_HOSTNAME_LENGTH: usize = 255;
+/// Maximum length of the APP-NAME header field
+pub const MAX_APPNAME_LENGTH: usize = 48;
+/// Maximum length of the PROCID header field
+pub const MAX_PROCID_LENGTH: usize = 128;
+/// Maximum length of the MSGID header field
+pub const MAX_MSGID_LENGTH: usize = 32;
+/// Maximum length of an SD-NAME (which covers both SD-IDs and PARAM-NAMEs)
+pub const MAX_SD_NAME_LENGTH: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The part of a message a `Violation` was found in
+pub enum Field {
+    Hostname,
+    AppName,
+    ProcId,
+    MsgId,
+    /// The SD-ID of a structured data element
+    SdId(String),
+    /// The PARAM-NAME of a parameter in the given SD-ID
+    SdParamName(String, String),
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Field::Hostname => f.write_str("HOSTNAME"),
+            Field::AppName => f.write_str("APP-NAME"),
+            Field::ProcId => f.write_str("PROCID"),
+            Field::MsgId => f.write_str("MSGID"),
+            Field::SdId(ref id) => write!(f, "SD-ID {:?}", id),
+            Field::SdParamName(ref id, ref name) => write!(f, "PARAM-NAME {:?} in {:?}", name, id),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+/// A single way in which a message does not conform to RFC 5424
+pub enum Violation {
+    #[error("{field} is {length} characters long; the maximum is {max}")]
+    TooLong {
+        field: Field,
+        length: usize,
+        max: usize,
+    },
+    #[error("{field} is empty; use NIL instead")]
+    Empty { field: Field },
+    #[error("{field} contains {found:?}, which is not PRINTUSASCII")]
+    NotPrintUsAscii { field: Field, found: char },
+    #[error("{field} contains {found:?}, which is not allowed in an SD-NAME")]
+    InvalidSdNameChar { field: Field, found: char },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+/// Something odd about a message, as reported by `lint`
+pub enum LintWarning {
+    /// Anything `validate` would report
+    #[error(transparent)]
+    Violation(#[from] Violation),
+    #[error("TIMESTAMP is NIL")]
+    NilTimestamp,
+    #[error("HOSTNAME is NIL")]
+    NilHostname,
+    #[error("there is a TIMESTAMP, but no timeQuality element to say how far to trust it")]
+    MissingTimeQuality,
+    /// An SD-ID which isn't registered and has no (valid) enterprise number
+    #[error(transparent)]
+    SdId(#[from] SdIdError),
+    /// A `timeQuality`, `origin` or `meta` param with a value the RFC doesn't allow
+    #[error(transparent)]
+    SdParam(#[from] SdParamError),
+}
+
+fn is_printusascii(c: char) -> bool {
+    ('!'..='~').contains(&c)
+}
+
+fn check_header_field(field: Field, value: &str, max: usize, violations: &mut Vec<Violation>) {
+    if value.is_empty() {
+        violations.push(Violation::Empty { field });
+        return;
+    }
+    if let Some(found) = value.chars().find(|c| !is_printusascii(*c)) {
+        violations.push(Violation::NotPrintUsAscii {
+            field: field.clone(),
+            found,
+        });
+    }
+    let length = value.len();
+    if length > max {
+        violations.push(Violation::TooLong { field, length, max });
+    }
+}
+
+fn check_sd_name(field: Field, value: &str, violations: &mut Vec<Violation>) {
+    if value.is_empty() {
+        violations.push(Violation::Empty { field });
+        return;
+    }
+    if let Some(found) = value
+        .chars()
+        .find(|c| !is_printusascii(*c) || *c == '=' || *c == ']' || *c == '"')
+    {
+        violations.push(Violation::InvalidSdNameChar {
+            field: field.clone(),
+            found,
+        });
+    }
+    let length = value.len();
+    if length > MAX_SD_NAME_LENGTH {
+        violations.push(Violation::TooLong {
+            field,
+            length,
+            max: MAX_SD_NAME_LENGTH,
+        });
+    }
+}
+
+/// Check a single SD-ID or PARAM-NAME, reporting any violations against `field`
+pub fn validate_sd_name(field: Field, name: &str) -> Result<(), Vec<Violation>> {
+    let mut violations = Vec::new();
+    check_sd_name(field, name, &mut violations);
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// Turn an arbitrary name (like a field name) into a valid SD-NAME, replacing anything which isn't
+/// allowed with `_` and cutting it down to `MAX_SD_NAME_LENGTH`
+pub(crate) fn sanitize_sd_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '=' | ']' | '"' => '_',
+            c if is_printusascii(c) => c,
+            _ => '_',
+        })
+        .take(MAX_SD_NAME_LENGTH)
+        .collect();
+    if name.is_empty() {
+        String::from("_")
+    } else {
+        name
+    }
+}
+
+/// Like `sanitize_sd_name`, but for a header field of up to `max` characters; empty values become
+/// `None` (NIL)
+#[cfg(any(feature = "tracing", feature = "log"))]
+pub(crate) fn sanitize_header_field(value: &str, max: usize) -> Option<String> {
+    if value.is_empty() {
+        return None;
+    }
+    Some(
+        value
+            .chars()
+            .map(|c| if is_printusascii(c) { c } else { '_' })
+            .take(max)
+            .collect(),
+    )
+}
+
+/// Check a message against the RFC 5424 field rules, returning every violation found
+///
+/// This is also available as `SyslogMessage::validate`.
+pub fn validate(msg: &SyslogMessage) -> Result<(), Vec<Violation>> {
+    let mut violations = Vec::new();
+    if let Some(ref hostname) = msg.hostname {
+        check_header_field(
+            Field::Hostname,
+            hostname,
+            MAX_HOSTNAME_LENGTH,
+            &mut violations,
+        );
+    }
+    if let Some(ref appname) = msg.appname {
+        check_header_field(Field::AppName, appname, MAX_APPNAME_LENGTH, &mut violations);
+    }
+    if let Some(ProcId::Name(ref name)) = msg.procid {
+        check_header_field(Field::ProcId, name, MAX_PROCID_LENGTH, &mut violations);
+    }
+    if let Some(ref msgid) = msg.msgid {
+        check_header_field(Field::MsgId, msgid, MAX_MSGID_LENGTH, &mut violations);
+    }
+    for (sd_id, params) in msg.sd.iter() {
+        check_sd_name(Field::SdId(sd_id.clone()), sd_id, &mut violations);
+        for param_name in params.keys() {
+            check_sd_name(
+                Field::SdParamName(sd_id.clone(), param_name.clone()),
+                param_name,
+                &mut violations,
+            );
+        }
+    }
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// Report everything questionable about a message, without rejecting it
+///
+/// This is everything `validate` reports, followed by NIL TIMESTAMP and HOSTNAME fields, a
+/// missing `timeQuality` element, SD-IDs without an enterprise number, and bad values in the
+/// registered SD elements. This is also available as `SyslogMessage::lint`.
+pub fn lint(msg: &SyslogMessage) -> Vec<LintWarning> {
+    let mut warnings: Vec<LintWarning> = match validate(msg) {
+        Ok(()) => Vec::new(),
+        Err(violations) => violations.into_iter().map(LintWarning::from).collect(),
+    };
+    if msg.timestamp.is_none() {
+        warnings.push(LintWarning::NilTimestamp);
+    }
+    if msg.hostname.is_none() {
+        warnings.push(LintWarning::NilHostname);
+    }
+    match msg.sd.time_quality() {
+        Ok(None) if msg.timestamp.is_some() => warnings.push(LintWarning::MissingTimeQuality),
+        Err(e) => warnings.push(e.into()),
+        _ => {}
+    }
+    for (sd_id, _) in msg.sd.iter() {
+        if let Err(e) = SdId::parse(sd_id) {
+            warnings.push(e.into());
+        }
+    }
+    let sd_param_errors = msg.sd.origin().err().into_iter().chain(msg.sd.meta().err());
+    warnings.extend(sd_param_errors.map(LintWarning::from));
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint, Field, LintWarning, Violation};
+    use crate::message::ProcId;
+    use crate::parse_message;
+    use crate::sd::{SdIdError, SdParamError};
+
+    #[test]
+    fn test_valid_message() {
+        let msg = parse_message(
+            r#"<78>1 2016-01-15T00:04:01Z host1 CROND 10391 - [meta sequenceId="29"] hi"#,
+        )
+        .expect("should parse");
+        assert_eq!(msg.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_collects_all_violations() {
+        let mut msg = parse_message("<1>1 - - - - - - hi").expect("should parse");
+        msg.hostname = Some("x".repeat(256));
+        msg.appname = Some(String::from("my app"));
+        msg.procid = Some(ProcId::Name(String::new()));
+        msg.msgid = Some(String::from("caf\u{e9}"));
+        msg.sd.insert_tuple("bad]id", "ok", "v");
+        msg.sd.insert_tuple("ok", "a=b", "v");
+        let violations = msg.validate().expect_err("should not validate");
+        assert_eq!(
+            violations,
+            vec![
+                Violation::TooLong {
+                    field: Field::Hostname,
+                    length: 256,
+                    max: 255
+                },
+                Violation::NotPrintUsAscii {
+                    field: Field::AppName,
+                    found: ' '
+                },
+                Violation::Empty {
+                    field: Field::ProcId
+                },
+                Violation::NotPrintUsAscii {
+                    field: Field::MsgId,
+                    found: '\u{e9}'
+                },
+                Violation::InvalidSdNameChar {
+                    field: Field::SdId(String::from("bad]id")),
+                    found: ']'
+                },
+                Violation::InvalidSdNameChar {
+                    field: Field::SdParamName(String::from("ok"), String::from("a=b")),
+                    found: '='
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint() {
+        let msg = parse_message(
+            r#"<78>1 2016-01-15T00:04:01Z host1 CROND 10391 - [timeQuality isSynced="0"] hi"#,
+        )
+        .expect("should parse");
+        assert_eq!(lint(&msg), vec![]);
+
+        let mut msg = parse_message(
+            r#"<1>1 2016-01-15T00:04:01Z - - - - [custom a="1"][meta sequenceId="0"] hi"#,
+        )
+        .expect("should parse");
+        msg.appname = Some("".into());
+        assert_eq!(
+            lint(&msg),
+            vec![
+                LintWarning::Violation(Violation::Empty {
+                    field: Field::AppName
+                }),
+                LintWarning::NilHostname,
+                LintWarning::MissingTimeQuality,
+                LintWarning::SdId(SdIdError::MissingEnterpriseNumber(String::from("custom"))),
+                LintWarning::SdParam(SdParamError::InvalidValue {
+                    sd_id: "meta",
+                    name: "sequenceId",
+                    value: String::from("0"),
+                }),
+            ]
+        );
+        assert_eq!(msg.lint()[1].to_string(), "HOSTNAME is NIL");
+    }
+}
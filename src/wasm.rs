@@ -0,0 +1,40 @@
+//! JavaScript bindings, for running the parser in a browser or a WASM-based edge runtime
+//! (requires the `wasm-bindgen` feature).
+//!
+//! Messages are handed to JavaScript as plain objects with the same shape as the
+//! `serde-serialize` output, e.g. `{ severity: "info", facility: "kern", hostname: "host1", ... }`.
+//!
+//! ```js
+//! import { parseMessage } from "syslog_rfc5424";
+//!
+//! const m = parseMessage("<78>1 2016-01-15T00:04:01Z host1 CROND 10391 - - some_message");
+//! console.log(m.hostname, m.msg);
+//! ```
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::message::SyslogMessage;
+use crate::parser;
+
+fn to_js(m: &SyslogMessage) -> Result<JsValue, JsError> {
+    // plain objects rather than `Map`s, so that fields can be read with `m.hostname`
+    m.serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Parse an RFC 5424 message into a plain JavaScript object, throwing an `Error` if it's invalid
+#[wasm_bindgen(js_name = parseMessage)]
+pub fn parse_message(input: &str) -> Result<JsValue, JsError> {
+    let m = parser::parse_message(input).map_err(|e| JsError::new(&e.to_string()))?;
+    to_js(&m)
+}
+
+/// Like `parseMessage`, but tolerating the real-world deviations accepted by
+/// `ParserConfig::with_lenient`
+#[wasm_bindgen(js_name = parseMessageLenient)]
+pub fn parse_message_lenient(input: &str) -> Result<JsValue, JsError> {
+    let config = parser::ParserConfig::new().with_lenient(true);
+    let m = parser::parse_message_with(&config, input).map_err(|e| JsError::new(&e.to_string()))?;
+    to_js(&m)
+}